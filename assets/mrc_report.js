@@ -0,0 +1,154 @@
+// Minimal SVG line plot for cache_mrc HTML reports, no dependencies.
+// Expects `MRC_DATA = {curves: [{label, dashed, points: [{size, miss_ratio, hits, misses}]}]}`
+(function () {
+  "use strict";
+  var W = 960, H = 540, M = { left: 70, right: 20, top: 20, bottom: 50 };
+  var COLORS = ["#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd",
+                "#8c564b", "#e377c2", "#7f7f7f", "#bcbd22", "#17becf"];
+  var SVG = "http://www.w3.org/2000/svg";
+  var hidden = {};
+  var logX = false;
+  // Cache size range picked by dragging, null shows everything
+  var zoom = null;
+
+  function el(name, attrs, parent) {
+    var node = document.createElementNS(SVG, name);
+    for (var key in attrs) node.setAttribute(key, attrs[key]);
+    if (parent) parent.appendChild(node);
+    return node;
+  }
+
+  function bounds() {
+    var min = Infinity, max = 0;
+    MRC_DATA.curves.forEach(function (curve) {
+      curve.points.forEach(function (p) {
+        if (p.size > 0) min = Math.min(min, p.size);
+        max = Math.max(max, p.size);
+      });
+    });
+    if (zoom) return { min: Math.max(zoom.min, 1), max: Math.max(zoom.max, 1), zoomed: true };
+    return { min: isFinite(min) ? min : 1, max: Math.max(max, 1), zoomed: false };
+  }
+
+  function draw() {
+    var root = document.getElementById("plot");
+    root.innerHTML = "";
+    var svg = el("svg", { width: W, height: H }, root);
+    var b = bounds();
+    var x = function (size) {
+      var t = logX
+        ? (Math.log(Math.max(size, b.min)) - Math.log(b.min)) / (Math.log(b.max) - Math.log(b.min) || 1)
+        : (size - (b.zoomed ? b.min : 0)) / ((b.max - (b.zoomed ? b.min : 0)) || 1);
+      return M.left + t * (W - M.left - M.right);
+    };
+    // Inverse of x, used to turn a drag into a size range
+    var sizeAt = function (px) {
+      var t = Math.min(Math.max((px - M.left) / (W - M.left - M.right), 0), 1);
+      if (logX) return b.min * Math.pow(b.max / b.min, t);
+      var low = b.zoomed ? b.min : 0;
+      return low + t * (b.max - low);
+    };
+    var y = function (ratio) {
+      return H - M.bottom - ratio * (H - M.top - M.bottom);
+    };
+
+    for (var i = 0; i <= 5; i++) {
+      var ratio = i / 5;
+      el("line", { x1: M.left, x2: W - M.right, y1: y(ratio), y2: y(ratio), stroke: "#ddd" }, svg);
+      el("text", { x: M.left - 8, y: y(ratio) + 4, "text-anchor": "end", "font-size": 12 }, svg)
+        .textContent = ratio.toFixed(1);
+      var size = sizeAt(M.left + i / 5 * (W - M.left - M.right));
+      el("text", { x: x(size), y: H - M.bottom + 18, "text-anchor": "middle", "font-size": 12 }, svg)
+        .textContent = Math.round(size).toLocaleString();
+    }
+    el("text", { x: W / 2, y: H - 10, "text-anchor": "middle" }, svg).textContent = "Cache size";
+    el("text", { x: 16, y: H / 2, transform: "rotate(-90 16 " + H / 2 + ")", "text-anchor": "middle" }, svg)
+      .textContent = "Miss ratio";
+
+    var clip = el("clipPath", { id: "plot-area" }, el("defs", {}, svg));
+    el("rect", { x: M.left, y: 0, width: W - M.left - M.right, height: H }, clip);
+    var area = el("g", { "clip-path": "url(#plot-area)" }, svg);
+
+    var tooltip = document.getElementById("tooltip");
+    MRC_DATA.curves.forEach(function (curve, index) {
+      if (hidden[index]) return;
+      var color = COLORS[index % COLORS.length];
+      var path = curve.points.map(function (p, i) {
+        return (i ? "L" : "M") + x(p.size) + "," + y(p.miss_ratio);
+      }).join("");
+      el("path", { d: path, fill: "none", stroke: color, "stroke-width": 2,
+                   "stroke-dasharray": curve.dashed ? "6,4" : "none" }, area);
+      curve.points.forEach(function (p) {
+        var dot = el("circle", { cx: x(p.size), cy: y(p.miss_ratio), r: 3, fill: color }, area);
+        dot.addEventListener("mouseenter", function (event) {
+          tooltip.style.display = "block";
+          tooltip.style.left = event.pageX + 12 + "px";
+          tooltip.style.top = event.pageY + 12 + "px";
+          tooltip.textContent = curve.label + ": size " + p.size + ", miss ratio " +
+            p.miss_ratio.toFixed(4) + ", hits " + p.hits + ", misses " + p.misses;
+        });
+        dot.addEventListener("mouseleave", function () {
+          tooltip.style.display = "none";
+        });
+      });
+    });
+
+    // Drag selection for zooming
+    var band = el("rect", { y: M.top, height: H - M.top - M.bottom, width: 0,
+                            fill: "#888", "fill-opacity": 0.2, display: "none" }, svg);
+    var start = null;
+    var offset = function (event) {
+      return event.clientX - svg.getBoundingClientRect().left;
+    };
+    svg.addEventListener("mousedown", function (event) {
+      start = offset(event);
+      band.setAttribute("display", "block");
+      band.setAttribute("x", start);
+      band.setAttribute("width", 0);
+    });
+    svg.addEventListener("mousemove", function (event) {
+      if (start === null) return;
+      var now = offset(event);
+      band.setAttribute("x", Math.min(start, now));
+      band.setAttribute("width", Math.abs(now - start));
+    });
+    svg.addEventListener("mouseup", function (event) {
+      if (start === null) return;
+      var end = offset(event);
+      var from = start;
+      start = null;
+      band.setAttribute("display", "none");
+      // Ignore clicks, they would zoom into a single size
+      if (Math.abs(end - from) < 5) return;
+      zoom = { min: sizeAt(Math.min(from, end)), max: sizeAt(Math.max(from, end)) };
+      draw();
+    });
+    svg.addEventListener("dblclick", function () {
+      zoom = null;
+      draw();
+    });
+
+    var legend = document.getElementById("legend");
+    legend.innerHTML = "";
+    MRC_DATA.curves.forEach(function (curve, index) {
+      var item = document.createElement("label");
+      var box = document.createElement("input");
+      box.type = "checkbox";
+      box.checked = !hidden[index];
+      box.addEventListener("change", function () {
+        hidden[index] = !box.checked;
+        draw();
+      });
+      item.appendChild(box);
+      item.style.color = COLORS[index % COLORS.length];
+      item.appendChild(document.createTextNode(" " + curve.label + " "));
+      legend.appendChild(item);
+    });
+  }
+
+  document.getElementById("log-x").addEventListener("change", function (event) {
+    logX = event.target.checked;
+    draw();
+  });
+  draw();
+})();
@@ -0,0 +1,28 @@
+// Simulate LRU with 10% SHARDS sampling over an in-memory synthetic trace
+// and print the miss ratio curve.
+use cache_mrc::{evict_policy::LruPolicy, minisim::MiniSim, shards::ShardsFixedRate, AccessRecord};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(42);
+    // Skewed popularity: low keys are much hotter than high ones
+    let records = (0..200_000u64)
+        .map(|i| AccessRecord {
+            timestamp: i,
+            command: 0,
+            key: (100_000.0 * rng.gen::<f64>().powi(3)) as u64,
+            size: 1024,
+            ttl: 0,
+        })
+        .collect::<Vec<_>>();
+
+    let shards = ShardsFixedRate::create_shards(Some(0.1));
+    let mut sim = MiniSim::<LruPolicy>::new(64 * 1024 * 1024, shards).unwrap();
+    for record in records.iter() {
+        sim.handle(record);
+    }
+
+    for (cache_size, miss_ratio) in sim.curve() {
+        println!("{cache_size}\t{miss_ratio:.4}");
+    }
+}
@@ -0,0 +1,116 @@
+// Implement EvictPolicy outside the crate and simulate it next to LRU.
+use std::collections::HashMap;
+
+use cache_mrc::{
+    evict_policy::{EvictPolicy, LruPolicy, NewPolicy},
+    minisim::MiniSim,
+    AccessRecord, Key,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+// LIFO (Last In First Out) evicts the most recently inserted key
+struct LifoPolicy {
+    capacity: u64,
+    size: u64,
+    cache: HashMap<Key, u64>,
+    stack: Vec<Key>,
+}
+
+impl NewPolicy for LifoPolicy {
+    fn new(capacity: u64) -> Self {
+        Self {
+            capacity,
+            size: 0,
+            cache: HashMap::new(),
+            stack: Vec::new(),
+        }
+    }
+}
+
+impl EvictPolicy for LifoPolicy {
+    fn get(&mut self, key: Key) -> Option<()> {
+        self.cache.get(&key).map(|_| ())
+    }
+
+    fn contains(&self, key: Key) -> bool {
+        self.cache.contains_key(&key)
+    }
+
+    fn resident(&self) -> Box<dyn Iterator<Item = (Key, u64)> + '_> {
+        // The top of the stack goes first
+        Box::new(
+            self.stack
+                .iter()
+                .rev()
+                .filter_map(|key| self.cache.get(key).map(|size| (*key, *size))),
+        )
+    }
+
+    fn put(&mut self, key: Key, size: u64) {
+        while self.size + size > self.capacity {
+            match self.stack.pop() {
+                Some(victim) => {
+                    if let Some(victim_size) = self.cache.remove(&victim) {
+                        self.size -= victim_size;
+                    }
+                }
+                None => return,
+            }
+        }
+        self.cache.insert(key, size);
+        self.stack.push(key);
+        self.size += size;
+    }
+
+    fn update_size(&mut self, key: Key, new_size: u64) -> bool {
+        let Some(size) = self.cache.get_mut(&key) else {
+            return false;
+        };
+        self.size = self.size - *size + new_size;
+        *size = new_size;
+        while self.size > self.capacity {
+            match self.stack.pop() {
+                Some(victim) => {
+                    if let Some(victim_size) = self.cache.remove(&victim) {
+                        self.size -= victim_size;
+                    }
+                }
+                None => break,
+            }
+        }
+        true
+    }
+
+    fn remove(&mut self, key: Key) -> Option<u64> {
+        let size = self.cache.remove(&key)?;
+        self.stack.retain(|&k| k != key);
+        self.size -= size;
+        Some(size)
+    }
+}
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let records = (0..100_000u64)
+        .map(|i| AccessRecord {
+            timestamp: i,
+            command: 0,
+            key: rng.gen_range(0..20_000),
+            size: 512,
+            ttl: 0,
+        })
+        .collect::<Vec<_>>();
+
+    let max_cache_size = 8 * 1024 * 1024;
+    let mut lifo = MiniSim::<LifoPolicy>::new(max_cache_size, None).unwrap();
+    let mut lru = MiniSim::<LruPolicy>::new(max_cache_size, None).unwrap();
+    for record in records.iter() {
+        lifo.handle(record);
+        lru.handle(record);
+    }
+
+    println!("cache_size\tLIFO\tLRU");
+    for ((cache_size, lifo_miss), (_, lru_miss)) in lifo.curve().into_iter().zip(lru.curve()) {
+        println!("{cache_size}\t{lifo_miss:.4}\t{lru_miss:.4}");
+    }
+}
@@ -0,0 +1,39 @@
+// Feed requests one at a time and query the curve while the trace is still
+// being consumed, as a long-running service would, and keep a Prometheus
+// textfile of its state up to date for the node exporter to scrape.
+use cache_mrc::{
+    evict_policy::FifoPolicy, minisim::MiniSim, shards::ShardsFixedRate, AccessRecord,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let shards = ShardsFixedRate::create_shards(Some(0.1));
+    // The metrics include the byte hit ratio of the last window of 100000
+    // requests, the requests between two reports
+    let mut sim = MiniSim::<FifoPolicy>::new(16 * 1024 * 1024, shards)
+        .unwrap()
+        .with_byte_hit_window(Some(100_000));
+    let metrics = std::env::temp_dir().join("cache_mrc_online.prom");
+
+    for i in 1..=500_000u64 {
+        let record = AccessRecord {
+            timestamp: i,
+            command: 0,
+            key: (50_000.0 * rng.gen::<f64>().powi(2)) as u64,
+            size: rng.gen_range(64..4096),
+            ttl: 0,
+        };
+        sim.handle(&record);
+
+        if i % 100_000 == 0 {
+            let curve = sim.curve();
+            let (smallest, first) = curve[0];
+            let (largest, last) = curve[curve.len() - 1];
+            println!(
+                "after {i} requests: miss ratio {first:.4} at {smallest} bytes, {last:.4} at {largest} bytes"
+            );
+            sim.write_prometheus("FIFO", &metrics).unwrap();
+        }
+    }
+}
@@ -0,0 +1,119 @@
+// A cache_mrc binary with one more policy: register it, then hand over to the
+// command line, which accepts it in --policies next to the built-in ones.
+//
+//   cargo run --release --example plugin_policy -- \
+//       --trace ./data/test_twitter.csv --policies "RANDOM(seed=7)" --policies LRU
+use std::{collections::HashMap, error::Error};
+
+use cache_mrc::{
+    evict_policy::{register_policy, EvictPolicy},
+    Key,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+// Evicts a key picked uniformly at random
+struct RandomPolicy {
+    capacity: u64,
+    size: u64,
+    // Key to (size, position in keys)
+    cache: HashMap<Key, (u64, usize)>,
+    keys: Vec<Key>,
+    rng: StdRng,
+    // Keys evicted for capacity, while recording for a watermark or an audit
+    evicted: Option<Vec<Key>>,
+}
+
+impl RandomPolicy {
+    fn with_seed(capacity: u64, seed: u64) -> Self {
+        Self {
+            capacity,
+            size: 0,
+            cache: HashMap::new(),
+            keys: Vec::new(),
+            rng: StdRng::seed_from_u64(seed),
+            evicted: None,
+        }
+    }
+
+    fn evict_to(&mut self, capacity: u64) {
+        while self.size > capacity && !self.keys.is_empty() {
+            let victim = self.keys[self.rng.gen_range(0..self.keys.len())];
+            self.remove(victim);
+            if let Some(evicted) = self.evicted.as_mut() {
+                evicted.push(victim);
+            }
+        }
+    }
+}
+
+impl EvictPolicy for RandomPolicy {
+    fn get(&mut self, key: Key) -> Option<()> {
+        self.cache.get(&key).map(|_| ())
+    }
+
+    fn contains(&self, key: Key) -> bool {
+        self.cache.contains_key(&key)
+    }
+
+    fn resident(&self) -> Box<dyn Iterator<Item = (Key, u64)> + '_> {
+        // Every key is as likely to go next
+        Box::new(self.keys.iter().map(|key| (*key, self.cache[key].0)))
+    }
+
+    fn put(&mut self, key: Key, size: u64) {
+        if size > self.capacity {
+            return;
+        }
+        self.evict_to(self.capacity - size);
+        self.cache.insert(key, (size, self.keys.len()));
+        self.keys.push(key);
+        self.size += size;
+    }
+
+    fn remove(&mut self, key: Key) -> Option<u64> {
+        let (size, position) = self.cache.remove(&key)?;
+        self.keys.swap_remove(position);
+        if let Some(moved) = self.keys.get(position) {
+            self.cache.get_mut(moved).unwrap().1 = position;
+        }
+        self.size -= size;
+        Some(size)
+    }
+
+    fn update_size(&mut self, key: Key, new_size: u64) -> bool {
+        let Some((size, _)) = self.cache.get_mut(&key) else {
+            return false;
+        };
+        self.size = self.size - *size + new_size;
+        *size = new_size;
+        self.evict_to(self.capacity);
+        true
+    }
+
+    fn set_capacity(&mut self, capacity: u64) {
+        self.capacity = capacity;
+        self.evict_to(capacity);
+    }
+
+    fn record_evictions(&mut self, enabled: bool) {
+        self.evicted = enabled.then(Vec::new);
+    }
+
+    fn take_evictions(&mut self) -> Vec<Key> {
+        self.evicted
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    register_policy(
+        "RANDOM",
+        Box::new(|capacity, params| {
+            let seed = params.get("seed").copied().unwrap_or(0.0) as u64;
+            Box::new(RandomPolicy::with_seed(capacity, seed))
+        }),
+    );
+    cache_mrc::cli::run_cli()
+}
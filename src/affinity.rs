@@ -0,0 +1,61 @@
+// Pinning simulation workers to cores, so each worker keeps its caches warm on
+// one core instead of migrating across sockets.
+
+// One logical CPU per physical core this process may run on, ordered by
+// socket then core, from the sysfs CPU topology. Empty where it isn't known.
+#[cfg(target_os = "linux")]
+pub fn physical_cores() -> Vec<usize> {
+    use std::{collections::BTreeMap, fs};
+
+    let read_id = |cpu: usize, name: &str| {
+        fs::read_to_string(format!("/sys/devices/system/cpu/cpu{cpu}/topology/{name}"))
+            .ok()
+            .and_then(|id| id.trim().parse::<i64>().ok())
+    };
+    // Hyperthread siblings share a (package, core) pair, keep the first of them
+    let mut cores = BTreeMap::new();
+    for cpu in allowed_cpus() {
+        let (Some(package), Some(core)) =
+            (read_id(cpu, "physical_package_id"), read_id(cpu, "core_id"))
+        else {
+            continue;
+        };
+        cores.entry((package, core)).or_insert(cpu);
+    }
+    cores.into_values().collect()
+}
+
+#[cfg(target_os = "linux")]
+fn allowed_cpus() -> Vec<usize> {
+    // Safety: cpu_set_t is plain data and sched_getaffinity only writes into it
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+            return Vec::new();
+        }
+        (0..libc::CPU_SETSIZE as usize)
+            .filter(|cpu| libc::CPU_ISSET(*cpu, &set))
+            .collect()
+    }
+}
+
+// Pin the calling thread to `cpu`, false if the OS refused
+#[cfg(target_os = "linux")]
+pub fn pin_current_thread(cpu: usize) -> bool {
+    // Safety: as above, the set is plain data owned by this frame
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) == 0
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn physical_cores() -> Vec<usize> {
+    Vec::new()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin_current_thread(_cpu: usize) -> bool {
+    false
+}
@@ -0,0 +1,75 @@
+use hashbrown::HashMap;
+
+use crate::AccessRecord;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkloadClass {
+    // Most keys are touched once, e.g. sequential scans
+    Scan,
+    // Keys are reused shortly after their previous access
+    TemporalLocality,
+    // Reuse is spread evenly, recency says little about the next access
+    RandomAccess,
+    Mixed,
+}
+
+impl WorkloadClass {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            WorkloadClass::Scan => {
+                "most keys are accessed only once, so the cache must resist scans"
+            }
+            WorkloadClass::TemporalLocality => {
+                "most reuses happen shortly after the previous access, so recency predicts hits"
+            }
+            WorkloadClass::RandomAccess => {
+                "reuse distances are spread out, so frequency predicts hits better than recency"
+            }
+            WorkloadClass::Mixed => "no single access pattern dominates",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct WorkloadStats {
+    // Fraction of distinct keys accessed exactly once
+    pub one_hit_fraction: f64,
+    // Fraction of reuses whose previous access is within a window of
+    // a tenth of the distinct keys
+    pub short_reuse_fraction: f64,
+    pub class: WorkloadClass,
+}
+
+pub fn classify_workload(access_records: &[AccessRecord]) -> WorkloadStats {
+    let mut last_seen: HashMap<u64, (usize, u64)> = HashMap::new(); // (last index, count)
+    let mut reuse_times = Vec::new();
+    for (i, record) in access_records.iter().enumerate() {
+        let entry = last_seen.entry(record.key).or_insert((i, 0));
+        if entry.1 > 0 {
+            reuse_times.push(i - entry.0);
+        }
+        *entry = (i, entry.1 + 1);
+    }
+
+    let unique_keys = last_seen.len().max(1);
+    let one_hit = last_seen.values().filter(|(_, count)| *count == 1).count();
+    let one_hit_fraction = one_hit as f64 / unique_keys as f64;
+    let window = (unique_keys / 10).max(1);
+    let short_reuses = reuse_times.iter().filter(|time| **time <= window).count();
+    let short_reuse_fraction = short_reuses as f64 / reuse_times.len().max(1) as f64;
+
+    let class = if one_hit_fraction > 0.6 {
+        WorkloadClass::Scan
+    } else if short_reuse_fraction > 0.5 {
+        WorkloadClass::TemporalLocality
+    } else if short_reuse_fraction < 0.2 {
+        WorkloadClass::RandomAccess
+    } else {
+        WorkloadClass::Mixed
+    };
+    WorkloadStats {
+        one_hit_fraction,
+        short_reuse_fraction,
+        class,
+    }
+}
@@ -0,0 +1,144 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::SimulationResult;
+
+// Miss ratio differences below this are treated as no change
+const TOLERANCE: f64 = 1e-9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    // The alternate configuration misses less
+    Helps,
+    Hurts,
+    Same,
+}
+
+impl std::fmt::Display for Effect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Effect::Helps => "helps",
+            Effect::Hurts => "hurts",
+            Effect::Same => "same",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DeltaPoint {
+    pub cache_size: f64,
+    pub primary: f64,
+    pub alternate: f64,
+}
+
+impl DeltaPoint {
+    // Alternate minus primary miss ratio, negative where the alternate helps
+    pub fn delta(&self) -> f64 {
+        self.alternate - self.primary
+    }
+
+    pub fn effect(&self) -> Effect {
+        let delta = self.delta();
+        if delta < -TOLERANCE {
+            Effect::Helps
+        } else if delta > TOLERANCE {
+            Effect::Hurts
+        } else {
+            Effect::Same
+        }
+    }
+}
+
+// Both curves on the primary's grid, the alternate interpolated where its grid differs
+pub fn delta_curve(primary: &SimulationResult, alternate: &SimulationResult) -> Vec<DeltaPoint> {
+    let alternate = alternate.miss_ratio_curve();
+    primary
+        .points
+        .iter()
+        .map(|(cache_size, miss_ratio)| DeltaPoint {
+            cache_size: *cache_size,
+            primary: *miss_ratio,
+            alternate: alternate.at(*cache_size as u64),
+        })
+        .collect()
+}
+
+// Runs of consecutive sizes with the same effect, as (first size, last size, effect)
+pub fn delta_regions(delta: &[DeltaPoint]) -> Vec<(f64, f64, Effect)> {
+    let mut regions: Vec<(f64, f64, Effect)> = Vec::new();
+    for point in delta {
+        match regions.last_mut() {
+            Some((_, end, effect)) if *effect == point.effect() => *end = point.cache_size,
+            _ => regions.push((point.cache_size, point.cache_size, point.effect())),
+        }
+    }
+    regions
+}
+
+// Write the delta as `cache_size,primary,alternate,delta,effect` CSV
+pub fn write_delta_curve(delta: &[DeltaPoint], path: &Path) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "cache_size,primary,alternate,delta,effect")?;
+    for point in delta {
+        writeln!(
+            writer,
+            "{},{},{},{},{}",
+            point.cache_size,
+            point.primary,
+            point.alternate,
+            point.delta(),
+            point.effect()
+        )?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CapacityUnit;
+
+    fn result(points: Vec<(f64, f64)>) -> SimulationResult {
+        SimulationResult {
+            points,
+            label: "LRU".to_string(),
+            capacity_unit: CapacityUnit::Bytes,
+            dashed: false,
+            requests: 1000,
+            passes: Vec::new(),
+            stderr: Vec::new(),
+            sampled_hits: Vec::new(),
+            sampled: None,
+            churn: Vec::new(),
+            evictions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn self_compare_has_zero_delta() {
+        let curve = result(vec![
+            (100.0, 0.9),
+            (250.0, 0.6),
+            (400.0, 0.61),
+            (1000.0, 0.2),
+        ]);
+        let delta = delta_curve(&curve, &curve);
+        assert_eq!(delta.len(), 4);
+        assert!(delta.iter().all(|point| point.delta() == 0.0));
+        assert_eq!(delta_regions(&delta), vec![(100.0, 1000.0, Effect::Same)]);
+    }
+
+    #[test]
+    fn alternate_is_interpolated_onto_the_primary_grid() {
+        let primary = result(vec![(100.0, 0.8), (200.0, 0.6)]);
+        let alternate = result(vec![(50.0, 0.8), (300.0, 0.3)]);
+        let delta = delta_curve(&primary, &alternate);
+        assert!((delta[0].alternate - 0.7).abs() < 1e-9);
+        assert_eq!(delta[0].effect(), Effect::Helps);
+        assert!((delta[1].alternate - 0.5).abs() < 1e-9);
+    }
+}
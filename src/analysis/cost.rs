@@ -0,0 +1,136 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::{config::CapacityUnit, SimulationResult};
+
+#[derive(Debug, Clone, Copy)]
+pub struct CostModel {
+    // Cost of provisioning one byte of cache
+    pub cost_per_byte: f64,
+    // Cost of serving one request from the backend
+    pub cost_per_miss: f64,
+}
+
+impl CostModel {
+    // Total cost of a cache of `cache_size` bytes serving `requests` at `miss_ratio`
+    pub fn cost(&self, cache_size: f64, miss_ratio: f64, requests: u64) -> f64 {
+        cache_size * self.cost_per_byte + miss_ratio * requests as f64 * self.cost_per_miss
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CostCurve {
+    pub label: String,
+    // (cache size, total cost) at every point of the miss ratio curve
+    pub points: Vec<(f64, f64)>,
+    // Cheapest (cache size, total cost)
+    pub optimum: (f64, f64),
+}
+
+// Walk a byte-unit miss ratio curve once, keeping the cheapest size seen so far.
+// The curve is not convex in general, so stopping at the first uphill step
+// could miss the optimum past a plateau.
+pub fn cost_curve(
+    result: &SimulationResult,
+    model: &CostModel,
+    requests: u64,
+) -> Option<CostCurve> {
+    if result.capacity_unit != CapacityUnit::Bytes {
+        return None;
+    }
+    let mut optimum: Option<(f64, f64)> = None;
+    let mut points = Vec::with_capacity(result.points.len());
+    for (cache_size, miss_ratio) in result.points.iter() {
+        let cost = model.cost(*cache_size, *miss_ratio, requests);
+        if !optimum.is_some_and(|(_, best)| best <= cost) {
+            optimum = Some((*cache_size, cost));
+        }
+        points.push((*cache_size, cost));
+    }
+    Some(CostCurve {
+        label: result.label.clone(),
+        points,
+        optimum: optimum?,
+    })
+}
+
+// Write the cost curves as `label,cache_size,cost` CSV
+pub fn write_cost_curves(curves: &[CostCurve], path: &Path) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "label,cache_size,cost")?;
+    for curve in curves {
+        for (cache_size, cost) in curve.points.iter() {
+            writeln!(writer, "{},{cache_size},{cost}", curve.label)?;
+        }
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(points: Vec<(f64, f64)>, capacity_unit: CapacityUnit) -> SimulationResult {
+        SimulationResult {
+            points,
+            label: "LRU".to_string(),
+            capacity_unit,
+            dashed: false,
+            requests: 1000,
+            passes: Vec::new(),
+            stderr: Vec::new(),
+            sampled_hits: Vec::new(),
+            sampled: None,
+            churn: Vec::new(),
+            evictions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn optimum_of_an_exponential_curve_is_where_the_slopes_cancel() {
+        // miss_ratio(s) = exp(-s / 1000), so d cost / ds = cost_per_byte -
+        // requests * cost_per_miss / 1000 * exp(-s / 1000) is 0 at
+        // s = 1000 * ln(requests * cost_per_miss / (1000 * cost_per_byte))
+        let scale = 1000.0;
+        let points = (0..=2000)
+            .map(|i| {
+                let cache_size = i as f64 * 10.0;
+                (cache_size, (-cache_size / scale).exp())
+            })
+            .collect();
+        let model = CostModel {
+            cost_per_byte: 0.001,
+            cost_per_miss: 1.0,
+        };
+        let requests = 1000;
+        let expected =
+            scale * (requests as f64 * model.cost_per_miss / (scale * model.cost_per_byte)).ln();
+
+        let curve = cost_curve(&result(points, CapacityUnit::Bytes), &model, requests).unwrap();
+        assert_eq!(curve.points.len(), 2001);
+        let (cache_size, cost) = curve.optimum;
+        assert!(
+            (cache_size - expected).abs() <= 10.0,
+            "{cache_size} vs {expected}"
+        );
+        let expected_cost = model.cost(expected, (-expected / scale).exp(), requests);
+        assert!(
+            (cost - expected_cost).abs() < 1e-3,
+            "{cost} vs {expected_cost}"
+        );
+        assert!(curve.points.iter().all(|(_, other)| *other >= cost));
+    }
+
+    #[test]
+    fn object_curves_have_no_cost() {
+        let model = CostModel {
+            cost_per_byte: 0.001,
+            cost_per_miss: 1.0,
+        };
+        let objects = result(vec![(10.0, 0.5)], CapacityUnit::Objects);
+        assert!(cost_curve(&objects, &model, 1000).is_none());
+    }
+}
@@ -0,0 +1,134 @@
+// Queries on a simulated miss ratio curve between and beyond its grid points.
+
+// (cache size, miss ratio)
+pub type MrcPoint = (f64, f64);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MissRatioCurve {
+    // Sorted by size, duplicate sizes are kept in their original order
+    points: Vec<MrcPoint>,
+}
+
+impl MissRatioCurve {
+    pub fn new(mut points: Vec<MrcPoint>) -> Self {
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self { points }
+    }
+
+    pub fn points(&self) -> &[MrcPoint] {
+        &self.points
+    }
+
+    // Miss ratio at `size`, clamped to the first and last points outside the grid.
+    // NaN for an empty curve.
+    pub fn at(&self, size: u64) -> f64 {
+        self.lookup(size).0
+    }
+
+    // Like `at`, also telling whether `size` was outside the simulated range
+    // and the value was clamped rather than interpolated
+    pub fn lookup(&self, size: u64) -> (f64, bool) {
+        let (Some(first), Some(last)) = (self.points.first(), self.points.last()) else {
+            return (f64::NAN, true);
+        };
+        let x = size as f64;
+        if x < first.0 {
+            return (first.1, true);
+        }
+        if x > last.0 {
+            return (last.1, true);
+        }
+        // First point at or past `x`, so of duplicate sizes the first one wins
+        let i = self.points.partition_point(|(px, _)| *px < x);
+        if i == 0 {
+            return (self.points[0].1, false);
+        }
+        let (x0, y0) = self.points[i - 1];
+        let (x1, y1) = self.points[i];
+        (y0 + (y1 - y0) * (x - x0) / (x1 - x0), false)
+    }
+
+    // Smallest size at which the interpolated miss ratio drops to `target`,
+    // None if the curve never gets that low. On a non-monotonic curve this is
+    // the first crossing.
+    pub fn inverse(&self, target: f64) -> Option<u64> {
+        let first = self.points.first()?;
+        if first.1 <= target {
+            return Some(first.0.ceil() as u64);
+        }
+        self.points.windows(2).find_map(|pair| {
+            let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+            if y0 > target && y1 <= target {
+                let x = x0 + (x1 - x0) * (y0 - target) / (y0 - y1);
+                Some(x.ceil() as u64)
+            } else {
+                None
+            }
+        })
+    }
+
+    // The curve interpolated at each size of `grid`
+    pub fn resample(&self, grid: &[u64]) -> Vec<MrcPoint> {
+        grid.iter()
+            .map(|size| (*size as f64, self.at(*size)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn duplicate_sizes_keep_their_order() {
+        // Two passes simulated 200, the first listed is the left end of the step
+        let curve =
+            MissRatioCurve::new(vec![(300.0, 0.2), (100.0, 0.8), (200.0, 0.6), (200.0, 0.4)]);
+        assert_eq!(
+            curve.points(),
+            &[(100.0, 0.8), (200.0, 0.6), (200.0, 0.4), (300.0, 0.2)]
+        );
+        assert!(close(curve.at(150), 0.7));
+        assert!(close(curve.at(200), 0.6));
+        assert!(close(curve.at(250), 0.3));
+        assert!(close(curve.at(300), 0.2));
+    }
+
+    #[test]
+    fn out_of_range_sizes_are_clamped_and_flagged() {
+        let curve = MissRatioCurve::new(vec![(100.0, 0.8), (300.0, 0.2)]);
+        assert_eq!(curve.lookup(50), (0.8, true));
+        assert_eq!(curve.lookup(400), (0.2, true));
+        assert_eq!(curve.lookup(100), (0.8, false));
+        let (inside, clamped) = curve.lookup(200);
+        assert!(close(inside, 0.5) && !clamped);
+        assert_eq!(
+            curve.resample(&[0, 200, 1000]),
+            vec![(0.0, 0.8), (200.0, curve.at(200)), (1000.0, 0.2)]
+        );
+
+        let empty = MissRatioCurve::default();
+        assert!(empty.at(100).is_nan());
+        assert!(empty.lookup(100).1);
+        assert_eq!(empty.inverse(0.5), None);
+    }
+
+    #[test]
+    fn inverse_finds_the_first_crossing() {
+        // The miss ratio rises again between 200 and 300
+        let curve =
+            MissRatioCurve::new(vec![(100.0, 0.8), (200.0, 0.5), (300.0, 0.6), (400.0, 0.3)]);
+        assert_eq!(curve.inverse(0.9), Some(100));
+        assert_eq!(curve.inverse(0.8), Some(100));
+        // 0.55 is crossed at 183.3 on the way down, not again past 300
+        assert_eq!(curve.inverse(0.55), Some(184));
+        assert_eq!(curve.inverse(0.5), Some(200));
+        assert_eq!(curve.inverse(0.45), Some(350));
+        assert_eq!(curve.inverse(0.1), None);
+        assert!(close(curve.at(250), 0.55));
+    }
+}
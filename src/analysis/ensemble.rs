@@ -0,0 +1,173 @@
+use crate::{config::CapacityUnit, SimulationResult};
+
+// Lower envelope of the simulated curves, one per capacity unit: at every
+// cache size, the miss ratio of whichever policy is best there.
+pub fn virtual_best(results: &[SimulationResult]) -> Vec<SimulationResult> {
+    let units = [CapacityUnit::Bytes, CapacityUnit::Objects];
+    let present = units
+        .iter()
+        .filter(|unit| results.iter().any(|result| result.capacity_unit == **unit))
+        .count();
+
+    let mut envelopes = Vec::new();
+    for unit in units {
+        let curves = results
+            .iter()
+            .filter(|result| result.capacity_unit == unit)
+            .collect::<Vec<_>>();
+        if curves.len() < 2 {
+            continue;
+        }
+        // Adaptive curves have their own grids, take the union and interpolate
+        let mut xs = curves
+            .iter()
+            .flat_map(|curve| curve.points.iter().map(|(x, _)| *x))
+            .collect::<Vec<_>>();
+        xs.sort_by(f64::total_cmp);
+        xs.dedup();
+        let mrcs = curves
+            .iter()
+            .map(|curve| curve.miss_ratio_curve())
+            .collect::<Vec<_>>();
+        let points = xs
+            .into_iter()
+            .map(|x| {
+                let best = mrcs
+                    .iter()
+                    .map(|mrc| mrc.at(x as u64))
+                    .fold(f64::INFINITY, f64::min);
+                (x, best)
+            })
+            .collect();
+        let label = if present > 1 {
+            format!("virtual best ({unit})")
+        } else {
+            "virtual best".to_string()
+        };
+        envelopes.push(SimulationResult {
+            points,
+            label,
+            capacity_unit: unit,
+            dashed: true,
+            requests: curves[0].requests,
+            passes: Vec::new(),
+            stderr: Vec::new(),
+            sampled_hits: Vec::new(),
+            churn: Vec::new(),
+            sampled: None,
+            evictions: Vec::new(),
+        });
+    }
+    envelopes
+}
+
+// The policy with the lowest miss ratio at the simulated byte size closest to `target`
+pub fn best_policy_at(results: &[SimulationResult], target: u64) -> Option<(&str, f64)> {
+    results
+        .iter()
+        .filter(|result| result.capacity_unit == CapacityUnit::Bytes)
+        .filter_map(|result| {
+            result
+                .points
+                .iter()
+                .min_by(|a, b| {
+                    let da = (a.0 - target as f64).abs();
+                    let db = (b.0 - target as f64).abs();
+                    da.total_cmp(&db)
+                })
+                .map(|(_, miss_ratio)| (result.label.as_str(), *miss_ratio))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::EvictionPolicy, tests::simulate_golden};
+
+    fn result(
+        label: &str,
+        capacity_unit: CapacityUnit,
+        points: Vec<(f64, f64)>,
+    ) -> SimulationResult {
+        SimulationResult {
+            points,
+            label: label.to_string(),
+            capacity_unit,
+            dashed: false,
+            requests: 1000,
+            passes: Vec::new(),
+            stderr: Vec::new(),
+            sampled_hits: Vec::new(),
+            churn: Vec::new(),
+            sampled: None,
+            evictions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn envelope_is_the_pointwise_minimum() {
+        let results = simulate_golden(|config| {
+            config.policies = Some(vec![
+                EvictionPolicy::LRU,
+                EvictionPolicy::FIFO,
+                EvictionPolicy::LFU,
+            ]);
+        });
+        let envelopes = virtual_best(&results);
+        assert_eq!(envelopes.len(), 1);
+        let envelope = &envelopes[0];
+        assert_eq!(envelope.label, "virtual best");
+        assert!(envelope.dashed);
+        assert_eq!(envelope.points.len(), results[0].points.len());
+        for (i, (x, best)) in envelope.points.iter().enumerate() {
+            let minimum = results
+                .iter()
+                .map(|result| {
+                    assert_eq!(result.points[i].0, *x);
+                    result.points[i].1
+                })
+                .fold(f64::INFINITY, f64::min);
+            assert_eq!(*best, minimum, "at {x}");
+        }
+    }
+
+    #[test]
+    fn crossing_curves_are_enveloped_per_unit() {
+        // FIFO is better below 300, LRU above, on different grids
+        let results = vec![
+            result("LRU", CapacityUnit::Bytes, vec![(100.0, 0.9), (500.0, 0.1)]),
+            result(
+                "FIFO",
+                CapacityUnit::Bytes,
+                vec![(100.0, 0.5), (300.0, 0.4), (500.0, 0.3)],
+            ),
+            result("LRU", CapacityUnit::Objects, vec![(1.0, 0.8), (2.0, 0.6)]),
+            result("FIFO", CapacityUnit::Objects, vec![(1.0, 0.7), (2.0, 0.7)]),
+        ];
+        let envelopes = virtual_best(&results);
+        let labels = envelopes
+            .iter()
+            .map(|envelope| envelope.label.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            labels,
+            vec!["virtual best (bytes)", "virtual best (objects)"]
+        );
+        // Interpolating LRU onto FIFO's grid may round its own points
+        let close = |points: &[(f64, f64)], expected: &[(f64, f64)]| {
+            points.len() == expected.len()
+                && points
+                    .iter()
+                    .zip(expected)
+                    .all(|(a, b)| a.0 == b.0 && (a.1 - b.1).abs() < 1e-9)
+        };
+        assert!(close(
+            &envelopes[0].points,
+            &[(100.0, 0.5), (300.0, 0.4), (500.0, 0.1)]
+        ));
+        assert!(close(&envelopes[1].points, &[(1.0, 0.7), (2.0, 0.6)]));
+        assert_eq!(best_policy_at(&results, 120), Some(("FIFO", 0.5)));
+        assert_eq!(best_policy_at(&results, 450), Some(("LRU", 0.1)));
+    }
+}
@@ -0,0 +1,66 @@
+use hashbrown::HashMap;
+
+use crate::AccessRecord;
+
+use super::Histogram;
+
+#[derive(Debug)]
+pub struct Footprint {
+    pub requests: u64,
+    pub unique_keys: u64,
+    // Sum of the first seen size of every distinct key
+    pub unique_bytes: u64,
+    pub mean_object_size: f64,
+    // Object size distribution over the distinct keys, in log2 buckets
+    pub size_histogram: Histogram,
+}
+
+pub fn footprint(access_records: &[AccessRecord]) -> Footprint {
+    let mut sizes = HashMap::new();
+    for record in access_records {
+        sizes.entry(record.key).or_insert(record.size as u64);
+    }
+    let unique_keys = sizes.len() as u64;
+    let unique_bytes = sizes.values().sum::<u64>();
+    let mut size_histogram = Histogram::log(1.0, 2f64.powi(32), 32);
+    for size in sizes.values() {
+        size_histogram.record(*size as f64);
+    }
+    let mean_object_size = if unique_keys == 0 {
+        0.0
+    } else {
+        unique_bytes as f64 / unique_keys as f64
+    };
+    Footprint {
+        requests: access_records.len() as u64,
+        unique_keys,
+        unique_bytes,
+        mean_object_size,
+        size_histogram,
+    }
+}
+
+impl Footprint {
+    // Share of the requests that are the first to their key, the miss ratio of
+    // an unbounded cache. An empty cache misses every request.
+    pub fn compulsory_miss_ratio(&self) -> f64 {
+        self.unique_keys as f64 / self.requests.max(1) as f64
+    }
+}
+
+// Heuristic check that the cache size and the trace size column use the same unit
+pub fn check_size_units(footprint: &Footprint, max_cache_size: u64) -> Result<(), String> {
+    if (max_cache_size as f64) < 10.0 * footprint.mean_object_size {
+        return Err(format!(
+            "cache size {} holds fewer than 10 objects (mean object size {:.1}), is the size column in a different unit?",
+            max_cache_size, footprint.mean_object_size
+        ));
+    }
+    if max_cache_size as f64 > 1000.0 * footprint.unique_bytes as f64 {
+        return Err(format!(
+            "cache size {} is over 1000x the unique bytes of the trace ({}), is the size column in a different unit? Try --size-multiplier",
+            max_cache_size, footprint.unique_bytes
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,103 @@
+// Bucketed counts shared by the analysis modules so they all bucket the same way.
+// Bucket i covers [edges[i], edges[i + 1]); values outside the edges are
+// clamped into the first or last bucket.
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    buckets: Vec<u64>,
+    edges: Vec<f64>,
+}
+
+impl Histogram {
+    // `num_buckets` buckets of equal width between `min` and `max`
+    pub fn linear(min: f64, max: f64, num_buckets: usize) -> Self {
+        assert!(max > min && num_buckets > 0);
+        let width = (max - min) / num_buckets as f64;
+        let edges = (0..=num_buckets).map(|i| min + width * i as f64).collect();
+        Self::with_edges(edges)
+    }
+
+    // `num_buckets` buckets whose edges grow geometrically from `min` to `max`
+    pub fn log(min: f64, max: f64, num_buckets: usize) -> Self {
+        assert!(min > 0.0 && max > min && num_buckets > 0);
+        let ratio = (max / min).powf(1.0 / num_buckets as f64);
+        let edges = (0..=num_buckets)
+            .map(|i| min * ratio.powi(i as i32))
+            .collect();
+        Self::with_edges(edges)
+    }
+
+    // Custom edges, which must be sorted and contain at least two values
+    pub fn with_edges(edges: Vec<f64>) -> Self {
+        assert!(edges.len() >= 2, "a histogram needs at least two edges");
+        assert!(
+            edges.windows(2).all(|pair| pair[0] < pair[1]),
+            "histogram edges must be strictly increasing"
+        );
+        Histogram {
+            buckets: vec![0; edges.len() - 1],
+            edges,
+        }
+    }
+
+    pub fn record(&mut self, value: f64) {
+        self.record_n(value, 1);
+    }
+
+    pub fn record_n(&mut self, value: f64, count: u64) {
+        let bucket = self.bucket_of(value);
+        self.buckets[bucket] += count;
+    }
+
+    pub fn bucket_of(&self, value: f64) -> usize {
+        self.edges
+            .partition_point(|edge| *edge <= value)
+            .saturating_sub(1)
+            .min(self.buckets.len() - 1)
+    }
+
+    pub fn count_at(&self, bucket: usize) -> u64 {
+        self.buckets[bucket]
+    }
+
+    pub fn num_buckets(&self) -> usize {
+        self.buckets.len()
+    }
+
+    pub fn edges(&self) -> &[f64] {
+        &self.edges
+    }
+
+    pub fn total(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    // (upper edge, fraction of values in this or a lower bucket) per bucket
+    pub fn cdf(&self) -> Vec<(f64, f64)> {
+        let total = self.total().max(1) as f64;
+        let mut cumulative = 0;
+        self.buckets
+            .iter()
+            .enumerate()
+            .map(|(i, count)| {
+                cumulative += count;
+                (self.edges[i + 1], cumulative as f64 / total)
+            })
+            .collect()
+    }
+
+    // Value below which `p` percent of the values fall, interpolated linearly
+    // within the bucket that contains it
+    pub fn percentile(&self, p: f64) -> f64 {
+        let target = p.clamp(0.0, 100.0) / 100.0 * self.total() as f64;
+        let mut cumulative = 0.0;
+        for (i, count) in self.buckets.iter().enumerate() {
+            let count = *count as f64;
+            if count > 0.0 && cumulative + count >= target {
+                let (lo, hi) = (self.edges[i], self.edges[i + 1]);
+                return lo + (hi - lo) * (target - cumulative) / count;
+            }
+            cumulative += count;
+        }
+        self.edges[self.edges.len() - 1]
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::BTreeSet;
+
+use hashbrown::HashMap;
+
+use crate::Key;
+
+// Per-key access counter with a cap on the number of distinct keys tracked.
+// Counts are exact until the cap is reached; after that it degrades into the
+// space-saving heavy hitters algorithm: a new key replaces the key with the
+// smallest count and inherits that count as its overestimation error.
+pub struct KeyCounter {
+    capacity: usize,
+    counts: HashMap<Key, (u64, u64)>, // (count, error)
+    by_count: BTreeSet<(u64, Key)>,
+    exact: bool,
+}
+
+impl KeyCounter {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            counts: HashMap::new(),
+            by_count: BTreeSet::new(),
+            exact: true,
+        }
+    }
+
+    pub fn record(&mut self, key: Key) {
+        if let Some((count, _)) = self.counts.get_mut(&key) {
+            self.by_count.remove(&(*count, key));
+            *count += 1;
+            self.by_count.insert((*count, key));
+            return;
+        }
+
+        if self.counts.len() < self.capacity {
+            self.counts.insert(key, (1, 0));
+            self.by_count.insert((1, key));
+            return;
+        }
+
+        // Over the cap, take over the slot of the coldest key
+        let (min_count, victim) = self.by_count.pop_first().unwrap();
+        self.counts.remove(&victim);
+        self.counts.insert(key, (min_count + 1, min_count));
+        self.by_count.insert((min_count + 1, key));
+        self.exact = false;
+    }
+
+    // Estimated count, never lower than the real one
+    pub fn count(&self, key: Key) -> u64 {
+        self.counts.get(&key).map(|(count, _)| *count).unwrap_or(0)
+    }
+
+    pub fn is_exact(&self) -> bool {
+        self.exact
+    }
+
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    // The k most frequent keys, hottest first
+    pub fn top_k(&self, k: usize) -> Vec<(Key, u64)> {
+        self.by_count
+            .iter()
+            .rev()
+            .take(k)
+            .map(|&(count, key)| (key, count))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Keys 1..=3 take 400, 250 and 167 accesses among 2000 keys seen once
+    fn skewed_stream() -> Vec<Key> {
+        let mut keys = Vec::new();
+        for i in 0..2000u64 {
+            keys.push(1000 + i);
+            if i % 5 == 0 {
+                keys.push(1);
+            }
+            if i % 8 == 0 {
+                keys.push(2);
+            }
+            if i % 12 == 0 {
+                keys.push(3);
+            }
+        }
+        keys
+    }
+
+    #[test]
+    fn heavy_hitters_survive_the_cap() {
+        let stream = skewed_stream();
+        // Space-saving keeps every key seen more than 2817 / 64 times
+        let mut counter = KeyCounter::new(64);
+        for key in stream.iter() {
+            counter.record(*key);
+        }
+        assert!(!counter.is_exact());
+        assert_eq!(counter.len(), 64);
+        let top = counter.top_k(3);
+        let keys = top.iter().map(|(key, _)| *key).collect::<Vec<_>>();
+        assert_eq!(keys, vec![1, 2, 3]);
+        // Space-saving only ever overestimates
+        for (key, count) in top {
+            let real = stream.iter().filter(|k| **k == key).count() as u64;
+            assert!(count >= real, "key {key}: {count} < {real}");
+        }
+    }
+
+    #[test]
+    fn counts_are_exact_under_the_cap() {
+        let mut counter = KeyCounter::new(usize::MAX);
+        for key in skewed_stream() {
+            counter.record(key);
+        }
+        assert!(counter.is_exact());
+        assert_eq!(counter.len(), 2003);
+        assert_eq!(counter.top_k(3), vec![(1, 400), (2, 250), (3, 167)]);
+        assert_eq!(counter.count(1000), 1);
+        assert_eq!(counter.count(7), 0);
+    }
+}
@@ -0,0 +1,42 @@
+use std::collections::BTreeMap;
+
+use hashbrown::HashSet;
+
+use crate::{AccessRecord, Key};
+
+// The mask of the top `bits` bits of a key
+pub fn top_bits_mask(bits: u32) -> u64 {
+    match bits {
+        0 => 0,
+        bits => u64::MAX << (64 - bits.min(64)),
+    }
+}
+
+// The group of `key`: its bits under `mask`, shifted down to start at bit 0
+pub fn key_group(key: Key, mask: u64) -> u64 {
+    (key & mask) >> mask.trailing_zeros().min(63)
+}
+
+// The records of every group of keys, by group id, in trace order. Err with
+// the number of groups found when there are more than `max_groups`.
+pub fn split_by_key_group(
+    access_records: &[AccessRecord],
+    mask: u64,
+    max_groups: usize,
+) -> Result<BTreeMap<u64, Vec<AccessRecord>>, usize> {
+    let groups = access_records
+        .iter()
+        .map(|record| key_group(record.key, mask))
+        .collect::<HashSet<_>>();
+    if groups.len() > max_groups {
+        return Err(groups.len());
+    }
+    let mut split: BTreeMap<u64, Vec<AccessRecord>> = BTreeMap::new();
+    for record in access_records {
+        split
+            .entry(key_group(record.key, mask))
+            .or_default()
+            .push(*record);
+    }
+    Ok(split)
+}
@@ -0,0 +1,65 @@
+// Point selection for adaptive curves: a coarse log-spaced pass, then
+// refinement passes around the point of maximum curvature.
+
+// `n` distinct sizes spaced evenly on a log scale from `min` to `max`
+pub fn log_spaced(min: u64, max: u64, n: usize) -> Vec<u64> {
+    let min = min.max(1) as f64;
+    let max = (max as f64).max(min);
+    let mut sizes = (0..n)
+        .map(|i| {
+            let t = if n > 1 {
+                i as f64 / (n - 1) as f64
+            } else {
+                1.0
+            };
+            (min * (max / min).powf(t)).round() as u64
+        })
+        .collect::<Vec<_>>();
+    sizes.dedup();
+    sizes
+}
+
+// Index of the point where the slope in (log size, miss ratio) changes the
+// most. `points` must be sorted by size.
+fn max_curvature_index(points: &[(f64, f64)]) -> Option<usize> {
+    let slope = |a: (f64, f64), b: (f64, f64)| {
+        let dx = b.0.max(1.0).ln() - a.0.max(1.0).ln();
+        if dx > 0.0 {
+            (b.1 - a.1) / dx
+        } else {
+            0.0
+        }
+    };
+    (1..points.len().saturating_sub(1))
+        .map(|i| {
+            let curvature =
+                (slope(points[i], points[i + 1]) - slope(points[i - 1], points[i])).abs();
+            (curvature, i)
+        })
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, i)| i)
+}
+
+// The interval around the point of maximum curvature
+pub fn max_curvature_interval(points: &[(f64, f64)]) -> Option<(f64, f64)> {
+    max_curvature_index(points).map(|i| (points[i - 1].0, points[i + 1].0))
+}
+
+// The knee of the curve, its point of maximum curvature
+pub fn knee_point(points: &[(f64, f64)]) -> Option<(f64, f64)> {
+    max_curvature_index(points).map(|i| points[i])
+}
+
+// Up to `n` new sizes inside the interval of maximum curvature, skipping sizes
+// already on the curve
+pub fn refine_sizes(points: &[(f64, f64)], n: usize) -> Vec<u64> {
+    let Some((low, high)) = max_curvature_interval(points) else {
+        return Vec::new();
+    };
+    let sizes = log_spaced(low as u64, high as u64, n + 2);
+    sizes
+        .into_iter()
+        .filter(|size| !points.iter().any(|(x, _)| *x as u64 == *size))
+        .take(n)
+        .collect()
+}
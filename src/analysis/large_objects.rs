@@ -0,0 +1,65 @@
+use hashbrown::HashMap;
+
+use crate::{AccessRecord, Key};
+
+// An object larger than most of the swept cache sizes. A smaller cache holds
+// it alone, flushing everything else, so the curve steps where it starts to fit.
+#[derive(Debug, Clone, Copy)]
+pub struct LargeObject {
+    pub key: Key,
+    // The largest size the key was requested with
+    pub size: u64,
+    pub requests: u64,
+}
+
+// The miss ratio of a curve on either side of where an object starts to fit
+#[derive(Debug, Clone, Copy)]
+pub struct Step {
+    // The largest point too small for the object, None when the curve starts
+    // at a size that already holds it
+    pub below: Option<(f64, f64)>,
+    // The smallest point that holds it
+    pub fits: (f64, f64),
+}
+
+impl Step {
+    pub fn miss_ratio_drop(&self) -> f64 {
+        self.below
+            .map_or(0.0, |(_, miss_ratio)| miss_ratio - self.fits.1)
+    }
+}
+
+// The keys ever requested with a size over `threshold`, largest first
+pub fn large_objects(access_records: &[AccessRecord], threshold: u64) -> Vec<LargeObject> {
+    let mut objects: HashMap<Key, LargeObject> = HashMap::new();
+    for record in access_records {
+        if record.size as u64 > threshold {
+            let object = objects.entry(record.key).or_insert(LargeObject {
+                key: record.key,
+                size: 0,
+                requests: 0,
+            });
+            object.size = object.size.max(record.size as u64);
+        }
+    }
+    // Requests made while the key was still small count too
+    for record in access_records {
+        if let Some(object) = objects.get_mut(&record.key) {
+            object.requests += 1;
+        }
+    }
+    let mut objects = objects.into_values().collect::<Vec<_>>();
+    objects.sort_by_key(|object| (std::cmp::Reverse(object.size), object.key));
+    objects
+}
+
+// Where an object of `size` starts to fit on the curve of `points`, sorted by
+// size. None when no point holds it.
+pub fn step_at(points: &[(f64, f64)], size: u64) -> Option<Step> {
+    let i = points.partition_point(|(cache_size, _)| *cache_size < size as f64);
+    let fits = *points.get(i)?;
+    Some(Step {
+        below: i.checked_sub(1).map(|below| points[below]),
+        fits,
+    })
+}
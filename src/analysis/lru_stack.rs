@@ -0,0 +1,104 @@
+use hashbrown::HashMap;
+
+use crate::Key;
+
+use super::reuse::Fenwick;
+
+// LRU hits at every cache size from one pass over the trace. An LRU cache that
+// evicts until the incoming object fits always holds the most recently
+// requested keys that fit, so a request hits every cache at least as large as
+// the bytes of the distinct keys requested since its key was last requested,
+// its own included. That only holds while every key keeps one size: on a hit
+// the cache keeps the size it was put with.
+pub struct LruStack {
+    // Position and size of the last request to every key
+    last_seen: HashMap<Key, (usize, u64)>,
+    // The size of each key at the position it was last requested at
+    marks: Fenwick,
+    position: usize,
+}
+
+impl LruStack {
+    // Room for `requests` requests
+    pub fn new(requests: usize) -> Self {
+        LruStack {
+            last_seen: HashMap::new(),
+            marks: Fenwick::new(requests),
+            position: 0,
+        }
+    }
+
+    // The smallest capacity a request for `key` of `size` hits at, u64::MAX
+    // for the first request to the key
+    pub fn access(&mut self, key: Key, size: u64) -> u64 {
+        let position = self.position;
+        self.position += 1;
+        let previous = self.last_seen.insert(key, (position, size));
+        let need = match previous {
+            // Nothing was put since, not even an object larger than the cache
+            // evicted it
+            Some((previous, _)) if previous + 1 == position => 0,
+            Some((previous, _)) => self.marks.prefix(position) - self.marks.prefix(previous),
+            None => u64::MAX,
+        };
+        if let Some((previous, previous_size)) = previous {
+            self.marks.add(previous, -(previous_size as i64));
+        }
+        self.marks.add(position, size as i64);
+        need
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evict_policy::{EvictPolicy, LruPolicy, NewPolicy};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    // Replay a random skewed trace through the stack and through simulated
+    // LRU caches, checking every request hits the same caches in both
+    fn check_against_lru(seed: u64, max_size: u64, capacities: &[u64]) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let sizes = (0..300)
+            .map(|_| rng.gen_range(1..=max_size))
+            .collect::<Vec<_>>();
+        let requests = 5000;
+        let mut stack = LruStack::new(requests);
+        let mut caches = capacities
+            .iter()
+            .map(|capacity| LruPolicy::new(*capacity))
+            .collect::<Vec<_>>();
+        for request in 0..requests {
+            let key = rng.gen_range(0..300).min(rng.gen_range(0..300));
+            let size = sizes[key as usize];
+            let need = stack.access(key, size);
+            for (cache, capacity) in caches.iter_mut().zip(capacities) {
+                let hit = cache.get(key).is_some();
+                if !hit {
+                    cache.put(key, size);
+                }
+                assert_eq!(
+                    hit,
+                    need <= *capacity,
+                    "seed {seed}, request {request}: key {key} of size {size} needs {need}, \
+                     capacity {capacity}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn hits_match_simulated_lru_by_bytes() {
+        // The smallest caches are below the largest objects
+        for seed in 0..8 {
+            check_against_lru(seed, 400, &[1, 100, 399, 1000, 5000, 20000, 100_000]);
+        }
+    }
+
+    #[test]
+    fn hits_match_simulated_lru_by_objects() {
+        for seed in 0..8 {
+            check_against_lru(seed, 1, &[1, 2, 5, 20, 80, 150, 300]);
+        }
+    }
+}
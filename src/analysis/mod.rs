@@ -0,0 +1,50 @@
+mod classify;
+mod compare;
+mod cost;
+mod curve;
+mod ensemble;
+mod footprint;
+mod histogram;
+mod key_counter;
+mod key_groups;
+mod knee;
+mod large_objects;
+mod lru_stack;
+mod recommendation;
+mod reuse;
+mod size_class;
+mod smooth;
+mod tiers;
+mod time_buckets;
+mod trace_stats;
+
+pub use classify::{classify_workload, WorkloadClass, WorkloadStats};
+pub use compare::{delta_curve, delta_regions, write_delta_curve, DeltaPoint, Effect};
+pub use cost::{cost_curve, write_cost_curves, CostCurve, CostModel};
+pub use curve::{MissRatioCurve, MrcPoint};
+pub use ensemble::{best_policy_at, virtual_best};
+pub use footprint::{check_size_units, footprint, Footprint};
+pub use histogram::Histogram;
+pub use key_counter::KeyCounter;
+pub use key_groups::{key_group, split_by_key_group, top_bits_mask};
+pub use knee::{knee_point, log_spaced, max_curvature_interval, refine_sizes};
+pub use large_objects::{large_objects, step_at, LargeObject, Step};
+pub use lru_stack::LruStack;
+pub use recommendation::recommend_policy;
+pub use reuse::{reuse_distances, ReuseDistances};
+pub use size_class::{write_size_classes, SizeClass, SizeClassCounter};
+pub use smooth::smooth_sampled;
+pub use tiers::{tier_report, Tier, TierReport, TierStats};
+pub use time_buckets::{write_time_buckets, TimeBucket, TimeBucketCounter};
+pub use trace_stats::{trace_stats, TraceStats};
+
+use crate::AccessRecord;
+
+// Count accesses per key, bounded by `max_tracked_keys` distinct keys
+pub fn count_keys(access_records: &[AccessRecord], max_tracked_keys: Option<usize>) -> KeyCounter {
+    let mut counter = KeyCounter::new(max_tracked_keys.unwrap_or(usize::MAX));
+    for record in access_records {
+        counter.record(record.key);
+    }
+    counter
+}
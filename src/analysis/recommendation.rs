@@ -0,0 +1,17 @@
+use crate::config::EvictionPolicy;
+
+use super::WorkloadClass;
+
+// Pick a policy for the workload class among the implemented policies.
+// `cache_size_fraction` is the cache size over the trace's unique bytes.
+pub fn recommend_policy(classification: WorkloadClass, cache_size_fraction: f64) -> EvictionPolicy {
+    match classification {
+        // 2Q keeps one-hit keys in its cold queue
+        WorkloadClass::Scan => EvictionPolicy::TWOQ,
+        WorkloadClass::TemporalLocality => EvictionPolicy::LRU,
+        // With room for most of the working set the policy barely matters, take the cheapest
+        WorkloadClass::RandomAccess if cache_size_fraction >= 0.5 => EvictionPolicy::FIFO,
+        WorkloadClass::RandomAccess => EvictionPolicy::LFU,
+        WorkloadClass::Mixed => EvictionPolicy::TWOQ,
+    }
+}
@@ -0,0 +1,143 @@
+use hashbrown::HashMap;
+
+use crate::AccessRecord;
+
+use super::Histogram;
+
+// Reuse distances of a trace: for every request to a key seen before, the
+// number of distinct keys requested since the previous request to it. First
+// requests have an infinite distance and are counted as cold.
+#[derive(Debug, Clone)]
+pub struct ReuseDistances {
+    // Log2 buckets of the finite distances, distance 0 falls in the first
+    pub histogram: Histogram,
+    pub cold: u64,
+}
+
+impl ReuseDistances {
+    pub fn requests(&self) -> u64 {
+        self.histogram.total() + self.cold
+    }
+
+    // (upper edge, fraction of the requests whose reuse distance is below it)
+    // per bucket. Ends at 1 minus the cold fraction, the gap to 1 is the
+    // compulsory misses.
+    pub fn cdf(&self) -> Vec<(f64, f64)> {
+        let finite = self.histogram.total();
+        let scale = finite as f64 / self.requests().max(1) as f64;
+        self.histogram
+            .cdf()
+            .into_iter()
+            .map(|(distance, fraction)| (distance, fraction * scale))
+            .collect()
+    }
+
+    pub fn cold_fraction(&self) -> f64 {
+        self.cold as f64 / self.requests().max(1) as f64
+    }
+}
+
+// Fenwick tree over request positions, with a weight where a key was last
+// requested: 1 for reuse distances, its size for LRU stack distances
+pub(super) struct Fenwick(Vec<u64>);
+
+impl Fenwick {
+    pub(super) fn new(positions: usize) -> Self {
+        Fenwick(vec![0; positions])
+    }
+
+    pub(super) fn add(&mut self, position: usize, delta: i64) {
+        let mut i = position + 1;
+        while i <= self.0.len() {
+            self.0[i - 1] = self.0[i - 1].wrapping_add_signed(delta);
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    // Sum over positions 0..end
+    pub(super) fn prefix(&self, end: usize) -> u64 {
+        let mut sum = 0;
+        let mut i = end;
+        while i > 0 {
+            sum += self.0[i - 1];
+            i -= i & i.wrapping_neg();
+        }
+        sum
+    }
+}
+
+// Exact reuse distances in O(n log n): the distinct keys between two requests
+// to a key are the keys whose last request falls between them
+pub fn reuse_distances(access_records: &[AccessRecord]) -> ReuseDistances {
+    let mut histogram = Histogram::log(1.0, 2f64.powi(32), 32);
+    let mut cold = 0;
+    let mut last_seen = HashMap::new();
+    let mut marks = Fenwick::new(access_records.len());
+    for (i, record) in access_records.iter().enumerate() {
+        match last_seen.insert(record.key, i) {
+            Some(previous) => {
+                let distance = marks.prefix(i) - marks.prefix(previous + 1);
+                histogram.record(distance as f64);
+                marks.add(previous, -1);
+            }
+            None => cold += 1,
+        }
+        marks.add(i, 1);
+    }
+    ReuseDistances { histogram, cold }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{analysis::footprint, synthetic::ZipfTrace};
+
+    fn records(keys: &[u64]) -> Vec<AccessRecord> {
+        keys.iter()
+            .enumerate()
+            .map(|(i, key)| AccessRecord {
+                timestamp: i as u64,
+                command: 0,
+                key: *key,
+                size: 1,
+                ttl: 0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn cdf_of_a_short_trace() {
+        // Reuses at distances 1 (1 over 2), 2 (2 over 1, 3) and 2 (1 over 3, 2)
+        let reuse = reuse_distances(&records(&[1, 2, 1, 3, 2, 1]));
+        assert_eq!((reuse.histogram.total(), reuse.cold), (3, 3));
+        let cdf = reuse.cdf();
+        assert_eq!(cdf[0], (2.0, 1.0 / 6.0));
+        assert_eq!(cdf[1], (4.0, 0.5));
+        assert!(cdf[2..].iter().all(|(_, fraction)| *fraction == 0.5));
+        assert_eq!(reuse.cold_fraction(), 0.5);
+    }
+
+    #[test]
+    fn cdf_rises_to_the_compulsory_miss_complement() {
+        let trace = ZipfTrace {
+            requests: 100_000,
+            keys: 10_000,
+            alpha: 0.9,
+            min_size: 1,
+            max_size: 1,
+            seed: 11,
+        }
+        .generate();
+        let reuse = reuse_distances(&trace);
+        let compulsory = footprint(&trace).compulsory_miss_ratio();
+        assert_eq!(reuse.requests(), 100_000);
+        assert_eq!(reuse.cold_fraction(), compulsory);
+
+        let cdf = reuse.cdf();
+        assert!(cdf.first().unwrap().1 >= 0.0);
+        assert!(cdf.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+        let (_, finite) = *cdf.last().unwrap();
+        assert!((finite - (1.0 - compulsory)).abs() < 1e-12, "{finite}");
+        assert!((finite + reuse.cold_fraction() - 1.0).abs() < 1e-12);
+    }
+}
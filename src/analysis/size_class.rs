@@ -0,0 +1,108 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use super::Histogram;
+
+// Requests and hits of the objects whose size falls in [lower, upper)
+#[derive(Debug, Clone, PartialEq)]
+pub struct SizeClass {
+    pub lower: f64,
+    pub upper: f64,
+    pub requests: u64,
+    pub bytes: u64,
+    pub hits: u64,
+    pub hit_bytes: u64,
+}
+
+impl SizeClass {
+    pub fn hit_ratio(&self) -> f64 {
+        self.hits as f64 / self.requests.max(1) as f64
+    }
+
+    pub fn byte_hit_ratio(&self) -> f64 {
+        self.hit_bytes as f64 / self.bytes.max(1) as f64
+    }
+}
+
+// Requests, bytes and hits per object size class at one cache size
+#[derive(Debug, Clone)]
+pub struct SizeClassCounter {
+    histogram: Histogram,
+    bytes: Vec<u64>,
+    hits: Vec<u64>,
+    hit_bytes: Vec<u64>,
+}
+
+impl SizeClassCounter {
+    // Classes between the given byte edges, log2 classes up to 4GB without any
+    pub fn new(edges: Option<&[u64]>) -> Self {
+        let histogram = match edges {
+            Some(edges) => Histogram::with_edges(edges.iter().map(|edge| *edge as f64).collect()),
+            None => Histogram::log(1.0, 2f64.powi(32), 32),
+        };
+        let num_buckets = histogram.num_buckets();
+        SizeClassCounter {
+            histogram,
+            bytes: vec![0; num_buckets],
+            hits: vec![0; num_buckets],
+            hit_bytes: vec![0; num_buckets],
+        }
+    }
+
+    pub fn record(&mut self, size: u32, hit: bool) {
+        let class = self.histogram.bucket_of(size as f64);
+        self.histogram.record(size as f64);
+        self.bytes[class] += size as u64;
+        if hit {
+            self.hits[class] += 1;
+            self.hit_bytes[class] += size as u64;
+        }
+    }
+
+    // The classes that saw requests, counts multiplied by `scale` to undo sampling
+    pub fn classes(&self, scale: f64) -> Vec<SizeClass> {
+        let edges = self.histogram.edges();
+        let scaled = |count: u64| (count as f64 * scale).round() as u64;
+        (0..self.histogram.num_buckets())
+            .filter(|class| self.histogram.count_at(*class) > 0)
+            .map(|class| SizeClass {
+                lower: edges[class],
+                upper: edges[class + 1],
+                requests: scaled(self.histogram.count_at(class)),
+                bytes: scaled(self.bytes[class]),
+                hits: scaled(self.hits[class]),
+                hit_bytes: scaled(self.hit_bytes[class]),
+            })
+            .collect()
+    }
+}
+
+// Write `label,size_class_lower,size_class_upper,requests,bytes,hit_ratio,byte_hit_ratio` CSV
+pub fn write_size_classes(
+    reports: &[(String, Vec<SizeClass>)],
+    path: &Path,
+) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(
+        writer,
+        "label,size_class_lower,size_class_upper,requests,bytes,hit_ratio,byte_hit_ratio"
+    )?;
+    for (label, classes) in reports {
+        for class in classes {
+            writeln!(
+                writer,
+                "{label},{},{},{},{},{:.6},{:.6}",
+                class.lower,
+                class.upper,
+                class.requests,
+                class.bytes,
+                class.hit_ratio(),
+                class.byte_hit_ratio()
+            )?;
+        }
+    }
+    writer.flush()
+}
@@ -0,0 +1,51 @@
+// Smoothing of sampled curves for display: with a low SHARDS rate the
+// smallest cache sizes rest on a handful of sampled hits each, and their miss
+// ratios jump around from point to point.
+
+use crate::SimulationResult;
+
+// Merge runs of adjacent points, from the smallest size up, until each run
+// holds at least `min_samples` sampled hits. A run becomes one point at its
+// mean size and mean miss ratio. A short run left at the largest sizes joins
+// the run before it. Curves without sampled hits come back as they are.
+pub fn smooth_sampled(result: &SimulationResult, min_samples: u64) -> SimulationResult {
+    if result.sampled_hits.len() != result.points.len() {
+        return result.clone();
+    }
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    let (mut start, mut samples) = (0, 0);
+    for (i, hits) in result.sampled_hits.iter().enumerate() {
+        samples += hits;
+        if samples >= min_samples {
+            runs.push((start, i + 1));
+            (start, samples) = (i + 1, 0);
+        }
+    }
+    if start < result.points.len() {
+        match runs.last_mut() {
+            Some(last) => last.1 = result.points.len(),
+            None => runs.push((start, result.points.len())),
+        }
+    }
+    let mean = |values: &[f64]| values.iter().sum::<f64>() / values.len() as f64;
+    let mut smoothed = SimulationResult {
+        points: Vec::with_capacity(runs.len()),
+        passes: Vec::new(),
+        stderr: Vec::new(),
+        sampled_hits: Vec::with_capacity(runs.len()),
+        churn: Vec::new(),
+        ..result.clone()
+    };
+    for (start, end) in runs {
+        let (sizes, miss_ratios): (Vec<f64>, Vec<f64>) =
+            result.points[start..end].iter().copied().unzip();
+        smoothed.points.push((mean(&sizes), mean(&miss_ratios)));
+        smoothed
+            .sampled_hits
+            .push(result.sampled_hits[start..end].iter().sum());
+        if result.stderr.len() == result.points.len() {
+            smoothed.stderr.push(mean(&result.stderr[start..end]));
+        }
+    }
+    smoothed
+}
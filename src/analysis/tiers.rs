@@ -0,0 +1,132 @@
+use hashbrown::HashMap;
+
+use crate::AccessRecord;
+
+use super::KeyCounter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tier {
+    Cold,
+    Warm,
+    Hot,
+}
+
+impl std::fmt::Display for Tier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Tier::Cold => "cold",
+            Tier::Warm => "warm",
+            Tier::Hot => "hot",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TierStats {
+    pub objects: u64,
+    // Sum of the first seen size of the keys in the tier
+    pub bytes: u64,
+    pub requests: u64,
+    // Hits with an unbounded cache: every request but the first of each key
+    pub hits: u64,
+}
+
+#[derive(Debug)]
+pub struct TierReport {
+    pub cold: TierStats,
+    pub warm: TierStats,
+    pub hot: TierStats,
+}
+
+impl TierReport {
+    pub fn tiers(&self) -> [(Tier, &TierStats); 3] {
+        [
+            (Tier::Cold, &self.cold),
+            (Tier::Warm, &self.warm),
+            (Tier::Hot, &self.hot),
+        ]
+    }
+
+    pub fn total_hits(&self) -> u64 {
+        self.cold.hits + self.warm.hits + self.hot.hits
+    }
+}
+
+// Keys with fewer than `warm` accesses are cold, fewer than `hot` warm, the
+// rest hot. Counts come from the key statistics prepass, so keys it no longer
+// tracks count as cold.
+pub fn tier_report(
+    access_records: &[AccessRecord],
+    key_counter: &KeyCounter,
+    (warm, hot): (u64, u64),
+) -> TierReport {
+    let mut sizes = HashMap::new();
+    for record in access_records {
+        sizes.entry(record.key).or_insert(record.size as u64);
+    }
+    let mut report = TierReport {
+        cold: TierStats::default(),
+        warm: TierStats::default(),
+        hot: TierStats::default(),
+    };
+    for (key, size) in sizes {
+        let count = key_counter.count(key);
+        let stats = if count >= hot {
+            &mut report.hot
+        } else if count >= warm {
+            &mut report.warm
+        } else {
+            &mut report.cold
+        };
+        stats.objects += 1;
+        stats.bytes += size;
+        stats.requests += count;
+        stats.hits += count.saturating_sub(1);
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn separated_frequencies_fall_in_their_tiers() {
+        // 10 cold keys of 10 bytes seen once, 5 warm keys of 100 bytes seen 5
+        // times, 2 hot keys of 1000 bytes seen 50 times
+        let classes = [(0..10, 10, 1), (100..105, 100, 5), (200..202, 1000, 50)];
+        let mut access_records = Vec::new();
+        for round in 0..50 {
+            for (keys, size, count) in classes.iter().cloned() {
+                for key in keys.filter(|_| round < count) {
+                    access_records.push(AccessRecord {
+                        timestamp: access_records.len() as u64,
+                        command: 0,
+                        key,
+                        size,
+                        ttl: 0,
+                    });
+                }
+            }
+        }
+        let mut key_counter = KeyCounter::new(1000);
+        for record in access_records.iter() {
+            key_counter.record(record.key);
+        }
+
+        let report = tier_report(&access_records, &key_counter, (2, 10));
+        let counts = report
+            .tiers()
+            .map(|(tier, stats)| (tier, stats.objects, stats.bytes, stats.requests, stats.hits));
+        assert_eq!(
+            counts,
+            [
+                (Tier::Cold, 10, 100, 10, 0),
+                (Tier::Warm, 5, 500, 25, 20),
+                (Tier::Hot, 2, 2000, 100, 98),
+            ]
+        );
+        assert_eq!(report.total_hits(), 118);
+    }
+}
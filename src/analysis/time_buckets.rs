@@ -0,0 +1,80 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+// Requests and miss ratio of one cache size over [start, start + width) of
+// trace time, `start` counted from the first request of the trace
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeBucket {
+    pub start: u64,
+    pub requests: u64,
+    pub miss_ratio: f64,
+}
+
+// Requests and hits per fixed span of trace time at one cache size
+#[derive(Debug, Clone)]
+pub struct TimeBucketCounter {
+    origin: u64,
+    width: u64,
+    requests: Vec<f64>,
+    hits: Vec<f64>,
+}
+
+impl TimeBucketCounter {
+    // Buckets `width` timestamps wide from `origin`, the trace's first timestamp
+    pub fn new(origin: u64, width: u64) -> Self {
+        TimeBucketCounter {
+            origin,
+            width: width.max(1),
+            requests: Vec::new(),
+            hits: Vec::new(),
+        }
+    }
+
+    // Count a request at `timestamp`, `weight` being the requests it stands
+    // for once sampled
+    pub fn record(&mut self, timestamp: u64, weight: f64, hit: bool) {
+        let bucket = (timestamp.saturating_sub(self.origin) / self.width) as usize;
+        if bucket >= self.requests.len() {
+            self.requests.resize(bucket + 1, 0.0);
+            self.hits.resize(bucket + 1, 0.0);
+        }
+        self.requests[bucket] += weight;
+        if hit {
+            self.hits[bucket] += weight;
+        }
+    }
+
+    // The buckets that saw requests, in time order
+    pub fn buckets(&self) -> Vec<TimeBucket> {
+        (0..self.requests.len())
+            .filter(|bucket| self.requests[*bucket] > 0.0)
+            .map(|bucket| TimeBucket {
+                start: bucket as u64 * self.width,
+                requests: self.requests[bucket].round() as u64,
+                miss_ratio: 1.0 - self.hits[bucket] / self.requests[bucket],
+            })
+            .collect()
+    }
+}
+
+// Write `label,bucket_start,requests,miss_ratio` CSV
+pub fn write_time_buckets(
+    reports: &[(String, Vec<TimeBucket>)],
+    path: &Path,
+) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "label,bucket_start,requests,miss_ratio")?;
+    for (label, buckets) in reports {
+        for bucket in buckets {
+            writeln!(
+                writer,
+                "{label},{},{},{:.6}",
+                bucket.start, bucket.requests, bucket.miss_ratio
+            )?;
+        }
+    }
+    writer.flush()
+}
@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+
+use crate::AccessRecord;
+
+// Ranges and distributions of the parsed fields, to check a trace was read
+// with the right column mapping before trusting its curves
+#[derive(Debug, Clone, Default)]
+pub struct TraceStats {
+    pub records: u64,
+    pub key_min: u64,
+    pub key_max: u64,
+    // Per request, not per distinct key
+    pub size_min: u32,
+    pub size_median: u32,
+    pub size_p99: u32,
+    pub size_max: u32,
+    pub zero_sizes: u64,
+    // Command value -> requests
+    pub commands: BTreeMap<u8, u64>,
+    pub timestamp_min: u64,
+    pub timestamp_max: u64,
+}
+
+impl TraceStats {
+    pub fn timestamp_span(&self) -> u64 {
+        self.timestamp_max - self.timestamp_min
+    }
+
+    // Field values that usually mean a column is missing or mapped wrong
+    pub fn anomalies(&self) -> Vec<String> {
+        let mut anomalies = Vec::new();
+        if self.records < 2 {
+            return anomalies;
+        }
+        if self.key_min == self.key_max {
+            anomalies.push(format!("every request is for key {}", self.key_min));
+        }
+        if self.size_min == self.size_max {
+            anomalies.push(format!(
+                "every request has size {}, is the size column mapped?",
+                self.size_min
+            ));
+        }
+        if self.zero_sizes > 0 {
+            anomalies.push(format!(
+                "{} requests have size 0, they are simulated as size 1",
+                self.zero_sizes
+            ));
+        }
+        if let [(command, _)] = self.commands.iter().collect::<Vec<_>>()[..] {
+            anomalies.push(format!("every request has command {command}"));
+        }
+        if self.timestamp_span() == 0 {
+            anomalies.push(format!(
+                "every timestamp is {}, time based features will not work",
+                self.timestamp_min
+            ));
+        }
+        anomalies
+    }
+}
+
+pub fn trace_stats(access_records: &[AccessRecord]) -> TraceStats {
+    let Some(first) = access_records.first() else {
+        return TraceStats::default();
+    };
+    let mut stats = TraceStats {
+        records: access_records.len() as u64,
+        key_min: first.key,
+        key_max: first.key,
+        timestamp_min: first.timestamp,
+        timestamp_max: first.timestamp,
+        ..TraceStats::default()
+    };
+    let mut sizes = Vec::with_capacity(access_records.len());
+    for record in access_records {
+        stats.key_min = stats.key_min.min(record.key);
+        stats.key_max = stats.key_max.max(record.key);
+        stats.timestamp_min = stats.timestamp_min.min(record.timestamp);
+        stats.timestamp_max = stats.timestamp_max.max(record.timestamp);
+        *stats.commands.entry(record.command).or_default() += 1;
+        if record.size == 0 {
+            stats.zero_sizes += 1;
+        }
+        sizes.push(record.size);
+    }
+    sizes.sort_unstable();
+    // Nearest rank
+    let percentile = |p: f64| {
+        sizes[((p / 100.0 * sizes.len() as f64).ceil() as usize).clamp(1, sizes.len()) - 1]
+    };
+    stats.size_min = sizes[0];
+    stats.size_median = percentile(50.0);
+    stats.size_p99 = percentile(99.0);
+    stats.size_max = sizes[sizes.len() - 1];
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_of_a_synthetic_trace() {
+        // Sizes 1..=1000 in a scrambled order, every fourth request a write
+        let access_records = (0..1000u64)
+            .map(|i| AccessRecord {
+                timestamp: 100 + i,
+                command: (i % 4 == 0) as u8,
+                key: i % 300,
+                size: (i * 7 % 1000) as u32 + 1,
+                ttl: 0,
+            })
+            .collect::<Vec<_>>();
+        let stats = trace_stats(&access_records);
+        assert_eq!(stats.records, 1000);
+        assert_eq!((stats.key_min, stats.key_max), (0, 299));
+        assert_eq!(
+            (
+                stats.size_min,
+                stats.size_median,
+                stats.size_p99,
+                stats.size_max
+            ),
+            (1, 500, 990, 1000)
+        );
+        assert_eq!(stats.commands, BTreeMap::from([(0, 750), (1, 250)]));
+        assert_eq!(stats.timestamp_span(), 999);
+        assert!(stats.anomalies().is_empty(), "{:?}", stats.anomalies());
+
+        let uniform = access_records
+            .iter()
+            .map(|record| AccessRecord {
+                command: 0,
+                size: 64,
+                timestamp: 5,
+                ..*record
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            trace_stats(&uniform).anomalies(),
+            vec![
+                "every request has size 64, is the size column mapped?",
+                "every request has command 0",
+                "every timestamp is 5, time based features will not work",
+            ]
+        );
+    }
+}
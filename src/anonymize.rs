@@ -0,0 +1,173 @@
+// Sanitizing traces for sharing, see the convert subcommand. Keys are rehashed
+// with a secret drawn for the run, so equal keys stay equal and the curves
+// are unchanged, but the original keys can't be looked up without the secret.
+use std::{
+    fs::File,
+    hash::Hasher,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use hashbrown::HashMap;
+use rand::Rng;
+
+use crate::{AccessRecord, Key};
+
+// What `anonymize` changes besides the keys and the start time
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AnonymizeOptions {
+    // Round every timestamp down to a multiple of this, after the shift
+    pub coarsen_time: Option<u64>,
+    pub drop_command: bool,
+    pub drop_ttl: bool,
+}
+
+// A keyed rehash of the keys. SipHash keyed with 128 random bits can't be
+// inverted by hashing candidate keys without the secret.
+#[derive(Debug, Clone, Copy)]
+pub struct KeyHasher {
+    secret: (u64, u64),
+}
+
+impl KeyHasher {
+    pub fn random() -> Self {
+        let mut rng = rand::thread_rng();
+        KeyHasher {
+            secret: (rng.gen(), rng.gen()),
+        }
+    }
+
+    // The secret as 32 hex digits
+    pub fn secret(&self) -> String {
+        format!("{:016x}{:016x}", self.secret.0, self.secret.1)
+    }
+
+    pub fn hash(&self, key: Key) -> Key {
+        // SipHash-2-4, std keeps it only under its deprecated name
+        #[allow(deprecated)]
+        let mut hasher = std::hash::SipHasher::new_with_keys(self.secret.0, self.secret.1);
+        hasher.write_u64(key);
+        hasher.finish()
+    }
+}
+
+// Rehash the keys of `access_records` with a fresh secret, drawing another
+// one in the unlikely case two keys collide, and shift the timestamps to
+// start at 0. Returns the secret used.
+pub fn anonymize(access_records: &mut [AccessRecord], options: AnonymizeOptions) -> KeyHasher {
+    let (hasher, renamed) = loop {
+        let hasher = KeyHasher::random();
+        let mut renamed: HashMap<Key, Key> = HashMap::new();
+        let mut originals: HashMap<Key, Key> = HashMap::new();
+        let collided = access_records.iter().any(|record| {
+            let hashed = *renamed
+                .entry(record.key)
+                .or_insert_with(|| hasher.hash(record.key));
+            *originals.entry(hashed).or_insert(record.key) != record.key
+        });
+        if !collided {
+            break (hasher, renamed);
+        }
+    };
+    let start = access_records
+        .iter()
+        .map(|record| record.timestamp)
+        .min()
+        .unwrap_or(0);
+    for record in access_records.iter_mut() {
+        record.key = renamed[&record.key];
+        record.timestamp -= start;
+        if let Some(step) = options.coarsen_time.filter(|step| *step > 0) {
+            record.timestamp -= record.timestamp % step;
+        }
+        if options.drop_command {
+            record.command = 0;
+        }
+        if options.drop_ttl {
+            record.ttl = 0;
+        }
+    }
+    hasher
+}
+
+// Write `access_records` as timestamp,command,key,size,ttl CSV, the format
+// --trace reads by default
+pub fn write_trace(access_records: &[AccessRecord], path: &Path) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "timestamp,command,key,size,ttl")?;
+    for record in access_records {
+        let ttl = match record.ttl {
+            u32::MAX => -1,
+            ttl => ttl as i64,
+        };
+        writeln!(
+            writer,
+            "{},{},{},{},{ttl}",
+            record.timestamp, record.command, record.key, record.size
+        )?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::{load_access_records, Config, EvictionPolicy},
+        export::CurveExport,
+        tests::simulate_golden,
+    };
+
+    #[test]
+    fn anonymized_golden_sample_misses_as_often() {
+        let mut config = Config {
+            trace: Some("data/golden/twitter_sample.csv".into()),
+            ..Default::default()
+        };
+        let (mut access_records, _) = load_access_records(&mut config).unwrap();
+        let original_keys = access_records
+            .iter()
+            .map(|record| record.key)
+            .collect::<Vec<_>>();
+        anonymize(&mut access_records, AnonymizeOptions::default());
+        assert!(access_records
+            .iter()
+            .zip(original_keys.iter())
+            .all(|(record, key)| record.key != *key));
+        let path =
+            std::env::temp_dir().join(format!("cache_mrc_anonymized_{}.csv", std::process::id()));
+        write_trace(&access_records, &path).unwrap();
+
+        // TWOQ left out for time, it behaves the same on any keys
+        let misses = |trace: Option<&Path>| {
+            simulate_golden(|config| {
+                config.policies = Some(vec![
+                    EvictionPolicy::LRU,
+                    EvictionPolicy::FIFO,
+                    EvictionPolicy::LFU,
+                    EvictionPolicy::LARGEST,
+                    EvictionPolicy::SLRU { protected: 0.8 },
+                    EvictionPolicy::LRUFQ { threshold: 1 },
+                    EvictionPolicy::FROZENHOT {
+                        hot: 0.5,
+                        rebuild: 1000,
+                    },
+                ]);
+                if let Some(trace) = trace {
+                    config.trace = Some(trace.to_path_buf());
+                    config.trace_checksum = None;
+                }
+            })
+            .iter()
+            .map(|result| {
+                let export = CurveExport::from_result(result);
+                let misses = export.points.iter().map(|point| point.misses);
+                (export.label, misses.collect::<Vec<_>>())
+            })
+            .collect::<Vec<_>>()
+        };
+        let anonymized = misses(Some(&path));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(anonymized, misses(None));
+    }
+}
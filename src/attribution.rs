@@ -0,0 +1,236 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use hashbrown::HashMap;
+
+use crate::{
+    analysis::KeyCounter,
+    config::EvictionPolicy,
+    evict_policy::{
+        registered_policy, EvictPolicy, FifoPolicy, FrozenHotPolicy, LargestFirstPolicy, LfuPolicy,
+        LruFrequencyPolicy, LruPolicy, NewPolicy, SlruPolicy, TwoQPolicy, WatermarkWrapper,
+    },
+    minisim::MiniSim,
+    AccessRecord, Key,
+};
+
+// Hits of one policy replayed at a single cache size
+#[derive(Debug, Default)]
+pub struct KeyHits {
+    pub total: u64,
+    // Only for the keys that were asked for
+    pub per_key: HashMap<Key, u64>,
+}
+
+// Replay the trace on a single empty cache and count the hits of all keys,
+// and of every key in `tracked` on its own
+pub fn key_hits<P: EvictPolicy>(
+    access_records: &[AccessRecord],
+    mut cache: P,
+    respect_ttl: bool,
+    tracked: &HashMap<Key, u64>,
+) -> KeyHits {
+    let mut insertions: HashMap<Key, AccessRecord> = HashMap::new();
+    let mut hits = KeyHits::default();
+    for access in access_records {
+        cache.advance_time(access.timestamp);
+        if cache.get(access.key).is_some() {
+            let expired = respect_ttl
+                && insertions
+                    .get(&access.key)
+                    .is_some_and(|inserted| MiniSim::<P>::is_expired(inserted, access.timestamp));
+            if !expired {
+                hits.total += 1;
+                if tracked.contains_key(&access.key) {
+                    *hits.per_key.entry(access.key).or_default() += 1;
+                }
+                continue;
+            }
+            cache.remove(access.key);
+        }
+        cache.put(access.key, access.size.max(1) as u64);
+        if respect_ttl {
+            insertions.insert(access.key, *access);
+        }
+    }
+    hits
+}
+
+// `key_hits` with the policy's watermark applied, if any
+fn hits_with_watermark<P: EvictPolicy>(
+    access_records: &[AccessRecord],
+    cache: P,
+    capacity: u64,
+    watermark: Option<f64>,
+    respect_ttl: bool,
+    tracked: &HashMap<Key, u64>,
+) -> KeyHits {
+    match watermark {
+        Some(watermark) => key_hits(
+            access_records,
+            WatermarkWrapper::wrap(cache, capacity, watermark),
+            respect_ttl,
+            tracked,
+        ),
+        None => key_hits(access_records, cache, respect_ttl, tracked),
+    }
+}
+
+fn policy_key_hits(
+    policy: &EvictionPolicy,
+    access_records: &[AccessRecord],
+    capacity: u64,
+    respect_ttl: bool,
+    tracked: &HashMap<Key, u64>,
+) -> KeyHits {
+    let (policy, watermark) = policy.split_watermark();
+    let (records, ttl) = (access_records, respect_ttl);
+    match policy {
+        EvictionPolicy::LRU => {
+            let cache = LruPolicy::new(capacity);
+            hits_with_watermark(records, cache, capacity, watermark, ttl, tracked)
+        }
+        EvictionPolicy::FIFO => {
+            let cache = FifoPolicy::new(capacity);
+            hits_with_watermark(records, cache, capacity, watermark, ttl, tracked)
+        }
+        EvictionPolicy::LFU => {
+            let cache = LfuPolicy::new(capacity);
+            hits_with_watermark(records, cache, capacity, watermark, ttl, tracked)
+        }
+        EvictionPolicy::TWOQ => {
+            let cache = TwoQPolicy::new(capacity);
+            hits_with_watermark(records, cache, capacity, watermark, ttl, tracked)
+        }
+        EvictionPolicy::LARGEST => {
+            let cache = LargestFirstPolicy::new(capacity);
+            hits_with_watermark(records, cache, capacity, watermark, ttl, tracked)
+        }
+        EvictionPolicy::SLRU { protected } => {
+            let cache = SlruPolicy::with_protected(capacity, *protected);
+            hits_with_watermark(records, cache, capacity, watermark, ttl, tracked)
+        }
+        EvictionPolicy::LRUFQ { threshold } => {
+            let cache = LruFrequencyPolicy::with_threshold(capacity, *threshold);
+            hits_with_watermark(records, cache, capacity, watermark, ttl, tracked)
+        }
+        EvictionPolicy::FROZENHOT { hot, rebuild } => {
+            let cache = FrozenHotPolicy::with_params(capacity, *hot, *rebuild);
+            hits_with_watermark(records, cache, capacity, watermark, ttl, tracked)
+        }
+        EvictionPolicy::Registered { name, params } => {
+            let factory = registered_policy(name).expect("policies stay registered");
+            let cache = factory(capacity, params);
+            hits_with_watermark(records, cache, capacity, watermark, ttl, tracked)
+        }
+        EvictionPolicy::Watermark { .. } => unreachable!("watermarks don't nest"),
+    }
+}
+
+// Hit counts of one key under both policies
+#[derive(Debug, Clone, Copy)]
+pub struct KeyAttribution {
+    pub key: Key,
+    // First seen size
+    pub size: u64,
+    pub requests: u64,
+    pub hits_a: u64,
+    pub hits_b: u64,
+}
+
+impl KeyAttribution {
+    // Positive where policy B hits the key more often
+    pub fn delta(&self) -> i64 {
+        self.hits_b as i64 - self.hits_a as i64
+    }
+}
+
+#[derive(Debug)]
+pub struct Attribution {
+    pub total_hits_a: u64,
+    pub total_hits_b: u64,
+    // Largest absolute hit difference first
+    pub keys: Vec<KeyAttribution>,
+}
+
+// Replay both policies at `capacity` and rank the keys by how differently they
+// hit. Only the keys tracked by `key_counter` are counted one by one, so the
+// memory stays bounded by --max-tracked-keys.
+pub fn attribute_hits(
+    (a, b): (&EvictionPolicy, &EvictionPolicy),
+    access_records: &[AccessRecord],
+    capacity: u64,
+    respect_ttl: bool,
+    key_counter: &KeyCounter,
+    threads: Option<usize>,
+) -> Attribution {
+    let tracked = key_counter
+        .top_k(key_counter.len())
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+    let replay_a = || policy_key_hits(a, access_records, capacity, respect_ttl, &tracked);
+    let replay_b = || policy_key_hits(b, access_records, capacity, respect_ttl, &tracked);
+    // The two replays take two threads, unless --threads allows only one
+    let (hits_a, hits_b) = if threads == Some(1) {
+        (replay_a(), replay_b())
+    } else {
+        rayon::join(replay_a, replay_b)
+    };
+    let mut sizes = HashMap::new();
+    for record in access_records {
+        if tracked.contains_key(&record.key) {
+            sizes.entry(record.key).or_insert(record.size as u64);
+        }
+    }
+    let mut keys = tracked
+        .iter()
+        .map(|(&key, &requests)| KeyAttribution {
+            key,
+            size: sizes.get(&key).copied().unwrap_or(0),
+            requests,
+            hits_a: hits_a.per_key.get(&key).copied().unwrap_or(0),
+            hits_b: hits_b.per_key.get(&key).copied().unwrap_or(0),
+        })
+        .filter(|attribution| attribution.delta() != 0)
+        .collect::<Vec<_>>();
+    // Ties by key so the output doesn't depend on hash order
+    keys.sort_by_key(|attribution| {
+        (
+            std::cmp::Reverse(attribution.delta().abs()),
+            attribution.key,
+        )
+    });
+    Attribution {
+        total_hits_a: hits_a.total,
+        total_hits_b: hits_b.total,
+        keys,
+    }
+}
+
+// Write the first `top` keys as `key,size,requests,hits_a,hits_b,delta` CSV
+pub fn write_attribution(
+    attribution: &Attribution,
+    top: usize,
+    path: &Path,
+) -> std::io::Result<usize> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "key,size,requests,hits_a,hits_b,delta")?;
+    let keys = &attribution.keys[..top.min(attribution.keys.len())];
+    for key in keys {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{}",
+            key.key,
+            key.size,
+            key.requests,
+            key.hits_a,
+            key.hits_b,
+            key.delta()
+        )?;
+    }
+    writer.flush()?;
+    Ok(keys.len())
+}
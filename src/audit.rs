@@ -0,0 +1,217 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use hashbrown::HashSet;
+
+use crate::{
+    analysis::{Footprint, Histogram},
+    config::{EvictionPolicy, InnerConfig, ParallelStrategy},
+    run_simulations, AccessRecord, Key,
+};
+
+// Why a key left the cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvictionReason {
+    // Evicted by the policy to make room
+    Capacity,
+    // Expired according to its TTL or --max-residency
+    Ttl,
+    // Removed by a delete request
+    Delete,
+    // Replaced by a write of a new value
+    Invalidate,
+}
+
+impl std::fmt::Display for EvictionReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            EvictionReason::Capacity => "capacity",
+            EvictionReason::Ttl => "ttl",
+            EvictionReason::Delete => "delete",
+            EvictionReason::Invalidate => "invalidate",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Eviction {
+    // Index in the trace of the request that caused the eviction
+    pub access_index: usize,
+    pub key: Key,
+    pub reason: EvictionReason,
+    // Requests since the key was last admitted
+    pub age: usize,
+}
+
+// Percentiles of the ages of the objects evicted for capacity, read from the
+// shared log-bucketed Histogram so they bucket like the other age analyses
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EvictionAges {
+    pub evictions: usize,
+    pub mean: f64,
+    pub p10: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: usize,
+}
+
+impl EvictionAges {
+    // None when nothing was evicted for capacity
+    pub fn new(evictions: &[Eviction]) -> Option<Self> {
+        let mut histogram = Histogram::log(1.0, 2f64.powi(32), 32);
+        let (mut total, mut max) = (0, 0);
+        for eviction in evictions
+            .iter()
+            .filter(|eviction| eviction.reason == EvictionReason::Capacity)
+        {
+            histogram.record(eviction.age as f64);
+            total += eviction.age;
+            max = max.max(eviction.age);
+        }
+        let count = histogram.total() as usize;
+        if count == 0 {
+            return None;
+        }
+        // No percentile beyond the largest age, which the top bucket may exceed
+        let percentile = |p: f64| histogram.percentile(p).min(max as f64);
+        Some(EvictionAges {
+            evictions: count,
+            mean: total as f64 / count as f64,
+            p10: percentile(10.0),
+            p50: percentile(50.0),
+            p90: percentile(90.0),
+            p99: percentile(99.0),
+            max,
+        })
+    }
+}
+
+// Replay the trace with each of `policies` as the curve simulations of `args`
+// do, on one cache of `capacity` in the first capacity unit, and list what
+// left each cache in trace order, by curve label. Policies whose replay fails
+// are left out, run_simulations logs why.
+pub fn policy_evictions(
+    policies: &[EvictionPolicy],
+    access_records: Arc<Vec<AccessRecord>>,
+    args: &InnerConfig,
+    footprint: &Footprint,
+    cacheable_keys: Option<Arc<HashSet<Key>>>,
+    capacity: u64,
+) -> Vec<(String, Vec<Eviction>)> {
+    let mut audit = args.clone();
+    audit.policies = policies.to_vec();
+    audit.eviction_log = true;
+    audit.cache_size = capacity;
+    audit.cache_sizes = Some(vec![capacity]);
+    audit.capacity_units.truncate(1);
+    audit.shards_rates = None;
+    audit.adaptive = None;
+    audit.stack_distance = false;
+    audit.parallel_strategy = ParallelStrategy::PerPolicy;
+    // The replay writes none of the files of the curve simulations
+    audit.dump_contents = None;
+    audit.timeline_every = None;
+    audit.prometheus = None;
+    let (results, _, _) = run_simulations(access_records, &audit, footprint, cacheable_keys);
+    results
+        .into_iter()
+        .map(|result| (result.label, result.evictions))
+        .collect()
+}
+
+// Write `evictions` as `access_index,evicted_key,reason,age` CSV
+pub fn write_eviction_audit(evictions: &[Eviction], path: &Path) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "access_index,evicted_key,reason,age")?;
+    for eviction in evictions.iter() {
+        writeln!(
+            writer,
+            "{},{},{},{}",
+            eviction.access_index, eviction.key, eviction.reason, eviction.age
+        )?;
+    }
+    writer.flush()
+}
+
+// Write `label,evictions,mean_age,p10,p50,p90,p99,max_age` CSV, ages in requests
+pub fn write_eviction_ages(reports: &[(String, EvictionAges)], path: &Path) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "label,evictions,mean_age,p10,p50,p90,p99,max_age")?;
+    for (label, ages) in reports {
+        writeln!(
+            writer,
+            "{label},{},{:.2},{:.0},{:.0},{:.0},{:.0},{}",
+            ages.evictions, ages.mean, ages.p10, ages.p50, ages.p90, ages.p99, ages.max
+        )?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::CapacityUnit,
+        evict_policy::{FifoPolicy, LruPolicy, NewPolicy},
+        minisim::MiniSim,
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    // Eviction ages of one cache of 100 objects over a skewed random trace
+    fn ages<P: NewPolicy>() -> EvictionAges {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut sim = MiniSim::<P>::from_sizes(vec![100], None)
+            .unwrap()
+            .with_capacity_unit(CapacityUnit::Objects)
+            .with_eviction_log(Some(100));
+        for timestamp in 0..50_000 {
+            let key = rng.gen_range(0..1000).min(rng.gen_range(0..1000));
+            sim.handle(&AccessRecord {
+                timestamp,
+                command: 0,
+                key,
+                size: 100,
+                ttl: 0,
+            });
+        }
+        EvictionAges::new(&sim.take_evictions()).unwrap()
+    }
+
+    #[test]
+    fn fifo_ages_are_more_concentrated_than_lru() {
+        // FIFO evicts each object a fixed number of misses after admitting it,
+        // LRU keeps popular objects long and drops one-hit objects early
+        let (fifo, lru) = (ages::<FifoPolicy>(), ages::<LruPolicy>());
+        assert!(fifo.p10 <= fifo.p50 && fifo.p50 <= fifo.p90 && fifo.p99 <= fifo.max as f64);
+        assert!(
+            fifo.p90 / fifo.p10 < lru.p90 / lru.p10,
+            "FIFO {fifo:?}, LRU {lru:?}"
+        );
+        assert!(fifo.max < lru.max, "FIFO {fifo:?}, LRU {lru:?}");
+    }
+
+    #[test]
+    fn only_capacity_evictions_have_ages() {
+        let eviction = |reason, age| Eviction {
+            access_index: 0,
+            key: 1,
+            reason,
+            age,
+        };
+        assert_eq!(EvictionAges::new(&[eviction(EvictionReason::Ttl, 5)]), None);
+        let ages = EvictionAges::new(&[
+            eviction(EvictionReason::Capacity, 4),
+            eviction(EvictionReason::Delete, 1000),
+            eviction(EvictionReason::Capacity, 8),
+        ])
+        .unwrap();
+        assert_eq!((ages.evictions, ages.mean, ages.max), (2, 6.0, 8));
+        assert!(ages.p50 >= 4.0 && ages.p99 <= 8.0, "{ages:?}");
+    }
+}
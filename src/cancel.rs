@@ -0,0 +1,73 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+// Cooperative cancellation shared between a host and the simulations it
+// started. Clones share the flag, cancelling any of them cancels them all.
+// Workers check it from their own threads, so it stays Send + Sync.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    // Wall-clock budget, the token counts as cancelled once it has passed
+    deadline: Option<Instant>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Set before cloning, clones made earlier keep their own deadline
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed) || self.timed_out()
+    }
+
+    pub fn timed_out(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+// The first Ctrl-C cancels `token` so the running simulations stop and the
+// finished curves are still drawn, a second one kills the process as usual
+#[cfg(unix)]
+pub fn cancel_on_ctrl_c(token: &CancellationToken) {
+    use std::sync::OnceLock;
+
+    static CTRL_C: OnceLock<CancellationToken> = OnceLock::new();
+
+    extern "C" fn on_sigint(_signal: libc::c_int) {
+        if let Some(token) = CTRL_C.get() {
+            token.cancel();
+        }
+        // Only async-signal-safe calls in here
+        unsafe {
+            libc::signal(libc::SIGINT, libc::SIG_DFL);
+        }
+    }
+
+    if CTRL_C.set(token.clone()).is_ok() {
+        let handler = on_sigint as extern "C" fn(libc::c_int);
+        unsafe {
+            libc::signal(libc::SIGINT, handler as libc::sighandler_t);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn cancel_on_ctrl_c(_token: &CancellationToken) {}
+
+const _: () = crate::assert_send_sync::<CancellationToken>();
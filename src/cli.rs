@@ -0,0 +1,861 @@
+use crate::analysis::Footprint;
+use crate::analysis::KeyCounter;
+use crate::config::{
+    format_checksum, load_access_records, parse_checksum, CacheSizeSpec, CapacityUnit, Config,
+    EvictionPolicy, InnerConfig, Mode,
+};
+use crate::error::SimError;
+use crate::evict_policy::{
+    EvictPolicy, FifoPolicy, FrozenHotPolicy, LargestFirstPolicy, LfuPolicy, LruFrequencyPolicy,
+    LruPolicy, NewPolicy, SlruPolicy, TwoQPolicy,
+};
+use crate::minisim::MiniSim;
+use crate::shards::ShardsFixedRate;
+use crate::synthetic::ZipfTrace;
+use crate::theme::Theme;
+use crate::{
+    analysis, anonymize, attribution, audit, cancel, compare_configs, config, draw, export,
+    over_time, run_export, simulate_all, size_classes, write_exports, AccessRecord, Key,
+    SimulationResult,
+};
+use hashbrown::HashSet;
+use std::{
+    cell::OnceCell,
+    error::Error,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tracing::{debug, info, warn, Level};
+use tracing_subscriber::FmtSubscriber;
+
+fn init_logger() {
+    // a builder for `FmtSubscriber`.
+    let subscriber = FmtSubscriber::builder()
+        // all spans/events with a level higher than TRACE (e.g, debug, info, warn, etc.)
+        // will be written to stdout.
+        .with_max_level(Level::TRACE)
+        // completes the builder.
+        .finish();
+
+    tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
+}
+
+fn recommend(
+    access_records: &[AccessRecord],
+    config: &InnerConfig,
+    footprint: &Footprint,
+    run: bool,
+) -> Result<(), Box<dyn Error>> {
+    let stats = analysis::classify_workload(access_records);
+    let cache_size_fraction = config.cache_size as f64 / footprint.unique_bytes.max(1) as f64;
+    let policy = analysis::recommend_policy(stats.class, cache_size_fraction);
+    println!(
+        "Workload: {:?} (one-hit keys {:.1}%, short reuses {:.1}%)",
+        stats.class,
+        stats.one_hit_fraction * 100.0,
+        stats.short_reuse_fraction * 100.0
+    );
+    println!(
+        "Recommended policy: {policy} at {:.1}% of the working set, because {}",
+        cache_size_fraction * 100.0,
+        stats.class.describe()
+    );
+    if run {
+        let points = match policy {
+            EvictionPolicy::LRU => run_policy(access_records, config, LruPolicy::new),
+            EvictionPolicy::FIFO => run_policy(access_records, config, FifoPolicy::new),
+            EvictionPolicy::LFU => run_policy(access_records, config, LfuPolicy::new),
+            EvictionPolicy::TWOQ => run_policy(access_records, config, TwoQPolicy::new),
+            EvictionPolicy::LARGEST => run_policy(access_records, config, LargestFirstPolicy::new),
+            EvictionPolicy::SLRU { protected } => run_policy(access_records, config, |capacity| {
+                SlruPolicy::with_protected(capacity, protected)
+            }),
+            EvictionPolicy::LRUFQ { threshold } => run_policy(access_records, config, |capacity| {
+                LruFrequencyPolicy::with_threshold(capacity, threshold)
+            }),
+            EvictionPolicy::FROZENHOT { hot, rebuild } => {
+                run_policy(access_records, config, |capacity| {
+                    FrozenHotPolicy::with_params(capacity, hot, rebuild)
+                })
+            }
+            EvictionPolicy::Registered { .. } | EvictionPolicy::Watermark { .. } => {
+                unreachable!("recommended policies are built in and have no watermark")
+            }
+        }?;
+        println!("cache_size,miss_ratio");
+        for (cache_size, miss_ratio) in points {
+            println!("{cache_size},{miss_ratio:.6}");
+        }
+    }
+    Ok(())
+}
+
+// Print the field statistics of the trace and what looks wrong in them
+fn validate_trace(access_records: &[AccessRecord]) {
+    let stats = analysis::trace_stats(access_records);
+    println!("Records: {}", stats.records);
+    println!("Keys: {} to {}", stats.key_min, stats.key_max);
+    println!(
+        "Sizes: min {}, median {}, p99 {}, max {}",
+        stats.size_min, stats.size_median, stats.size_p99, stats.size_max
+    );
+    println!(
+        "Timestamps: {} to {} (span {})",
+        stats.timestamp_min,
+        stats.timestamp_max,
+        stats.timestamp_span()
+    );
+    for (command, count) in stats.commands.iter() {
+        println!(
+            "Command {command}: {count} requests ({:.1}%)",
+            *count as f64 * 100.0 / stats.records.max(1) as f64
+        );
+    }
+    let anomalies = stats.anomalies();
+    if anomalies.is_empty() {
+        println!("No anomalies found");
+    }
+    for anomaly in anomalies {
+        println!("Anomaly: {anomaly}");
+    }
+}
+
+// Per-key access counts of a trace, counted on first use. Only --top-keys,
+// --tier-thresholds and --attribute need them, and the counter keeps every
+// distinct key up to --max-tracked-keys.
+struct KeyCounts<'a> {
+    access_records: &'a [AccessRecord],
+    max_tracked_keys: Option<usize>,
+    counter: OnceCell<KeyCounter>,
+}
+
+impl<'a> KeyCounts<'a> {
+    fn new(access_records: &'a [AccessRecord], max_tracked_keys: Option<usize>) -> Self {
+        Self {
+            access_records,
+            max_tracked_keys,
+            counter: OnceCell::new(),
+        }
+    }
+
+    fn get(&self) -> &KeyCounter {
+        self.counter.get_or_init(|| {
+            let key_counter = analysis::count_keys(self.access_records, self.max_tracked_keys);
+            info!(
+                "Distinct keys tracked: {} ({})",
+                key_counter.len(),
+                if key_counter.is_exact() {
+                    "exact"
+                } else {
+                    "heavy hitters only"
+                }
+            );
+            for (key, count) in key_counter.top_k(5) {
+                debug!("Hot key {key}: {count} accesses");
+            }
+            key_counter
+        })
+    }
+}
+
+// Keys --top-keys allows in the cache, None when every key is cacheable
+fn top_keys_filter(config: &InnerConfig, key_counts: &KeyCounts) -> Option<Arc<HashSet<Key>>> {
+    config.top_keys.map(|n| {
+        let key_counter = key_counts.get();
+        let top = key_counter.top_k(n);
+        if top.len() < n {
+            warn!(
+                "Only {} keys available for --top-keys {n}, raise --max-tracked-keys",
+                top.len()
+            );
+        }
+        let threshold = top.last().map(|(_, count)| *count).unwrap_or(0);
+        info!(
+            "Caching only the top {} keys, threshold: {threshold} accesses{}",
+            top.len(),
+            if key_counter.is_exact() {
+                ""
+            } else {
+                " (estimated)"
+            }
+        );
+        Arc::new(top.into_iter().map(|(key, _)| key).collect::<HashSet<_>>())
+    })
+}
+
+// Largest objects reported one by one with --large-objects
+const LARGE_OBJECTS_REPORTED: usize = 5;
+
+// The objects larger than the median swept cache size, warned about since a
+// smaller cache holds one of them alone and the byte curves step where each
+// starts to fit
+fn find_large_objects(
+    access_records: &[AccessRecord],
+    config: &InnerConfig,
+) -> Vec<analysis::LargeObject> {
+    if !config.capacity_units.contains(&CapacityUnit::Bytes) {
+        return Vec::new();
+    }
+    let (median, largest) = match config.cache_sizes.as_deref() {
+        Some(sizes) if !sizes.is_empty() => (sizes[sizes.len() / 2], sizes[sizes.len() - 1]),
+        _ => (config.cache_size / 2, config.cache_size),
+    };
+    let objects = analysis::large_objects(access_records, median);
+    if let Some(object) = objects.first() {
+        let never_fit = objects
+            .iter()
+            .filter(|object| object.size > largest)
+            .count();
+        warn!(
+            "{} objects are larger than half the swept cache sizes ({median} bytes), the byte \
+             curves step where each starts to fit{}. The largest is key {} ({} bytes, {} \
+             requests), see --large-objects",
+            objects.len(),
+            if never_fit > 0 {
+                format!(", {never_fit} never fit")
+            } else {
+                String::new()
+            },
+            object.key,
+            object.size,
+            object.requests
+        );
+    }
+    objects
+}
+
+// Log the miss ratio drop of the exact byte curves where each of the largest
+// objects starts to fit. Sampled curves scale the cache but not the object, so
+// their steps are elsewhere.
+fn report_large_objects(objects: &[analysis::LargeObject], results: &[SimulationResult]) {
+    let requests = results.first().map_or(0, |result| result.requests).max(1);
+    for object in objects.iter().take(LARGE_OBJECTS_REPORTED) {
+        let share = object.requests as f64 / requests as f64;
+        for result in results.iter().filter(|result| {
+            result.capacity_unit == CapacityUnit::Bytes
+                && result.sampled_hits.is_empty()
+                && !result.dashed
+        }) {
+            match analysis::step_at(&result.points, object.size) {
+                Some(step) => info!(
+                    "{}: key {} ({} bytes, {share:.4} of the requests) first fits at cache \
+                     size {}, the miss ratio drops {:.4} there",
+                    result.label,
+                    object.key,
+                    object.size,
+                    step.fits.0,
+                    step.miss_ratio_drop()
+                ),
+                None => info!(
+                    "{}: key {} ({} bytes, {share:.4} of the requests) never fits",
+                    result.label, object.key, object.size
+                ),
+            }
+        }
+    }
+}
+
+fn run_policy<P: EvictPolicy>(
+    access_records: &[AccessRecord],
+    config: &InnerConfig,
+    build_policy: impl Fn(u64) -> P,
+) -> Result<Vec<(f64, f64)>, SimError> {
+    let shards = ShardsFixedRate::create_shards(config.sample_rate);
+    let mut sim = MiniSim::new_with(config.cache_size, shards, build_policy)?
+        .with_ttl(config.respect_ttl)
+        .with_write_commands(
+            config.write_commands.clone(),
+            config.delete_commands.clone(),
+        );
+    for access in access_records {
+        sim.handle(access);
+    }
+    Ok(sim.curve())
+}
+
+// Sum the curves of the `inputs` exports and draw them like a simulation's
+fn combine(inputs: &[PathBuf], config: Config) -> Result<(), Box<dyn Error>> {
+    let exports = inputs
+        .iter()
+        .map(|path| export::read_json_export(path).map(|run| (path.display().to_string(), run)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let (results, notes) = export::combine_exports(&exports)?;
+    for note in notes {
+        info!("{note}");
+    }
+    let sources = exports
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    info!(
+        "Combined {} curves over {} requests from {}",
+        results.len(),
+        results.first().map_or(0, |result| result.requests),
+        sources.join(", ")
+    );
+    // The policies and sizes come from the exports, not the config
+    let largest = results
+        .iter()
+        .flat_map(|result| result.points.iter().map(|(size, _)| *size as u64))
+        .max()
+        .unwrap_or(1);
+    // Without figure options of its own, draw like the first run that has some
+    let figure = exports
+        .iter()
+        .find_map(|(_, run)| run.figure.clone())
+        .filter(|_| !config.sets_figure());
+    let mut config = InnerConfig::try_from(Config {
+        policies: Some(config.policies.unwrap_or_default()),
+        cache_size: Some(vec![CacheSizeSpec::Bytes(largest)]),
+        ..config
+    })?;
+    // Runs on different traces have no single checksum
+    config.trace_checksum = None;
+    if let Some(figure) = figure {
+        info!(
+            "Drawing with the {:?} theme and {:?} preset of the first export",
+            figure.theme, figure.preset
+        );
+        config.theme = Theme::new(&figure);
+        config.figure = figure;
+    }
+    let mut written = Vec::new();
+    match config.output.clone() {
+        Some(output) => written.extend(draw::draw_lines(
+            &results,
+            output,
+            config.output_format,
+            None,
+            None,
+            false,
+            &config.theme,
+        )),
+        None => print!("{}", draw::format_table(&results)),
+    }
+    written.extend(write_exports(&results, &[], &config, &sources));
+    info!("Wrote {} files:", written.len());
+    for path in written.iter() {
+        info!("  {path:?}");
+    }
+    Ok(())
+}
+
+// `check`: compare the simulated curves with those of the golden export.
+// Fails when the export is of another trace or a curve doesn't match.
+fn check(
+    results: &[SimulationResult],
+    golden: &Path,
+    tolerance: f64,
+    checksum: u64,
+) -> Result<(), Box<dyn Error>> {
+    let run = export::read_json_export(golden)?;
+    if let Some(expected) = run.trace_checksum.as_deref() {
+        if parse_checksum(expected)? != checksum {
+            return Err(format!(
+                "{golden:?} is of a trace with checksum {expected}, this one has {}",
+                format_checksum(checksum)
+            )
+            .into());
+        }
+    }
+    let checks = export::check_golden(results, &run, tolerance);
+    for check in checks.iter() {
+        match check.mismatch.as_ref() {
+            None => info!(
+                "{}: matches, miss ratios at most {:.2e} apart",
+                check.label, check.max_deviation
+            ),
+            Some(mismatch) => warn!("{}: {mismatch}", check.label),
+        }
+    }
+    for result in results
+        .iter()
+        .filter(|result| !run.curves.iter().any(|curve| curve.label == result.label))
+    {
+        info!("{} has no golden curve, it wasn't checked", result.label);
+    }
+    let mismatched = checks
+        .iter()
+        .filter(|check| check.mismatch.is_some())
+        .count();
+    if mismatched > 0 {
+        return Err(format!(
+            "{mismatched} of the {} golden curves of {golden:?} don't match within {tolerance}",
+            checks.len()
+        )
+        .into());
+    }
+    info!("All {} curves match {golden:?}", checks.len());
+    Ok(())
+}
+
+// The generated --demo trace and its checksum
+fn demo_trace() -> (Vec<AccessRecord>, u64) {
+    let trace = ZipfTrace::demo();
+    let access_records = trace.generate();
+    let bytes = access_records
+        .iter()
+        .flat_map(|record| [record.key.to_le_bytes(), (record.size as u64).to_le_bytes()])
+        .flatten()
+        .collect::<Vec<_>>();
+    info!(
+        "Demo: generated {} requests over {} keys, Zipf alpha {}, object sizes {}..{} bytes",
+        trace.requests, trace.keys, trace.alpha, trace.min_size, trace.max_size
+    );
+    (access_records, config::checksum(&bytes))
+}
+
+// --attribute: which keys the hit difference of the two policies comes from
+fn report_attribution(
+    access_records: &[AccessRecord],
+    config: &InnerConfig,
+    key_counts: &KeyCounts,
+) -> Result<(), Box<dyn Error>> {
+    let Some((a, b)) = config.attribute.as_ref() else {
+        return Ok(());
+    };
+    let capacity = config.probe_size.unwrap_or(config.cache_size);
+    let result = attribution::attribute_hits(
+        (a, b),
+        access_records,
+        capacity,
+        config.respect_ttl,
+        key_counts.get(),
+        config.threads,
+    );
+    let path = config.output_path().with_extension("attribution.csv");
+    let written = attribution::write_attribution(&result, config.top, &path)?;
+    let explained = result.keys[..written]
+        .iter()
+        .map(|key| key.delta())
+        .sum::<i64>();
+    let difference = result.total_hits_b as i64 - result.total_hits_a as i64;
+    info!(
+        "At cache size {capacity}, {a} hits {} times and {b} {} times ({difference:+})",
+        result.total_hits_a, result.total_hits_b
+    );
+    info!(
+        "Wrote the {written} keys with the largest hit differences to {path:?}, \
+         accounting for {explained:+} hits"
+    );
+    Ok(())
+}
+
+// Simulate every group of keys under `mask` on its own, each writing its
+// outputs next to --output with a _group_<id> suffix
+fn simulate_groups(
+    access_records: &[AccessRecord],
+    config: &mut InnerConfig,
+    mask: u64,
+) -> Result<(), Box<dyn Error>> {
+    let groups =
+        analysis::split_by_key_group(access_records, mask, config.max_groups).map_err(|found| {
+            format!(
+                "key mask {mask:#018x} splits the trace into {found} groups, more than \
+                 --max-groups {}",
+                config.max_groups
+            )
+        })?;
+    info!(
+        "Key mask {mask:#018x} splits the trace into {} groups",
+        groups.len()
+    );
+    let output = config.output.clone();
+    let mut failures = Vec::new();
+    for (group, records) in groups {
+        let footprint = analysis::footprint(&records);
+        info!(
+            "Group {group}: {} requests, {} unique keys, {} unique bytes",
+            records.len(),
+            footprint.unique_keys,
+            footprint.unique_bytes
+        );
+        config.output = output
+            .as_deref()
+            .map(|output| draw::suffixed_path(output, &format!("group_{group}")));
+        // Working set fractions are of the group's own footprint
+        if config.needs_working_set() {
+            config.resolve_cache_sizes(Some(footprint.unique_bytes))?;
+            info!("Group {group} cache size {}", config.describe_cache_size());
+        }
+        let key_counts = KeyCounts::new(&records, config.max_tracked_keys);
+        report_attribution(&records, config, &key_counts)?;
+        let cacheable_keys = top_keys_filter(config, &key_counts);
+        let (_, group_failures) =
+            simulate_all(Arc::new(records), config, &footprint, cacheable_keys);
+        failures.extend(group_failures);
+    }
+    config.output = output;
+    if !failures.is_empty() {
+        warn!("{} curves failed, the others were drawn", failures.len());
+    }
+    Ok(())
+}
+
+// Simulate every policy at `capacity` alone and report its miss ratio per
+// bucket of trace time, to <output>.over_time.csv or stdout without --output
+fn report_over_time(
+    access_records: &[AccessRecord],
+    config: &InnerConfig,
+    capacity: u64,
+) -> Result<(), Box<dyn Error>> {
+    let origin = access_records
+        .iter()
+        .map(|access| access.timestamp)
+        .min()
+        .unwrap_or(0);
+    let end = access_records
+        .iter()
+        .map(|access| access.timestamp)
+        .max()
+        .unwrap_or(0);
+    let width = config
+        .time_bucket
+        .unwrap_or_else(|| (end - origin).div_ceil(100).max(1));
+    let run = over_time::OverTimeRun {
+        access_records,
+        capacity,
+        sample_rate: config.sample_rate,
+        respect_ttl: config.respect_ttl,
+        write_commands: &config.write_commands,
+        delete_commands: &config.delete_commands,
+        origin,
+        width,
+    };
+    let mut reports = Vec::new();
+    for policy in config.policies.iter() {
+        let buckets = over_time::miss_ratio_over_time(policy, &run)?;
+        if let (Some(first), Some(last)) = (buckets.first(), buckets.last()) {
+            info!(
+                "{policy} at cache size {capacity}: miss ratio {:.4} in the first bucket, \
+                 {:.4} in the last",
+                first.miss_ratio, last.miss_ratio
+            );
+        }
+        reports.push((policy.to_string(), buckets));
+    }
+    let Some(output) = config.output.as_deref() else {
+        println!("label,bucket_start,requests,miss_ratio");
+        for (label, buckets) in reports.iter() {
+            for bucket in buckets {
+                println!(
+                    "{label},{},{},{:.6}",
+                    bucket.start, bucket.requests, bucket.miss_ratio
+                );
+            }
+        }
+        return Ok(());
+    };
+    let path = output.with_extension("over_time.csv");
+    analysis::write_time_buckets(&reports, &path)?;
+    info!("Wrote the miss ratio per {width} of trace time at cache size {capacity} to {path:?}");
+    let chart = draw::draw_over_time(
+        &reports,
+        capacity,
+        draw::suffixed_path(output, "over_time"),
+        config.output_format,
+        &config.theme,
+    );
+    info!("Wrote the miss ratio over time to {chart:?}");
+    Ok(())
+}
+
+// The cache_mrc command line, for wrapper binaries that register policies first,
+// see evict_policy::register_policy
+pub fn run_cli() -> Result<(), Box<dyn Error>> {
+    init_logger();
+    let mut config = Config::load()?;
+    if let Some(Mode::Combine { inputs }) = config.command_mode.clone() {
+        return combine(&inputs, config);
+    }
+    let (access_records, checksum) = match config.trace.as_ref() {
+        _ if config.demo => {
+            config.apply_demo_defaults();
+            demo_trace()
+        }
+        Some(_) => load_access_records(&mut config)?,
+        None => {
+            return Err(
+                "no trace to simulate, pass --trace <FILE>, or --demo for a generated one".into(),
+            )
+        }
+    };
+    for warning in config.audit(&access_records) {
+        warn!("{warning}");
+    }
+    if config.validate_trace {
+        validate_trace(&access_records);
+        return Ok(());
+    }
+    if let Some(Mode::Convert {
+        output,
+        anonymize,
+        print_key,
+        coarsen_time,
+        drop_command,
+        drop_ttl,
+    }) = config.command_mode.clone()
+    {
+        let mut access_records = access_records;
+        if anonymize {
+            let options = anonymize::AnonymizeOptions {
+                coarsen_time,
+                drop_command,
+                drop_ttl,
+            };
+            let hasher = anonymize::anonymize(&mut access_records, options);
+            info!("Anonymized {} records: {options:?}", access_records.len());
+            if print_key {
+                println!("Key secret: {}", hasher.secret());
+            }
+        }
+        anonymize::write_trace(&access_records, &output)?;
+        info!("Wrote {} records to {output:?}", access_records.len());
+        return Ok(());
+    }
+    let command_mode = config.command_mode.clone();
+    let schema = config.trace_schema(&access_records);
+    let mut config = InnerConfig::try_from(config)?;
+    let mut dropped = config.drop_unsupported_policies(&schema)?;
+    for (policy, reason) in dropped.iter() {
+        warn!("Dropping {policy}, {reason}");
+    }
+    if let Some(expected) = config
+        .trace_checksum
+        .filter(|expected| *expected != checksum)
+    {
+        return Err(format!(
+            "trace {:?} has checksum {}, expected {}",
+            config.trace,
+            format_checksum(checksum),
+            format_checksum(expected)
+        )
+        .into());
+    }
+    config.trace_checksum = Some(checksum);
+    cancel::cancel_on_ctrl_c(&config.cancel);
+    info!("Simulation config: {:?}", config);
+    debug_assert!(!access_records.is_empty());
+    debug!("Access records: length: {}", access_records.len());
+    for record in access_records.iter().take(5) {
+        debug!("{:?}", record);
+    }
+    let footprint = analysis::footprint(&access_records);
+    info!(
+        "Trace footprint: {} unique keys, {} unique bytes, mean object size {:.1}",
+        footprint.unique_keys, footprint.unique_bytes, footprint.mean_object_size
+    );
+    debug!(
+        "Object size p50: {:.0}, p99: {:.0}",
+        footprint.size_histogram.percentile(50.0),
+        footprint.size_histogram.percentile(99.0)
+    );
+    if config.needs_working_set() {
+        config.resolve_cache_sizes(Some(footprint.unique_bytes))?;
+        info!("Cache size {}", config.describe_cache_size());
+    }
+    if let Err(e) = analysis::check_size_units(&footprint, config.cache_size) {
+        if config.strict_units {
+            return Err(e.into());
+        }
+        warn!("!!! {e}");
+    }
+    config.load_warm_start()?;
+    if let Some(warm_start) = config.warm_start.as_ref() {
+        let bytes = warm_start.objects.iter().map(|(_, size)| size).sum::<u64>();
+        info!(
+            "Warm start from {:?}: {} objects, {bytes} bytes",
+            warm_start.path,
+            warm_start.objects.len()
+        );
+    }
+    if let Some(mask) = config.group_mask {
+        return simulate_groups(&access_records, &mut config, mask);
+    }
+    if let Some(capacity) = config.over_time {
+        return report_over_time(&access_records, &config, capacity);
+    }
+    let large_objects = find_large_objects(&access_records, &config);
+    if let Some(Mode::Recommend { run }) = command_mode {
+        return recommend(&access_records, &config, &footprint, run);
+    }
+    let access_records = Arc::new(access_records);
+    let key_counts = KeyCounts::new(&access_records, config.max_tracked_keys);
+    if let Some(thresholds) = config.tier_thresholds {
+        let report = analysis::tier_report(&access_records, key_counts.get(), thresholds);
+        let total_hits = report.total_hits().max(1);
+        for (tier, stats) in report.tiers() {
+            info!(
+                "{tier} tier: {} objects, {} bytes, {} requests, {:.1}% of the hits",
+                stats.objects,
+                stats.bytes,
+                stats.requests,
+                100.0 * stats.hits as f64 / total_hits as f64
+            );
+        }
+    }
+    let cacheable_keys = top_keys_filter(&config, &key_counts);
+    let audit_capacity = config.audit_cache_size.unwrap_or(config.cache_size);
+    let audit = |policies: &[config::EvictionPolicy]| {
+        audit::policy_evictions(
+            policies,
+            access_records.clone(),
+            &config,
+            &footprint,
+            cacheable_keys.clone(),
+            audit_capacity,
+        )
+    };
+    if let Some(path) = config.audit_evictions.as_ref() {
+        let capacity = audit_capacity;
+        match audit(&config.policies[..1]).pop() {
+            Some((label, evictions)) => {
+                audit::write_eviction_audit(&evictions, path)?;
+                info!(
+                    "Wrote {} {label} evictions at cache size {capacity} to {path:?}",
+                    evictions.len()
+                );
+            }
+            None => warn!("The eviction audit replay failed, {path:?} is not written"),
+        }
+    }
+    if config.eviction_ages {
+        let capacity = audit_capacity;
+        let mut reports = Vec::new();
+        for (policy, evictions) in audit(&config.policies) {
+            let Some(ages) = audit::EvictionAges::new(&evictions) else {
+                info!("{policy} evicts nothing at cache size {capacity}");
+                continue;
+            };
+            info!(
+                "{policy} eviction ages at cache size {capacity}: p10 {:.0}, p50 {:.0}, \
+                 p90 {:.0}, p99 {:.0}, max {} requests over {} evictions",
+                ages.p10, ages.p50, ages.p90, ages.p99, ages.max, ages.evictions
+            );
+            reports.push((policy.to_string(), ages));
+        }
+        let path = config.output_path().with_extension("eviction_ages.csv");
+        audit::write_eviction_ages(&reports, &path)?;
+        info!("Wrote the eviction ages at cache size {capacity} to {path:?}");
+    }
+    report_attribution(&access_records, &config, &key_counts)?;
+    if config.size_classes {
+        let run = size_classes::SizeClassRun {
+            access_records: &access_records,
+            capacity: config.probe_size.unwrap_or(config.cache_size),
+            sample_rate: config.sample_rate,
+            respect_ttl: config.respect_ttl,
+            write_commands: &config.write_commands,
+            delete_commands: &config.delete_commands,
+            edges: config.size_class_edges.as_deref(),
+        };
+        let mut reports = Vec::new();
+        for policy in config.policies.iter() {
+            let classes = size_classes::size_classes(policy, &run)?;
+            for class in classes.iter() {
+                debug!(
+                    "{policy} size class {}..{}: hit ratio {:.4}, byte hit ratio {:.4}",
+                    class.lower,
+                    class.upper,
+                    class.hit_ratio(),
+                    class.byte_hit_ratio()
+                );
+            }
+            reports.push((policy.to_string(), classes));
+        }
+        let path = config.output_path().with_extension("size_classes.csv");
+        analysis::write_size_classes(&reports, &path)?;
+        info!(
+            "Wrote hit ratios by size class at cache size {} to {path:?}",
+            run.capacity
+        );
+        if config.size_class_chart {
+            let chart = draw::draw_size_classes(
+                &reports,
+                draw::suffixed_path(config.output_path(), "size_classes"),
+                config.output_format,
+            );
+            info!("Wrote the size class chart to {chart:?}");
+        }
+    }
+    let (results, mut failures) =
+        simulate_all(access_records.clone(), &config, &footprint, cacheable_keys);
+    if config.large_objects {
+        report_large_objects(&large_objects, &results);
+    }
+    if let Some(Mode::Check {
+        golden,
+        tolerance,
+        bless,
+    }) = &command_mode
+    {
+        if *bless {
+            export::write_json_export(&run_export(&results, &[], &config, &[]), golden)?;
+            info!("Wrote the golden curves to {golden:?}");
+            return Ok(());
+        }
+        return check(&results, golden, *tolerance, checksum);
+    }
+    if let Some(path) = config.compare_config.as_ref() {
+        let alternate = Config::from_file(path)?;
+        alternate.validate()?;
+        if let Some(expected) = alternate
+            .trace_checksum
+            .as_deref()
+            .map(parse_checksum)
+            .transpose()?
+            .filter(|expected| *expected != checksum)
+        {
+            warn!(
+                "{path:?} expects trace checksum {}, the loaded trace has {}",
+                format_checksum(expected),
+                format_checksum(checksum)
+            );
+        }
+        if alternate
+            .trace
+            .as_ref()
+            .is_some_and(|trace| *trace != config.trace)
+        {
+            warn!(
+                "Ignoring the trace of {path:?}, both configurations run on {:?}",
+                config.trace
+            );
+        }
+        let mut alternate = InnerConfig::try_from(alternate)?;
+        alternate.resolve_cache_sizes(Some(footprint.unique_bytes))?;
+        alternate.load_warm_start()?;
+        for (policy, reason) in alternate.drop_unsupported_policies(&schema)? {
+            warn!("Dropping {policy} from {path:?}, {reason}");
+            dropped.push((policy, reason));
+        }
+        alternate.cancel = config.cancel.clone();
+        info!("Comparing with {path:?}: {:?}", alternate);
+        let alternate_keys = top_keys_filter(&alternate, &key_counts);
+        failures.extend(compare_configs(
+            access_records.clone(),
+            &results,
+            &alternate,
+            &footprint,
+            alternate_keys,
+            config.output_path(),
+        ));
+    }
+    // A figure missing an expected curve is explained at the end of the log
+    for (policy, reason) in dropped.iter() {
+        warn!("{policy} was not simulated, {reason}");
+    }
+    if failures
+        .iter()
+        .any(|failure| matches!(failure, SimError::Cancelled { .. }))
+    {
+        warn!("Interrupted, the curves that finished were drawn");
+        return Ok(());
+    }
+    if !failures.is_empty() {
+        warn!("{} curves failed, the others were drawn", failures.len());
+        return Ok(());
+    }
+    debug!("Simulation completed successfully");
+    Ok(())
+}
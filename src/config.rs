@@ -1,45 +1,580 @@
 use std::{
-    fs::{self, File},
-    io::BufReader,
+    collections::BTreeMap,
+    fs::File,
+    hash::Hasher,
+    io::{BufRead, BufReader, Read},
     path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
 };
 
-use crate::AccessRecord;
+use crate::{
+    analysis::{self, CostModel},
+    cancel::CancellationToken,
+    theme::Theme,
+    AccessRecord, Key, SampleCounts, NUM_CACHE_SIZE,
+};
 use clap::Parser;
 use csv::ReaderBuilder;
+use fasthash::{xx, FastHasher};
+use hashbrown::HashSet;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use tracing::{debug, error};
+use tracing::{debug, error, info, warn};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Parser, Default)]
 #[clap(author, version, about, long_about = None)]
-#[serde(default)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
     /// Path to the configuration file
     #[arg(long, value_name = "FILE")]
     #[serde(skip)]
     pub config_file: Option<PathBuf>,
 
+    #[command(subcommand)]
+    #[serde(skip)]
+    pub command_mode: Option<Mode>,
+
     /// Path to the trace file
-    #[arg(long, value_name = "FILE")]
+    #[arg(long, value_name = "FILE", global = true)]
     pub trace: Option<PathBuf>,
 
+    /// Simulate a generated Zipf trace instead of --trace. Without --policies,
+    /// --cache-size and --output it runs LRU, FIFO and LFU up to half the
+    /// unique bytes and draws demo_mrc.png in the current directory
+    #[arg(long)]
+    pub demo: bool,
+
+    /// Print statistics of the parsed trace fields and flag suspicious values,
+    /// then exit without simulating
+    #[arg(long = "validate")]
+    pub validate_trace: bool,
+
+    /// Expected XXH64 checksum of the trace file, as logged when it is loaded.
+    /// The run stops if the trace doesn't match
+    #[arg(long, value_name = "HEX", global = true)]
+    pub trace_checksum: Option<String>,
+
+    /// Trace file format: csv or jsonl (one JSON object per line)
+    #[arg(long, value_enum)]
+    pub format: Option<TraceFormat>,
+
+    /// JSON field names for jsonl traces, e.g. key=obj_id,size=obj_size.
+    /// Unmapped fields use their own name, missing ttl and command default to 0
+    #[arg(long, use_value_delimiter = true, value_delimiter = ',')]
+    pub json_fields: Option<Vec<String>>,
+
     /// Sample rate
     #[arg(long)]
     pub sample_rate: Option<f64>,
 
-    /// Path to the output file
-    #[arg(long, value_name = "FILE")]
+    /// SHARDS rates to compare against the unsampled simulation (e.g., 0.1,0.01).
+    /// Each policy runs once without SHARDS plus once per rate
+    #[arg(long, use_value_delimiter = true, value_delimiter = ',')]
+    pub shards_rates: Option<Vec<f64>>,
+
+    /// How SHARDS picks keys: uniform (default) at the sample rate, or with a
+    /// probability proportional to the object size, for --byte-miss-ratio on
+    /// traces with heavy-tailed sizes
+    #[arg(long, value_enum)]
+    pub shards_weighting: Option<ShardsWeighting>,
+
+    /// Skip a SHARDS curve whose sample has fewer than N distinct keys, and
+    /// say so instead of drawing a curve of noise (default 1)
+    #[arg(long, value_name = "N")]
+    pub min_sampled_keys: Option<u64>,
+
+    /// Skip a SHARDS curve whose sample has fewer than N requests (default 1)
+    #[arg(long, value_name = "N")]
+    pub min_sampled_requests: Option<u64>,
+
+    /// Plot the byte miss ratio, the fraction of the requested bytes that miss,
+    /// instead of the fraction of requests
+    #[arg(long)]
+    pub byte_miss_ratio: bool,
+
+    /// Count the bytes every cache size admits and evicts, exported with each point,
+    /// and draw the admission ratio (bytes admitted per byte requested) next to the
+    /// miss ratio curve: what the policies write to a flash cache
+    #[arg(long)]
+    pub churn: bool,
+
+    /// Path to the output file. Without one the key points of every curve are
+    /// printed as a table instead of drawing a figure
+    #[arg(long, value_name = "FILE", global = true)]
     pub output: Option<PathBuf>,
 
-    /// Cache eviction policies (LRU, FIFO, etc.)
-    #[arg(long, value_enum, use_value_delimiter = true, value_delimiter = ',')]
+    /// Figure format: png, pgfplots (LaTeX .tex) or both
+    #[arg(long, value_enum, global = true)]
+    pub output_format: Option<OutputFormat>,
+
+    /// Figure theme: light (gnuplot's defaults), dark, or colorblind (Okabe-Ito colors,
+    /// one per built-in policy). The [plot] table of a config file overrides single settings
+    #[arg(long, value_enum)]
+    pub theme: Option<ThemeName>,
+
+    /// [plot] table of a config file: background, foreground (text and border), grid,
+    /// font ("Name,size"), font_size, tick_font_size, line_width, scale, palette (the
+    /// colors curves take in turn) and colors (policy name to color). Colors are
+    /// written #RRGGBB
+    #[arg(skip)]
+    pub plot: Option<PlotStyle>,
+
+    /// Font sizes and line widths of the PNG figures: paper (large enough to read once
+    /// scaled into a column), slides, or default (gnuplot's). The --plot-* options and
+    /// the [plot] table override single settings
+    #[arg(long, value_enum)]
+    pub plot_preset: Option<PlotPreset>,
+
+    /// Resolution of the PNG figures as a multiple of 1920x1080, with the fonts and
+    /// lines scaled along so the figure looks the same, only sharper
+    #[arg(long, value_name = "SCALE")]
+    pub plot_scale: Option<f64>,
+
+    /// Base font size of the PNG figures in points, before --plot-scale
+    #[arg(long, value_name = "PT")]
+    pub plot_font_size: Option<f64>,
+
+    /// Font size of the tick labels of the PNG figures in points
+    #[arg(long, value_name = "PT")]
+    pub plot_tick_font_size: Option<f64>,
+
+    /// Width of the curves, a multiple of gnuplot's default
+    #[arg(long, value_name = "WIDTH")]
+    pub plot_line_width: Option<f64>,
+
+    /// Plot cache sizes relative to the trace: working-set divides byte sizes by the
+    /// unique bytes and object counts by the unique keys
+    #[arg(long, value_enum)]
+    pub x_normalize: Option<XNormalize>,
+
+    /// Extra outputs next to the figure: html (interactive report, needs the html feature),
+    /// json (hit and miss counters of every curve, <output>.json, see combine)
+    #[arg(
+        long,
+        value_enum,
+        use_value_delimiter = true,
+        value_delimiter = ',',
+        global = true
+    )]
+    pub export: Option<Vec<ExportFormat>>,
+
+    /// Path of the interactive HTML report, implies --export html
+    #[arg(long, value_name = "FILE")]
+    pub report: Option<PathBuf>,
+
+    /// Also draw every curve on its own figure, with a CSV of its points,
+    /// named <output>_<curve id>
+    #[arg(long)]
+    pub separate_figures: bool,
+
+    /// Also draw the reuse distance CDF of the trace on its own figure named
+    /// <output>_reuse_cdf. The gap to 1 is the share of cold requests
+    #[arg(long)]
+    pub plot_reuse_cdf: bool,
+
+    /// Also write the gnuplot script of the miss ratio figure, with the points
+    /// inline, to edit and render by hand
+    #[arg(long, value_name = "FILE")]
+    pub emit_gnuplot_script: Option<PathBuf>,
+
+    /// Cache eviction policies (LRU, FIFO, LFU, TWOQ, SLRU, LARGEST, LRUFQ, FROZENHOT).
+    /// Parameters go in parentheses, e.g. SLRU(protected=0.5), separated by ';' if there
+    /// are several. LRUFQ(threshold=1) is an LRU that moves a victim hit more than
+    /// `threshold` times back to the head once instead of evicting it.
+    /// FROZENHOT(hot=0.5;rebuild=100000) freezes the hottest keys in `hot` of the cache,
+    /// rebuilt every `rebuild` requests, with a FIFO for the rest.
+    /// Every policy takes watermark=F to evict down to F of the capacity on overflow.
+    /// Binaries built on the library also accept the names they register, see
+    /// examples/plugin_policy.rs
+    #[arg(long, use_value_delimiter = true, value_delimiter = ',')]
     #[serde(default = "default_eviction_policies")]
     pub policies: Option<Vec<EvictionPolicy>>,
 
-    /// Cache size (e.g., 100KB, 2MB)
-    #[arg(short, long, value_parser = parse_size)]
+    /// Units the cache capacity is measured in (bytes, objects). With both, each
+    /// policy produces one curve per unit
+    #[arg(long, value_enum, use_value_delimiter = true, value_delimiter = ',')]
+    pub capacity_units: Option<Vec<CapacityUnit>>,
+
+    /// Cache size (e.g., 100KB, 2MB), or a fraction of the trace's unique bytes with
+    /// a ws suffix (e.g., 0.1ws). The curve sweeps up to this size. With several
+    /// sizes (e.g., 0.01ws,0.1ws,1.0ws) exactly those sizes are simulated
+    #[arg(
+        short,
+        long,
+        global = true,
+        use_value_delimiter = true,
+        value_delimiter = ','
+    )]
+    #[serde(deserialize_with = "deserialize_cache_sizes")]
+    pub cache_size: Option<Vec<CacheSizeSpec>>,
+
+    /// Number of evenly spaced cache sizes simulated up to --cache-size (default 100)
+    #[arg(long, value_name = "N")]
+    pub num_sizes: Option<u64>,
+
+    /// Smallest cache size of the sweep up to --cache-size, e.g. 1MB. A bounded
+    /// sweep is simulated as given, past the trace footprint too
+    #[arg(long, value_parser = parse_size)]
+    #[serde(deserialize_with = "deserialize_cache_size")]
+    pub min_cache_size: Option<u64>,
+
+    /// Largest cache size of the sweep, the same as a single --cache-size
+    #[arg(long, value_parser = parse_size, conflicts_with = "cache_size")]
+    #[serde(deserialize_with = "deserialize_cache_size")]
+    pub max_cache_size: Option<u64>,
+
+    /// How the --num-sizes points of the sweep are spaced: linear or log. Without
+    /// --min-cache-size a log sweep starts at the first point of the linear one
+    #[arg(long)]
+    pub size_spacing: Option<SizeSpacing>,
+
+    /// Simulate the whole --cache-size range even past the trace footprint. By
+    /// default the sweep stops at the footprint, where the curve is already flat
+    #[arg(long)]
+    pub sweep_beyond_footprint: bool,
+
+    /// Fail instead of warning when the size column looks like a different unit than the cache size
+    #[arg(long)]
+    pub strict_units: bool,
+
+    /// Multiply the size column by this factor (e.g., 512 for sector-based traces)
+    #[arg(long)]
+    pub size_multiplier: Option<u32>,
+
+    /// Unit of the size column: bytes, kb (1024 bytes) or blocks:N (N bytes per block).
+    /// Sizes are converted to bytes on load, on top of --size-multiplier
+    #[arg(long)]
+    pub size_unit_in: Option<SizeUnit>,
+
+    /// Round every object size up to its slab class on load, like a slab
+    /// allocator does: memcached for memcached's default classes (growth factor
+    /// 1.25), or a file with one class size in bytes per line
+    #[arg(long, value_name = "memcached|FILE")]
+    pub slab_classes: Option<SlabClasses>,
+
+    /// Expire objects according to the ttl column
+    #[arg(long)]
+    pub respect_ttl: bool,
+
+    /// When --respect-ttl removes expired objects: lazy on their next request,
+    /// or proactive as the trace clock passes their expiry, freeing their space
+    /// for other objects. Default lazy
+    #[arg(long)]
+    pub ttl_expiry: Option<TtlExpiry>,
+
+    /// Trace time per bucket of the proactive --ttl-expiry sweep, an expired
+    /// object is removed at most this much late. Default 1
+    #[arg(long, value_name = "N")]
+    pub expiry_granularity: Option<u64>,
+
+    /// CSV file with a header and two columns (alias, canonical) mapping aliased keys
+    /// to their canonical key before sampling and simulation
+    #[arg(long, value_name = "FILE")]
+    pub key_alias_file: Option<PathBuf>,
+
+    /// Check every policy's size accounting after each request and stop the
+    /// simulation at the first violation. Slow, meant for policy development
+    #[arg(long)]
+    pub strict_invariants: bool,
+
+    /// Fail instead of dropping, with a warning, the policies the trace can't
+    /// support (e.g. LARGEST on a trace without a size column)
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Annotate the figures with where every curve starts and ends: a miss
+    /// ratio of 1 at size 0 and the compulsory miss ratio (unique keys over
+    /// requests) as the cache grows without bound
+    #[arg(long)]
+    pub annotate_asymptotes: bool,
+
+    /// Report how much each of the largest objects moves the byte curves:
+    /// the miss ratio drop at the first size that holds it. Objects larger
+    /// than half the swept sizes are warned about either way
+    #[arg(long)]
+    pub large_objects: bool,
+
+    /// Split the trace into this many contiguous segments and report the
+    /// standard error of every point's miss ratio across them. The segments
+    /// are consecutive in time, so workload drift widens the error as well
+    #[arg(long, value_name = "N")]
+    pub segments: Option<usize>,
+
+    /// Shade a band of one standard error around every curve, needs --segments
+    #[arg(long)]
+    pub error_bands: bool,
+
+    /// Smooth the SHARDS curves in the figures and the table: merge adjacent
+    /// sizes until every point rests on at least N sampled hits. The exports
+    /// keep the raw points, with the sampled hits of each
+    #[arg(long, value_name = "N")]
+    pub smooth_min_samples: Option<u64>,
+
+    /// Commands (see --command) whose requests only probe the caches: they count
+    /// as hits or misses on their own but never change what is cached, e.g. 2,3
+    #[arg(long, use_value_delimiter = true, value_delimiter = ',')]
+    pub probe_commands: Option<Vec<u8>>,
+
+    /// Fraction of the requests picked at random to only probe the caches,
+    /// like --probe-commands
+    #[arg(long)]
+    pub probe_rate: Option<f64>,
+
+    /// Commands (see --command) whose requests write the object, e.g. 1. A write
+    /// never counts as a hit: it resizes a cached copy in place, expired or not,
+    /// or admits the object, and with --respect-ttl restarts its expiry from the
+    /// request's ttl
+    #[arg(long, use_value_delimiter = true, value_delimiter = ',')]
+    pub write_commands: Option<Vec<u8>>,
+
+    /// Commands (see --command) whose requests delete the object from every cache,
+    /// e.g. 4. A delete never counts as a hit
+    #[arg(long, use_value_delimiter = true, value_delimiter = ',')]
+    pub delete_commands: Option<Vec<u8>>,
+
+    /// Stable sort the trace by timestamp before simulating
+    #[arg(long)]
+    pub sort_by_time: bool,
+
+    /// Clock of the time based options (--respect-ttl, --max-residency,
+    /// --collapse-window, --sort-by-time, --over-time): the timestamp column, or
+    /// the request index with TTLs and durations counted in requests. Defaults
+    /// to the column, unless every timestamp is the same
+    #[arg(long)]
+    pub time_source: Option<TimeSource>,
+
+    /// Wall-clock budget for the simulations (e.g., 60s). Curves still running
+    /// when it runs out stop and are drawn over the part of the trace they covered
+    #[arg(long, value_parser = parse_duration)]
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub max_time: Option<f64>,
+
+    /// Evict every object after it has been cached this long (e.g., 1h), whatever its TTL
+    #[arg(long, value_parser = parse_duration)]
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub max_residency: Option<f64>,
+
+    /// Count repeated requests for a key within this much trace time (e.g., 10ms, 1s) as
+    /// one request, like a cache collapsing retries. Trace timestamps are in seconds
+    #[arg(long, value_parser = parse_duration)]
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub collapse_window: Option<f64>,
+
+    /// Merge every run of consecutive requests for the same key into its first
+    /// request, for curves over distinct consecutive accesses. The merged repeats
+    /// leave the policies and the miss ratio denominator alike
+    #[arg(long)]
+    pub collapse_consecutive: bool,
+
+    /// Randomly permute the trace with this seed before simulating, to measure how much
+    /// of the hit ratio comes from request ordering
+    #[arg(long)]
+    pub shuffle_trace: Option<u64>,
+
+    /// With --shuffle-trace, only shuffle within blocks of this many records (e.g., 1M)
+    /// so coarse phases of the trace are kept
+    #[arg(long, value_parser = parse_count)]
+    #[serde(deserialize_with = "deserialize_count")]
+    pub shuffle_block: Option<usize>,
+
+    /// Also run this configuration file on the same loaded trace and write the per-size
+    /// miss ratio delta between its first curve and the first curve of this run.
+    /// Loading options come from this run
+    #[arg(long, value_name = "FILE")]
+    pub compare_config: Option<PathBuf>,
+
+    /// Report the best policy at the target cache size and plot the virtual best
+    /// (lower envelope) curve over all policies
+    #[arg(long)]
+    pub ensemble: bool,
+
+    /// Cost of one byte of cache, with --cost-per-miss reports the cheapest cache size
+    #[arg(long)]
+    pub cost_per_byte: Option<f64>,
+
+    /// Cost of one cache miss, with --cost-per-byte reports the cheapest cache size
+    #[arg(long)]
+    pub cost_per_miss: Option<f64>,
+
+    /// Cache size the ensemble report names the best policy for, defaults to the cache size
+    #[arg(long, value_parser = parse_size)]
+    #[serde(deserialize_with = "deserialize_cache_size")]
+    pub target_cache_size: Option<u64>,
+
+    /// Simulate a coarse log-spaced grid first, then refine around the knee of each curve
+    #[arg(long)]
+    pub adaptive: bool,
+
+    /// Total number of points per adaptive curve, over all passes
+    #[arg(long)]
+    pub point_budget: Option<usize>,
+
+    /// Number of adaptive refinement passes after the coarse pass
+    #[arg(long)]
+    pub refine_rounds: Option<usize>,
+
+    /// Only cache the N most requested keys, requests for other keys always miss
+    #[arg(long)]
+    pub top_keys: Option<usize>,
+
+    /// Write every object the first policy removes at --audit-cache-size, and
+    /// why (capacity, ttl, delete or invalidate), to this CSV file
+    #[arg(long, value_name = "FILE")]
+    pub audit_evictions: Option<PathBuf>,
+
+    /// Cache size the eviction audit runs at, defaults to the cache size
+    #[arg(long, value_parser = parse_size)]
+    #[serde(deserialize_with = "deserialize_cache_size")]
+    pub audit_cache_size: Option<u64>,
+
+    /// Write the percentiles of how many requests objects stay cached before
+    /// every policy evicts them at --audit-cache-size to <output>.eviction_ages.csv
+    #[arg(long)]
+    pub eviction_ages: bool,
+
+    /// Two policies to compare key by key at --probe-size, e.g. LRU,FIFO. Writes
+    /// the keys whose hit counts differ the most to <output>.attribution.csv
+    #[arg(long, use_value_delimiter = true, value_delimiter = ',')]
+    pub attribute: Option<Vec<EvictionPolicy>>,
+
+    /// Write the hit ratio and byte hit ratio of every policy per object size class,
+    /// at --probe-size, to <output>.size_classes.csv
+    #[arg(long)]
+    pub size_classes: bool,
+
+    /// Size class edges in bytes for --size-classes, e.g. 1024,65536,1048576.
+    /// Sizes below the first or above the last edge go in the outer classes.
+    /// Defaults to powers of two
+    #[arg(long, use_value_delimiter = true, value_delimiter = ',')]
+    pub size_class_edges: Option<Vec<u64>>,
+
+    /// Also draw the --size-classes hit ratios as a bar chart named <output>_size_classes
+    #[arg(long)]
+    pub size_class_chart: bool,
+
+    /// Write the objects resident at the end of the run in the cache closest to
+    /// --probe-size to <FILE>_<curve id>, as key,size CSV with the next victim first
+    #[arg(long, value_name = "FILE")]
+    pub dump_contents: Option<PathBuf>,
+
+    /// Load every simulated cache with the objects of a --dump-contents
+    /// snapshot before the trace starts, to model a cache that is already
+    /// warm. A cache smaller than the snapshot evicts its first objects as it
+    /// fills, in its policy's order. Not supported with SHARDS
+    #[arg(long, value_name = "FILE")]
+    pub warm_start: Option<PathBuf>,
+
+    /// Every N requests, snapshot the objects resident in the cache closest to
+    /// --probe-size and write the snapshots as a CSV timeline named
+    /// <output>_<curve id>_timeline.csv, one row per frame
+    #[arg(long, value_name = "N")]
+    pub timeline_every: Option<u64>,
+
+    /// Every --prometheus-every requests, write the hit ratio at every cache
+    /// size, the requests and sampled keys so far and the byte hit ratio of
+    /// the last --prometheus-every requests in the Prometheus text format to
+    /// <FILE>_<curve id>, for the node exporter's textfile collector to scrape
+    /// while the run goes on
+    #[arg(long, value_name = "FILE")]
+    pub prometheus_file: Option<PathBuf>,
+
+    /// Requests between two writes of --prometheus-file, defaults to 100000
+    #[arg(long, value_name = "N")]
+    pub prometheus_every: Option<u64>,
+
+    /// Cache size --attribute, --size-classes, --dump-contents and --timeline-every run at,
+    /// defaults to the cache size
+    #[arg(long, value_parser = parse_size)]
+    #[serde(deserialize_with = "deserialize_cache_size")]
+    pub probe_size: Option<u64>,
+
+    /// Keys written by --attribute, the largest hit count differences first
+    /// (default 1000). Only keys tracked under --max-tracked-keys are ranked
+    #[arg(long)]
+    pub top: Option<usize>,
+
+    /// Only run the exact simulation, ignoring --sample-rate and --shards-rates
+    #[arg(long)]
+    pub disable_shards: bool,
+
+    /// Number of worker threads shared by all simulations, defaults to the number of CPUs
+    #[arg(long)]
+    pub threads: Option<usize>,
+
+    /// How the simulations are spread over the threads: per-policy (default, one job
+    /// per curve), per-size (each curve's sizes split over the threads),
+    /// nested-shards (one job per policy running its --shards-rates curves in
+    /// turn, holding one curve's caches at a time) or auto
+    #[arg(long, value_enum)]
+    pub parallel_strategy: Option<ParallelStrategy>,
+
+    /// Pin simulation worker threads to physical cores, round-robin (Linux only)
+    #[arg(long)]
+    pub pin_cores: bool,
+
+    /// Run the simulations one after another on the main thread, in the configured
+    /// order, for debuggers and rr. The results are the same as with the pool
+    #[arg(long)]
+    pub sequential: bool,
+
+    /// Compute the LRU curves from one stack distance pass over the trace instead
+    /// of simulating every cache size. The other policies are still simulated, and
+    /// so is LRU with settings the pass can't reproduce: TTLs, probes, write
+    /// commands, a watermark, --max-residency, --dump-contents, --timeline-every
+    /// or keys whose size changes
+    #[arg(long)]
+    pub stack_distance: bool,
+
+    /// Max distinct keys tracked by the key statistics prepass
+    #[arg(long)]
+    pub max_tracked_keys: Option<usize>,
+
+    /// Simulate every group of keys that share their top N bits on its own, for
+    /// traces with the tenant in the key prefix. Each group gets its own curves,
+    /// exports and --attribute report, named <output>_group_<id>
+    #[arg(long, value_name = "N", conflicts_with = "group_by_key_mask")]
+    pub group_by_key_bits: Option<u32>,
+
+    /// Like --group-by-key-bits with any mask of the key, e.g. 0xFFFF000000000000
+    #[arg(long, value_name = "MASK", value_parser = parse_key_mask)]
+    #[serde(deserialize_with = "deserialize_key_mask")]
+    pub group_by_key_mask: Option<u64>,
+
+    /// Fail when the key groups outnumber N, each one is a full simulation (default 256)
+    #[arg(long, value_name = "N")]
+    pub max_groups: Option<usize>,
+
+    /// Simulate only --fixed-size and write every policy's miss ratio per
+    /// --time-bucket of trace time to <output>.over_time.csv, drawn with time
+    /// on the x axis as <output>_over_time, instead of the curves
+    #[arg(long, requires = "fixed_size")]
+    pub over_time: bool,
+
+    /// Cache size --over-time simulates, e.g. 4GB
+    #[arg(long, value_parser = parse_size)]
     #[serde(deserialize_with = "deserialize_cache_size")]
-    pub cache_size: Option<u64>,
+    pub fixed_size: Option<u64>,
+
+    /// Span of trace timestamps per --over-time bucket, defaults to a hundredth
+    /// of the trace's span
+    #[arg(long, value_name = "N")]
+    pub time_bucket: Option<u64>,
+
+    /// Parse CSV traces on this many threads, each taking a range of lines.
+    /// Capped by --threads
+    #[arg(long)]
+    pub parse_threads: Option<usize>,
+
+    /// Access counts at which keys become warm and hot, e.g. 2,10. Reports
+    /// the objects, bytes and hits of each tier
+    #[arg(long, use_value_delimiter = true, value_delimiter = ',')]
+    pub tier_thresholds: Option<Vec<u64>>,
+
+    /// What to do with trace records that can't be parsed: fail, warn (and drop) or skip
+    #[arg(long, value_enum)]
+    pub on_bad_record: Option<OnBadRecord>,
 
     #[arg(long)]
     pub timestamp: Option<i32>,
@@ -57,22 +592,421 @@ pub struct Config {
     pub ttl: Option<i32>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct InnerConfig {
-    pub output: PathBuf,
+    // Cancels every simulation started with this config, hosts keep a clone
+    pub cancel: CancellationToken,
+    // Wall-clock budget of each batch of simulations
+    pub max_time: Option<Duration>,
+    pub trace: PathBuf,
+    // Checksum of the loaded trace, or the expected one until it is loaded
+    pub trace_checksum: Option<u64>,
+    // None prints a table instead of the figure, see `output_path`
+    pub output: Option<PathBuf>,
+    pub output_format: OutputFormat,
+    pub x_normalize: Option<XNormalize>,
+    pub theme: Theme,
+    // What `theme` was built from, for the exports
+    pub figure: FigureOptions,
+    pub export: Vec<ExportFormat>,
+    pub report: Option<PathBuf>,
+    pub separate_figures: bool,
+    pub plot_reuse_cdf: bool,
+    pub emit_gnuplot_script: Option<PathBuf>,
     pub policies: Vec<EvictionPolicy>,
+    // --cache-size as given, see `resolve_cache_sizes`
+    pub cache_size_spec: Vec<CacheSizeSpec>,
+    // Largest resolved cache size, 0 until the working set fractions are resolved
     pub cache_size: u64,
+    // Every resolved cache size in increasing order when several were given
+    pub cache_sizes: Option<Vec<u64>>,
+    // Points of the evenly spaced grid when no sizes are listed
+    pub num_sizes: u64,
+    // Lower end and spacing of a bounded sweep, see `sweep`
+    pub min_cache_size: Option<u64>,
+    pub size_spacing: SizeSpacing,
+    pub sweep_beyond_footprint: bool,
+    pub capacity_units: Vec<CapacityUnit>,
+    pub shards_weighting: ShardsWeighting,
+    pub min_sampled: SampleCounts,
+    pub byte_miss_ratio: bool,
+    pub churn: bool,
+    pub slab_classes: Option<SlabClasses>,
     pub sample_rate: Option<f64>,
+    pub shards_rates: Option<Vec<f64>>,
+    pub max_tracked_keys: Option<usize>,
+    // Key bits every group shares, see --group-by-key-bits
+    pub group_mask: Option<u64>,
+    pub max_groups: usize,
+    // Cache size and bucket width of --over-time
+    pub over_time: Option<u64>,
+    pub time_bucket: Option<u64>,
+    pub threads: Option<usize>,
+    pub parallel_strategy: ParallelStrategy,
+    pub pin_cores: bool,
+    pub sequential: bool,
+    pub stack_distance: bool,
+    pub strict_units: bool,
+    pub respect_ttl: bool,
+    // Bucket width of the proactive expiry sweep, None for lazy expiry
+    pub expiry_granularity: Option<u64>,
+    pub strict_invariants: bool,
+    pub strict: bool,
+    pub annotate_asymptotes: bool,
+    pub large_objects: bool,
+    pub segments: Option<usize>,
+    pub error_bands: bool,
+    pub smooth_min_samples: Option<u64>,
+    pub probe_commands: Vec<u8>,
+    pub probe_rate: Option<f64>,
+    pub write_commands: Vec<u8>,
+    pub delete_commands: Vec<u8>,
+    // Seconds of trace time
+    pub max_residency: Option<u64>,
+    // Seed and block size the trace was shuffled with
+    pub shuffle: Option<(u64, Option<usize>)>,
+    // Clock the time based options ran on, None when none are set
+    pub time_source: Option<TimeSource>,
+    pub ensemble: bool,
+    pub target_cache_size: Option<u64>,
+    pub cost_model: Option<CostModel>,
+    pub adaptive: Option<AdaptiveConfig>,
+    pub top_keys: Option<usize>,
+    pub compare_config: Option<PathBuf>,
+    // Access counts where the warm and hot tiers start
+    pub tier_thresholds: Option<(u64, u64)>,
+    pub audit_evictions: Option<PathBuf>,
+    pub audit_cache_size: Option<u64>,
+    pub eviction_ages: bool,
+    // Log what leaves the simulated cache, only set for the replay of
+    // audit::policy_evictions
+    pub eviction_log: bool,
+    pub attribute: Option<(EvictionPolicy, EvictionPolicy)>,
+    pub dump_contents: Option<PathBuf>,
+    // Loaded once the cache sizes are resolved, see `load_warm_start`
+    pub warm_start: Option<WarmStart>,
+    pub timeline_every: Option<u64>,
+    // --prometheus-file and the requests between two writes
+    pub prometheus: Option<(PathBuf, u64)>,
+    pub probe_size: Option<u64>,
+    pub top: usize,
+    pub size_classes: bool,
+    pub size_class_edges: Option<Vec<u64>>,
+    pub size_class_chart: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AdaptiveConfig {
+    pub point_budget: usize,
+    pub refine_rounds: usize,
 }
 
-impl From<Config> for InnerConfig {
-    fn from(config: Config) -> Self {
-        InnerConfig {
-            output: config.output.unwrap(),
+impl TryFrom<Config> for InnerConfig {
+    type Error = String;
+
+    fn try_from(config: Config) -> Result<Self, String> {
+        let figure = config.figure_options();
+        let time_source = config
+            .uses_timestamps()
+            .then(|| config.time_source.unwrap_or_default());
+        let mut inner = InnerConfig {
+            cancel: CancellationToken::new(),
+            max_time: config.max_time.map(Duration::from_secs_f64),
+            trace: config.trace.clone().unwrap_or_default(),
+            trace_checksum: config
+                .trace_checksum
+                .as_deref()
+                .map(parse_checksum)
+                .transpose()?,
+            output: config.output,
+            output_format: config.output_format.unwrap_or_default(),
+            x_normalize: config.x_normalize,
+            theme: Theme::new(&figure),
+            figure,
+            export: {
+                let mut export = config.export.unwrap_or_default();
+                if config.report.is_some() && !export.contains(&ExportFormat::Html) {
+                    export.push(ExportFormat::Html);
+                }
+                export
+            },
+            report: config.report,
+            separate_figures: config.separate_figures,
+            plot_reuse_cdf: config.plot_reuse_cdf,
+            emit_gnuplot_script: config.emit_gnuplot_script,
             policies: config.policies.unwrap(),
-            cache_size: config.cache_size.unwrap(),
-            sample_rate: config.sample_rate,
+            // --over-time needs no curve sizes of its own
+            cache_size_spec: config
+                .cache_size
+                .or_else(|| {
+                    config
+                        .max_cache_size
+                        .or(config.fixed_size)
+                        .map(|size| vec![CacheSizeSpec::Bytes(size)])
+                })
+                .unwrap(),
+            cache_size: 0,
+            cache_sizes: None,
+            num_sizes: config.num_sizes.unwrap_or(NUM_CACHE_SIZE),
+            min_cache_size: config.min_cache_size,
+            size_spacing: config.size_spacing.unwrap_or_default(),
+            sweep_beyond_footprint: config.sweep_beyond_footprint,
+            capacity_units: config
+                .capacity_units
+                .unwrap_or_else(|| vec![CapacityUnit::Bytes]),
+            // --disable-shards leaves only the exact simulation
+            sample_rate: config.sample_rate.filter(|_| !config.disable_shards),
+            shards_rates: config.shards_rates.filter(|_| !config.disable_shards),
+            shards_weighting: config.shards_weighting.unwrap_or_default(),
+            min_sampled: SampleCounts {
+                keys: config.min_sampled_keys.unwrap_or(1),
+                requests: config.min_sampled_requests.unwrap_or(1),
+            },
+            byte_miss_ratio: config.byte_miss_ratio,
+            churn: config.churn,
+            slab_classes: config.slab_classes,
+            max_tracked_keys: config.max_tracked_keys,
+            group_mask: config
+                .group_by_key_mask
+                .or(config.group_by_key_bits.map(analysis::top_bits_mask)),
+            max_groups: config.max_groups.unwrap_or(256),
+            over_time: config.fixed_size.filter(|_| config.over_time),
+            time_bucket: config.time_bucket,
+            threads: config.threads,
+            parallel_strategy: config.parallel_strategy.unwrap_or_default(),
+            pin_cores: config.pin_cores,
+            sequential: config.sequential,
+            stack_distance: config.stack_distance,
+            strict_units: config.strict_units,
+            respect_ttl: config.respect_ttl,
+            expiry_granularity: (config.ttl_expiry == Some(TtlExpiry::Proactive))
+                .then(|| config.expiry_granularity.unwrap_or(1)),
+            strict_invariants: config.strict_invariants,
+            strict: config.strict,
+            annotate_asymptotes: config.annotate_asymptotes,
+            large_objects: config.large_objects,
+            segments: config.segments,
+            error_bands: config.error_bands,
+            smooth_min_samples: config.smooth_min_samples,
+            probe_commands: config.probe_commands.unwrap_or_default(),
+            probe_rate: config.probe_rate,
+            write_commands: config.write_commands.unwrap_or_default(),
+            delete_commands: config.delete_commands.unwrap_or_default(),
+            max_residency: config.max_residency.map(|seconds| seconds.ceil() as u64),
+            time_source,
+            shuffle: config
+                .shuffle_trace
+                .map(|seed| (seed, config.shuffle_block)),
+            ensemble: config.ensemble,
+            cost_model: config.cost_per_byte.zip(config.cost_per_miss).map(
+                |(cost_per_byte, cost_per_miss)| CostModel {
+                    cost_per_byte,
+                    cost_per_miss,
+                },
+            ),
+            target_cache_size: config.target_cache_size,
+            adaptive: config.adaptive.then(|| AdaptiveConfig {
+                point_budget: config.point_budget.unwrap_or(64),
+                refine_rounds: config.refine_rounds.unwrap_or(2),
+            }),
+            top_keys: config.top_keys,
+            compare_config: config.compare_config.clone(),
+            tier_thresholds: config
+                .tier_thresholds
+                .as_ref()
+                .map(|thresholds| (thresholds[0], thresholds[1])),
+            audit_evictions: config.audit_evictions,
+            audit_cache_size: config.audit_cache_size,
+            eviction_ages: config.eviction_ages,
+            eviction_log: false,
+            attribute: config
+                .attribute
+                .as_ref()
+                .map(|policies| (policies[0].clone(), policies[1].clone())),
+            dump_contents: config.dump_contents,
+            warm_start: config.warm_start.map(|path| WarmStart {
+                path,
+                objects: Arc::new(Vec::new()),
+            }),
+            timeline_every: config.timeline_every,
+            prometheus: config
+                .prometheus_file
+                .map(|path| (path, config.prometheus_every.unwrap_or(100_000))),
+            probe_size: config.probe_size,
+            top: config.top.unwrap_or(1000),
+            size_classes: config.size_classes,
+            size_class_edges: config.size_class_edges,
+            size_class_chart: config.size_class_chart,
+        };
+        // Working set fractions wait for the trace footprint
+        if !inner.needs_working_set() {
+            inner.resolve_cache_sizes(None)?;
+        }
+        Ok(inner)
+    }
+}
+
+// The objects of a --warm-start snapshot in eviction order, the next victim
+// first. Shared by every simulator, and kept out of the logged config.
+#[derive(Clone)]
+pub struct WarmStart {
+    pub path: PathBuf,
+    pub objects: Arc<Vec<(Key, u64)>>,
+}
+
+impl std::fmt::Debug for WarmStart {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} ({} objects)", self.path, self.objects.len())
+    }
+}
+
+impl InnerConfig {
+    // Path the files written next to the figure are named after. Config::validate
+    // turns down every option that writes one without --output.
+    pub fn output_path(&self) -> &Path {
+        self.output
+            .as_deref()
+            .expect("options writing next to the figure need --output")
+    }
+
+    // Whether --cache-size has working set fractions, see `resolve_cache_sizes`
+    pub fn needs_working_set(&self) -> bool {
+        self.cache_size_spec
+            .iter()
+            .any(|spec| matches!(spec, CacheSizeSpec::WorkingSet(_)))
+    }
+
+    // Turn --cache-size into bytes, `working_set` being the unique bytes of the
+    // trace. Fails on working set fractions without a working set.
+    pub fn resolve_cache_sizes(&mut self, working_set: Option<u64>) -> Result<(), String> {
+        let mut sizes = self
+            .cache_size_spec
+            .iter()
+            .map(|spec| spec.resolve(working_set))
+            .collect::<Result<Vec<_>, _>>()?;
+        sizes.sort_unstable();
+        sizes.dedup();
+        self.cache_size = *sizes.last().unwrap();
+        let bounded = self.min_cache_size.is_some() || self.size_spacing == SizeSpacing::Log;
+        self.cache_sizes = if sizes.len() > 1 {
+            Some(sizes)
+        } else if bounded {
+            Some(self.sweep(self.cache_size)?)
+        } else {
+            None
+        };
+        Ok(())
+    }
+
+    // The --num-sizes sizes from --min-cache-size to `max`, spaced per
+    // --size-spacing, both ends included
+    fn sweep(&self, max: u64) -> Result<Vec<u64>, String> {
+        let min = self.min_cache_size.unwrap_or(max / self.num_sizes).max(1);
+        if min >= max {
+            return Err(format!(
+                "--min-cache-size {min} must be below the largest cache size {max}"
+            ));
+        }
+        let n = self.num_sizes as usize;
+        let mut sizes = match self.size_spacing {
+            SizeSpacing::Linear => (0..n)
+                .map(|i| {
+                    let step = (max - min) as u128 * i as u128 / (n - 1) as u128;
+                    min + step as u64
+                })
+                .collect::<Vec<_>>(),
+            SizeSpacing::Log => analysis::log_spaced(min, max, n),
+        };
+        sizes.dedup();
+        Ok(sizes)
+    }
+
+    // Read the --warm-start snapshot, checking that every object fits in the
+    // largest cache. Call after `resolve_cache_sizes`.
+    pub fn load_warm_start(&mut self) -> Result<(), String> {
+        let Some(warm_start) = self.warm_start.as_mut() else {
+            return Ok(());
+        };
+        let path = &warm_start.path;
+        let mut reader = ReaderBuilder::new()
+            .has_headers(true)
+            .from_path(path)
+            .map_err(|e| format!("failed to read the --warm-start snapshot {path:?}: {e}"))?;
+        let mut objects = Vec::new();
+        let mut seen = HashSet::new();
+        for (line, row) in reader.deserialize::<(Key, u64)>().enumerate() {
+            let (key, size) = row.map_err(|e| format!("{path:?} is not a key,size CSV: {e}"))?;
+            if size == 0 {
+                return Err(format!(
+                    "object {key} on line {} of {path:?} has size 0",
+                    line + 2
+                ));
+            }
+            if size > self.cache_size {
+                return Err(format!(
+                    "object {key} on line {} of {path:?} has size {size}, larger than the \
+                     largest cache size {}, was it dumped in another unit?",
+                    line + 2,
+                    self.cache_size
+                ));
+            }
+            if !seen.insert(key) {
+                return Err(format!("{path:?} lists object {key} twice"));
+            }
+            objects.push((key, size));
         }
+        warm_start.objects = Arc::new(objects);
+        Ok(())
+    }
+
+    // --cache-size as given, followed by the resolved bytes if they differ,
+    // e.g. "0.1ws,0.5ws (1048576,5242880 bytes)"
+    pub fn describe_cache_size(&self) -> String {
+        let spec = self
+            .cache_size_spec
+            .iter()
+            .map(CacheSizeSpec::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        if !self.needs_working_set() {
+            return spec;
+        }
+        let resolved = match self.cache_sizes.as_ref() {
+            Some(sizes) => sizes
+                .iter()
+                .map(u64::to_string)
+                .collect::<Vec<_>>()
+                .join(","),
+            None => self.cache_size.to_string(),
+        };
+        format!("{spec} ({resolved} bytes)")
+    }
+
+    // Drop the policies `schema` can't support, returning each with the reason.
+    // Fails instead with --strict, or when no policy is left.
+    pub fn drop_unsupported_policies(
+        &mut self,
+        schema: &TraceSchema,
+    ) -> Result<Vec<(EvictionPolicy, String)>, String> {
+        let (supported, dropped): (Vec<_>, Vec<_>) = std::mem::take(&mut self.policies)
+            .into_iter()
+            .map(|policy| {
+                let reason = policy.unsupported_by(schema);
+                (policy, reason)
+            })
+            .partition(|(_, reason)| reason.is_none());
+        let dropped = dropped
+            .into_iter()
+            .map(|(policy, reason)| (policy, reason.unwrap()))
+            .collect::<Vec<_>>();
+        self.policies = supported.into_iter().map(|(policy, _)| policy).collect();
+        if let Some((policy, reason)) = dropped.first().filter(|_| self.strict) {
+            return Err(format!("{policy} can't run on this trace: {reason}"));
+        }
+        if self.policies.is_empty() {
+            return Err("none of the policies can run on this trace".to_string());
+        }
+        Ok(dropped)
     }
 }
 
@@ -80,144 +1014,2386 @@ impl Config {
     pub fn from_file(path: &PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
         let content = std::fs::read_to_string(path)?;
         let mut args: Config = toml::from_str(&content)?;
+        args.command_mode = Config::parse().command_mode;
         args.config_file = Some(path.clone());
         Ok(args)
     }
+
+    // Whether any option reads the time of the requests, see --time-source
+    pub fn uses_timestamps(&self) -> bool {
+        self.respect_ttl
+            || self.max_residency.is_some()
+            || self.collapse_window.is_some()
+            || self.sort_by_time
+            || self.over_time
+    }
 }
 
 fn default_eviction_policies() -> Option<Vec<EvictionPolicy>> {
     Some(vec![EvictionPolicy::LRU])
 }
 
+fn deserialize_duration<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s: String = serde::Deserialize::deserialize(deserializer)?;
+    parse_duration(&s).map_err(serde::de::Error::custom)
+}
+
+// Parse a duration such as 10ms, 500us, 2s or 1h into seconds
+fn parse_duration(s: &str) -> Result<Option<f64>, String> {
+    let s = s.trim().to_lowercase();
+    let units = [
+        ("ns", 1e-9),
+        ("us", 1e-6),
+        ("ms", 1e-3),
+        ("s", 1.0),
+        ("m", 60.0),
+        ("h", 3600.0),
+        ("d", 86400.0),
+    ];
+    let (value, scale) = units
+        .iter()
+        .find_map(|(suffix, scale)| s.strip_suffix(suffix).map(|value| (value, *scale)))
+        .unwrap_or((s.as_str(), 1.0));
+    let value = value
+        .trim()
+        .parse::<f64>()
+        .map_err(|e| format!("invalid duration '{s}': {e}"))?;
+    if value < 0.0 {
+        return Err(format!("duration '{s}' is negative"));
+    }
+    Ok(Some(value * scale))
+}
+
+// A record count with an optional K, M or G (powers of 1000) suffix
+fn parse_count(s: &str) -> Result<Option<usize>, String> {
+    let s = s.trim().to_uppercase();
+    let (value, scale) = [("K", 1_000), ("M", 1_000_000), ("G", 1_000_000_000)]
+        .iter()
+        .find_map(|(suffix, scale)| s.strip_suffix(suffix).map(|value| (value, *scale)))
+        .unwrap_or((s.as_str(), 1));
+    let value = value
+        .trim()
+        .parse::<usize>()
+        .map_err(|e| format!("invalid count '{s}': {e}"))?;
+    if value == 0 {
+        return Err("count must be positive".to_string());
+    }
+    Ok(Some(value * scale))
+}
+
+fn deserialize_count<'de, D>(deserializer: D) -> Result<Option<usize>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s: String = serde::Deserialize::deserialize(deserializer)?;
+    parse_count(&s).map_err(serde::de::Error::custom)
+}
+
+// A key mask, in hex with 0x or in decimal
+fn parse_key_mask(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(&hex.replace('_', ""), 16),
+        None => s.replace('_', "").parse::<u64>(),
+    }
+    .map_err(|e| format!("{s} is not a key mask: {e}"))
+}
+
+fn deserialize_key_mask<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s: String = serde::Deserialize::deserialize(deserializer)?;
+    parse_key_mask(&s)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
 fn deserialize_cache_size<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
 where
     D: serde::Deserializer<'de>,
 {
     let s: String = serde::Deserialize::deserialize(deserializer)?;
-    parse_size(&s).map_err(serde::de::Error::custom)
+    parse_size(&s).map(Some).map_err(serde::de::Error::custom)
+}
+
+#[derive(clap::Subcommand, Clone, Debug)]
+pub enum Mode {
+    /// Classify the trace's workload and recommend an eviction policy
+    Recommend {
+        /// Also simulate the recommended policy and print its miss ratio curve
+        #[arg(long)]
+        run: bool,
+    },
+    /// Combine the curves of several --export json runs into one curve per
+    /// policy, weighting every run by its request count. Takes --output,
+    /// --output-format and --export like a simulation
+    Combine {
+        /// Exports to combine, their curves must have the same labels
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+    },
+    /// Simulate as usual, then compare the curves against a golden --export
+    /// json file and fail when a miss ratio is off by more than --tolerance.
+    /// data/golden has a fixture to run it on
+    Check {
+        /// Export of a trusted run with the same configuration
+        golden: PathBuf,
+        /// Largest difference allowed between two miss ratios
+        #[arg(long, default_value_t = 0.001)]
+        tolerance: f64,
+        /// Write the simulated curves to the golden file instead, after a
+        /// change that moves them on purpose
+        #[arg(long)]
+        bless: bool,
+    },
+    /// Write the loaded trace as timestamp,command,key,size,ttl CSV, after the
+    /// usual column mapping, --size-multiplier and --slab-classes
+    Convert {
+        /// CSV file to write
+        output: PathBuf,
+        /// Rehash the keys with a secret drawn for this run and shift the
+        /// timestamps to start at 0. Equal keys stay equal, so the curves
+        /// don't change
+        #[arg(long)]
+        anonymize: bool,
+        /// Print the secret the keys were rehashed with
+        #[arg(long, requires = "anonymize")]
+        print_key: bool,
+        /// Round the timestamps down to a multiple of N
+        #[arg(long, value_name = "N", requires = "anonymize")]
+        coarsen_time: Option<u64>,
+        /// Write 0 for every command
+        #[arg(long, requires = "anonymize")]
+        drop_command: bool,
+        /// Write 0 for every TTL
+        #[arg(long, requires = "anonymize")]
+        drop_ttl: bool,
+    },
 }
 
 // 确保 EvictionPolicy 可以被序列化和反序列化
-#[derive(clap::ValueEnum, Clone, Debug, Deserialize, Serialize)]
+// Written as a spec string such as "LRU" or "SLRU(protected=0.5)"
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
 pub enum EvictionPolicy {
     LRU,
     FIFO,
     LFU,
     TWOQ,
+    // Evicts the largest object first, a size-only baseline
+    LARGEST,
+    // `protected` is the fraction of the capacity for keys hit at least twice
+    SLRU {
+        protected: f64,
+    },
+    // LRU that spares tail keys hit more than `threshold` times once
+    LRUFQ {
+        threshold: u64,
+    },
+    // `hot` is the fraction of the capacity frozen, rebuilt every `rebuild` requests
+    FROZENHOT {
+        hot: f64,
+        rebuild: u64,
+    },
+    // A policy registered with evict_policy::register_policy, `name` uppercase
+    Registered {
+        name: String,
+        params: crate::evict_policy::PolicyParams,
+    },
+    // Any policy with the `watermark` parameter: once the cache overflows, it
+    // evicts down to this fraction of the capacity
+    Watermark {
+        policy: Box<EvictionPolicy>,
+        watermark: f64,
+    },
+}
+
+// Record fields the loaded trace actually carries, for the policies that need them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceSchema {
+    pub sizes: bool,
+    pub ttls: bool,
 }
 
 impl EvictionPolicy {
-    pub fn to_string(&self) -> String {
+    // Why the policy can't run on a trace with `schema`, None if it can
+    pub fn unsupported_by(&self, schema: &TraceSchema) -> Option<String> {
         match self {
-            EvictionPolicy::LRU => "LRU",
-            EvictionPolicy::FIFO => "FIFO",
-            EvictionPolicy::LFU => "LFU",
-            EvictionPolicy::TWOQ => "TWOQ",
+            EvictionPolicy::LARGEST if !schema.sizes => Some(
+                "it ranks objects by size and the trace has no size column, every object is 1 byte"
+                    .to_string(),
+            ),
+            EvictionPolicy::Watermark { policy, .. } => policy.unsupported_by(schema),
+            _ => None,
         }
-        .to_string()
     }
-}
-
-fn parse_size(s: &str) -> Result<Option<u64>, String> {
-    let s = s.trim().to_uppercase();
-    let cache_size = if s.ends_with("KB") {
-        s[..s.len() - 2]
-            .parse::<u64>()
-            .map(|n| n * 1024)
-            .map_err(|e| e.to_string())
-    } else if s.ends_with("MB") {
-        s[..s.len() - 2]
-            .parse::<u64>()
-            .map(|n| n * 1024 * 1024)
-            .map_err(|e| e.to_string())
-    } else if s.ends_with("GB") {
-        s[..s.len() - 2]
-            .parse::<u64>()
-            .map(|n| n * 1024 * 1024 * 1024)
-            .map_err(|e| e.to_string())
-    } else {
-        s.parse::<u64>().map_err(|e| e.to_string())
-    };
-    cache_size.map(Some)
-}
-
-pub fn load_access_records(arg: &Config) -> Vec<AccessRecord> {
-    let trace_path = arg.trace.as_ref().unwrap();
-    let file = File::open(trace_path).unwrap();
-    let reader = BufReader::new(file);
-    let mut rdr = ReaderBuilder::new().has_headers(true).from_reader(reader);
 
-    if is_default_parsing(arg) {
-        parse_default(&mut rdr)
-    } else {
-        parse_custom(arg, &mut rdr)
+    // The policy without its watermark, and the watermark if one is set
+    pub fn split_watermark(&self) -> (&EvictionPolicy, Option<f64>) {
+        match self {
+            EvictionPolicy::Watermark { policy, watermark } => (policy, Some(*watermark)),
+            policy => (policy, None),
+        }
     }
 }
 
-fn is_default_parsing(arg: &Config) -> bool {
-    arg.timestamp.is_none()
+impl std::fmt::Display for EvictionPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvictionPolicy::LRU => f.write_str("LRU"),
+            EvictionPolicy::FIFO => f.write_str("FIFO"),
+            EvictionPolicy::LFU => f.write_str("LFU"),
+            EvictionPolicy::TWOQ => f.write_str("TWOQ"),
+            EvictionPolicy::LARGEST => f.write_str("LARGEST"),
+            EvictionPolicy::SLRU { protected } => write!(f, "SLRU(protected={protected})"),
+            EvictionPolicy::LRUFQ { threshold } => write!(f, "LRUFQ(threshold={threshold})"),
+            EvictionPolicy::FROZENHOT { hot, rebuild } => {
+                write!(f, "FROZENHOT(hot={hot};rebuild={rebuild})")
+            }
+            EvictionPolicy::Registered { name, params } if params.is_empty() => f.write_str(name),
+            EvictionPolicy::Registered { name, params } => {
+                let params = params
+                    .iter()
+                    .map(|(key, value)| format!("{key}={value}"))
+                    .collect::<Vec<_>>();
+                write!(f, "{name}({})", params.join(";"))
+            }
+            EvictionPolicy::Watermark { policy, watermark } => {
+                let policy = policy.to_string();
+                match policy.strip_suffix(')') {
+                    Some(params) => write!(f, "{params};watermark={watermark})"),
+                    None => write!(f, "{policy}(watermark={watermark})"),
+                }
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for EvictionPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (name, params) = match s.split_once('(') {
+            Some((name, rest)) => {
+                let params = rest
+                    .strip_suffix(')')
+                    .ok_or_else(|| format!("missing ')' in policy {s}"))?;
+                (name.trim(), params)
+            }
+            None => (s, ""),
+        };
+        let mut params = params
+            .split([',', ';'])
+            .filter(|param| !param.trim().is_empty())
+            .map(|param| {
+                let (key, value) = param
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected key=value, got {param} in policy {s}"))?;
+                let value = value
+                    .trim()
+                    .parse::<f64>()
+                    .map_err(|e| format!("invalid value for {} in policy {s}: {e}", key.trim()))?;
+                Ok((key.trim().to_lowercase(), value))
+            })
+            .collect::<Result<hashbrown::HashMap<_, _>, String>>()?;
+        // Registered policies take any parameters, their factory checks them.
+        // The watermark stays behind to wrap them like the built-in ones.
+        let mut registered = crate::evict_policy::registered_policy(name).map(|_| {
+            let watermark = params.remove("watermark");
+            let registered = params.drain().collect();
+            params.extend(watermark.map(|watermark| ("watermark".to_string(), watermark)));
+            registered
+        });
+        let mut param = |key: &str, default: f64| params.remove(key).unwrap_or(default);
+
+        let policy = match name.to_uppercase().as_str() {
+            upper if registered.is_some() => EvictionPolicy::Registered {
+                name: upper.to_string(),
+                params: registered.take().unwrap_or_default(),
+            },
+            "LRU" => EvictionPolicy::LRU,
+            "FIFO" => EvictionPolicy::FIFO,
+            "LFU" => EvictionPolicy::LFU,
+            "TWOQ" => EvictionPolicy::TWOQ,
+            "LARGEST" => EvictionPolicy::LARGEST,
+            "SLRU" => {
+                let protected = param("protected", 0.8);
+                if !(0.0..=1.0).contains(&protected) {
+                    return Err(format!(
+                        "SLRU protected fraction {protected} is not in 0..=1"
+                    ));
+                }
+                EvictionPolicy::SLRU { protected }
+            }
+            "LRUFQ" => {
+                let threshold = param("threshold", 1.0);
+                if !(threshold >= 0.0 && threshold.fract() == 0.0) {
+                    return Err(format!(
+                        "LRUFQ threshold {threshold} is not a whole number of hits"
+                    ));
+                }
+                EvictionPolicy::LRUFQ {
+                    threshold: threshold as u64,
+                }
+            }
+            "FROZENHOT" => {
+                let hot = param("hot", 0.5);
+                if !(0.0..=1.0).contains(&hot) {
+                    return Err(format!("FROZENHOT hot fraction {hot} is not in 0..=1"));
+                }
+                let rebuild = param("rebuild", 100_000.0);
+                if !(rebuild >= 1.0 && rebuild.fract() == 0.0) {
+                    return Err(format!(
+                        "FROZENHOT rebuild interval {rebuild} is not a positive whole number"
+                    ));
+                }
+                EvictionPolicy::FROZENHOT {
+                    hot,
+                    rebuild: rebuild as u64,
+                }
+            }
+            _ => return Err(format!("unknown policy {name}")),
+        };
+        let watermark = param("watermark", 1.0);
+        if !(watermark > 0.0 && watermark <= 1.0) {
+            return Err(format!("watermark {watermark} is not in (0, 1]"));
+        }
+        let policy = if watermark < 1.0 {
+            EvictionPolicy::Watermark {
+                policy: Box::new(policy),
+                watermark,
+            }
+        } else {
+            policy
+        };
+        if let Some(key) = params.keys().next() {
+            return Err(format!("unknown parameter {key} for policy {name}"));
+        }
+        Ok(policy)
+    }
+}
+
+impl TryFrom<String> for EvictionPolicy {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<EvictionPolicy> for String {
+    fn from(policy: EvictionPolicy) -> Self {
+        policy.to_string()
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CapacityUnit {
+    // Every object is charged its size
+    Bytes,
+    // Every object takes one slot
+    Objects,
+}
+
+impl std::fmt::Display for CapacityUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            CapacityUnit::Bytes => "bytes",
+            CapacityUnit::Objects => "objects",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TraceFormat {
+    #[default]
+    Csv,
+    Jsonl,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Png,
+    Pgfplots,
+    Both,
+}
+
+// One --cache-size value, written as a byte size such as 2MB or as a fraction
+// of the trace's unique bytes such as 0.1ws
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum CacheSizeSpec {
+    Bytes(u64),
+    WorkingSet(f64),
+}
+
+impl CacheSizeSpec {
+    // Bytes this size stands for, given the unique bytes of the trace
+    pub fn resolve(&self, working_set: Option<u64>) -> Result<u64, String> {
+        match (self, working_set) {
+            (CacheSizeSpec::Bytes(bytes), _) => Ok(*bytes),
+            (CacheSizeSpec::WorkingSet(fraction), Some(working_set)) if working_set > 0 => {
+                Ok(((working_set as f64 * fraction) as u64).max(1))
+            }
+            (CacheSizeSpec::WorkingSet(fraction), _) => Err(format!(
+                "cache size {fraction}ws needs the trace working set, which is unknown or empty"
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for CacheSizeSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CacheSizeSpec::Bytes(bytes) => write!(f, "{bytes}"),
+            CacheSizeSpec::WorkingSet(fraction) => write!(f, "{fraction}ws"),
+        }
+    }
+}
+
+impl std::str::FromStr for CacheSizeSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        match s.strip_suffix("ws") {
+            Some(fraction) => match fraction.trim().parse::<f64>() {
+                Ok(fraction) if fraction > 0.0 && fraction.is_finite() => {
+                    Ok(CacheSizeSpec::WorkingSet(fraction))
+                }
+                Ok(_) => Err(format!("working set fraction in {s} must be positive")),
+                Err(e) => Err(format!("invalid working set fraction in {s}: {e}")),
+            },
+            None => Ok(CacheSizeSpec::Bytes(parse_size(&s)?)),
+        }
+    }
+}
+
+impl TryFrom<String> for CacheSizeSpec {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<CacheSizeSpec> for String {
+    fn from(spec: CacheSizeSpec) -> Self {
+        spec.to_string()
+    }
+}
+
+// --cache-size in a config file: one string, possibly comma separated, or a list
+fn deserialize_cache_sizes<'de, D>(deserializer: D) -> Result<Option<Vec<CacheSizeSpec>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    let values = match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(s) => vec![s],
+        OneOrMany::Many(values) => values,
+    };
+    values
+        .iter()
+        .flat_map(|value| value.split(','))
+        .map(|s| s.parse().map_err(serde::de::Error::custom))
+        .collect::<Result<Vec<_>, _>>()
+        .map(Some)
+}
+
+// Unit of the trace size column, written as bytes, kb or blocks:N
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum SizeUnit {
+    Bytes,
+    Kb,
+    Blocks(u32),
+}
+
+impl SizeUnit {
+    // Bytes in one unit
+    pub fn bytes(&self) -> u32 {
+        match self {
+            SizeUnit::Bytes => 1,
+            SizeUnit::Kb => 1024,
+            SizeUnit::Blocks(block_size) => *block_size,
+        }
+    }
+}
+
+impl std::fmt::Display for SizeUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SizeUnit::Bytes => f.write_str("bytes"),
+            SizeUnit::Kb => f.write_str("kb"),
+            SizeUnit::Blocks(block_size) => write!(f, "blocks:{block_size}"),
+        }
+    }
+}
+
+impl std::str::FromStr for SizeUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        match s.split_once(':') {
+            Some(("blocks", block_size)) => match block_size.trim().parse::<u32>() {
+                Ok(0) => Err("block size must be positive".to_string()),
+                Ok(block_size) => Ok(SizeUnit::Blocks(block_size)),
+                Err(e) => Err(format!("invalid block size in {s}: {e}")),
+            },
+            None if s == "bytes" => Ok(SizeUnit::Bytes),
+            None if s == "kb" => Ok(SizeUnit::Kb),
+            _ => Err(format!(
+                "unknown size unit {s}, expected bytes, kb or blocks:N"
+            )),
+        }
+    }
+}
+
+// Slab classes object sizes are rounded up to, written as memcached or as the
+// path of a file listing them
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum SlabClasses {
+    Memcached,
+    File(PathBuf),
+}
+
+// memcached's defaults: 48 byte item header plus the 48 byte minimum value,
+// growing by 1.25 and aligned to 8 bytes up to the 1 MB item size limit
+const MEMCACHED_MIN_CHUNK: f64 = 96.0;
+const MEMCACHED_GROWTH_FACTOR: f64 = 1.25;
+const MEMCACHED_MAX_ITEM: u32 = 1024 * 1024;
+
+impl SlabClasses {
+    // Class sizes in increasing order
+    pub fn sizes(&self) -> Result<Vec<u32>, String> {
+        match self {
+            SlabClasses::Memcached => {
+                let mut sizes = Vec::new();
+                let mut size = MEMCACHED_MIN_CHUNK;
+                while size <= MEMCACHED_MAX_ITEM as f64 / MEMCACHED_GROWTH_FACTOR {
+                    sizes.push((size as u32).next_multiple_of(8));
+                    size *= MEMCACHED_GROWTH_FACTOR;
+                }
+                sizes.push(MEMCACHED_MAX_ITEM);
+                Ok(sizes)
+            }
+            SlabClasses::File(path) => {
+                let content = std::fs::read_to_string(path)
+                    .map_err(|e| format!("failed to read slab classes from {path:?}: {e}"))?;
+                let mut sizes = content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(|line| match line.parse::<u32>() {
+                        Ok(0) => Err(format!("slab class sizes in {path:?} must be positive")),
+                        Ok(size) => Ok(size),
+                        Err(e) => Err(format!("invalid slab class {line} in {path:?}: {e}")),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                if sizes.is_empty() {
+                    return Err(format!("{path:?} lists no slab classes"));
+                }
+                sizes.sort_unstable();
+                sizes.dedup();
+                Ok(sizes)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for SlabClasses {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SlabClasses::Memcached => f.write_str("memcached"),
+            SlabClasses::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+impl std::str::FromStr for SlabClasses {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "" => Err("slab classes must be memcached or a file".to_string()),
+            s if s.eq_ignore_ascii_case("memcached") => Ok(SlabClasses::Memcached),
+            path => Ok(SlabClasses::File(PathBuf::from(path))),
+        }
+    }
+}
+
+impl TryFrom<String> for SlabClasses {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<SlabClasses> for String {
+    fn from(classes: SlabClasses) -> Self {
+        classes.to_string()
+    }
+}
+
+impl TryFrom<String> for SizeUnit {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<SizeUnit> for String {
+    fn from(unit: SizeUnit) -> Self {
+        unit.to_string()
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeName {
+    #[default]
+    Light,
+    Dark,
+    // Okabe-Ito colors, told apart with the common color vision deficiencies
+    Colorblind,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlotPreset {
+    #[default]
+    Default,
+    // Fonts and lines that stay readable with the figure scaled down to one
+    // column of a two-column paper
+    Paper,
+    Slides,
+}
+
+// The figure options of a run, kept in its JSON export so `combine` can draw
+// the curves the same way
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FigureOptions {
+    pub theme: ThemeName,
+    pub preset: PlotPreset,
+    // The [plot] table with the --plot-* options on top
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plot: Option<PlotStyle>,
+}
+
+// Settings of the [plot] table, each overriding the one of the theme
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlotStyle {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub foreground: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub font: Option<String>,
+    // Base font size in points, replacing the size of `font`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub font_size: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tick_font_size: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line_width: Option<f64>,
+    // PNG resolution as a multiple of 1920x1080
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub palette: Option<Vec<String>>,
+    // Policy name, such as "LRU", to the color of its curve
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub colors: Option<BTreeMap<String, String>>,
+}
+
+impl PlotStyle {
+    // Reject colors the figures can't all take, gnuplot knows names pgfplots doesn't
+    pub fn check(&self) -> Result<(), String> {
+        let colors = [&self.background, &self.foreground, &self.grid]
+            .into_iter()
+            .flatten()
+            .chain(self.palette.iter().flatten())
+            .chain(self.colors.iter().flat_map(|colors| colors.values()));
+        for color in colors {
+            let hex = color.strip_prefix('#').unwrap_or_default();
+            if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!("plot color {color} is not written #RRGGBB"));
+            }
+        }
+        let sizes = [
+            ("line_width", self.line_width),
+            ("font_size", self.font_size),
+            ("tick_font_size", self.tick_font_size),
+            ("scale", self.scale),
+        ];
+        for (name, value) in sizes {
+            if let Some(value) = value.filter(|value| !value.is_finite() || *value <= 0.0) {
+                return Err(format!("plot {name} {value} is not positive"));
+            }
+        }
+        if self
+            .palette
+            .as_ref()
+            .is_some_and(|palette| palette.is_empty())
+        {
+            return Err("plot palette is empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum XNormalize {
+    // Fraction of the trace's unique bytes (or unique keys for object counts)
+    WorkingSet,
+}
+
+// How SHARDS samples keys
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ShardsWeighting {
+    // Every key with the sample rate
+    #[default]
+    Uniform,
+    // Every key with the sample rate times its size over the mean object size,
+    // capped at 1, each sampled request weighted by the inverse. Large objects
+    // hold most of the bytes of a heavy-tailed trace, sampling them more often
+    // makes byte miss ratio curves more accurate.
+    Size,
+}
+
+// How the points of a bounded sweep are spread between its ends
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SizeSpacing {
+    // Evenly in bytes or objects
+    #[default]
+    Linear,
+    // Evenly on a log scale, as many points per doubling at every size
+    Log,
+}
+
+// When expired objects leave the caches with --respect-ttl
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TtlExpiry {
+    // On the next request for the object, which misses
+    #[default]
+    Lazy,
+    // As soon as the trace clock passes the object's expiry
+    Proactive,
+}
+
+// Where the time based options read the time of a request from
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TimeSource {
+    // The timestamp column of the trace
+    #[default]
+    Column,
+    // The index of the request in the trace, a logical clock for traces
+    // without usable timestamps
+    Index,
+}
+
+impl std::fmt::Display for TimeSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimeSource::Column => f.write_str("column"),
+            TimeSource::Index => f.write_str("index"),
+        }
+    }
+}
+
+// How simulations are spread over the worker threads. The curves are the same
+// with every strategy, only time and memory differ.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ParallelStrategy {
+    // One job per curve holding all its cache sizes. Memory is one set of
+    // caches plus one copy of the seen keys per running curve, the trace is
+    // replayed once per curve. Uses at most as many threads as curves.
+    #[default]
+    PerPolicy,
+    // Every curve's cache sizes are split into one job per thread. The caches
+    // take the same memory, but each job keeps its own copy of the seen keys
+    // (and TTL bookkeeping) and replays the whole trace.
+    PerSize,
+    // One job per policy and capacity unit running its curves at every
+    // --shards-rates rate, exact included, one after another. Memory is one
+    // set of caches plus one copy of the seen keys per running policy and
+    // unit, the rates nested in its job. Uses at most as many threads as
+    // policies times units. Without --shards-rates it is per-policy.
+    NestedShards,
+    // per-size when there are fewer curves than threads and the trace is big
+    // enough to be worth splitting while the extra key copies stay small,
+    // nested-shards when several rates are compared and the seen keys of a
+    // curve per thread would not stay small, per-policy otherwise
+    Auto,
+}
+
+impl std::fmt::Display for ParallelStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParallelStrategy::PerPolicy => f.write_str("per-policy"),
+            ParallelStrategy::PerSize => f.write_str("per-size"),
+            ParallelStrategy::NestedShards => f.write_str("nested-shards"),
+            ParallelStrategy::Auto => f.write_str("auto"),
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnBadRecord {
+    // Stop loading with the record's line and error
+    #[default]
+    Fail,
+    // Log every bad record and drop it
+    Warn,
+    // Drop bad records, only reporting how many
+    Skip,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    // Self-contained interactive page
+    Html,
+    // Hit and miss counters of every curve, what `combine` reads
+    Json,
+}
+
+fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim().to_uppercase();
+    if s.ends_with("KB") {
+        s[..s.len() - 2]
+            .parse::<u64>()
+            .map(|n| n * 1024)
+            .map_err(|e| e.to_string())
+    } else if s.ends_with("MB") {
+        s[..s.len() - 2]
+            .parse::<u64>()
+            .map(|n| n * 1024 * 1024)
+            .map_err(|e| e.to_string())
+    } else if s.ends_with("GB") {
+        s[..s.len() - 2]
+            .parse::<u64>()
+            .map(|n| n * 1024 * 1024 * 1024)
+            .map_err(|e| e.to_string())
+    } else {
+        s.parse::<u64>().map_err(|e| e.to_string())
+    }
+}
+
+// The records of the trace, and the XXH64 checksum of the trace file. Settles
+// --time-source when the time based options need a clock, see `time_source`
+pub fn load_access_records(arg: &mut Config) -> Result<(Vec<AccessRecord>, u64), String> {
+    let trace_path = arg.trace.as_ref().unwrap();
+    let file = File::open(trace_path).unwrap();
+    let reader = BufReader::new(file);
+
+    let format = arg.format.unwrap_or_default();
+    // --threads bounds the parse as it does the simulations
+    let parse_threads = arg
+        .parse_threads
+        .unwrap_or(1)
+        .min(arg.threads.unwrap_or(usize::MAX));
+    let parallel = match format {
+        TraceFormat::Csv if parse_threads > 1 => {
+            let parsed = parse_csv_parallel(arg, trace_path, parse_threads)?;
+            if parsed.is_none() {
+                info!("Trace has quoted fields, parsing it on a single thread");
+            }
+            parsed
+        }
+        _ => None,
+    };
+    let (mut access_records, checksum) = match (parallel, format) {
+        (Some(parsed), _) => parsed,
+        (None, TraceFormat::Jsonl) => {
+            let (expected, checksum) = prescan_trace(trace_path, format).unwrap();
+            debug!("Prescan found {expected} records");
+            (parse_jsonl(arg, reader, expected)?, checksum)
+        }
+        (None, TraceFormat::Csv) => {
+            let (expected, checksum) = prescan_trace(trace_path, format).unwrap();
+            debug!("Prescan found {expected} records");
+            let mut rdr = ReaderBuilder::new().has_headers(true).from_reader(reader);
+            let mut bad_records = 0;
+            let access_records = parse_csv(arg, &mut rdr, expected, 2, &mut bad_records)?;
+            report_bad_records(bad_records);
+            (access_records, checksum)
+        }
+    };
+    info!("Trace checksum: {}", format_checksum(checksum));
+
+    // Checked by validate
+    let multiplier = size_multiplier(arg).unwrap();
+    if multiplier != 1 {
+        scale_sizes(&mut access_records, multiplier)?;
+    }
+
+    if let Some(classes) = arg.slab_classes.as_ref() {
+        // Checked by validate
+        let sizes = classes.sizes().unwrap();
+        let (requested, charged) = quantize_sizes(&mut access_records, &sizes);
+        info!(
+            "Rounded object sizes up to {} {classes} slab classes, {:.2}% internal \
+             fragmentation over the requested bytes ({requested} to {charged})",
+            sizes.len(),
+            (charged - requested) as f64 * 100.0 / requested.max(1) as f64
+        );
+    }
+
+    if let Some(path) = arg.key_alias_file.as_ref() {
+        let aliases = load_key_aliases(path)?;
+        let mut remapped = 0;
+        for record in access_records.iter_mut() {
+            if let Some(&canonical) = aliases.get(&record.key) {
+                record.key = canonical;
+                remapped += 1;
+            }
+        }
+        info!(
+            "Remapped {remapped} of {} records using {} key aliases",
+            access_records.len(),
+            aliases.len()
+        );
+    }
+
+    if arg.uses_timestamps() || arg.time_source.is_some() {
+        let time_source = time_source(arg, &access_records);
+        if time_source == TimeSource::Index {
+            for (i, record) in access_records.iter_mut().enumerate() {
+                record.timestamp = i as u64;
+            }
+        }
+        arg.time_source = Some(time_source);
+    }
+
+    let out_of_order = access_records
+        .windows(2)
+        .filter(|pair| pair[1].timestamp < pair[0].timestamp)
+        .count();
+    if out_of_order > 0 {
+        if arg.sort_by_time {
+            info!("Sorting trace by timestamp ({out_of_order} records out of order)");
+            // Stable, so records with equal timestamps keep their trace order
+            access_records.sort_by_key(|record| record.timestamp);
+        } else if arg.respect_ttl || arg.collapse_window.is_some() {
+            warn!(
+                "Trace has {out_of_order} out of order timestamps but time based features are enabled, consider --sort-by-time"
+            );
+        }
+    }
+
+    if let Some(window) = arg.collapse_window {
+        let total = access_records.len();
+        collapse_requests(&mut access_records, window);
+        info!(
+            "Collapsed {} of {total} requests repeated within {window}s",
+            total - access_records.len()
+        );
+    }
+
+    if arg.collapse_consecutive {
+        let total = access_records.len();
+        access_records.dedup_by_key(|record| record.key);
+        info!(
+            "Collapsed {} of {total} requests repeating the key before them",
+            total - access_records.len()
+        );
+    }
+
+    if let Some(seed) = arg.shuffle_trace {
+        let block = arg.shuffle_block.unwrap_or(access_records.len()).max(1);
+        let mut rng = StdRng::seed_from_u64(seed);
+        for chunk in access_records.chunks_mut(block) {
+            chunk.shuffle(&mut rng);
+        }
+        info!(
+            "Shuffled {} records with seed {seed} in blocks of {block}",
+            access_records.len()
+        );
+    }
+    Ok((access_records, checksum))
+}
+
+// The clock of the time based options: --time-source if given, otherwise the
+// timestamp column unless it is the same for every request, when the
+// request index stands in for it
+fn time_source(arg: &Config, access_records: &[AccessRecord]) -> TimeSource {
+    if let Some(time_source) = arg.time_source {
+        return time_source;
+    }
+    let degenerate = access_records.len() > 1
+        && access_records
+            .iter()
+            .all(|record| record.timestamp == access_records[0].timestamp);
+    if !degenerate {
+        return TimeSource::Column;
+    }
+    warn!(
+        "!!! every request has timestamp {}, the time based options run on the request \
+         index instead: TTLs and durations count requests, not seconds. Pass \
+         --time-source column to keep the timestamps",
+        access_records[0].timestamp
+    );
+    TimeSource::Index
+}
+
+// Bytes per unit of the size column, --size-multiplier times --size-unit-in
+fn size_multiplier(arg: &Config) -> Result<u32, String> {
+    let multiplier = arg.size_multiplier.unwrap_or(1);
+    let unit = arg.size_unit_in.map_or(1, |unit| unit.bytes());
+    multiplier.checked_mul(unit).ok_or_else(|| {
+        format!(
+            "--size-multiplier {multiplier} times {unit} bytes per --size-unit-in overflows \
+             the 32-bit object size"
+        )
+    })
+}
+
+// Convert every size to bytes, failing on the first record whose size in
+// bytes doesn't fit an object size instead of wrapping or clamping it
+fn scale_sizes(access_records: &mut [AccessRecord], multiplier: u32) -> Result<(), String> {
+    for (i, record) in access_records.iter_mut().enumerate() {
+        record.size = record.size.checked_mul(multiplier).ok_or_else(|| {
+            format!(
+                "record {} (key {}, timestamp {}) has size {} which times {multiplier} \
+                 bytes per unit overflows the 32-bit object size",
+                i + 1,
+                record.key,
+                record.timestamp,
+                record.size
+            )
+        })?;
+    }
+    Ok(())
+}
+
+// XXH64 of the raw trace bytes, so runs on different machines can check they
+// read the same file
+pub(crate) fn checksum(bytes: &[u8]) -> u64 {
+    let mut hasher = xx::Hasher64::new();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+// How checksums are logged and written in configs, 16 hex digits
+pub fn format_checksum(checksum: u64) -> String {
+    format!("{checksum:016x}")
+}
+
+pub fn parse_checksum(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    if s.len() != 16 {
+        return Err(format!("trace checksum {s} is not 16 hex digits"));
+    }
+    u64::from_str_radix(s, 16).map_err(|e| format!("invalid trace checksum {s}: {e}"))
+}
+
+// Round every record's size up to the smallest of the increasing `classes`
+// that holds it. Sizes above the largest class are left as they are. Returns
+// the bytes of all requests before and after.
+fn quantize_sizes(access_records: &mut [AccessRecord], classes: &[u32]) -> (u64, u64) {
+    let (mut requested, mut charged) = (0, 0);
+    for record in access_records.iter_mut() {
+        requested += record.size as u64;
+        let class = classes.partition_point(|class| *class < record.size);
+        if let Some(&size) = classes.get(class) {
+            record.size = size;
+        }
+        charged += record.size as u64;
+    }
+    (requested, charged)
+}
+
+// Drop requests that repeat a key within `window` seconds of the last kept
+// request for it, so a burst of retries counts once
+fn collapse_requests(access_records: &mut Vec<AccessRecord>, window: f64) {
+    let mut last_kept: hashbrown::HashMap<Key, u64> = hashbrown::HashMap::new();
+    access_records.retain(|record| {
+        if let Some(&kept) = last_kept.get(&record.key) {
+            if record.timestamp >= kept && (record.timestamp - kept) as f64 <= window {
+                return false;
+            }
+        }
+        last_kept.insert(record.key, record.timestamp);
+        true
+    });
+}
+
+// Count the records of a trace by scanning the memory-mapped file for newlines,
+// and checksum it on the way. Quoted CSV fields may hold newlines, so quoted
+// traces are counted by the CSV reader.
+fn prescan_trace(path: &PathBuf, format: TraceFormat) -> std::io::Result<(usize, u64)> {
+    let file = File::open(path)?;
+    if file.metadata()?.len() == 0 {
+        return Ok((0, checksum(&[])));
+    }
+    // Safety: the trace is only read, and is not expected to change while loading
+    let bytes = unsafe { memmap2::Mmap::map(&file)? };
+    let checksum = checksum(&bytes);
+    if matches!(format, TraceFormat::Csv) && bytes.contains(&b'"') {
+        let mut rdr = ReaderBuilder::new()
+            .has_headers(true)
+            .from_reader(&bytes[..]);
+        return Ok((rdr.records().count(), checksum));
+    }
+    let mut lines = bytes.iter().filter(|byte| **byte == b'\n').count();
+    if bytes.last() != Some(&b'\n') {
+        lines += 1;
+    }
+    let records = match format {
+        TraceFormat::Csv => lines.saturating_sub(1),
+        TraceFormat::Jsonl => lines,
+    };
+    Ok((records, checksum))
+}
+
+// Logs loading progress every tenth of the expected records
+struct Progress {
+    expected: usize,
+    next: usize,
+}
+
+impl Progress {
+    fn new(expected: usize) -> Self {
+        Progress {
+            expected,
+            next: expected / 10,
+        }
+    }
+
+    fn tick(&mut self, done: usize) {
+        if done >= self.next && self.expected >= 10 {
+            info!(
+                "Loaded {done} of {} records ({}%)",
+                self.expected,
+                done * 100 / self.expected
+            );
+            self.next += self.expected / 10;
+        }
+    }
+}
+
+fn load_key_aliases(path: &PathBuf) -> Result<hashbrown::HashMap<Key, Key>, String> {
+    let file = File::open(path)
+        .map_err(|e| format!("failed to read the --key-alias-file {path:?}: {e}"))?;
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .from_reader(BufReader::new(file));
+    let mut aliases = hashbrown::HashMap::new();
+    for (line, result) in rdr.deserialize().enumerate() {
+        let (alias, canonical): (Key, Key) = result.map_err(|e| {
+            format!(
+                "line {} of {path:?} is not an alias,canonical pair of keys: {e}",
+                line + 2
+            )
+        })?;
+        aliases.insert(alias, canonical);
+    }
+    Ok(aliases)
+}
+
+fn is_default_parsing(arg: &Config) -> bool {
+    arg.timestamp.is_none()
         && arg.command.is_none()
         && arg.key.is_none()
         && arg.size.is_none()
         && arg.ttl.is_none()
 }
 
-fn parse_default(rdr: &mut csv::Reader<BufReader<File>>) -> Vec<AccessRecord> {
-    debug!("Parsing access records with default fields");
-    let mut access_records = Vec::new();
-    for result in rdr.deserialize() {
-        let record: AccessRecord = result.unwrap();
-        access_records.push(record);
-    }
-    access_records
-}
-
-fn parse_custom(arg: &Config, rdr: &mut csv::Reader<BufReader<File>>) -> Vec<AccessRecord> {
-    let mut access_records = Vec::new();
-    for result in rdr.records() {
-        let record = result.unwrap();
-        let timestamp = parse_field(&record, arg.timestamp, 0);
-        let command = parse_field(&record, arg.command, 0) as u8;
-        let key = parse_field(&record, arg.key, 0);
-        let size = parse_field(&record, arg.size, 1) as u32;
-        let ttl = parse_field(&record, arg.ttl, 0) as u32;
-
-        access_records.push(AccessRecord {
-            timestamp,
-            command,
-            key,
-            size,
-            ttl,
-        });
+// Count a record that failed to parse, failing or logging according to the policy
+fn bad_record(
+    policy: OnBadRecord,
+    line: u64,
+    error: &str,
+    bad_records: &mut usize,
+) -> Result<(), String> {
+    match policy {
+        OnBadRecord::Fail => {
+            return Err(format!(
+                "invalid record at line {line}: {error}, see --on-bad-record"
+            ))
+        }
+        OnBadRecord::Warn => warn!("Dropping invalid record at line {line}: {error}"),
+        OnBadRecord::Skip => {}
     }
-    access_records
+    *bad_records += 1;
+    Ok(())
 }
 
-fn parse_field(record: &csv::StringRecord, field_opt: Option<i32>, default: u64) -> u64 {
-    if let Some(index) = field_opt {
-        if index == -1 {
-            default
-        } else {
-            record[index as usize].parse().unwrap()
-        }
+fn report_bad_records(bad_records: usize) {
+    if bad_records > 0 {
+        warn!("Dropped {bad_records} invalid records");
+    }
+}
+
+// Parse CSV records with the default or the custom column mapping. `first_line`
+// is the trace line number of the first record, for error messages.
+fn parse_csv<R: Read>(
+    arg: &Config,
+    rdr: &mut csv::Reader<R>,
+    expected: usize,
+    first_line: u64,
+    bad_records: &mut usize,
+) -> Result<Vec<AccessRecord>, String> {
+    if is_default_parsing(arg) {
+        parse_default(arg, rdr, expected, first_line, bad_records)
     } else {
-        default
+        parse_custom(arg, rdr, expected, first_line, bad_records)
+    }
+}
+
+// Parse a CSV trace on `threads` threads, each taking a range of whole lines.
+// None if the trace has quoted fields, which may hold newlines.
+fn parse_csv_parallel(
+    arg: &Config,
+    path: &PathBuf,
+    threads: usize,
+) -> Result<Option<(Vec<AccessRecord>, u64)>, String> {
+    let read_error = |e: std::io::Error| format!("failed to read the trace {path:?}: {e}");
+    let file = File::open(path).map_err(read_error)?;
+    if file.metadata().map_err(read_error)?.len() == 0 {
+        return Ok(Some((Vec::new(), checksum(&[]))));
+    }
+    // Safety: the trace is only read, and is not expected to change while loading
+    let bytes = unsafe { memmap2::Mmap::map(&file).map_err(read_error)? };
+    if bytes.contains(&b'"') {
+        return Ok(None);
+    }
+    let after_newline = |from: usize| {
+        bytes[from..]
+            .iter()
+            .position(|byte| *byte == b'\n')
+            .map_or(bytes.len(), |i| from + i + 1)
+    };
+    // Each range parses with its own copy of the header
+    let header_end = after_newline(0);
+    let header = &bytes[..header_end];
+    let chunk = (bytes.len() - header_end).div_ceil(threads).max(1);
+    let mut ranges = Vec::new();
+    let mut start = header_end;
+    // Header is line 1
+    let mut first_line = 2;
+    while start < bytes.len() {
+        let end = after_newline((start + chunk).min(bytes.len()) - 1);
+        ranges.push((start, end, first_line));
+        first_line += bytes[start..end]
+            .iter()
+            .filter(|byte| **byte == b'\n')
+            .count() as u64;
+        start = end;
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap();
+    let parsed = pool.install(|| {
+        ranges
+            .par_iter()
+            .map(|&(start, end, first_line)| {
+                let mut rdr = ReaderBuilder::new()
+                    .has_headers(true)
+                    .from_reader(header.chain(&bytes[start..end]));
+                let mut bad_records = 0;
+                // Progress is only logged by the serial parser
+                let records = parse_csv(arg, &mut rdr, 0, first_line, &mut bad_records)?;
+                Ok((records, bad_records))
+            })
+            .collect::<Result<Vec<_>, String>>()
+    })?;
+    debug!("Parsed {} ranges on {threads} threads", ranges.len());
+    let mut access_records = Vec::with_capacity(parsed.iter().map(|(r, _)| r.len()).sum());
+    let mut bad_records = 0;
+    for (records, bad) in parsed {
+        access_records.extend(records);
+        bad_records += bad;
+    }
+    report_bad_records(bad_records);
+    Ok(Some((access_records, checksum(&bytes))))
+}
+
+fn parse_default<R: Read>(
+    arg: &Config,
+    rdr: &mut csv::Reader<R>,
+    expected: usize,
+    first_line: u64,
+    bad_records: &mut usize,
+) -> Result<Vec<AccessRecord>, String> {
+    let policy = arg.on_bad_record.unwrap_or_default();
+    let mut access_records = Vec::with_capacity(expected);
+    let mut progress = Progress::new(expected);
+    for (i, result) in rdr.deserialize().enumerate() {
+        progress.tick(i);
+        match result {
+            Ok(record) => access_records.push(record),
+            Err(e) => bad_record(policy, first_line + i as u64, &e.to_string(), bad_records)?,
+        }
+    }
+    Ok(access_records)
+}
+
+fn parse_custom_record(arg: &Config, record: &csv::StringRecord) -> Result<AccessRecord, String> {
+    Ok(AccessRecord {
+        timestamp: parse_field(record, "timestamp", arg.timestamp, 0)?,
+        command: parse_field(record, "command", arg.command, 0)? as u8,
+        key: parse_field(record, "key", arg.key, 0)?,
+        size: parse_field(record, "size", arg.size, 1)? as u32,
+        ttl: parse_ttl_field(record, arg.ttl)?,
+    })
+}
+
+fn parse_custom<R: Read>(
+    arg: &Config,
+    rdr: &mut csv::Reader<R>,
+    expected: usize,
+    first_line: u64,
+    bad_records: &mut usize,
+) -> Result<Vec<AccessRecord>, String> {
+    let policy = arg.on_bad_record.unwrap_or_default();
+    let mut access_records = Vec::with_capacity(expected);
+    let mut progress = Progress::new(expected);
+    for (i, result) in rdr.records().enumerate() {
+        progress.tick(i);
+        let parsed = result
+            .map_err(|e| e.to_string())
+            .and_then(|record| parse_custom_record(arg, &record));
+        match parsed {
+            Ok(record) => access_records.push(record),
+            Err(e) => bad_record(policy, first_line + i as u64, &e, bad_records)?,
+        }
+    }
+    Ok(access_records)
+}
+
+// Names of the JSON fields holding each AccessRecord field
+struct JsonFields {
+    timestamp: String,
+    command: String,
+    key: String,
+    size: String,
+    ttl: String,
+}
+
+impl JsonFields {
+    fn from_mapping(mapping: &[String]) -> Result<Self, String> {
+        let mut fields = JsonFields {
+            timestamp: "timestamp".to_string(),
+            command: "command".to_string(),
+            key: "key".to_string(),
+            size: "size".to_string(),
+            ttl: "ttl".to_string(),
+        };
+        for entry in mapping {
+            let (field, name) = entry.split_once('=').ok_or_else(|| {
+                format!("invalid json field mapping '{entry}', expected field=name")
+            })?;
+            let slot = match field.trim() {
+                "timestamp" => &mut fields.timestamp,
+                "command" => &mut fields.command,
+                "key" => &mut fields.key,
+                "size" => &mut fields.size,
+                "ttl" => &mut fields.ttl,
+                other => {
+                    return Err(format!(
+                        "unknown record field '{other}' in json field mapping"
+                    ))
+                }
+            };
+            *slot = name.trim().to_string();
+        }
+        Ok(fields)
+    }
+}
+
+// Numbers may also be encoded as strings, a missing field takes the default
+fn json_u64(object: &serde_json::Value, name: &str, default: Option<u64>) -> Result<u64, String> {
+    match object.get(name) {
+        None | Some(serde_json::Value::Null) => {
+            default.ok_or_else(|| format!("missing field '{name}'"))
+        }
+        Some(serde_json::Value::Number(n)) => n
+            .as_u64()
+            .ok_or_else(|| format!("field '{name}' is not an unsigned integer: {n}")),
+        Some(serde_json::Value::String(s)) => s.trim().parse::<u64>().map_err(|e| e.to_string()),
+        Some(other) => Err(format!("field '{name}' is not a number: {other}")),
+    }
+}
+
+fn json_ttl(object: &serde_json::Value, name: &str) -> Result<u32, String> {
+    match object.get(name) {
+        None | Some(serde_json::Value::Null) => Ok(0),
+        Some(serde_json::Value::Number(n)) => parse_ttl(&n.to_string()),
+        Some(serde_json::Value::String(s)) => parse_ttl(s),
+        Some(other) => Err(format!("field '{name}' is not a number: {other}")),
+    }
+}
+
+fn parse_json_record(line: &str, fields: &JsonFields) -> Result<AccessRecord, String> {
+    let object: serde_json::Value = serde_json::from_str(line).map_err(|e| e.to_string())?;
+    Ok(AccessRecord {
+        timestamp: json_u64(&object, &fields.timestamp, Some(0))?,
+        command: json_u64(&object, &fields.command, Some(0))? as u8,
+        key: json_u64(&object, &fields.key, None)?,
+        size: json_u64(&object, &fields.size, Some(1))? as u32,
+        ttl: json_ttl(&object, &fields.ttl)?,
+    })
+}
+
+fn parse_jsonl(
+    arg: &Config,
+    reader: BufReader<File>,
+    expected: usize,
+) -> Result<Vec<AccessRecord>, String> {
+    debug!("Parsing access records from JSON lines");
+    let fields = JsonFields::from_mapping(arg.json_fields.as_deref().unwrap_or_default()).unwrap();
+    let policy = arg.on_bad_record.unwrap_or_default();
+    let mut access_records = Vec::with_capacity(expected);
+    let mut bad_records = 0;
+    let mut progress = Progress::new(expected);
+    for (i, line) in reader.lines().enumerate() {
+        progress.tick(i);
+        let line = line.unwrap();
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_json_record(&line, &fields) {
+            Ok(record) => access_records.push(record),
+            Err(e) => bad_record(policy, i as u64 + 1, &e, &mut bad_records)?,
+        }
+    }
+    report_bad_records(bad_records);
+    Ok(access_records)
+}
+
+// The column at `field_opt`, or `default` when it is unset or -1
+fn column<'a>(
+    record: &'a csv::StringRecord,
+    name: &str,
+    field_opt: Option<i32>,
+) -> Result<Option<&'a str>, String> {
+    match field_opt {
+        Some(index) if index != -1 => record.get(index as usize).map(Some).ok_or_else(|| {
+            format!(
+                "{name} column {index} is out of range, the row has {} fields",
+                record.len()
+            )
+        }),
+        _ => Ok(None),
+    }
+}
+
+fn parse_field(
+    record: &csv::StringRecord,
+    name: &str,
+    field_opt: Option<i32>,
+    default: u64,
+) -> Result<u64, String> {
+    match column(record, name, field_opt)? {
+        Some(value) => value
+            .trim()
+            .parse()
+            .map_err(|e| format!("invalid {name} '{value}': {e}")),
+        None => Ok(default),
+    }
+}
+
+// A ttl of -1 marks a permanent object and is stored as u32::MAX
+fn parse_ttl(s: &str) -> Result<u32, String> {
+    if s.trim() == "-1" {
+        return Ok(u32::MAX);
+    }
+    s.trim().parse::<u32>().map_err(|e| e.to_string())
+}
+
+fn parse_ttl_field(record: &csv::StringRecord, field_opt: Option<i32>) -> Result<u32, String> {
+    match column(record, "ttl", field_opt)? {
+        Some(value) => parse_ttl(value).map_err(|e| format!("invalid ttl '{value}': {e}")),
+        None => Ok(0),
     }
 }
 
+pub fn deserialize_ttl<'de, D>(deserializer: D) -> Result<u32, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s: String = serde::Deserialize::deserialize(deserializer)?;
+    parse_ttl(&s).map_err(serde::de::Error::custom)
+}
+
 impl Config {
-    pub fn load() -> Self {
+    pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
         let args = Config::parse();
         if let Some(path) = &args.config_file {
-            match Config::from_file(path) {
-                Ok(config) => config,
-                Err(e) => {
-                    error!("Failed to load configuration file: {}", e);
-                    args
-                }
+            let cli_args = Config {
+                config_file: None,
+                ..args.clone()
+            };
+            if serde_json::to_value(&cli_args)? != serde_json::to_value(Config::default())? {
+                warn!("Command line options are ignored when --config-file is given");
             }
+            let config = Config::from_file(path).map_err(|e| {
+                error!("Failed to load configuration file: {}", e);
+                e
+            })?;
+            config.validate()?;
+            Ok(config)
         } else {
-            args
+            args.validate()?;
+            Ok(args)
+        }
+    }
+
+    // Fill in what --demo leaves unset
+    pub fn apply_demo_defaults(&mut self) {
+        self.policies.get_or_insert_with(|| {
+            vec![
+                EvictionPolicy::LRU,
+                EvictionPolicy::FIFO,
+                EvictionPolicy::LFU,
+            ]
+        });
+        self.cache_size
+            .get_or_insert_with(|| vec![CacheSizeSpec::WorkingSet(0.5)]);
+        self.output
+            .get_or_insert_with(|| PathBuf::from("demo_mrc.png"));
+    }
+
+    // The [plot] table with the --plot-* options on top, None when neither
+    // sets anything
+    pub fn plot_style(&self) -> Option<PlotStyle> {
+        let flags = [
+            self.plot_scale,
+            self.plot_font_size,
+            self.plot_tick_font_size,
+            self.plot_line_width,
+        ];
+        if flags.iter().all(Option::is_none) {
+            return self.plot.clone();
+        }
+        let style = self.plot.clone().unwrap_or_default();
+        Some(PlotStyle {
+            scale: self.plot_scale.or(style.scale),
+            font_size: self.plot_font_size.or(style.font_size),
+            tick_font_size: self.plot_tick_font_size.or(style.tick_font_size),
+            line_width: self.plot_line_width.or(style.line_width),
+            ..style
+        })
+    }
+
+    // Whether any option of the figure look is given
+    pub fn sets_figure(&self) -> bool {
+        self.theme.is_some() || self.plot_preset.is_some() || self.plot_style().is_some()
+    }
+
+    pub fn figure_options(&self) -> FigureOptions {
+        FigureOptions {
+            theme: self.theme.unwrap_or_default(),
+            preset: self.plot_preset.unwrap_or_default(),
+            plot: self.plot_style(),
         }
     }
+
+    // Reject settings that can't be right whatever the trace holds
+    pub fn validate(&self) -> Result<(), String> {
+        if self.demo && self.trace.is_some() {
+            return Err(
+                "--demo generates its own trace, --trace can't be given with it".to_string(),
+            );
+        }
+        let columns = [
+            ("timestamp", self.timestamp),
+            ("command", self.command),
+            ("key", self.key),
+            ("size", self.size),
+            ("ttl", self.ttl),
+        ];
+        let rates = self
+            .sample_rate
+            .iter()
+            .chain(self.shards_rates.iter().flatten());
+        for rate in rates {
+            if !(*rate > 0.0 && *rate <= 1.0) {
+                return Err(format!("sample rate {rate} is not in (0, 1]"));
+            }
+        }
+        if let Some(policies) = &self.attribute {
+            if policies.len() != 2 {
+                return Err(format!(
+                    "--attribute takes two policies, got {}",
+                    policies.len()
+                ));
+            }
+        }
+        if let Some(rate) = self
+            .probe_rate
+            .filter(|rate| !(*rate > 0.0 && *rate <= 1.0))
+        {
+            return Err(format!("probe rate {rate} is not in (0, 1]"));
+        }
+        if self.output.is_none() {
+            let export_path = self.export.as_ref().is_some_and(|formats| {
+                formats
+                    .iter()
+                    .any(|format| *format != ExportFormat::Html || self.report.is_none())
+            });
+            let named_after_output = [
+                ("--export", export_path),
+                ("--separate-figures", self.separate_figures),
+                ("--plot-reuse-cdf", self.plot_reuse_cdf),
+                (
+                    "--cost-per-byte",
+                    self.cost_per_byte.is_some() && self.cost_per_miss.is_some(),
+                ),
+                ("--compare-config", self.compare_config.is_some()),
+                ("--attribute", self.attribute.is_some()),
+                ("--eviction-ages", self.eviction_ages),
+                ("--size-classes", self.size_classes),
+                ("--timeline-every", self.timeline_every.is_some()),
+            ];
+            if let Some((flag, _)) = named_after_output.iter().find(|(_, set)| *set) {
+                return Err(format!(
+                    "{flag} writes files named after --output, which is not set"
+                ));
+            }
+        }
+        let command_lists = [
+            ("--probe-commands", &self.probe_commands),
+            ("--write-commands", &self.write_commands),
+            ("--delete-commands", &self.delete_commands),
+        ];
+        for (i, (first, commands)) in command_lists.iter().enumerate() {
+            for (second, others) in command_lists[i + 1..].iter() {
+                let (Some(commands), Some(others)) = (commands, others) else {
+                    continue;
+                };
+                if let Some(command) = commands.iter().find(|command| others.contains(command)) {
+                    return Err(format!("command {command} is in both {first} and {second}"));
+                }
+            }
+        }
+        if let Some(classes) = &self.slab_classes {
+            classes.sizes()?;
+        }
+        size_multiplier(self)?;
+        if let Some(style) = self.plot_style() {
+            style.check()?;
+        }
+        if self
+            .group_by_key_bits
+            .is_some_and(|bits| !(1..=64).contains(&bits))
+        {
+            return Err("--group-by-key-bits takes 1 to 64 bits".to_string());
+        }
+        if self.group_by_key_mask == Some(0) {
+            return Err("--group-by-key-mask must keep at least one bit of the key".to_string());
+        }
+        if self.max_groups == Some(0) {
+            return Err("--max-groups must be at least 1".to_string());
+        }
+        if self.group_by_key_bits.is_some() || self.group_by_key_mask.is_some() {
+            if self.command_mode.is_some() {
+                return Err("grouping by key only works for a plain simulation".to_string());
+            }
+            if self.compare_config.is_some() {
+                return Err("--compare-config can't be combined with grouping by key".to_string());
+            }
+        }
+        if self.over_time {
+            if self.fixed_size.is_none() {
+                return Err("--over-time needs the --fixed-size to simulate".to_string());
+            }
+            if self.command_mode.is_some() || self.compare_config.is_some() {
+                return Err("--over-time only works for a plain simulation".to_string());
+            }
+            if self.group_by_key_bits.is_some() || self.group_by_key_mask.is_some() {
+                return Err("--over-time can't be combined with grouping by key".to_string());
+            }
+        }
+        if self.expiry_granularity == Some(0) {
+            return Err("--expiry-granularity takes a positive span of trace time".to_string());
+        }
+        if self.time_bucket == Some(0) {
+            return Err("--time-bucket takes a positive span of timestamps".to_string());
+        }
+        if self.min_cache_size == Some(0) || self.max_cache_size == Some(0) {
+            return Err("--min-cache-size and --max-cache-size must be at least 1".to_string());
+        }
+        if self.max_cache_size.is_some() && self.cache_size.is_some() {
+            return Err("--max-cache-size and --cache-size both set the largest size".to_string());
+        }
+        if self.min_cache_size.is_some() || self.size_spacing.is_some() {
+            let sizes = self.cache_size.as_deref().unwrap_or_default();
+            if sizes.len() > 1 {
+                return Err(
+                    "--min-cache-size and --size-spacing bound a sweep up to one --cache-size, \
+                     not listed sizes"
+                        .to_string(),
+                );
+            }
+            let max = match sizes.first() {
+                Some(CacheSizeSpec::Bytes(max)) => Some(*max),
+                Some(_) => None,
+                None => self.max_cache_size,
+            };
+            if let (Some(min), Some(max)) = (self.min_cache_size, max) {
+                if min >= max {
+                    return Err(format!(
+                        "--min-cache-size {min} must be below the largest cache size {max}"
+                    ));
+                }
+            }
+        }
+        if self.num_sizes.is_some_and(|sizes| sizes < 2) {
+            return Err("--num-sizes must be at least 2 to draw a curve".to_string());
+        }
+        if self.timeline_every == Some(0) {
+            return Err("--timeline-every takes a positive number of requests".to_string());
+        }
+        if self.prometheus_every == Some(0) {
+            return Err("--prometheus-every takes a positive number of requests".to_string());
+        }
+        if self.warm_start.is_some()
+            && !self.disable_shards
+            && (self.sample_rate.is_some() || self.shards_rates.is_some())
+        {
+            return Err(
+                "--warm-start loads unsampled caches, drop --sample-rate and --shards-rates \
+                 or pass --disable-shards"
+                    .to_string(),
+            );
+        }
+        if self.smooth_min_samples == Some(0) {
+            return Err("--smooth-min-samples takes a positive number of hits".to_string());
+        }
+        if let Some(segments) = self.segments.filter(|segments| *segments < 2) {
+            return Err(format!(
+                "--segments takes at least 2 segments, got {segments}"
+            ));
+        }
+        if let Some(edges) = &self.size_class_edges {
+            if edges.len() < 2 || edges.windows(2).any(|pair| pair[0] >= pair[1]) {
+                return Err(format!(
+                    "--size-class-edges takes at least two increasing sizes, got {edges:?}"
+                ));
+            }
+        }
+        if let Some(checksum) = &self.trace_checksum {
+            parse_checksum(checksum)?;
+        }
+        if let Some(thresholds) = &self.tier_thresholds {
+            if thresholds.len() != 2 || thresholds[0] > thresholds[1] {
+                return Err(format!(
+                    "--tier-thresholds takes two ascending access counts, got {thresholds:?}"
+                ));
+            }
+        }
+        for (name, index) in columns {
+            if let Some(index) = index.filter(|index| *index < -1) {
+                return Err(format!(
+                    "invalid {name} column index {index}, use -1 for the default value"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // The record fields the trace carries: csv columns that are mapped (all of
+    // them with the default header parsing), jsonl fields that any record sets
+    pub fn trace_schema(&self, access_records: &[AccessRecord]) -> TraceSchema {
+        let mapped = |field: Option<i32>| field.is_some_and(|index| index != -1);
+        match self.format {
+            Some(TraceFormat::Jsonl) => TraceSchema {
+                sizes: access_records.iter().any(|record| record.size != 1),
+                ttls: access_records.iter().any(|record| record.ttl != 0),
+            },
+            _ if is_default_parsing(self) => TraceSchema {
+                sizes: true,
+                ttls: true,
+            },
+            _ => TraceSchema {
+                sizes: mapped(self.size),
+                ttls: mapped(self.ttl),
+            },
+        }
+    }
+
+    // Configured inputs that the selected modes will ignore, one message each
+    pub fn audit(&self, access_records: &[AccessRecord]) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let mapped = |field: Option<i32>| field.is_some_and(|index| index != -1);
+        let jsonl = matches!(self.format, Some(TraceFormat::Jsonl));
+
+        if !self.respect_ttl {
+            let with_ttl = access_records
+                .iter()
+                .filter(|record| record.ttl != 0 && record.ttl != u32::MAX)
+                .count();
+            if mapped(self.ttl) || (with_ttl > 0 && !jsonl && is_default_parsing(self)) {
+                warnings.push(format!(
+                    "ttl column mapped but --respect-ttl not set; TTLs of {with_ttl} records will be ignored"
+                ));
+            } else if jsonl && with_ttl > 0 {
+                warnings.push(format!(
+                    "trace has ttl values but --respect-ttl not set; TTLs of {with_ttl} records will be ignored"
+                ));
+            }
+        }
+        let command_modes = [
+            ("--probe-commands", self.probe_commands.is_some()),
+            ("--write-commands", self.write_commands.is_some()),
+            ("--delete-commands", self.delete_commands.is_some()),
+        ];
+        for (flag, _) in command_modes.iter().filter(|(_, set)| *set) {
+            if !jsonl && !mapped(self.command) {
+                warnings.push(format!(
+                    "{flag} needs the command column (--command), every command is 0"
+                ));
+            }
+        }
+        if mapped(self.command) && command_modes.iter().all(|(_, set)| !set) {
+            warnings.push(format!(
+                "command column mapped but no mode uses commands; the command of {} records will be ignored",
+                access_records.len()
+            ));
+        }
+        if jsonl && !is_default_parsing(self) {
+            warnings.push(
+                "column indices (--timestamp, --key, ...) are ignored for jsonl traces, use --json-fields".to_string(),
+            );
+        }
+        if jsonl && self.parse_threads.is_some_and(|threads| threads > 1) {
+            warnings.push("--parse-threads only applies to csv traces".to_string());
+        }
+        if !jsonl && self.json_fields.is_some() {
+            warnings.push("--json-fields is only used with --format jsonl".to_string());
+        }
+        if self.audit_evictions.is_none() && !self.eviction_ages && self.audit_cache_size.is_some()
+        {
+            warnings.push(
+                "--audit-cache-size is only used with --audit-evictions and --eviction-ages"
+                    .to_string(),
+            );
+        }
+        let probed = self.attribute.is_some()
+            || self.size_classes
+            || self.dump_contents.is_some()
+            || self.timeline_every.is_some();
+        if !probed && self.probe_size.is_some() {
+            warnings.push(
+                "--probe-size is only used with --attribute, --size-classes, --dump-contents and --timeline-every"
+                    .to_string(),
+            );
+        }
+        if self.adaptive
+            && self
+                .cache_size
+                .as_ref()
+                .is_some_and(|sizes| sizes.len() > 1)
+        {
+            warnings.push(
+                "--adaptive refines its own sizes, only the largest --cache-size is used"
+                    .to_string(),
+            );
+        }
+        if let Some(strategy) = self.parallel_strategy.filter(|strategy| {
+            self.adaptive
+                && matches!(
+                    strategy,
+                    ParallelStrategy::PerSize | ParallelStrategy::NestedShards
+                )
+        }) {
+            warnings.push(format!(
+                "--parallel-strategy {strategy} has no effect with --adaptive"
+            ));
+        }
+        if self.parallel_strategy == Some(ParallelStrategy::NestedShards)
+            && self.shards_rates.is_none()
+        {
+            warnings.push(
+                "--parallel-strategy nested-shards is per-policy without --shards-rates"
+                    .to_string(),
+            );
+        }
+        if self.sequential
+            && (self.threads.is_some() || self.pin_cores || self.parallel_strategy.is_some())
+        {
+            warnings.push(
+                "--threads, --pin-cores and --parallel-strategy are ignored with --sequential"
+                    .to_string(),
+            );
+        }
+        if self.adaptive && self.dump_contents.is_some() {
+            warnings.push("--dump-contents is ignored with --adaptive".to_string());
+        }
+        if self.adaptive && self.timeline_every.is_some() {
+            warnings.push("--timeline-every is ignored with --adaptive".to_string());
+        }
+        if self.adaptive && self.prometheus_file.is_some() {
+            warnings.push("--prometheus-file is ignored with --adaptive".to_string());
+        }
+        if self.prometheus_every.is_some() && self.prometheus_file.is_none() {
+            warnings.push("--prometheus-every is ignored without --prometheus-file".to_string());
+        }
+        if self.adaptive && self.segments.is_some() {
+            warnings.push("--segments is ignored with --adaptive".to_string());
+        }
+        if self.error_bands && self.segments.is_none() {
+            warnings.push("--error-bands needs --segments, no bands are drawn".to_string());
+        }
+        if !self.over_time && (self.fixed_size.is_some() || self.time_bucket.is_some()) {
+            warnings
+                .push("--fixed-size and --time-bucket are only used with --over-time".to_string());
+        }
+        if !self.respect_ttl && self.ttl_expiry.is_some() {
+            warnings.push("--ttl-expiry is only used with --respect-ttl".to_string());
+        }
+        if self.ttl_expiry != Some(TtlExpiry::Proactive) && self.expiry_granularity.is_some() {
+            warnings
+                .push("--expiry-granularity is only used with --ttl-expiry proactive".to_string());
+        }
+        if self.attribute.is_none() && self.top.is_some() {
+            warnings.push("--top is only used with --attribute".to_string());
+        }
+        if !self.size_classes && (self.size_class_edges.is_some() || self.size_class_chart) {
+            warnings.push(
+                "--size-class-edges and --size-class-chart are only used with --size-classes"
+                    .to_string(),
+            );
+        }
+        if self.shards_rates.is_some() && self.sample_rate.is_some() {
+            warnings.push("--sample-rate is ignored when --shards-rates is given".to_string());
+        }
+        if self.shards_weighting.is_some()
+            && (self.disable_shards || (self.sample_rate.is_none() && self.shards_rates.is_none()))
+        {
+            warnings.push("--shards-weighting needs --sample-rate or --shards-rates".to_string());
+        }
+        if (self.min_sampled_keys.is_some() || self.min_sampled_requests.is_some())
+            && (self.disable_shards || (self.sample_rate.is_none() && self.shards_rates.is_none()))
+        {
+            warnings.push(
+                "--min-sampled-keys and --min-sampled-requests only apply to SHARDS curves"
+                    .to_string(),
+            );
+        }
+        if self.smooth_min_samples.is_some()
+            && (self.disable_shards || (self.sample_rate.is_none() && self.shards_rates.is_none()))
+        {
+            warnings.push(
+                "--smooth-min-samples only smooths SHARDS curves, none are sampled".to_string(),
+            );
+        }
+        if let Some(weighting) = self
+            .shards_weighting
+            .filter(|weighting| *weighting != ShardsWeighting::Uniform && !self.byte_miss_ratio)
+        {
+            warnings.push(format!(
+                "--shards-weighting {} is meant for --byte-miss-ratio, it samples the small \
+                 objects that most requests are for less often",
+                format!("{weighting:?}").to_lowercase()
+            ));
+        }
+        if self.num_sizes.is_some()
+            && (self.adaptive
+                || self
+                    .cache_size
+                    .as_ref()
+                    .is_some_and(|sizes| sizes.len() > 1))
+        {
+            warnings.push(
+                "--num-sizes only spaces the sweep up to one --cache-size, listed sizes and \
+                 --adaptive pick their own"
+                    .to_string(),
+            );
+        }
+        if self.adaptive && (self.min_cache_size.is_some() || self.size_spacing.is_some()) {
+            warnings.push(
+                "--min-cache-size and --size-spacing are ignored with --adaptive".to_string(),
+            );
+        }
+        if !self.adaptive && (self.point_budget.is_some() || self.refine_rounds.is_some()) {
+            warnings.push(
+                "--point-budget and --refine-rounds are only used with --adaptive".to_string(),
+            );
+        }
+        if self.cost_per_byte.is_some() != self.cost_per_miss.is_some() {
+            warnings.push(
+                "--cost-per-byte and --cost-per-miss must be given together, skipping the cost report"
+                    .to_string(),
+            );
+        }
+        if self.shuffle_trace.is_none() && self.shuffle_block.is_some() {
+            warnings.push("--shuffle-block is only used with --shuffle-trace".to_string());
+        }
+        if self.shuffle_trace.is_some() && (self.respect_ttl || self.max_residency.is_some()) {
+            warnings.push(
+                "--shuffle-trace reorders timestamps, TTLs and --max-residency see them out of order"
+                    .to_string(),
+            );
+        }
+        if !self.ensemble && self.target_cache_size.is_some() {
+            warnings.push("--target-cache-size is only used with --ensemble".to_string());
+        }
+        if (self.size_multiplier.is_some() || self.size_unit_in.is_some())
+            && self
+                .capacity_units
+                .as_ref()
+                .is_some_and(|units| units.iter().all(|unit| *unit == CapacityUnit::Objects))
+        {
+            warnings.push(
+                "--size-multiplier and --size-unit-in have no effect when capacity is only measured in objects"
+                    .to_string(),
+            );
+        }
+        if self.slab_classes.is_some()
+            && self
+                .capacity_units
+                .as_ref()
+                .is_some_and(|units| units.iter().all(|unit| *unit == CapacityUnit::Objects))
+        {
+            warnings.push(
+                "--slab-classes has no effect when capacity is only measured in objects"
+                    .to_string(),
+            );
+        }
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        evict_policy::{EvictPolicy, LruPolicy, NewPolicy},
+        synthetic::ZipfTrace,
+    };
+
+    fn parse(arg: &Config, csv: &str) -> (Result<Vec<AccessRecord>, String>, usize) {
+        let mut rdr = ReaderBuilder::new()
+            .has_headers(true)
+            .from_reader(csv.as_bytes());
+        let mut bad_records = 0;
+        let parsed = parse_csv(arg, &mut rdr, 0, 2, &mut bad_records);
+        (parsed, bad_records)
+    }
+
+    #[test]
+    fn negative_column_index_is_a_config_error() {
+        let config = Config {
+            key: Some(-2),
+            ..Default::default()
+        };
+        let error = config.validate().unwrap_err();
+        assert!(error.contains("key column index -2"), "{error}");
+    }
+
+    #[test]
+    fn key_alias_file_errors_are_returned() {
+        let missing = std::env::temp_dir().join("cache_mrc_missing_aliases.csv");
+        let error = load_key_aliases(&missing).unwrap_err();
+        assert!(error.contains("--key-alias-file"), "{error}");
+
+        let path = std::env::temp_dir().join("cache_mrc_bad_aliases.csv");
+        std::fs::write(&path, "alias,canonical\n1,2\nthree,4\n").unwrap();
+        let error = load_key_aliases(&path).unwrap_err();
+        assert!(error.contains("line 3"), "{error}");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn out_of_range_column_follows_on_bad_record() {
+        let csv = "timestamp,command,key,size,ttl\n1,0,7,100,0\n2,0,8,100,0\n";
+        let config = |policy| Config {
+            size: Some(9),
+            on_bad_record: Some(policy),
+            ..Default::default()
+        };
+
+        let (parsed, _) = parse(&config(OnBadRecord::Fail), csv);
+        let error = parsed.unwrap_err();
+        assert!(error.contains("line 2"), "{error}");
+        assert!(error.contains("size column 9 is out of range"), "{error}");
+
+        let (parsed, bad_records) = parse(&config(OnBadRecord::Skip), csv);
+        assert!(parsed.unwrap().is_empty());
+        assert_eq!(bad_records, 2);
+
+        let in_range = Config {
+            size: Some(3),
+            ..config(OnBadRecord::Fail)
+        };
+        let (parsed, _) = parse(&in_range, csv);
+        let sizes = parsed.unwrap().iter().map(|r| r.size).collect::<Vec<_>>();
+        assert_eq!(sizes, vec![100, 100]);
+    }
+
+    #[test]
+    fn jsonl_records_default_missing_ttl_and_command() {
+        let fields = JsonFields::from_mapping(&[]).unwrap();
+        let record = |line| {
+            let r = parse_json_record(line, &fields).unwrap();
+            (r.timestamp, r.command, r.key, r.size, r.ttl)
+        };
+        assert_eq!(
+            record(r#"{"timestamp": 1, "key": 7, "size": 100}"#),
+            (1, 0, 7, 100, 0)
+        );
+        assert_eq!(
+            record(r#"{"timestamp": 2, "command": 3, "key": 8, "size": 200, "ttl": 60}"#),
+            (2, 3, 8, 200, 60)
+        );
+        assert_eq!(
+            record(r#"{"key": "9", "ttl": -1, "command": null}"#),
+            (0, 0, 9, 1, u32::MAX)
+        );
+        let error = parse_json_record(r#"{"timestamp": 3, "size": 100}"#, &fields).unwrap_err();
+        assert!(error.contains("missing field 'key'"), "{error}");
+
+        let mapping = ["key=obj_id".to_string(), "ttl=expiry".to_string()];
+        let fields = JsonFields::from_mapping(&mapping).unwrap();
+        let r = parse_json_record(r#"{"obj_id": 5, "expiry": 30, "ttl": 99}"#, &fields).unwrap();
+        assert_eq!((r.key, r.ttl, r.command), (5, 30, 0));
+    }
+
+    #[test]
+    fn audit_warns_once_per_ignored_input() {
+        let records = [AccessRecord {
+            timestamp: 1,
+            command: 0,
+            key: 7,
+            size: 100,
+            ttl: 60,
+        }];
+
+        let config = Config {
+            ttl: Some(4),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.audit(&records),
+            vec!["ttl column mapped but --respect-ttl not set; TTLs of 1 records will be ignored"]
+        );
+        let config = Config {
+            respect_ttl: true,
+            ..config
+        };
+        assert!(config.audit(&records).is_empty());
+
+        let config = Config {
+            command: Some(1),
+            ..Default::default()
+        };
+        let warnings = config.audit(&records);
+        assert_eq!(warnings.len(), 1, "{warnings:?}");
+        assert!(
+            warnings[0].starts_with("command column mapped but no mode uses commands"),
+            "{warnings:?}"
+        );
+
+        let config = Config {
+            json_fields: Some(vec!["key=obj_id".to_string()]),
+            respect_ttl: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            config.audit(&records),
+            vec!["--json-fields is only used with --format jsonl"]
+        );
+    }
+
+    #[test]
+    fn unknown_toml_keys_are_rejected_with_the_valid_ones() {
+        let error = toml::from_str::<Config>("trace = \"a.csv\"\ncache_sizes = [\"1GB\"]\n")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("unknown field `cache_sizes`"), "{error}");
+        assert!(error.contains("`cache_size`"), "{error}");
+    }
+
+    #[test]
+    fn malformed_trace_checksum_is_an_error() {
+        let config =
+            toml::from_str::<Config>("trace = \"a.csv\"\ntrace_checksum = \"not hex\"\n").unwrap();
+        let error = InnerConfig::try_from(config).unwrap_err();
+        assert_eq!(error, "trace checksum not hex is not 16 hex digits");
+    }
+
+    #[test]
+    fn collapsing_retry_bursts_raises_the_miss_ratio() {
+        // Every request is retried twice within the same second
+        let mut access_records = ZipfTrace {
+            requests: 10_000,
+            keys: 2_000,
+            alpha: 0.8,
+            min_size: 100,
+            max_size: 100,
+            seed: 9,
+        }
+        .generate()
+        .into_iter()
+        .enumerate()
+        .flat_map(|(i, record)| {
+            let record = AccessRecord {
+                timestamp: 10 * i as u64,
+                ..record
+            };
+            [record, record, record]
+        })
+        .collect::<Vec<_>>();
+        let miss_ratio = |access_records: &[AccessRecord]| {
+            let mut cache = LruPolicy::new(20_000);
+            let misses = access_records
+                .iter()
+                .filter(|record| {
+                    let miss = cache.get(record.key).is_none();
+                    if miss {
+                        cache.put(record.key, record.size as u64);
+                    }
+                    miss
+                })
+                .count();
+            misses as f64 / access_records.len() as f64
+        };
+        let with_retries = miss_ratio(&access_records);
+        collapse_requests(&mut access_records, 1.0);
+        assert_eq!(access_records.len(), 10_000);
+        // The retries all hit, collapsing them leaves the misses to fewer requests
+        let collapsed = miss_ratio(&access_records);
+        assert!(
+            (collapsed - 3.0 * with_retries).abs() < 1e-9,
+            "{collapsed} vs {with_retries}"
+        );
+    }
+
+    #[test]
+    fn prescan_counts_the_records_the_parser_reads() {
+        let traces = [
+            (
+                "timestamp,command,key,size,ttl\n1,0,7,100,0\n2,0,8,100,0\n3,0,7,100,0\n",
+                3,
+            ),
+            // No newline after the last record
+            (
+                "timestamp,command,key,size,ttl\n1,0,7,100,0\n2,0,8,100,0",
+                2,
+            ),
+            // A quoted newline is part of its record
+            (
+                "timestamp,command,key,size,ttl,note\n1,0,7,100,0,\"a\nb\"\n2,0,8,100,0,c\n",
+                2,
+            ),
+        ];
+        let path =
+            std::env::temp_dir().join(format!("cache_mrc_prescan_{}.csv", std::process::id()));
+        for (trace, records) in traces {
+            std::fs::write(&path, trace).unwrap();
+            let (prescanned, _) = prescan_trace(&path, TraceFormat::Csv).unwrap();
+            assert_eq!(prescanned, records, "{trace:?}");
+            let mut config = Config {
+                trace: Some(path.clone()),
+                ..Default::default()
+            };
+            let (access_records, _) = load_access_records(&mut config).unwrap();
+            assert_eq!(prescanned, access_records.len(), "{trace:?}");
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn parallel_parse_matches_serial_parse() {
+        let load = |parse_threads| {
+            let mut config = Config {
+                trace: Some(PathBuf::from("data/golden/twitter_sample.csv")),
+                parse_threads,
+                ..Default::default()
+            };
+            let (records, checksum) = load_access_records(&mut config).unwrap();
+            let records = records
+                .iter()
+                .map(|r| (r.timestamp, r.command, r.key, r.size, r.ttl))
+                .collect::<Vec<_>>();
+            (records, checksum)
+        };
+        let serial = load(None);
+        assert_eq!(serial.0.len(), 10000);
+        assert_eq!(load(Some(4)), serial);
+        assert_eq!(load(Some(7)), serial);
+    }
+
+    #[test]
+    fn scaled_size_overflow_is_reported() {
+        // 5000000 blocks of 4096 bytes is about 20GB, over the 32-bit object size
+        let path = std::env::temp_dir().join("cache_mrc_overflowing_sizes.csv");
+        std::fs::write(
+            &path,
+            "timestamp,command,key,size,ttl\n1,0,7,100,0\n2,0,8,5000000,0\n3,0,9,100,0\n",
+        )
+        .unwrap();
+        let mut config = Config {
+            trace: Some(path.clone()),
+            size_unit_in: Some(SizeUnit::Blocks(4096)),
+            ..Default::default()
+        };
+        let error = load_access_records(&mut config).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(
+            error.contains("record 2 (key 8, timestamp 2) has size 5000000"),
+            "{error}"
+        );
+
+        // The multiplier itself overflows before any record is read
+        let config = Config {
+            size_multiplier: Some(1 << 20),
+            size_unit_in: Some(SizeUnit::Blocks(1 << 12)),
+            ..Default::default()
+        };
+        let error = config.validate().unwrap_err();
+        assert!(
+            error.contains("overflows the 32-bit object size"),
+            "{error}"
+        );
+
+        // Sizes that fit are scaled exactly
+        let mut records = vec![AccessRecord {
+            timestamp: 1,
+            command: 0,
+            key: 7,
+            size: 1 << 19,
+            ttl: 0,
+        }];
+        scale_sizes(&mut records, 1 << 12).unwrap();
+        assert_eq!(records[0].size, 1 << 31);
+    }
 }
@@ -1,31 +1,1083 @@
-use std::path::PathBuf;
+use std::{
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+};
 
-use gnuplot::{AutoOption::Fix, AxesCommon, Figure, PlotOption::Caption};
+use gnuplot::{
+    AlignType::{AlignRight, AlignTop},
+    ArrowheadType,
+    AutoOption::{Auto, Fix},
+    AxesCommon,
+    BorderLocation2D::{Bottom, Left, Right, Top},
+    Coordinate, DashType, Figure,
+    LabelOption::{Font, TextColor},
+    LegendOption::Placement,
+    PlotOption::{ArrowType, Axes, Caption, Color, FillAlpha, LineStyle, LineWidth},
+    Tick, XAxis, YAxis,
+};
 
-use crate::SimulationResult;
+use crate::{
+    analysis::{self, Footprint, ReuseDistances, SizeClass, TimeBucket},
+    config::{CapacityUnit, OutputFormat},
+    theme::Theme,
+    SimulationResult,
+};
+
+// What the x values of `result` are divided by, the unique bytes or keys of
+// the trace with `working_set`, 1 otherwise
+fn x_scale(result: &SimulationResult, working_set: Option<&Footprint>) -> f64 {
+    match (working_set, result.capacity_unit) {
+        (Some(footprint), CapacityUnit::Bytes) => footprint.unique_bytes.max(1) as f64,
+        (Some(footprint), CapacityUnit::Objects) => footprint.unique_keys.max(1) as f64,
+        (None, _) => 1.0,
+    }
+}
+
+// `path` with `_suffix` appended to its file stem, keeping the extension
+pub fn suffixed_path(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap().to_string_lossy();
+    let file_name = match path.extension() {
+        Some(extension) => format!("{stem}_{suffix}.{}", extension.to_string_lossy()),
+        None => format!("{stem}_{suffix}"),
+    };
+    path.with_file_name(file_name)
+}
+
+// File name friendly id of a curve label, e.g. "SLRU(protected=0.5) (objects)"
+// becomes "slru_protected_0_5_objects"
+pub(crate) fn curve_id(label: &str) -> String {
+    let mut id = String::with_capacity(label.len());
+    for c in label.chars() {
+        if c.is_ascii_alphanumeric() {
+            id.push(c.to_ascii_lowercase());
+        } else if !id.is_empty() && !id.ends_with('_') {
+            id.push('_');
+        }
+    }
+    let id = id.trim_end_matches('_');
+    if id.is_empty() {
+        "curve".to_string()
+    } else {
+        id.to_string()
+    }
+}
+
+// The one standard error band around the points of `result`, if it has errors
+fn error_band(result: &SimulationResult) -> Option<Vec<(f64, f64, f64)>> {
+    (!result.stderr.is_empty() && result.stderr.len() == result.points.len()).then(|| {
+        result
+            .points
+            .iter()
+            .zip(result.stderr.iter())
+            .map(|((x, y), stderr)| (*x, (y - stderr).max(0.0), (y + stderr).min(1.0)))
+            .collect()
+    })
+}
+
+// Draw every curve on its own figure named `<output>_<curve_id>`, next to a
+// `cache_size,miss_ratio` CSV of its points, with a `stderr` column when the
+// curve has errors. Returns the files written.
+pub fn draw_separate(
+    results: &[SimulationResult],
+    output: &Path,
+    format: OutputFormat,
+    working_set: Option<&Footprint>,
+    compulsory: Option<f64>,
+    error_bands: bool,
+    theme: &Theme,
+) -> Vec<PathBuf> {
+    let mut written = Vec::new();
+    let mut used = Vec::new();
+    for result in results {
+        // Labels that only differ in punctuation get a numeric suffix
+        let mut id = curve_id(&result.label);
+        if used.contains(&id) {
+            id = (2..)
+                .map(|i| format!("{id}_{i}"))
+                .find(|candidate| !used.contains(candidate))
+                .unwrap();
+        }
+        let path = suffixed_path(output, &id);
+        used.push(id);
+        let csv_path = path.with_extension("csv");
+        let mut csv = String::new();
+        if result.stderr.is_empty() {
+            csv.push_str("cache_size,miss_ratio\n");
+            for (x, y) in result.points.iter() {
+                writeln!(csv, "{x},{y}").unwrap();
+            }
+        } else {
+            csv.push_str("cache_size,miss_ratio,stderr\n");
+            for ((x, y), stderr) in result.points.iter().zip(result.stderr.iter()) {
+                writeln!(csv, "{x},{y},{stderr}").unwrap();
+            }
+        }
+        fs::write(&csv_path, csv).unwrap();
+        written.push(csv_path);
+        written.extend(draw_lines(
+            std::slice::from_ref(result),
+            path,
+            format,
+            working_set,
+            compulsory,
+            error_bands,
+            theme,
+        ));
+    }
+    written
+}
+
+// The miss ratio of every curve at its smallest size, at its knee and at its
+// largest size, as a text table for a run without --output
+pub fn format_table(results: &[SimulationResult]) -> String {
+    let width = results
+        .iter()
+        .map(|result| result.label.len())
+        .fold("curve".len(), usize::max);
+    let cell = |point: Option<(f64, f64)>| match point {
+        Some((size, miss_ratio)) => format!("{:>10}  {miss_ratio:>10.4}", short_size(size)),
+        None => format!("{:>10}  {:>10}", "-", "-"),
+    };
+    let mut table = String::new();
+    writeln!(
+        table,
+        "{:<width$}  {:>10}  {:>10}  {:>10}  {:>10}  {:>10}  {:>10}",
+        "curve", "min size", "miss ratio", "knee size", "miss ratio", "max size", "miss ratio"
+    )
+    .unwrap();
+    for result in results {
+        writeln!(
+            table,
+            "{:<width$}  {}  {}  {}",
+            result.label,
+            cell(result.points.first().copied()),
+            cell(analysis::knee_point(&result.points)),
+            cell(result.points.last().copied()),
+        )
+        .unwrap();
+    }
+    table
+}
 
 // Draw the lines
 // Parameter: Vec<SimulationResult>
-pub fn draw_lines(results: &[SimulationResult], path: PathBuf) {
-    let mut fg = Figure::new();
+// With `working_set`, cache sizes are plotted as a fraction of the trace footprint.
+// With `compulsory`, the asymptotes are annotated: a miss ratio of 1 at size 0
+// and a dotted line at the compulsory miss ratio.
+// With `error_bands`, curves with standard errors are shaded one error either side.
+// `theme` gives the colors, fonts and line widths.
+// Returns the files written.
+pub fn draw_lines(
+    results: &[SimulationResult],
+    path: PathBuf,
+    format: OutputFormat,
+    working_set: Option<&Footprint>,
+    compulsory: Option<f64>,
+    error_bands: bool,
+    theme: &Theme,
+) -> Vec<PathBuf> {
+    let mut written = Vec::new();
+    if matches!(format, OutputFormat::Pgfplots | OutputFormat::Both) {
+        let tex_path = path.with_extension("tex");
+        draw_pgfplots(
+            results,
+            tex_path.clone(),
+            working_set,
+            compulsory,
+            error_bands,
+            theme,
+        );
+        written.push(tex_path);
+    }
+    if matches!(format, OutputFormat::Pgfplots) {
+        return written;
+    }
 
-    let width = 1920;
-    let height = 1080;
+    let mut fg = Figure::new();
 
-    fg.set_title("Miss ratio curve");
+    // Text in the foreground color, the figure title on the axes to take it
+    let text = theme
+        .foreground
+        .iter()
+        .map(|color| TextColor(color.as_str()))
+        .collect::<Vec<_>>();
+    let mut tick_text = text.clone();
+    if let Some(size) = theme.tick_font_size {
+        tick_text.push(Font("", size));
+    }
+    if theme.foreground.is_none() {
+        fg.set_title("Miss ratio curve");
+    }
     let axes = fg.axes2d();
     axes.set_x_grid(true)
         .set_y_grid(true)
         // 设置 y 轴范围为 0 到 1
         .set_y_range(Fix(0.0), Fix(1.0));
-    for result in results {
-        axes.set_x_label("Cache size", &[])
-            .set_y_label("Miss ratio", &[])
+    if let Some(grid) = theme.grid.as_deref() {
+        axes.set_grid_options(false, &[Color(grid)]);
+    }
+    if let Some(foreground) = theme.foreground.as_deref() {
+        axes.set_title("Miss ratio curve", &text)
+            .set_border(false, &[Bottom, Left, Top, Right], &[Color(foreground)])
+            .set_legend(
+                Coordinate::Graph(0.98),
+                Coordinate::Graph(0.98),
+                &[Placement(AlignRight, AlignTop)],
+                &text,
+            );
+    }
+    if theme.foreground.is_some() || theme.tick_font_size.is_some() {
+        axes.set_x_ticks(Some((Auto, 0)), &[], &tick_text)
+            .set_y_ticks(Some((Auto, 0)), &[], &tick_text);
+    }
+    // Object count curves go on the top x axis when mixed with byte curves,
+    // normalized curves share the bottom one
+    let mixed_units = working_set.is_none()
+        && results
+            .iter()
+            .any(|result| result.capacity_unit != results[0].capacity_unit);
+    let x_label = if working_set.is_some() {
+        "Cache size / working set"
+    } else {
+        "Cache size"
+    };
+    let labels = results
+        .iter()
+        .map(|result| result.label.as_str())
+        .collect::<Vec<_>>();
+    for (result, color) in results.iter().zip(theme.curve_colors(&labels)) {
+        let scale = x_scale(result, working_set);
+        let x_axis = if mixed_units && result.capacity_unit == CapacityUnit::Objects {
+            XAxis::X2
+        } else {
+            XAxis::X1
+        };
+        if let Some(band) = error_band(result).filter(|_| error_bands) {
+            axes.fill_between(
+                band.iter().map(|(x, _, _)| *x / scale),
+                band.iter().map(|(_, low, _)| *low),
+                band.iter().map(|(_, _, high)| *high),
+                &[Axes(x_axis, YAxis::Y1), Color("gray"), FillAlpha(0.3)],
+            );
+        }
+        let mut options = vec![
+            Caption(result.label.as_str()),
+            Axes(x_axis, YAxis::Y1),
+            LineStyle(if result.dashed {
+                DashType::Dash
+            } else {
+                DashType::Solid
+            }),
+        ];
+        if let Some(color) = color.as_deref() {
+            options.push(Color(color));
+        }
+        if let Some(line_width) = theme.line_width {
+            options.push(LineWidth(line_width));
+        }
+        axes.set_x_label(x_label, &text)
+            .set_y_label("Miss ratio", &text)
             .lines(
-                result.points.iter().map(|(x, _)| *x),
+                result.points.iter().map(|(x, _)| *x / scale),
                 result.points.iter().map(|(_, y)| *y),
-                &[Caption(result.label.as_str())],
+                &options,
             );
     }
-    fg.save_to_png(path, width, height).unwrap();
+    if mixed_units {
+        let max_objects = results
+            .iter()
+            .filter(|result| result.capacity_unit == CapacityUnit::Objects)
+            .flat_map(|result| result.points.iter().map(|(x, _)| *x))
+            .fold(0.0, f64::max);
+        axes.set_x_label("Cache size (bytes)", &text)
+            .set_x2_label("Cache size (objects)", &text)
+            .set_x2_ticks_custom(
+                (0..=5).map(|i| Tick::Major(i as f64 * max_objects / 5.0, Auto::<String>)),
+                &[],
+                &tick_text,
+            );
+    }
+    if let Some(compulsory) = compulsory {
+        let mut line = vec![ArrowType(ArrowheadType::NoArrow), LineStyle(DashType::Dot)];
+        if let Some(foreground) = theme.foreground.as_deref() {
+            line.push(Color(foreground));
+        }
+        axes.arrow(
+            Coordinate::Graph(0.0),
+            Coordinate::Axis(compulsory),
+            Coordinate::Graph(1.0),
+            Coordinate::Axis(compulsory),
+            &line,
+        )
+        .label(
+            &format!("compulsory misses {compulsory:.4} (size -> infinity)"),
+            Coordinate::Graph(0.01),
+            Coordinate::Axis(compulsory + 0.02),
+            &text,
+        )
+        .label(
+            "miss ratio 1 at size 0",
+            Coordinate::Graph(0.01),
+            Coordinate::Graph(0.97),
+            &text,
+        );
+    }
+    fg.set_terminal(&png_terminal(theme), &path.to_string_lossy())
+        .show()
+        .unwrap();
+    written.push(path);
+    written
+}
+
+// Draw the admission ratio of every curve counted with --churn against the
+// cache size, the bytes a flash cache writes per byte requested. Returns the
+// files written.
+pub fn draw_admission(
+    results: &[SimulationResult],
+    path: PathBuf,
+    format: OutputFormat,
+    working_set: Option<&Footprint>,
+    theme: &Theme,
+) -> Vec<PathBuf> {
+    let results = results
+        .iter()
+        .filter(|result| result.churn.len() == result.points.len() && !result.churn.is_empty())
+        .collect::<Vec<_>>();
+    let labels = results
+        .iter()
+        .map(|result| result.label.as_str())
+        .collect::<Vec<_>>();
+    let colors = theme.curve_colors(&labels);
+    let curve = |result: &SimulationResult| {
+        let scale = x_scale(result, working_set);
+        result
+            .points
+            .iter()
+            .zip(result.churn.iter())
+            .map(|((x, _), churn)| (x / scale, churn.admission_ratio))
+            .collect::<Vec<_>>()
+    };
+    let x_label = if working_set.is_some() {
+        "Cache size / working set"
+    } else {
+        "Cache size"
+    };
+    let mut written = Vec::new();
+    if matches!(format, OutputFormat::Pgfplots | OutputFormat::Both) {
+        let mut tex = String::new();
+        tex.push_str("% Admission ratio generated by cache_mrc\n");
+        tex.push_str("% Requires \\usepackage{pgfplots} in the preamble\n");
+        tex.push_str("\\begin{tikzpicture}\n");
+        tex.push_str("\\begin{axis}[\n");
+        tex.push_str("    title={Admission ratio},\n");
+        writeln!(tex, "    xlabel={{{x_label}}},").unwrap();
+        tex.push_str("    ylabel={Bytes admitted / bytes requested},\n");
+        tex.push_str("    ymin=0, ymax=1,\n");
+        tex.push_str("    grid=major,\n");
+        tex.push_str("    legend pos=north east,\n");
+        tex.push_str("]\n");
+        for (result, color) in results.iter().zip(colors.iter()) {
+            let mut style = vec!["mark=none".to_string()];
+            if result.dashed {
+                style.push("dashed".to_string());
+            }
+            if let Some(color) = color.as_deref() {
+                style.push(format!("color={{rgb,255:{}}}", rgb255(color)));
+            }
+            if let Some(line_width) = theme.line_width {
+                style.push(format!("line width={}pt", line_width * 0.5));
+            }
+            writeln!(tex, "\\addplot+[{}] coordinates {{", style.join(", ")).unwrap();
+            for (x, y) in curve(result) {
+                writeln!(tex, "    ({x},{y})").unwrap();
+            }
+            tex.push_str("};\n");
+            writeln!(tex, "\\addlegendentry{{{}}}", escape_latex(&result.label)).unwrap();
+        }
+        tex.push_str("\\end{axis}\n");
+        tex.push_str("\\end{tikzpicture}\n");
+        let tex_path = path.with_extension("tex");
+        fs::write(&tex_path, tex).unwrap();
+        written.push(tex_path);
+    }
+    if matches!(format, OutputFormat::Pgfplots) {
+        return written;
+    }
+
+    let mut fg = Figure::new();
+    fg.set_title("Admission ratio");
+    let axes = fg.axes2d();
+    axes.set_x_grid(true)
+        .set_y_grid(true)
+        .set_y_range(Fix(0.0), Fix(1.0))
+        .set_x_label(x_label, &[])
+        .set_y_label("Bytes admitted / bytes requested", &[]);
+    for (result, color) in results.iter().zip(colors.iter()) {
+        let points = curve(result);
+        let mut options = vec![
+            Caption(result.label.as_str()),
+            LineStyle(if result.dashed {
+                DashType::Dash
+            } else {
+                DashType::Solid
+            }),
+        ];
+        if let Some(color) = color.as_deref() {
+            options.push(Color(color));
+        }
+        if let Some(line_width) = theme.line_width {
+            options.push(LineWidth(line_width));
+        }
+        axes.lines(
+            points.iter().map(|(x, _)| *x),
+            points.iter().map(|(_, y)| *y),
+            &options,
+        );
+    }
+    fg.set_terminal(&png_terminal(theme), &path.to_string_lossy())
+        .show()
+        .unwrap();
+    written.push(path);
+    written
+}
+
+// Draw each policy's miss ratio per time bucket at one cache size, with the
+// trace time since the first request on the x axis. Returns the files written.
+pub fn draw_over_time(
+    reports: &[(String, Vec<TimeBucket>)],
+    capacity: u64,
+    path: PathBuf,
+    format: OutputFormat,
+    theme: &Theme,
+) -> Vec<PathBuf> {
+    let labels = reports
+        .iter()
+        .map(|(label, _)| label.as_str())
+        .collect::<Vec<_>>();
+    let colors = theme.curve_colors(&labels);
+    let title = format!("Miss ratio over time at cache size {capacity}");
+    let mut written = Vec::new();
+    if matches!(format, OutputFormat::Pgfplots | OutputFormat::Both) {
+        let mut tex = String::new();
+        tex.push_str("% Miss ratio over time generated by cache_mrc\n");
+        tex.push_str("% Requires \\usepackage{pgfplots} in the preamble\n");
+        tex.push_str("\\begin{tikzpicture}\n");
+        tex.push_str("\\begin{axis}[\n");
+        writeln!(tex, "    title={{{title}}},").unwrap();
+        tex.push_str("    xlabel={Time since the first request},\n");
+        tex.push_str("    ylabel={Miss ratio},\n");
+        tex.push_str("    ymin=0, ymax=1,\n");
+        tex.push_str("    grid=major,\n");
+        tex.push_str("    legend pos=north east,\n");
+        tex.push_str("]\n");
+        for ((label, buckets), color) in reports.iter().zip(colors.iter()) {
+            let mut style = vec!["mark=none".to_string()];
+            if let Some(color) = color.as_deref() {
+                style.push(format!("color={{rgb,255:{}}}", rgb255(color)));
+            }
+            if let Some(line_width) = theme.line_width {
+                style.push(format!("line width={}pt", line_width * 0.5));
+            }
+            writeln!(tex, "\\addplot+[{}] coordinates {{", style.join(", ")).unwrap();
+            for bucket in buckets {
+                writeln!(tex, "    ({},{})", bucket.start, bucket.miss_ratio).unwrap();
+            }
+            tex.push_str("};\n");
+            writeln!(tex, "\\addlegendentry{{{}}}", escape_latex(label)).unwrap();
+        }
+        tex.push_str("\\end{axis}\n");
+        tex.push_str("\\end{tikzpicture}\n");
+        let tex_path = path.with_extension("tex");
+        fs::write(&tex_path, tex).unwrap();
+        written.push(tex_path);
+    }
+    if matches!(format, OutputFormat::Pgfplots) {
+        return written;
+    }
+
+    let mut fg = Figure::new();
+    fg.set_title(&title);
+    let axes = fg.axes2d();
+    axes.set_x_grid(true)
+        .set_y_grid(true)
+        .set_y_range(Fix(0.0), Fix(1.0))
+        .set_x_label("Time since the first request", &[])
+        .set_y_label("Miss ratio", &[]);
+    for ((label, buckets), color) in reports.iter().zip(colors.iter()) {
+        let mut options = vec![Caption(label.as_str())];
+        if let Some(color) = color.as_deref() {
+            options.push(Color(color));
+        }
+        if let Some(line_width) = theme.line_width {
+            options.push(LineWidth(line_width));
+        }
+        axes.lines(
+            buckets.iter().map(|bucket| bucket.start as f64),
+            buckets.iter().map(|bucket| bucket.miss_ratio),
+            &options,
+        );
+    }
+    fg.set_terminal(&png_terminal(theme), &path.to_string_lossy())
+        .show()
+        .unwrap();
+    written.push(path);
+    written
+}
+
+// Draw the reuse distance CDF of a trace on a log2 x axis, with a dashed line
+// at 1 minus the cold fraction that the CDF approaches. Returns the files written.
+pub fn draw_reuse_cdf(reuse: &ReuseDistances, path: PathBuf, format: OutputFormat) -> Vec<PathBuf> {
+    // Stop at the largest distance seen, the empty buckets above it are flat
+    let mut cdf = reuse.cdf();
+    let finite = cdf.last().map(|(_, y)| *y).unwrap_or(0.0);
+    if let Some(end) = cdf.iter().position(|(_, y)| *y >= finite) {
+        cdf.truncate(end + 1);
+    }
+    let warm = 1.0 - reuse.cold_fraction();
+    let max_distance = cdf.last().map(|(x, _)| *x).unwrap_or(2.0);
+    let mut written = Vec::new();
+    if matches!(format, OutputFormat::Pgfplots | OutputFormat::Both) {
+        let mut tex = String::new();
+        tex.push_str("% Reuse distance CDF generated by cache_mrc\n");
+        tex.push_str("% Requires \\usepackage{pgfplots} in the preamble\n");
+        tex.push_str("\\begin{tikzpicture}\n");
+        tex.push_str("\\begin{semilogxaxis}[\n");
+        tex.push_str("    title={Reuse distance CDF},\n");
+        tex.push_str("    xlabel={Reuse distance (distinct keys)},\n");
+        tex.push_str("    ylabel={Fraction of requests},\n");
+        tex.push_str("    log basis x=2,\n");
+        tex.push_str("    ymin=0, ymax=1,\n");
+        tex.push_str("    grid=major,\n");
+        tex.push_str("    legend pos=south east,\n");
+        tex.push_str("]\n");
+        tex.push_str("\\addplot+[mark=none] coordinates {\n");
+        for (x, y) in cdf.iter() {
+            writeln!(tex, "    ({x},{y})").unwrap();
+        }
+        tex.push_str("};\n");
+        tex.push_str("\\addlegendentry{Reuse distance CDF}\n");
+        writeln!(
+            tex,
+            "\\addplot+[mark=none, dashed] coordinates {{ ({},{warm}) ({max_distance},{warm}) }};",
+            cdf.first().map(|(x, _)| *x).unwrap_or(1.0)
+        )
+        .unwrap();
+        tex.push_str("\\addlegendentry{1 - cold fraction}\n");
+        tex.push_str("\\end{semilogxaxis}\n");
+        tex.push_str("\\end{tikzpicture}\n");
+        let tex_path = path.with_extension("tex");
+        fs::write(&tex_path, tex).unwrap();
+        written.push(tex_path);
+    }
+    if matches!(format, OutputFormat::Pgfplots) {
+        return written;
+    }
+
+    let mut fg = Figure::new();
+    fg.set_title("Reuse distance CDF");
+    let axes = fg.axes2d();
+    axes.set_x_grid(true)
+        .set_y_grid(true)
+        .set_x_log(Some(2.0))
+        .set_y_range(Fix(0.0), Fix(1.0))
+        .set_x_label("Reuse distance (distinct keys)", &[])
+        .set_y_label("Fraction of requests", &[])
+        .lines(
+            cdf.iter().map(|(x, _)| *x),
+            cdf.iter().map(|(_, y)| *y),
+            &[Caption("Reuse distance CDF")],
+        )
+        .lines(
+            [cdf.first().map(|(x, _)| *x).unwrap_or(1.0), max_distance],
+            [warm, warm],
+            &[Caption("1 - cold fraction"), LineStyle(DashType::Dash)],
+        );
+    fg.save_to_png(&path, 1920, 1080).unwrap();
+    written.push(path);
+    written
+}
+
+// Byte count with a binary unit suffix, e.g. 1536 becomes "1.5K"
+fn short_size(bytes: f64) -> String {
+    let units = ["", "K", "M", "G", "T"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{}{}", (value * 10.0).round() / 10.0, units[unit])
+}
+
+// Draw the hit ratio of every size class as a bar chart, one bar per policy
+// and class. Classes missing from a policy are drawn at 0. Returns the files written.
+pub fn draw_size_classes(
+    reports: &[(String, Vec<SizeClass>)],
+    path: PathBuf,
+    format: OutputFormat,
+) -> Vec<PathBuf> {
+    // Every class seen by any policy, by lower edge
+    let mut classes = reports
+        .iter()
+        .flat_map(|(_, classes)| classes.iter().map(|class| (class.lower, class.upper)))
+        .collect::<Vec<_>>();
+    classes.sort_by(|a, b| a.0.total_cmp(&b.0));
+    classes.dedup();
+    let names = classes
+        .iter()
+        .map(|(lower, upper)| format!("{}-{}", short_size(*lower), short_size(*upper)))
+        .collect::<Vec<_>>();
+    let hit_ratios = |report: &[SizeClass]| {
+        classes
+            .iter()
+            .map(|(lower, _)| {
+                report
+                    .iter()
+                    .find(|class| class.lower == *lower)
+                    .map_or(0.0, SizeClass::hit_ratio)
+            })
+            .collect::<Vec<_>>()
+    };
+    let mut written = Vec::new();
+    if matches!(format, OutputFormat::Pgfplots | OutputFormat::Both) {
+        let mut tex = String::new();
+        tex.push_str("% Hit ratio by object size class generated by cache_mrc\n");
+        tex.push_str("% Requires \\usepackage{pgfplots} in the preamble\n");
+        tex.push_str("\\begin{tikzpicture}\n");
+        tex.push_str("\\begin{axis}[\n");
+        tex.push_str("    ybar,\n");
+        tex.push_str("    title={Hit ratio by object size class},\n");
+        tex.push_str("    xlabel={Object size (bytes)},\n");
+        tex.push_str("    ylabel={Hit ratio},\n");
+        tex.push_str("    ymin=0, ymax=1,\n");
+        writeln!(tex, "    symbolic x coords={{{}}},", names.join(",")).unwrap();
+        tex.push_str("    xtick=data,\n");
+        tex.push_str("    x tick label style={rotate=45, anchor=east},\n");
+        tex.push_str("    legend pos=north east,\n");
+        tex.push_str("]\n");
+        for (label, report) in reports {
+            tex.push_str("\\addplot coordinates {\n");
+            for (name, hit_ratio) in names.iter().zip(hit_ratios(report)) {
+                writeln!(tex, "    ({name},{hit_ratio})").unwrap();
+            }
+            tex.push_str("};\n");
+            writeln!(tex, "\\addlegendentry{{{}}}", escape_latex(label)).unwrap();
+        }
+        tex.push_str("\\end{axis}\n");
+        tex.push_str("\\end{tikzpicture}\n");
+        let tex_path = path.with_extension("tex");
+        fs::write(&tex_path, tex).unwrap();
+        written.push(tex_path);
+    }
+    if matches!(format, OutputFormat::Pgfplots) {
+        return written;
+    }
+
+    let mut fg = Figure::new();
+    fg.set_title("Hit ratio by object size class");
+    let axes = fg.axes2d();
+    axes.set_y_grid(true)
+        .set_y_range(Fix(0.0), Fix(1.0))
+        .set_x_range(Fix(-0.5), Fix(classes.len() as f64 - 0.5))
+        .set_x_label("Object size (bytes)", &[])
+        .set_y_label("Hit ratio", &[])
+        .set_x_ticks_custom(
+            names
+                .iter()
+                .enumerate()
+                .map(|(i, name)| Tick::Major(i as f64, Fix(name.clone()))),
+            &[],
+            &[],
+        );
+    // The bars of one class share 80% of its slot
+    let width = 0.8 / reports.len().max(1) as f64;
+    for (i, (label, report)) in reports.iter().enumerate() {
+        let offset = -0.4 + width * (i as f64 + 0.5);
+        axes.boxes_set_width(
+            (0..classes.len()).map(|class| class as f64 + offset),
+            hit_ratios(report),
+            std::iter::repeat_n(width, classes.len()),
+            &[Caption(label.as_str())],
+        );
+    }
+    fg.save_to_png(&path, 1920, 1080).unwrap();
+    written.push(path);
+    written
+}
+
+// Write the same figure as PGFplots code, meant to be \input into a LaTeX document.
+// Fonts are given relative to the document font so the figure matches the paper,
+// which also keeps its background and text color: only the curve colors, line
+// widths and grid color of `theme` apply.
+fn draw_pgfplots(
+    results: &[SimulationResult],
+    path: PathBuf,
+    working_set: Option<&Footprint>,
+    compulsory: Option<f64>,
+    error_bands: bool,
+    theme: &Theme,
+) {
+    let mut tex = String::new();
+    tex.push_str("% Miss ratio curve generated by cache_mrc\n");
+    tex.push_str("% Requires \\usepackage{pgfplots} in the preamble\n");
+    tex.push_str("\\begin{tikzpicture}\n");
+    tex.push_str("\\begin{axis}[\n");
+    tex.push_str("    title={Miss ratio curve},\n");
+    if working_set.is_some() {
+        tex.push_str("    xlabel={Cache size / working set},\n");
+    } else {
+        tex.push_str("    xlabel={Cache size},\n");
+    }
+    tex.push_str("    ylabel={Miss ratio},\n");
+    tex.push_str("    ymin=0, ymax=1,\n");
+    tex.push_str("    grid=major,\n");
+    tex.push_str("    legend pos=north east,\n");
+    tex.push_str("    title style={font=\\normalsize},\n");
+    tex.push_str("    label style={font=\\small},\n");
+    tex.push_str("    tick label style={font=\\footnotesize},\n");
+    tex.push_str("    legend style={font=\\footnotesize},\n");
+    if let Some(grid) = theme.grid.as_deref() {
+        writeln!(
+            tex,
+            "    grid style={{color={{rgb,255:{}}}}},",
+            rgb255(grid)
+        )
+        .unwrap();
+    }
+    tex.push_str("]\n");
+    let labels = results
+        .iter()
+        .map(|result| result.label.as_str())
+        .collect::<Vec<_>>();
+    for (result, color) in results.iter().zip(theme.curve_colors(&labels)) {
+        let scale = x_scale(result, working_set);
+        if let Some(band) = error_band(result).filter(|_| error_bands) {
+            tex.push_str(
+                "\\addplot[draw=none, fill=gray, fill opacity=0.3, forget plot] coordinates {\n",
+            );
+            for (x, _, high) in band.iter() {
+                writeln!(tex, "    ({},{high})", x / scale).unwrap();
+            }
+            for (x, low, _) in band.iter().rev() {
+                writeln!(tex, "    ({},{low})", x / scale).unwrap();
+            }
+            tex.push_str("} -- cycle;\n");
+        }
+        let mut style = vec!["mark=none".to_string()];
+        if result.dashed {
+            style.push("dashed".to_string());
+        }
+        if let Some(color) = color.as_deref() {
+            style.push(format!("color={{rgb,255:{}}}", rgb255(color)));
+        }
+        if let Some(line_width) = theme.line_width {
+            // gnuplot line widths are multiples of its default, 0.5pt wide
+            style.push(format!("line width={}pt", line_width * 0.5));
+        }
+        writeln!(tex, "\\addplot+[{}] coordinates {{", style.join(", ")).unwrap();
+        for (i, (x, y)) in result.points.iter().enumerate() {
+            // Normalized points keep the absolute size in a comment
+            let mut notes = Vec::new();
+            if working_set.is_some() {
+                notes.push(format!("size {x}"));
+            }
+            if let Some(pass) = result.passes.get(i) {
+                notes.push(format!("pass {pass}"));
+            }
+            if notes.is_empty() {
+                writeln!(tex, "    ({},{y})", x / scale).unwrap();
+            } else {
+                writeln!(tex, "    ({},{y}) % {}", x / scale, notes.join(", ")).unwrap();
+            }
+        }
+        tex.push_str("};\n");
+        writeln!(tex, "\\addlegendentry{{{}}}", escape_latex(&result.label)).unwrap();
+    }
+    if let Some(compulsory) = compulsory {
+        let level = format!("{{rel axis cs:0,0}} |- {{axis cs:0,{compulsory}}}");
+        writeln!(
+            tex,
+            "\\draw[dotted] ({level}) -- ({{rel axis cs:1,0}} |- {{axis cs:0,{compulsory}}});"
+        )
+        .unwrap();
+        writeln!(
+            tex,
+            "\\node[anchor=south west, font=\\footnotesize] at ({level}) \
+             {{compulsory misses {compulsory:.4} (size $\\to\\infty$)}};"
+        )
+        .unwrap();
+        tex.push_str(
+            "\\node[anchor=north west, font=\\footnotesize] at (rel axis cs:0,1) \
+             {miss ratio 1 at size 0};\n",
+        );
+    }
+    tex.push_str("\\end{axis}\n");
+    tex.push_str("\\end{tikzpicture}\n");
+    fs::write(path, tex).unwrap();
+}
+
+// Write the gnuplot script that draws the same figure as `draw_lines`, with
+// the points inline as data blocks (gnuplot 5 or later). Running it renders
+// `path` with a .png extension.
+pub fn write_gnuplot_script(
+    results: &[SimulationResult],
+    path: &Path,
+    working_set: Option<&Footprint>,
+    compulsory: Option<f64>,
+    error_bands: bool,
+    theme: &Theme,
+) -> std::io::Result<()> {
+    let mixed_units = working_set.is_none()
+        && results
+            .iter()
+            .any(|result| result.capacity_unit != results[0].capacity_unit);
+    let mut gp = String::new();
+    gp.push_str("# Miss ratio curve generated by cache_mrc\n");
+    gp.push_str("# Requires gnuplot 5 for the inline data blocks\n");
+    writeln!(gp, "set terminal {}", png_terminal(theme)).unwrap();
+    writeln!(
+        gp,
+        "set output \"{}\"",
+        escape_gnuplot(&path.with_extension("png").to_string_lossy())
+    )
+    .unwrap();
+    gp.push_str("set encoding utf8\n");
+    gp.push_str("set termoption noenhanced\n");
+    gp.push_str("set title \"Miss ratio curve\"\n");
+    if working_set.is_some() {
+        gp.push_str("set xlabel \"Cache size / working set\"\n");
+    } else if mixed_units {
+        gp.push_str("set xlabel \"Cache size (bytes)\"\n");
+        gp.push_str("set x2label \"Cache size (objects)\"\n");
+        gp.push_str("set xtics nomirror\n");
+        gp.push_str("set x2tics\n");
+    } else {
+        gp.push_str("set xlabel \"Cache size\"\n");
+    }
+    gp.push_str("set ylabel \"Miss ratio\"\n");
+    gp.push_str("set yrange [0:1]\n");
+    if let Some(size) = theme.tick_font_size {
+        writeln!(gp, "set tics font \",{size}\"").unwrap();
+    }
+    match theme.grid.as_deref() {
+        Some(grid) => writeln!(gp, "set grid linecolor rgb \"{grid}\"").unwrap(),
+        None => gp.push_str("set grid\n"),
+    }
+    if let Some(foreground) = theme.foreground.as_deref() {
+        writeln!(gp, "set border linecolor rgb \"{foreground}\"").unwrap();
+        for element in ["title", "xlabel", "x2label", "ylabel", "tics", "key"] {
+            writeln!(gp, "set {element} textcolor rgb \"{foreground}\"").unwrap();
+        }
+    }
+    if let Some(compulsory) = compulsory {
+        let (line, text) = match theme.foreground.as_deref() {
+            Some(foreground) => (
+                format!(" linecolor rgb \"{foreground}\""),
+                format!(" textcolor rgb \"{foreground}\""),
+            ),
+            None => (String::new(), String::new()),
+        };
+        writeln!(
+            gp,
+            "set arrow from graph 0, first {compulsory} to graph 1, first {compulsory} nohead dashtype 3{line}"
+        )
+        .unwrap();
+        writeln!(
+            gp,
+            "set label \"compulsory misses {compulsory:.4} (size -> infinity)\" at graph 0.01, first {}{text}",
+            compulsory + 0.02
+        )
+        .unwrap();
+        writeln!(
+            gp,
+            "set label \"miss ratio 1 at size 0\" at graph 0.01, graph 0.97{text}"
+        )
+        .unwrap();
+    }
+    for (i, result) in results.iter().enumerate() {
+        writeln!(gp, "\n# {}", result.label).unwrap();
+        writeln!(gp, "$curve{i} << EOD").unwrap();
+        let scale = x_scale(result, working_set);
+        for (j, (x, y)) in result.points.iter().enumerate() {
+            // Normalized points keep the absolute size in a comment
+            let mut notes = Vec::new();
+            if working_set.is_some() {
+                notes.push(format!("size {x}"));
+            }
+            if let Some(pass) = result.passes.get(j) {
+                notes.push(format!("pass {pass}"));
+            }
+            // The standard error, when the curve has one, is the third column
+            let stderr = result
+                .stderr
+                .get(j)
+                .map_or(String::new(), |stderr| format!(" {stderr}"));
+            if notes.is_empty() {
+                writeln!(gp, "{} {y}{stderr}", x / scale).unwrap();
+            } else {
+                writeln!(gp, "{} {y}{stderr} # {}", x / scale, notes.join(", ")).unwrap();
+            }
+        }
+        gp.push_str("EOD\n");
+    }
+    let labels = results
+        .iter()
+        .map(|result| result.label.as_str())
+        .collect::<Vec<_>>();
+    let colors = theme.curve_colors(&labels);
+    let plots = results
+        .iter()
+        .enumerate()
+        .flat_map(|(i, result)| {
+            let axes = if mixed_units && result.capacity_unit == CapacityUnit::Objects {
+                "x2y1"
+            } else {
+                "x1y1"
+            };
+            let band = error_band(result).filter(|_| error_bands).map(|_| {
+                format!(
+                    "$curve{i} using 1:($2-$3):($2+$3) axes {axes} \
+                     with filledcurves fillstyle transparent solid 0.3 linecolor \"gray\" notitle"
+                )
+            });
+            let mut style = format!("dashtype {}", if result.dashed { 2 } else { 1 });
+            if let Some(color) = colors[i].as_deref() {
+                write!(style, " linecolor rgb \"{color}\"").unwrap();
+            }
+            if let Some(line_width) = theme.line_width {
+                write!(style, " linewidth {line_width}").unwrap();
+            }
+            let line = format!(
+                "$curve{i} using 1:2 axes {axes} with lines {style} title \"{}\"",
+                escape_gnuplot(&result.label)
+            );
+            band.into_iter().chain([line])
+        })
+        .collect::<Vec<_>>();
+    writeln!(gp, "\nplot {}", plots.join(", \\\n     ")).unwrap();
+    fs::write(path, gp)
+}
+
+// "#RRGGBB" as the "R,G,B" of an xcolor rgb,255 color, colors are checked
+// when the config is loaded
+fn rgb255(color: &str) -> String {
+    let hex = color.trim_start_matches('#');
+    (0..3)
+        .map(|i| {
+            u8::from_str_radix(hex.get(2 * i..2 * i + 2).unwrap_or("00"), 16)
+                .unwrap_or(0)
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// The pngcairo terminal of the miss ratio figures: the canvas, the scaling of
+// the fonts and lines with it, the background and the font
+fn png_terminal(theme: &Theme) -> String {
+    let scale = theme.scale.unwrap_or(1.0);
+    let mut terminal = format!(
+        "pngcairo size {},{}",
+        (1920.0 * scale).round(),
+        (1080.0 * scale).round()
+    );
+    if let Some(scale) = theme.scale {
+        write!(terminal, " fontscale {scale} linewidth {scale}").unwrap();
+    }
+    if let Some(background) = theme.background.as_deref() {
+        write!(terminal, " background \"{background}\"").unwrap();
+    }
+    if let Some(font) = theme.gnuplot_font() {
+        write!(terminal, " font \"{}\"", escape_gnuplot(&font)).unwrap();
+    }
+    terminal
+}
+
+// Escape a string for a double-quoted gnuplot string
+fn escape_gnuplot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_latex(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(label: &str, points: Vec<(f64, f64)>) -> SimulationResult {
+        SimulationResult {
+            points,
+            label: label.to_string(),
+            capacity_unit: CapacityUnit::Bytes,
+            dashed: false,
+            requests: 1000,
+            passes: Vec::new(),
+            stderr: Vec::new(),
+            sampled_hits: Vec::new(),
+            sampled: None,
+            churn: Vec::new(),
+            evictions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn gnuplot_script_has_a_data_block_and_a_plot_per_curve() {
+        let results = [
+            result("LRU", vec![(100.0, 0.9), (200.0, 0.5), (400.0, 0.25)]),
+            result("SLRU(protected=0.5)", vec![(100.0, 0.8), (200.0, 0.4)]),
+        ];
+        let path = std::env::temp_dir().join(format!("cache_mrc_script_{}.gp", std::process::id()));
+        write_gnuplot_script(&results, &path, None, Some(0.2), false, &Theme::default()).unwrap();
+        let script = fs::read_to_string(&path).unwrap();
+
+        assert!(
+            script.contains("\n$curve0 << EOD\n100 0.9\n200 0.5\n400 0.25\nEOD\n"),
+            "{script}"
+        );
+        assert!(
+            script.contains("\n$curve1 << EOD\n100 0.8\n200 0.4\nEOD\n"),
+            "{script}"
+        );
+        assert!(!script.contains("$curve2"));
+        let plot = &script[script.find("\nplot ").expect("no plot command")..];
+        assert!(plot.contains("$curve0 using 1:2 axes x1y1 with lines dashtype 1 title \"LRU\""));
+        assert!(plot.contains(
+            "$curve1 using 1:2 axes x1y1 with lines dashtype 1 title \"SLRU(protected=0.5)\""
+        ));
+        assert!(script.starts_with("# Miss ratio curve generated by cache_mrc\n"));
+        assert!(script.contains(&format!(
+            "set output \"{}\"",
+            path.with_extension("png").display()
+        )));
+
+        // Smoke-run the script where gnuplot is installed
+        match std::process::Command::new("gnuplot").arg(&path).output() {
+            Ok(output) => {
+                assert!(
+                    output.status.success(),
+                    "{}",
+                    String::from_utf8_lossy(&output.stderr)
+                );
+                fs::remove_file(path.with_extension("png")).unwrap();
+            }
+            Err(e) => assert_eq!(e.kind(), std::io::ErrorKind::NotFound),
+        }
+        fs::remove_file(&path).unwrap();
+    }
 }
@@ -0,0 +1,75 @@
+use std::fmt;
+
+use crate::{SampleCounts, SimulationResult};
+
+// Why a curve could not be simulated
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimError {
+    // The cache sizes can't be simulated, e.g. too small for the policy
+    InvalidCapacity {
+        capacity: u64,
+        reason: String,
+    },
+    // A policy parameter is out of range
+    InvalidPolicy {
+        policy: String,
+        reason: String,
+    },
+    // The simulation panicked in its worker, the other curves are unaffected
+    WorkerPanicked {
+        label: String,
+        message: String,
+    },
+    // SHARDS at `rate` sampled fewer keys or requests of the `requests` than
+    // --min-sampled-keys and --min-sampled-requests, the curve would be noise
+    // or 0/0
+    Undersampled {
+        label: String,
+        rate: f64,
+        sampled: SampleCounts,
+        requests: u64,
+    },
+    // The run's CancellationToken was cancelled. `partial` is what the curve
+    // looked like when it stopped, None if no record had been simulated.
+    Cancelled {
+        label: String,
+        partial: Option<Box<SimulationResult>>,
+    },
+}
+
+impl fmt::Display for SimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimError::InvalidCapacity { capacity, reason } => {
+                write!(f, "invalid cache size {capacity}: {reason}")
+            }
+            SimError::InvalidPolicy { policy, reason } => {
+                write!(f, "invalid policy {policy}: {reason}")
+            }
+            SimError::WorkerPanicked { label, message } => {
+                write!(f, "{label} simulation panicked: {message}")
+            }
+            SimError::Undersampled {
+                label,
+                rate,
+                sampled,
+                requests,
+            } => write!(
+                f,
+                "{label} sampled {} keys over {} of the {requests} requests at SHARDS rate \
+                 {rate:.2e}, too few for a curve, skipping it",
+                sampled.keys, sampled.requests
+            ),
+            SimError::Cancelled { label, partial } => match partial {
+                Some(partial) => write!(
+                    f,
+                    "{label} simulation cancelled, partial curve over the first {} requests",
+                    partial.requests
+                ),
+                None => write!(f, "{label} simulation cancelled before it started"),
+            },
+        }
+    }
+}
+
+impl std::error::Error for SimError {}
@@ -4,7 +4,7 @@ use hashbrown::HashMap;
 
 use crate::Key;
 
-use super::EvictPolicy;
+use super::{check_accounting, EvictPolicy, NewPolicy};
 
 // FIFO (First In First Out) Policy implementation
 pub struct FifoPolicy {
@@ -12,28 +12,61 @@ pub struct FifoPolicy {
     size: u64,
     cache: HashMap<Key, u64>,
     queue: VecDeque<Key>,
+    evicted: Option<Vec<Key>>,
 }
 
-impl EvictPolicy for FifoPolicy {
+impl FifoPolicy {
+    // Evict until the cache fits its capacity again
+    fn shrink(&mut self) {
+        while self.size > self.capacity {
+            if let Some(old_key) = self.queue.pop_front() {
+                if let Some(old_size) = self.cache.remove(&old_key) {
+                    self.size -= old_size;
+                    if let Some(evicted) = self.evicted.as_mut() {
+                        evicted.push(old_key);
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+impl NewPolicy for FifoPolicy {
     fn new(capacity: u64) -> Self {
         Self {
             capacity,
             size: 0,
             cache: HashMap::new(),
             queue: VecDeque::new(),
+            evicted: None,
         }
     }
+}
 
+impl EvictPolicy for FifoPolicy {
     fn get(&mut self, key: Key) -> Option<()> {
         self.cache.get(&key).map(|_| ())
     }
 
+    fn contains(&self, key: Key) -> bool {
+        self.cache.contains_key(&key)
+    }
+
+    fn resident(&self) -> Box<dyn Iterator<Item = (Key, u64)> + '_> {
+        Box::new(self.queue.iter().map(|key| (*key, self.cache[key])))
+    }
+
     fn put(&mut self, key: Key, size: u64) {
         // Evict items if necessary
         while self.size + size > self.capacity {
             if let Some(old_key) = self.queue.pop_front() {
                 if let Some(old_size) = self.cache.remove(&old_key) {
                     self.size -= old_size;
+                    if let Some(evicted) = self.evicted.as_mut() {
+                        evicted.push(old_key);
+                    }
                 }
             } else {
                 break; // Prevent infinite loop
@@ -44,4 +77,76 @@ impl EvictPolicy for FifoPolicy {
         self.queue.push_back(key);
         self.size += size;
     }
+
+    fn update_size(&mut self, key: Key, new_size: u64) -> bool {
+        let Some(size) = self.cache.get_mut(&key) else {
+            return false;
+        };
+        self.size = self.size - *size + new_size;
+        *size = new_size;
+        self.shrink();
+        true
+    }
+
+    fn set_capacity(&mut self, capacity: u64) {
+        self.capacity = capacity;
+        self.shrink();
+    }
+
+    fn remove(&mut self, key: Key) -> Option<u64> {
+        let size = self.cache.remove(&key)?;
+        self.queue.retain(|&k| k != key);
+        self.size -= size;
+        Some(size)
+    }
+
+    fn record_evictions(&mut self, enabled: bool) {
+        self.evicted = enabled.then(Vec::new);
+    }
+
+    fn take_evictions(&mut self) -> Vec<Key> {
+        self.evicted
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    fn debug_validate(&self) -> Result<(), String> {
+        if self.queue.len() != self.cache.len() {
+            return Err(format!(
+                "{} keys queued but {} resident",
+                self.queue.len(),
+                self.cache.len()
+            ));
+        }
+        check_accounting(self.size, self.capacity, self.cache.values().copied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resident_lists_the_oldest_insertion_first() {
+        let mut policy = FifoPolicy::new(30);
+        policy.record_evictions(true);
+        for key in [1, 2, 3] {
+            policy.put(key, 10);
+        }
+        // A hit doesn't move a key
+        policy.get(1);
+        assert_eq!(
+            policy.resident().collect::<Vec<_>>(),
+            vec![(1, 10), (2, 10), (3, 10)]
+        );
+        // The first resident object is the next victim
+        policy.put(4, 10);
+        assert_eq!(policy.take_evictions(), vec![1]);
+        policy.remove(3);
+        assert_eq!(
+            policy.resident().collect::<Vec<_>>(),
+            vec![(2, 10), (4, 10)]
+        );
+    }
 }
@@ -0,0 +1,296 @@
+use std::collections::VecDeque;
+
+use hashbrown::HashMap;
+
+use crate::Key;
+
+use super::{check_accounting, EvictPolicy, NewPolicy};
+
+// Fraction of the capacity frozen by `new`
+const DEFAULT_HOT: f64 = 0.5;
+// Requests between two rebuilds of the frozen set by `new`
+const DEFAULT_REBUILD: u64 = 100_000;
+
+// FrozenHot Policy implementation
+// The most requested resident keys of the last interval are frozen: hits on
+// them touch nothing and they are never evicted until the next rebuild. The
+// rest of the cache is a FIFO. Every `rebuild` requests the frozen set is
+// rebuilt from the request counts of the interval, filling up to `hot` of the
+// capacity, and the keys that drop out of it go back to the FIFO tail.
+pub struct FrozenHotPolicy {
+    capacity: u64,
+    hot: f64,
+    rebuild: u64,
+    requests: u64,
+    // Requests per key since the last rebuild
+    counts: HashMap<Key, u64>,
+    // Frozen keys, hottest first
+    frozen: Vec<Key>,
+    frozen_sizes: HashMap<Key, u64>,
+    frozen_size: u64,
+    queue: VecDeque<Key>,
+    fifo: HashMap<Key, u64>,
+    fifo_size: u64,
+    evicted: Option<Vec<Key>>,
+}
+
+impl FrozenHotPolicy {
+    // `hot` is the fraction of the capacity frozen, `rebuild` the requests
+    // between two rebuilds of the frozen set
+    pub fn with_params(capacity: u64, hot: f64, rebuild: u64) -> Self {
+        Self {
+            capacity,
+            hot: hot.clamp(0.0, 1.0),
+            rebuild: rebuild.max(1),
+            requests: 0,
+            counts: HashMap::new(),
+            frozen: Vec::new(),
+            frozen_sizes: HashMap::new(),
+            frozen_size: 0,
+            queue: VecDeque::new(),
+            fifo: HashMap::new(),
+            fifo_size: 0,
+            evicted: None,
+        }
+    }
+
+    // Frozen keys, hottest first
+    pub fn frozen(&self) -> &[Key] {
+        &self.frozen
+    }
+
+    // Freeze the resident keys requested most since the last rebuild, up to
+    // `hot` of the capacity, and thaw the others into the FIFO
+    fn rebuild_frozen(&mut self) {
+        let mut candidates = self
+            .frozen
+            .iter()
+            .map(|key| (*key, self.frozen_sizes[key]))
+            .chain(self.queue.iter().map(|key| (*key, self.fifo[key])))
+            .filter_map(|(key, size)| self.counts.get(&key).map(|count| (key, size, *count)))
+            .collect::<Vec<_>>();
+        // Ties go to the key that was frozen or queued first
+        candidates.sort_by_key(|candidate| std::cmp::Reverse(candidate.2));
+        let budget = (self.capacity as f64 * self.hot) as u64;
+        let mut frozen = Vec::new();
+        let mut frozen_size = 0;
+        for (key, size, _) in candidates {
+            if frozen_size + size <= budget {
+                frozen.push(key);
+                frozen_size += size;
+            }
+        }
+
+        let mut frozen_sizes = HashMap::with_capacity(frozen.len());
+        for key in frozen.iter() {
+            let size = match self.frozen_sizes.remove(key) {
+                Some(size) => size,
+                None => {
+                    let size = self.fifo.remove(key).unwrap();
+                    self.fifo_size -= size;
+                    size
+                }
+            };
+            frozen_sizes.insert(*key, size);
+        }
+        self.queue.retain(|key| self.fifo.contains_key(key));
+        // Thawed keys go behind the queued ones, coldest last
+        for key in self.frozen.iter() {
+            if let Some(size) = self.frozen_sizes.remove(key) {
+                self.fifo.insert(*key, size);
+                self.queue.push_back(*key);
+                self.fifo_size += size;
+            }
+        }
+        self.frozen = frozen;
+        self.frozen_sizes = frozen_sizes;
+        self.frozen_size = frozen_size;
+        self.counts.clear();
+    }
+
+    // Evict until `incoming` more bytes fit, from the FIFO head and, once it
+    // is empty, from the coldest frozen key
+    fn evict(&mut self, incoming: u64) {
+        while self.fifo_size + self.frozen_size + incoming > self.capacity {
+            let victim = if let Some(key) = self.queue.pop_front() {
+                let size = self.fifo.remove(&key).unwrap();
+                self.fifo_size -= size;
+                key
+            } else if let Some(key) = self.frozen.pop() {
+                let size = self.frozen_sizes.remove(&key).unwrap();
+                self.frozen_size -= size;
+                key
+            } else {
+                break;
+            };
+            if let Some(evicted) = self.evicted.as_mut() {
+                evicted.push(victim);
+            }
+        }
+    }
+}
+
+impl NewPolicy for FrozenHotPolicy {
+    fn new(capacity: u64) -> Self {
+        Self::with_params(capacity, DEFAULT_HOT, DEFAULT_REBUILD)
+    }
+}
+
+impl EvictPolicy for FrozenHotPolicy {
+    fn get(&mut self, key: Key) -> Option<()> {
+        // Every request goes through get, so it drives the rebuilds
+        self.requests += 1;
+        *self.counts.entry(key).or_default() += 1;
+        let hit = self.frozen_sizes.contains_key(&key) || self.fifo.contains_key(&key);
+        if self.requests.is_multiple_of(self.rebuild) {
+            self.rebuild_frozen();
+        }
+        hit.then_some(())
+    }
+
+    fn contains(&self, key: Key) -> bool {
+        self.frozen_sizes.contains_key(&key) || self.fifo.contains_key(&key)
+    }
+
+    fn resident(&self) -> Box<dyn Iterator<Item = (Key, u64)> + '_> {
+        Box::new(
+            self.queue.iter().map(|key| (*key, self.fifo[key])).chain(
+                self.frozen
+                    .iter()
+                    .rev()
+                    .map(|key| (*key, self.frozen_sizes[key])),
+            ),
+        )
+    }
+
+    fn put(&mut self, key: Key, size: u64) {
+        if self.contains(key) {
+            self.update_size(key, size);
+            return;
+        }
+        self.evict(size);
+        self.fifo.insert(key, size);
+        self.queue.push_back(key);
+        self.fifo_size += size;
+    }
+
+    fn remove(&mut self, key: Key) -> Option<u64> {
+        if let Some(size) = self.fifo.remove(&key) {
+            self.queue.retain(|&k| k != key);
+            self.fifo_size -= size;
+            return Some(size);
+        }
+        let size = self.frozen_sizes.remove(&key)?;
+        self.frozen.retain(|&k| k != key);
+        self.frozen_size -= size;
+        Some(size)
+    }
+
+    fn update_size(&mut self, key: Key, new_size: u64) -> bool {
+        if let Some(size) = self.fifo.get_mut(&key) {
+            self.fifo_size = self.fifo_size - *size + new_size;
+            *size = new_size;
+        } else if let Some(size) = self.frozen_sizes.get_mut(&key) {
+            self.frozen_size = self.frozen_size - *size + new_size;
+            *size = new_size;
+        } else {
+            return false;
+        }
+        self.evict(0);
+        true
+    }
+
+    fn set_capacity(&mut self, capacity: u64) {
+        self.capacity = capacity;
+        self.evict(0);
+    }
+
+    fn record_evictions(&mut self, enabled: bool) {
+        self.evicted = enabled.then(Vec::new);
+    }
+
+    fn take_evictions(&mut self) -> Vec<Key> {
+        self.evicted
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    fn debug_validate(&self) -> Result<(), String> {
+        if self.queue.len() != self.fifo.len() {
+            return Err(format!(
+                "{} keys queued but {} in the FIFO",
+                self.queue.len(),
+                self.fifo.len()
+            ));
+        }
+        if self.frozen.len() != self.frozen_sizes.len() {
+            return Err(format!(
+                "{} keys listed frozen but {} have sizes",
+                self.frozen.len(),
+                self.frozen_sizes.len()
+            ));
+        }
+        let frozen = self.frozen_sizes.values().sum::<u64>();
+        if frozen != self.frozen_size {
+            return Err(format!(
+                "frozen size is {} but its objects sum to {frozen}",
+                self.frozen_size
+            ));
+        }
+        check_accounting(
+            self.fifo_size + self.frozen_size,
+            self.capacity,
+            self.fifo
+                .values()
+                .chain(self.frozen_sizes.values())
+                .copied(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A request as MiniSim makes it: a get, and a put on a miss
+    fn request(policy: &mut FrozenHotPolicy, key: Key) {
+        if policy.get(key).is_none() {
+            policy.put(key, 10);
+        }
+    }
+
+    #[test]
+    fn frozen_set_holds_between_rebuilds_and_changes_at_them() {
+        // Half of 40 bytes frozen, rebuilt every 10 requests
+        let mut policy = FrozenHotPolicy::with_params(40, 0.5, 10);
+        policy.record_evictions(true);
+        for key in [1, 2, 3, 4, 1, 1, 1, 2, 2, 3] {
+            request(&mut policy, key);
+        }
+        assert_eq!(policy.frozen(), &[1, 2]);
+        assert!(policy.take_evictions().is_empty());
+
+        // New keys churn through the FIFO half, the frozen keys stay put
+        // although nothing requests them
+        for key in [5, 6, 7, 7, 7, 6, 6, 7, 6] {
+            request(&mut policy, key);
+            assert_eq!(policy.frozen(), &[1, 2]);
+        }
+        assert_eq!(policy.take_evictions(), vec![3, 4, 5]);
+        assert!(policy.contains(1) && policy.contains(2));
+        assert_eq!(policy.debug_validate(), Ok(()));
+
+        // The 20th request rebuilds from this interval's counts and thaws 1
+        // and 2 into the FIFO, where they are the next victims
+        request(&mut policy, 7);
+        assert_eq!(policy.frozen(), &[7, 6]);
+        assert_eq!(
+            policy.resident().collect::<Vec<_>>(),
+            vec![(1, 10), (2, 10), (6, 10), (7, 10)]
+        );
+        request(&mut policy, 8);
+        assert_eq!(policy.take_evictions(), vec![1]);
+        assert_eq!(policy.debug_validate(), Ok(()));
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use hashbrown::HashMap;
+
+use crate::Key;
+
+use super::{check_accounting, EvictPolicy, NewPolicy};
+
+// Size-only baseline: always evict the largest resident object, the oldest
+// insertion first among objects of the same size. Hits don't change the order.
+pub struct LargestFirstPolicy {
+    capacity: u64,
+    size: u64,
+    cache: HashMap<Key, u64>,
+    // Size -> keys of that size in insertion order
+    by_size: BTreeMap<u64, VecDeque<Key>>,
+    evicted: Option<Vec<Key>>,
+}
+
+impl LargestFirstPolicy {
+    // Evict the largest objects until `incoming` more bytes fit
+    fn evict(&mut self, incoming: u64) {
+        while self.size + incoming > self.capacity {
+            let Some(mut entry) = self.by_size.last_entry() else {
+                break;
+            };
+            let old_key = entry.get_mut().pop_front().unwrap();
+            if entry.get().is_empty() {
+                entry.remove();
+            }
+            if let Some(old_size) = self.cache.remove(&old_key) {
+                self.size -= old_size;
+                if let Some(evicted) = self.evicted.as_mut() {
+                    evicted.push(old_key);
+                }
+            }
+        }
+    }
+
+    fn unlink(&mut self, key: Key, size: u64) {
+        if let Some(keys) = self.by_size.get_mut(&size) {
+            keys.retain(|&k| k != key);
+            if keys.is_empty() {
+                self.by_size.remove(&size);
+            }
+        }
+    }
+}
+
+impl NewPolicy for LargestFirstPolicy {
+    fn new(capacity: u64) -> Self {
+        Self {
+            capacity,
+            size: 0,
+            cache: HashMap::new(),
+            by_size: BTreeMap::new(),
+            evicted: None,
+        }
+    }
+}
+
+impl EvictPolicy for LargestFirstPolicy {
+    fn get(&mut self, key: Key) -> Option<()> {
+        self.cache.get(&key).map(|_| ())
+    }
+
+    fn contains(&self, key: Key) -> bool {
+        self.cache.contains_key(&key)
+    }
+
+    fn resident(&self) -> Box<dyn Iterator<Item = (Key, u64)> + '_> {
+        Box::new(
+            self.by_size
+                .iter()
+                .rev()
+                .flat_map(|(size, keys)| keys.iter().map(|key| (*key, *size))),
+        )
+    }
+
+    fn put(&mut self, key: Key, size: u64) {
+        if self.cache.contains_key(&key) {
+            self.update_size(key, size);
+            return;
+        }
+        self.evict(size);
+        self.cache.insert(key, size);
+        self.by_size.entry(size).or_default().push_back(key);
+        self.size += size;
+    }
+
+    fn update_size(&mut self, key: Key, new_size: u64) -> bool {
+        let Some(&size) = self.cache.get(&key) else {
+            return false;
+        };
+        // A resized object goes behind the objects already of its new size
+        if size != new_size {
+            self.unlink(key, size);
+            self.by_size.entry(new_size).or_default().push_back(key);
+            self.cache.insert(key, new_size);
+            self.size = self.size - size + new_size;
+        }
+        self.evict(0);
+        true
+    }
+
+    fn set_capacity(&mut self, capacity: u64) {
+        self.capacity = capacity;
+        self.evict(0);
+    }
+
+    fn remove(&mut self, key: Key) -> Option<u64> {
+        let size = self.cache.remove(&key)?;
+        self.unlink(key, size);
+        self.size -= size;
+        Some(size)
+    }
+
+    fn record_evictions(&mut self, enabled: bool) {
+        self.evicted = enabled.then(Vec::new);
+    }
+
+    fn take_evictions(&mut self) -> Vec<Key> {
+        self.evicted
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    fn debug_validate(&self) -> Result<(), String> {
+        let listed = self.by_size.values().map(VecDeque::len).sum::<usize>();
+        if listed != self.cache.len() {
+            return Err(format!(
+                "{listed} keys in the size lists but {} resident",
+                self.cache.len()
+            ));
+        }
+        check_accounting(self.size, self.capacity, self.cache.values().copied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forced_evictions_take_the_largest_first_and_the_oldest_among_ties() {
+        let mut policy = LargestFirstPolicy::new(100);
+        policy.record_evictions(true);
+        for (key, size) in [(1, 10), (2, 30), (3, 20), (4, 30), (5, 10)] {
+            policy.put(key, size);
+        }
+        assert!(policy.take_evictions().is_empty());
+        // A hit doesn't save 4, and of the two 30 byte objects 2 came first
+        policy.get(4);
+        policy.put(6, 25);
+        assert_eq!(policy.take_evictions(), vec![2]);
+        policy.set_capacity(40);
+        assert_eq!(policy.take_evictions(), vec![4, 6]);
+        // 1 and 5 are the same size, 1 goes first
+        policy.set_capacity(15);
+        assert_eq!(policy.take_evictions(), vec![3, 1]);
+        assert_eq!(policy.resident().collect::<Vec<_>>(), vec![(5, 10)]);
+        assert_eq!(policy.debug_validate(), Ok(()));
+    }
+}
@@ -1,4 +1,4 @@
-use super::EvictPolicy;
+use super::{check_accounting, EvictPolicy, NewPolicy};
 use crate::Key;
 use std::collections::{BTreeMap, HashMap};
 pub struct LfuPolicy {
@@ -6,18 +6,22 @@ pub struct LfuPolicy {
     size: u64,
     key_to_freq_and_size: HashMap<Key, (u64, u64)>, // (frequency, size)
     freq_to_keys: BTreeMap<u64, Vec<Key>>,
+    evicted: Option<Vec<Key>>,
 }
 
-impl EvictPolicy for LfuPolicy {
+impl NewPolicy for LfuPolicy {
     fn new(capacity: u64) -> Self {
         LfuPolicy {
             capacity,
             size: 0,
             key_to_freq_and_size: HashMap::new(),
             freq_to_keys: BTreeMap::new(),
+            evicted: None,
         }
     }
+}
 
+impl EvictPolicy for LfuPolicy {
     fn get(&mut self, key: Key) -> Option<()> {
         if let Some((freq, _)) = self.key_to_freq_and_size.get_mut(&key) {
             // Remove key from current frequency
@@ -32,10 +36,7 @@ impl EvictPolicy for LfuPolicy {
             *freq += 1;
 
             // Add key to new frequency
-            self.freq_to_keys
-                .entry(*freq)
-                .or_insert_with(Vec::new)
-                .push(key);
+            self.freq_to_keys.entry(*freq).or_default().push(key);
 
             Some(())
         } else {
@@ -43,6 +44,18 @@ impl EvictPolicy for LfuPolicy {
         }
     }
 
+    fn contains(&self, key: Key) -> bool {
+        self.key_to_freq_and_size.contains_key(&key)
+    }
+
+    fn resident(&self) -> Box<dyn Iterator<Item = (Key, u64)> + '_> {
+        // The lowest frequency goes first, all of its keys at once
+        Box::new(self.freq_to_keys.values().flatten().map(|key| {
+            let (_, size) = self.key_to_freq_and_size[key];
+            (*key, size)
+        }))
+    }
+
     fn put(&mut self, key: Key, size: u64) {
         if self.capacity == 0 || size > self.capacity {
             return;
@@ -54,32 +67,87 @@ impl EvictPolicy for LfuPolicy {
             return;
         }
 
-        // Evict least frequently used item(s)
-        while self.size + size > self.capacity {
+        self.evict(size);
+
+        // Add new key
+        self.key_to_freq_and_size.insert(key, (1, size));
+        self.freq_to_keys.entry(1).or_default().push(key);
+        self.size += size;
+    }
+
+    fn update_size(&mut self, key: Key, new_size: u64) -> bool {
+        let Some((_, size)) = self.key_to_freq_and_size.get_mut(&key) else {
+            return false;
+        };
+        self.size = self.size - *size + new_size;
+        *size = new_size;
+        self.evict(0);
+        true
+    }
+
+    fn set_capacity(&mut self, capacity: u64) {
+        self.capacity = capacity;
+        self.evict(0);
+    }
+
+    fn remove(&mut self, key: Key) -> Option<u64> {
+        let (freq, size) = self.key_to_freq_and_size.remove(&key)?;
+        if let Some(keys) = self.freq_to_keys.get_mut(&freq) {
+            keys.retain(|&k| k != key);
+            if keys.is_empty() {
+                self.freq_to_keys.remove(&freq);
+            }
+        }
+        self.size -= size;
+        Some(size)
+    }
+
+    fn record_evictions(&mut self, enabled: bool) {
+        self.evicted = enabled.then(Vec::new);
+    }
+
+    fn take_evictions(&mut self) -> Vec<Key> {
+        self.evicted
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    fn debug_validate(&self) -> Result<(), String> {
+        let listed = self.freq_to_keys.values().map(Vec::len).sum::<usize>();
+        if listed != self.key_to_freq_and_size.len() {
+            return Err(format!(
+                "{listed} keys in the frequency lists but {} resident",
+                self.key_to_freq_and_size.len()
+            ));
+        }
+        check_accounting(
+            self.size,
+            self.capacity,
+            self.key_to_freq_and_size.values().map(|(_, size)| *size),
+        )
+    }
+}
+
+impl LfuPolicy {
+    // Evict least frequently used item(s) until `incoming` more bytes fit
+    fn evict(&mut self, incoming: u64) {
+        while self.size + incoming > self.capacity {
             if let Some((&least_freq, keys)) = self.freq_to_keys.iter_mut().next() {
-                let evicted_keys: Vec<Key> = keys.drain(..).collect();
+                let evicted_keys: Vec<Key> = std::mem::take(keys);
                 for evicted_key in evicted_keys {
                     if let Some((_, evicted_size)) = self.key_to_freq_and_size.remove(&evicted_key)
                     {
                         self.size -= evicted_size;
+                        if let Some(evicted) = self.evicted.as_mut() {
+                            evicted.push(evicted_key);
+                        }
                     }
                 }
                 self.freq_to_keys.remove(&least_freq);
             } else {
                 break; // No more items to evict
             }
-
-            if self.size + size <= self.capacity {
-                break;
-            }
         }
-
-        // Add new key
-        self.key_to_freq_and_size.insert(key, (1, size));
-        self.freq_to_keys
-            .entry(1)
-            .or_insert_with(Vec::new)
-            .push(key);
-        self.size += size;
     }
 }
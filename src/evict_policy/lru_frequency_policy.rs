@@ -0,0 +1,158 @@
+use crate::Key;
+
+use super::{check_accounting, EvictPolicy, NewPolicy};
+
+// Hits above which `new` gives a key a second chance
+const DEFAULT_THRESHOLD: u64 = 1;
+
+// LRU with a frequency based second chance: a victim at the LRU tail hit more
+// than `threshold` times since it was inserted or last spared goes back to
+// the head with its count cleared instead of being evicted. Cheaper than LFU,
+// and a scan can only push out keys that haven't proven themselves.
+pub struct LruFrequencyPolicy {
+    capacity: u64,
+    threshold: u64,
+    size: u64,
+    // Key to (size, hits since inserted or spared)
+    cache: lru::LruCache<Key, (u64, u64)>,
+    evicted: Option<Vec<Key>>,
+}
+
+impl LruFrequencyPolicy {
+    // Keys hit more than `threshold` times are spared once
+    pub fn with_threshold(capacity: u64, threshold: u64) -> Self {
+        Self {
+            capacity,
+            threshold,
+            size: 0,
+            cache: lru::LruCache::unbounded(),
+            evicted: None,
+        }
+    }
+
+    // Evict until `incoming` more bytes fit. Every spared key has its count
+    // cleared, so one pass over the cache finds a victim.
+    fn evict(&mut self, incoming: u64) {
+        while self.size + incoming > self.capacity {
+            let Some((key, (size, hits))) = self.cache.pop_lru() else {
+                break;
+            };
+            if hits > self.threshold {
+                self.cache.put(key, (size, 0));
+                continue;
+            }
+            self.size -= size;
+            if let Some(evicted) = self.evicted.as_mut() {
+                evicted.push(key);
+            }
+        }
+    }
+}
+
+impl NewPolicy for LruFrequencyPolicy {
+    fn new(capacity: u64) -> Self {
+        Self::with_threshold(capacity, DEFAULT_THRESHOLD)
+    }
+}
+
+impl EvictPolicy for LruFrequencyPolicy {
+    fn get(&mut self, key: Key) -> Option<()> {
+        let (_, hits) = self.cache.get_mut(&key)?;
+        *hits = hits.saturating_add(1);
+        Some(())
+    }
+
+    fn contains(&self, key: Key) -> bool {
+        self.cache.contains(&key)
+    }
+
+    fn resident(&self) -> Box<dyn Iterator<Item = (Key, u64)> + '_> {
+        // The keys that would be spared go after all the others, in LRU order
+        let threshold = self.threshold;
+        let tail = move |spared: bool| {
+            self.cache
+                .iter()
+                .rev()
+                .filter(move |(_, (_, hits))| (*hits > threshold) == spared)
+                .map(|(key, (size, _))| (*key, *size))
+        };
+        Box::new(tail(false).chain(tail(true)))
+    }
+
+    fn put(&mut self, key: Key, size: u64) {
+        self.remove(key);
+        self.evict(size);
+        self.cache.put(key, (size, 0));
+        self.size += size;
+    }
+
+    fn remove(&mut self, key: Key) -> Option<u64> {
+        let (size, _) = self.cache.pop(&key)?;
+        self.size -= size;
+        Some(size)
+    }
+
+    fn update_size(&mut self, key: Key, new_size: u64) -> bool {
+        let Some((size, _)) = self.cache.peek_mut(&key) else {
+            return false;
+        };
+        self.size = self.size - *size + new_size;
+        *size = new_size;
+        self.evict(0);
+        true
+    }
+
+    fn set_capacity(&mut self, capacity: u64) {
+        self.capacity = capacity;
+        self.evict(0);
+    }
+
+    fn record_evictions(&mut self, enabled: bool) {
+        self.evicted = enabled.then(Vec::new);
+    }
+
+    fn take_evictions(&mut self) -> Vec<Key> {
+        self.evicted
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    fn debug_validate(&self) -> Result<(), String> {
+        check_accounting(
+            self.size,
+            self.capacity,
+            self.cache.iter().map(|(_, (size, _))| *size),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frequent_tail_key_is_spared_once_and_rare_one_evicted() {
+        let mut policy = LruFrequencyPolicy::with_threshold(30, 1);
+        policy.record_evictions(true);
+        policy.put(1, 10);
+        policy.get(1);
+        policy.get(1);
+        policy.put(2, 10);
+        policy.put(3, 10);
+        // 1 is at the LRU tail but was hit twice, 2 behind it never
+        policy.put(4, 10);
+        assert_eq!(policy.take_evictions(), vec![2]);
+        assert!(policy.contains(1));
+        policy.put(5, 10);
+        assert_eq!(policy.take_evictions(), vec![3]);
+        // Sparing cleared its hits, the next time 1 is the victim
+        policy.put(6, 10);
+        assert_eq!(policy.take_evictions(), vec![1]);
+        assert_eq!(
+            policy.resident().map(|(key, _)| key).collect::<Vec<_>>(),
+            vec![4, 5, 6]
+        );
+        assert_eq!(policy.debug_validate(), Ok(()));
+    }
+}
@@ -1,34 +1,64 @@
-use std::num::NonZeroUsize;
-
 use crate::Key;
 
-use super::EvictPolicy;
+use super::{check_accounting, EvictPolicy, NewPolicy};
 
 // LRU (Least Recently Used) Policy implementation
 pub struct LruPolicy {
     capacity: u64,
     size: u64,
     cache: lru::LruCache<Key, u64>,
+    evicted: Option<Vec<Key>>,
 }
 
-impl EvictPolicy for LruPolicy {
+impl LruPolicy {
+    // Evict until the cache fits its capacity again
+    fn shrink(&mut self) {
+        while self.size > self.capacity {
+            if let Some((evicted_key, evicted_size)) = self.cache.pop_lru() {
+                self.size -= evicted_size;
+                if let Some(evicted) = self.evicted.as_mut() {
+                    evicted.push(evicted_key);
+                }
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+impl NewPolicy for LruPolicy {
     fn new(capacity: u64) -> Self {
         Self {
             capacity,
             size: 0,
-            cache: lru::LruCache::new(NonZeroUsize::new(capacity as usize).unwrap()),
+            // Capacity is accounted in bytes by `size`, don't let the entry count bound it
+            cache: lru::LruCache::unbounded(),
+            evicted: None,
         }
     }
+}
 
+impl EvictPolicy for LruPolicy {
     fn get(&mut self, key: Key) -> Option<()> {
         self.cache.get(&key).map(|_| ())
     }
 
+    fn contains(&self, key: Key) -> bool {
+        self.cache.contains(&key)
+    }
+
+    fn resident(&self) -> Box<dyn Iterator<Item = (Key, u64)> + '_> {
+        Box::new(self.cache.iter().rev().map(|(key, size)| (*key, *size)))
+    }
+
     fn put(&mut self, key: Key, size: u64) {
         // Evict items if necessary
         while self.size + size > self.capacity {
-            if let Some((_, evicted_size)) = self.cache.pop_lru() {
+            if let Some((evicted_key, evicted_size)) = self.cache.pop_lru() {
                 self.size -= evicted_size;
+                if let Some(evicted) = self.evicted.as_mut() {
+                    evicted.push(evicted_key);
+                }
             } else {
                 break;
             }
@@ -36,4 +66,75 @@ impl EvictPolicy for LruPolicy {
         self.cache.put(key, size);
         self.size += size;
     }
+
+    fn update_size(&mut self, key: Key, new_size: u64) -> bool {
+        let Some(size) = self.cache.peek_mut(&key) else {
+            return false;
+        };
+        self.size = self.size - *size + new_size;
+        *size = new_size;
+        self.shrink();
+        true
+    }
+
+    fn set_capacity(&mut self, capacity: u64) {
+        self.capacity = capacity;
+        self.shrink();
+    }
+
+    fn remove(&mut self, key: Key) -> Option<u64> {
+        let size = self.cache.pop(&key)?;
+        self.size -= size;
+        Some(size)
+    }
+
+    fn record_evictions(&mut self, enabled: bool) {
+        self.evicted = enabled.then(Vec::new);
+    }
+
+    fn take_evictions(&mut self) -> Vec<Key> {
+        self.evicted
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    fn debug_validate(&self) -> Result<(), String> {
+        check_accounting(
+            self.size,
+            self.capacity,
+            self.cache.iter().map(|(_, size)| *size),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resident_lists_the_least_recently_used_first() {
+        let mut policy = LruPolicy::new(30);
+        policy.record_evictions(true);
+        for key in [1, 2, 3] {
+            policy.put(key, 10);
+        }
+        assert_eq!(
+            policy.resident().collect::<Vec<_>>(),
+            vec![(1, 10), (2, 10), (3, 10)]
+        );
+        policy.get(1);
+        assert_eq!(
+            policy.resident().collect::<Vec<_>>(),
+            vec![(2, 10), (3, 10), (1, 10)]
+        );
+        // The first resident object is the next victim
+        policy.put(4, 10);
+        assert_eq!(policy.take_evictions(), vec![2]);
+        policy.remove(1);
+        assert_eq!(
+            policy.resident().collect::<Vec<_>>(),
+            vec![(3, 10), (4, 10)]
+        );
+    }
 }
@@ -0,0 +1,155 @@
+use std::collections::VecDeque;
+
+use hashbrown::HashMap;
+
+use crate::Key;
+
+use super::{EvictPolicy, NewPolicy};
+
+// Evicts every key once it has been resident for `max_residency` seconds of
+// trace time, whatever the trace TTL and the base policy say. Hits don't
+// extend residency, a key only restarts its clock when it is put again.
+pub struct MaxResidencyWrapper<E: EvictPolicy> {
+    inner: E,
+    max_residency: u64,
+    now: u64,
+    // Insertion time of every resident key
+    inserted: HashMap<Key, u64>,
+    // (insertion time, key) in insertion order, stale entries are skipped
+    queue: VecDeque<(u64, Key)>,
+    // Keys removed for their residency, when recording evictions
+    expired: Option<Vec<Key>>,
+}
+
+impl<E: EvictPolicy> MaxResidencyWrapper<E> {
+    pub fn wrap(inner: E, max_residency: u64) -> Self {
+        Self {
+            inner,
+            max_residency,
+            now: 0,
+            inserted: HashMap::new(),
+            queue: VecDeque::new(),
+            expired: None,
+        }
+    }
+}
+
+impl<E: NewPolicy> NewPolicy for MaxResidencyWrapper<E> {
+    fn new(capacity: u64) -> Self {
+        Self::wrap(E::new(capacity), u64::MAX)
+    }
+}
+
+impl<E: EvictPolicy> EvictPolicy for MaxResidencyWrapper<E> {
+    fn advance_time(&mut self, timestamp: u64) {
+        self.now = self.now.max(timestamp);
+        self.inner.advance_time(timestamp);
+        while let Some(&(inserted_at, key)) = self.queue.front() {
+            if self.now - inserted_at < self.max_residency {
+                break;
+            }
+            self.queue.pop_front();
+            if self.inserted.get(&key) == Some(&inserted_at) {
+                self.inserted.remove(&key);
+                self.inner.remove(key);
+                if let Some(expired) = self.expired.as_mut() {
+                    expired.push(key);
+                }
+            }
+        }
+    }
+
+    fn get(&mut self, key: Key) -> Option<()> {
+        self.inner.get(key)
+    }
+
+    fn contains(&self, key: Key) -> bool {
+        self.inner.contains(key)
+    }
+
+    fn resident(&self) -> Box<dyn Iterator<Item = (Key, u64)> + '_> {
+        self.inner.resident()
+    }
+
+    fn put(&mut self, key: Key, size: u64) {
+        self.inner.put(key, size);
+        if self.max_residency == u64::MAX {
+            return;
+        }
+        // Keys the base policy evicted linger in the queue until they expire
+        self.inserted.insert(key, self.now);
+        self.queue.push_back((self.now, key));
+    }
+
+    fn remove(&mut self, key: Key) -> Option<u64> {
+        self.inserted.remove(&key);
+        self.inner.remove(key)
+    }
+
+    fn update_size(&mut self, key: Key, new_size: u64) -> bool {
+        self.inner.update_size(key, new_size)
+    }
+
+    fn set_capacity(&mut self, capacity: u64) {
+        self.inner.set_capacity(capacity);
+    }
+
+    fn record_evictions(&mut self, enabled: bool) {
+        self.inner.record_evictions(enabled);
+        self.expired = enabled.then(Vec::new);
+    }
+
+    fn take_evictions(&mut self) -> Vec<Key> {
+        self.inner.take_evictions()
+    }
+
+    fn take_expirations(&mut self) -> Vec<Key> {
+        self.expired
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    fn debug_validate(&self) -> Result<(), String> {
+        self.inner.debug_validate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evict_policy::LruPolicy;
+
+    #[test]
+    fn hot_key_is_evicted_once_its_residency_is_up() {
+        let mut policy = MaxResidencyWrapper::wrap(LruPolicy::new(10), 60);
+        policy.record_evictions(true);
+        policy.advance_time(0);
+        policy.put(1, 1);
+        // Hit every 10 seconds, always the most recently used key
+        for timestamp in (10..60).step_by(10) {
+            policy.advance_time(timestamp);
+            assert_eq!(policy.get(1), Some(()));
+            if timestamp == 30 {
+                policy.put(2, 1);
+            }
+        }
+
+        policy.advance_time(60);
+        assert!(!policy.contains(1));
+        assert_eq!(policy.get(1), None);
+        assert_eq!(policy.take_expirations(), vec![1]);
+        assert!(policy.take_evictions().is_empty());
+        // Key 2 went in 30 seconds later and has 30 left
+        assert!(policy.contains(2));
+
+        // A new put restarts the clock
+        policy.put(1, 1);
+        policy.advance_time(90);
+        assert!(policy.contains(1));
+        assert!(!policy.contains(2));
+        policy.advance_time(120);
+        assert!(!policy.contains(1));
+        assert_eq!(policy.take_expirations(), vec![2, 1]);
+    }
+}
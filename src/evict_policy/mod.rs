@@ -1,16 +1,176 @@
 use crate::Key;
 
 mod fifo_policy;
+mod frozen_hot_policy;
+mod largest_first_policy;
 mod lfu_policy;
+mod lru_frequency_policy;
 mod lru_policy;
+mod max_residency;
+mod registry;
+mod slru_policy;
 mod twoq_policy;
+mod validation_wrapper;
+mod watermark;
 pub use fifo_policy::FifoPolicy;
+pub use frozen_hot_policy::FrozenHotPolicy;
+pub use largest_first_policy::LargestFirstPolicy;
 pub use lfu_policy::LfuPolicy;
+pub use lru_frequency_policy::LruFrequencyPolicy;
 pub use lru_policy::LruPolicy;
+pub use max_residency::MaxResidencyWrapper;
+pub use registry::{register_policy, registered_policy, PolicyFactory, PolicyParams};
+pub use slru_policy::SlruPolicy;
 pub use twoq_policy::TwoQPolicy;
-// Define the EvictPolicy trait
+pub use validation_wrapper::ValidationPolicy;
+pub use watermark::WatermarkWrapper;
+// Define the EvictPolicy trait. Policies are built and simulated on pool
+// workers, so they must be Send, see crate::assert_send
 pub trait EvictPolicy: Send {
-    fn new(capacity: u64) -> Self;
     fn get(&mut self, key: Key) -> Option<()>;
+    // Whether the key is resident, without counting as an access
+    fn contains(&self, key: Key) -> bool;
+    // (key, size) of every resident object in eviction order, the next
+    // victim first, as the policy would evict them with no further requests
+    fn resident(&self) -> Box<dyn Iterator<Item = (Key, u64)> + '_>;
     fn put(&mut self, key: Key, size: u64);
+    // Remove the key, returning its size if it was resident
+    fn remove(&mut self, key: Key) -> Option<u64>;
+    // Change the size of a resident key without touching its position in the
+    // eviction order, evicting as usual if the cache is now over capacity.
+    // Returns whether the key was resident.
+    fn update_size(&mut self, key: Key, new_size: u64) -> bool;
+
+    // Load a snapshot of `resident` objects before the trace starts, in
+    // eviction order with the next victim first. When they don't all fit,
+    // the policy evicts the first ones as it fills.
+    fn warm(&mut self, objects: &[(Key, u64)]) {
+        for (key, size) in objects {
+            self.put(*key, *size);
+        }
+    }
+
+    // Called with the trace timestamp before every request, for time based policies
+    fn advance_time(&mut self, _timestamp: u64) {}
+
+    // Change the capacity, evicting (and recording) as usual if the cache is now
+    // over it. Wrappers such as WatermarkWrapper rely on it, policies that keep
+    // the default ignore them.
+    fn set_capacity(&mut self, _capacity: u64) {}
+
+    // Start or stop recording the keys evicted to make room, for auditing
+    fn record_evictions(&mut self, _enabled: bool) {}
+    // Keys evicted for capacity since the last call, oldest first
+    fn take_evictions(&mut self) -> Vec<Key> {
+        Vec::new()
+    }
+    // Keys removed since the last call because they stayed too long, such as
+    // by MaxResidencyWrapper, oldest first. Recorded along with the evictions.
+    fn take_expirations(&mut self) -> Vec<Key> {
+        Vec::new()
+    }
+
+    // Check the internal accounting, run after every request with --strict-invariants
+    fn debug_validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+// Policies with a default configuration, built from the capacity alone. Boxed
+// policies have none and are built with MiniSim::new_with instead.
+pub trait NewPolicy: EvictPolicy + Sized {
+    fn new(capacity: u64) -> Self;
+}
+
+// Policies built at runtime, such as the registered ones, are simulated boxed
+impl EvictPolicy for Box<dyn EvictPolicy> {
+    fn get(&mut self, key: Key) -> Option<()> {
+        (**self).get(key)
+    }
+
+    fn contains(&self, key: Key) -> bool {
+        (**self).contains(key)
+    }
+
+    fn resident(&self) -> Box<dyn Iterator<Item = (Key, u64)> + '_> {
+        (**self).resident()
+    }
+
+    fn put(&mut self, key: Key, size: u64) {
+        (**self).put(key, size)
+    }
+
+    fn remove(&mut self, key: Key) -> Option<u64> {
+        (**self).remove(key)
+    }
+
+    fn update_size(&mut self, key: Key, new_size: u64) -> bool {
+        (**self).update_size(key, new_size)
+    }
+
+    fn warm(&mut self, objects: &[(Key, u64)]) {
+        (**self).warm(objects)
+    }
+
+    fn advance_time(&mut self, timestamp: u64) {
+        (**self).advance_time(timestamp)
+    }
+
+    fn set_capacity(&mut self, capacity: u64) {
+        (**self).set_capacity(capacity)
+    }
+
+    fn record_evictions(&mut self, enabled: bool) {
+        (**self).record_evictions(enabled)
+    }
+
+    fn take_evictions(&mut self) -> Vec<Key> {
+        (**self).take_evictions()
+    }
+
+    fn take_expirations(&mut self) -> Vec<Key> {
+        (**self).take_expirations()
+    }
+
+    fn debug_validate(&self) -> Result<(), String> {
+        (**self).debug_validate()
+    }
+}
+
+const _: () = {
+    crate::assert_send::<Box<dyn EvictPolicy>>();
+    crate::assert_send::<FifoPolicy>();
+    crate::assert_send::<FrozenHotPolicy>();
+    crate::assert_send::<LargestFirstPolicy>();
+    crate::assert_send::<LfuPolicy>();
+    crate::assert_send::<LruFrequencyPolicy>();
+    crate::assert_send::<LruPolicy>();
+    crate::assert_send::<MaxResidencyWrapper<LruPolicy>>();
+    crate::assert_send::<SlruPolicy>();
+    crate::assert_send::<TwoQPolicy>();
+    crate::assert_send::<ValidationPolicy<LruPolicy, FifoPolicy>>();
+    crate::assert_send::<WatermarkWrapper<LruPolicy>>();
+    crate::assert_send_sync::<PolicyFactory>();
+};
+
+// Shared `debug_validate` check: `size` is the sum of the resident object sizes
+// and fits in `capacity`, except when a single object larger than the cache is
+// resident on its own
+pub(crate) fn check_accounting(
+    size: u64,
+    capacity: u64,
+    resident: impl Iterator<Item = u64>,
+) -> Result<(), String> {
+    let (count, total) = resident.fold((0, 0), |(count, total), size| (count + 1, total + size));
+    if size != total {
+        return Err(format!(
+            "size is {size} but the {count} resident objects sum to {total}"
+        ));
+    }
+    if size > capacity && count > 1 {
+        return Err(format!(
+            "size {size} exceeds the capacity {capacity} with {count} resident objects"
+        ));
+    }
+    Ok(())
 }
@@ -0,0 +1,41 @@
+// Policies registered at runtime by code outside the crate, see register_policy
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, OnceLock, RwLock},
+};
+
+use super::EvictPolicy;
+
+// The parameters of a spec such as "MYPOLICY(alpha=0.5;beta=2)" keyed by
+// lowercase name, without the watermark which the simulator applies itself
+pub type PolicyParams = BTreeMap<String, f64>;
+
+// Builds one cache of the given capacity with the spec parameters
+pub type PolicyFactory = Box<dyn Fn(u64, &PolicyParams) -> Box<dyn EvictPolicy> + Send + Sync>;
+
+type Registry = RwLock<HashMap<String, Arc<PolicyFactory>>>;
+
+fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+// Make `name` usable as a --policies spec, ahead of the built-in policies of
+// the same name. Names are case insensitive like the built-in ones, a second
+// registration of a name replaces the first. Register before the config is
+// parsed, the parser only accepts names registered by then.
+pub fn register_policy(name: &str, factory: PolicyFactory) {
+    registry()
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(name.trim().to_uppercase(), Arc::new(factory));
+}
+
+// The factory registered under `name`, if any
+pub fn registered_policy(name: &str) -> Option<Arc<PolicyFactory>> {
+    registry()
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&name.trim().to_uppercase())
+        .cloned()
+}
@@ -0,0 +1,174 @@
+use crate::Key;
+
+use super::{check_accounting, EvictPolicy, NewPolicy};
+
+// Fraction of the capacity given to the protected segment by `new`
+const DEFAULT_PROTECTED: f64 = 0.8;
+
+// SLRU (Segmented LRU) Policy implementation
+// New keys enter the probationary segment, a hit there promotes the key to
+// the protected segment. Keys pushed out of the protected segment go back to
+// the head of the probationary segment, evictions come from its tail.
+pub struct SlruPolicy {
+    capacity: u64,
+    protected_fraction: f64,
+    protected_capacity: u64,
+    probation_size: u64,
+    protected_size: u64,
+    probation: lru::LruCache<Key, u64>,
+    protected: lru::LruCache<Key, u64>,
+    evicted: Option<Vec<Key>>,
+}
+
+impl SlruPolicy {
+    // `protected` is the fraction of the capacity reserved for keys hit at least twice
+    pub fn with_protected(capacity: u64, protected: f64) -> Self {
+        let protected_fraction = protected.clamp(0.0, 1.0);
+        Self {
+            capacity,
+            protected_fraction,
+            protected_capacity: (capacity as f64 * protected_fraction) as u64,
+            probation_size: 0,
+            protected_size: 0,
+            probation: lru::LruCache::unbounded(),
+            protected: lru::LruCache::unbounded(),
+            evicted: None,
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.probation_size + self.protected_size > self.capacity {
+            let victim = match self.probation.pop_lru() {
+                Some((key, size)) => {
+                    self.probation_size -= size;
+                    key
+                }
+                None => match self.protected.pop_lru() {
+                    Some((key, size)) => {
+                        self.protected_size -= size;
+                        key
+                    }
+                    None => break,
+                },
+            };
+            if let Some(evicted) = self.evicted.as_mut() {
+                evicted.push(victim);
+            }
+        }
+    }
+
+    // Move keys from the protected tail to the probationary head until the
+    // protected segment fits again
+    fn demote(&mut self) {
+        while self.protected_size > self.protected_capacity {
+            let Some((key, size)) = self.protected.pop_lru() else {
+                break;
+            };
+            self.protected_size -= size;
+            self.probation.put(key, size);
+            self.probation_size += size;
+        }
+    }
+}
+
+impl NewPolicy for SlruPolicy {
+    fn new(capacity: u64) -> Self {
+        Self::with_protected(capacity, DEFAULT_PROTECTED)
+    }
+}
+
+impl EvictPolicy for SlruPolicy {
+    fn get(&mut self, key: Key) -> Option<()> {
+        if self.protected.get(&key).is_some() {
+            return Some(());
+        }
+        let size = self.probation.pop(&key)?;
+        self.probation_size -= size;
+        self.protected.put(key, size);
+        self.protected_size += size;
+        self.demote();
+        Some(())
+    }
+
+    fn contains(&self, key: Key) -> bool {
+        self.protected.contains(&key) || self.probation.contains(&key)
+    }
+
+    fn resident(&self) -> Box<dyn Iterator<Item = (Key, u64)> + '_> {
+        Box::new(
+            self.probation
+                .iter()
+                .rev()
+                .chain(self.protected.iter().rev())
+                .map(|(key, size)| (*key, *size)),
+        )
+    }
+
+    fn put(&mut self, key: Key, size: u64) {
+        self.remove(key);
+        self.probation.put(key, size);
+        self.probation_size += size;
+        self.evict();
+    }
+
+    fn remove(&mut self, key: Key) -> Option<u64> {
+        if let Some(size) = self.probation.pop(&key) {
+            self.probation_size -= size;
+            return Some(size);
+        }
+        let size = self.protected.pop(&key)?;
+        self.protected_size -= size;
+        Some(size)
+    }
+
+    fn update_size(&mut self, key: Key, new_size: u64) -> bool {
+        if let Some(size) = self.probation.peek_mut(&key) {
+            self.probation_size = self.probation_size - *size + new_size;
+            *size = new_size;
+        } else if let Some(size) = self.protected.peek_mut(&key) {
+            self.protected_size = self.protected_size - *size + new_size;
+            *size = new_size;
+            self.demote();
+        } else {
+            return false;
+        }
+        self.evict();
+        true
+    }
+
+    fn set_capacity(&mut self, capacity: u64) {
+        self.capacity = capacity;
+        self.protected_capacity = (capacity as f64 * self.protected_fraction) as u64;
+        self.demote();
+        self.evict();
+    }
+
+    fn record_evictions(&mut self, enabled: bool) {
+        self.evicted = enabled.then(Vec::new);
+    }
+
+    fn take_evictions(&mut self) -> Vec<Key> {
+        self.evicted
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    fn debug_validate(&self) -> Result<(), String> {
+        let protected = self.protected.iter().map(|(_, size)| *size).sum::<u64>();
+        if protected != self.protected_size {
+            return Err(format!(
+                "protected size is {} but its objects sum to {protected}",
+                self.protected_size
+            ));
+        }
+        check_accounting(
+            self.probation_size + self.protected_size,
+            self.capacity,
+            self.probation
+                .iter()
+                .chain(self.protected.iter())
+                .map(|(_, size)| *size),
+        )
+    }
+}
@@ -1,7 +1,7 @@
 use crate::Key;
 use std::collections::{HashMap, VecDeque};
 
-use super::EvictPolicy;
+use super::{check_accounting, EvictPolicy, NewPolicy};
 
 pub struct TwoQPolicy {
     hot: VecDeque<Key>,
@@ -10,9 +10,10 @@ pub struct TwoQPolicy {
     capacity: u64,
     size: u64,
     key_to_size: HashMap<Key, u64>,
+    evicted: Option<Vec<Key>>,
 }
 
-impl EvictPolicy for TwoQPolicy {
+impl NewPolicy for TwoQPolicy {
     fn new(capacity: u64) -> Self {
         TwoQPolicy {
             hot: VecDeque::new(),
@@ -21,9 +22,12 @@ impl EvictPolicy for TwoQPolicy {
             capacity,
             size: 0,
             key_to_size: HashMap::new(),
+            evicted: None,
         }
     }
+}
 
+impl EvictPolicy for TwoQPolicy {
     fn get(&mut self, key: Key) -> Option<()> {
         if let Some(&idx) = self.cold_map.get(&key) {
             self.cold.remove(idx);
@@ -40,6 +44,20 @@ impl EvictPolicy for TwoQPolicy {
         Some(())
     }
 
+    fn contains(&self, key: Key) -> bool {
+        self.key_to_size.contains_key(&key)
+    }
+
+    fn resident(&self) -> Box<dyn Iterator<Item = (Key, u64)> + '_> {
+        Box::new(
+            self.hot
+                .iter()
+                .rev()
+                .chain(self.cold.iter().rev())
+                .map(|key| (*key, self.key_to_size[key])),
+        )
+    }
+
     fn put(&mut self, key: Key, size: u64) {
         if self.get(key).is_some() {
             // Key already exists, update its size
@@ -54,6 +72,9 @@ impl EvictPolicy for TwoQPolicy {
             if let Some(evicted_key) = self.evict_one() {
                 if let Some(evicted_size) = self.key_to_size.remove(&evicted_key) {
                     self.size -= evicted_size;
+                    if let Some(evicted) = self.evicted.as_mut() {
+                        evicted.push(evicted_key);
+                    }
                 }
             } else {
                 // Can't make space, don't add the new item
@@ -63,8 +84,8 @@ impl EvictPolicy for TwoQPolicy {
 
         // Add new item
         self.size += size;
-        self.key_to_size.insert(key.clone(), size);
-        self.cold.push_front(key.clone());
+        self.key_to_size.insert(key, size);
+        self.cold.push_front(key);
         self.update_cold_indices();
 
         // Move from cold to hot if necessary
@@ -75,12 +96,77 @@ impl EvictPolicy for TwoQPolicy {
             }
         }
     }
+
+    fn update_size(&mut self, key: Key, new_size: u64) -> bool {
+        let Some(size) = self.key_to_size.get_mut(&key) else {
+            return false;
+        };
+        self.size = self.size - *size + new_size;
+        *size = new_size;
+        self.shrink();
+        true
+    }
+
+    fn set_capacity(&mut self, capacity: u64) {
+        self.capacity = capacity;
+        self.shrink();
+    }
+
+    fn remove(&mut self, key: Key) -> Option<u64> {
+        let size = self.key_to_size.remove(&key)?;
+        if let Some(idx) = self.cold_map.remove(&key) {
+            self.cold.remove(idx);
+            self.update_cold_indices();
+        } else if let Some(pos) = self.hot.iter().position(|k| k == &key) {
+            self.hot.remove(pos);
+        }
+        self.size -= size;
+        Some(size)
+    }
+
+    fn record_evictions(&mut self, enabled: bool) {
+        self.evicted = enabled.then(Vec::new);
+    }
+
+    fn take_evictions(&mut self) -> Vec<Key> {
+        self.evicted
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    fn debug_validate(&self) -> Result<(), String> {
+        let queued = self.hot.len() + self.cold.len();
+        if queued != self.key_to_size.len() {
+            return Err(format!(
+                "{queued} keys queued but {} resident",
+                self.key_to_size.len()
+            ));
+        }
+        check_accounting(self.size, self.capacity, self.key_to_size.values().copied())
+    }
 }
 
 impl TwoQPolicy {
     fn update_cold_indices(&mut self) {
         for (i, key) in self.cold.iter().enumerate() {
-            self.cold_map.insert(key.clone(), i);
+            self.cold_map.insert(*key, i);
+        }
+    }
+
+    // Evict until the cache fits its capacity again
+    fn shrink(&mut self) {
+        while self.size > self.capacity {
+            if let Some(evicted_key) = self.evict_one() {
+                if let Some(evicted_size) = self.key_to_size.remove(&evicted_key) {
+                    self.size -= evicted_size;
+                    if let Some(evicted) = self.evicted.as_mut() {
+                        evicted.push(evicted_key);
+                    }
+                }
+            } else {
+                break;
+            }
         }
     }
 
@@ -0,0 +1,110 @@
+use tracing::warn;
+
+use crate::Key;
+
+use super::{EvictPolicy, NewPolicy};
+
+// Development tool: runs two policies side by side and checks they agree on
+// every hit and miss. A disagreement panics in debug builds and is logged in
+// release builds. Results, sizes and evictions are the first policy's.
+pub struct ValidationPolicy<A: EvictPolicy, B: EvictPolicy> {
+    primary: A,
+    reference: B,
+    disagreements: u64,
+}
+
+impl<A: EvictPolicy, B: EvictPolicy> ValidationPolicy<A, B> {
+    pub fn from_policies(primary: A, reference: B) -> Self {
+        Self {
+            primary,
+            reference,
+            disagreements: 0,
+        }
+    }
+
+    // Number of gets where the two policies disagreed
+    pub fn disagreements(&self) -> u64 {
+        self.disagreements
+    }
+}
+
+impl<A: NewPolicy, B: NewPolicy> NewPolicy for ValidationPolicy<A, B> {
+    fn new(capacity: u64) -> Self {
+        Self::from_policies(A::new(capacity), B::new(capacity))
+    }
+}
+
+impl<A: EvictPolicy, B: EvictPolicy> EvictPolicy for ValidationPolicy<A, B> {
+    fn get(&mut self, key: Key) -> Option<()> {
+        let primary = self.primary.get(key);
+        let reference = self.reference.get(key);
+        if primary.is_some() != reference.is_some() {
+            self.disagreements += 1;
+            let hit = |result: Option<()>| if result.is_some() { "hit" } else { "miss" };
+            let message = format!(
+                "policies disagree on key {key}: {} {}, {} {}",
+                std::any::type_name::<A>(),
+                hit(primary),
+                std::any::type_name::<B>(),
+                hit(reference)
+            );
+            if cfg!(debug_assertions) {
+                panic!("{message}");
+            }
+            warn!("{message}");
+        }
+        primary
+    }
+
+    fn contains(&self, key: Key) -> bool {
+        self.primary.contains(key)
+    }
+
+    fn resident(&self) -> Box<dyn Iterator<Item = (Key, u64)> + '_> {
+        self.primary.resident()
+    }
+
+    fn advance_time(&mut self, timestamp: u64) {
+        self.primary.advance_time(timestamp);
+        self.reference.advance_time(timestamp);
+    }
+
+    fn put(&mut self, key: Key, size: u64) {
+        self.primary.put(key, size);
+        self.reference.put(key, size);
+    }
+
+    fn remove(&mut self, key: Key) -> Option<u64> {
+        self.reference.remove(key);
+        self.primary.remove(key)
+    }
+
+    fn update_size(&mut self, key: Key, new_size: u64) -> bool {
+        self.reference.update_size(key, new_size);
+        self.primary.update_size(key, new_size)
+    }
+
+    fn set_capacity(&mut self, capacity: u64) {
+        self.primary.set_capacity(capacity);
+        self.reference.set_capacity(capacity);
+    }
+
+    fn record_evictions(&mut self, enabled: bool) {
+        self.primary.record_evictions(enabled);
+    }
+
+    fn take_evictions(&mut self) -> Vec<Key> {
+        self.primary.take_evictions()
+    }
+
+    fn take_expirations(&mut self) -> Vec<Key> {
+        self.primary.take_expirations()
+    }
+
+    fn debug_validate(&self) -> Result<(), String> {
+        self.primary.debug_validate()?;
+        self.reference
+            .debug_validate()
+            .map_err(|e| format!("reference policy: {e}"))
+    }
+}
@@ -0,0 +1,156 @@
+use crate::Key;
+
+use super::{EvictPolicy, NewPolicy};
+
+// Once the base policy has to evict, keep evicting until the cache is down to
+// `watermark` of its capacity, like caches that free space in batches between
+// a high and a low watermark. A watermark of 1.0 behaves like the base policy.
+// The base policy must support `set_capacity` and eviction recording.
+pub struct WatermarkWrapper<E: EvictPolicy> {
+    inner: E,
+    capacity: u64,
+    watermark: f64,
+    // Whether the caller asked for evictions, the base policy always records them
+    recording: bool,
+    evicted: Vec<Key>,
+}
+
+impl<E: EvictPolicy> WatermarkWrapper<E> {
+    pub fn wrap(mut inner: E, capacity: u64, watermark: f64) -> Self {
+        inner.record_evictions(true);
+        Self {
+            inner,
+            capacity,
+            watermark: watermark.clamp(0.0, 1.0),
+            recording: false,
+            evicted: Vec::new(),
+        }
+    }
+
+    // After an operation that evicted, drain down to the low watermark
+    fn drain(&mut self) {
+        let mut evicted = self.inner.take_evictions();
+        if !evicted.is_empty() && self.watermark < 1.0 {
+            let low = (self.capacity as f64 * self.watermark) as u64;
+            self.inner.set_capacity(low);
+            self.inner.set_capacity(self.capacity);
+            evicted.extend(self.inner.take_evictions());
+        }
+        if self.recording {
+            self.evicted.extend(evicted);
+        }
+    }
+}
+
+impl<E: NewPolicy> NewPolicy for WatermarkWrapper<E> {
+    fn new(capacity: u64) -> Self {
+        Self::wrap(E::new(capacity), capacity, 1.0)
+    }
+}
+
+impl<E: EvictPolicy> EvictPolicy for WatermarkWrapper<E> {
+    fn get(&mut self, key: Key) -> Option<()> {
+        self.inner.get(key)
+    }
+
+    fn contains(&self, key: Key) -> bool {
+        self.inner.contains(key)
+    }
+
+    fn resident(&self) -> Box<dyn Iterator<Item = (Key, u64)> + '_> {
+        self.inner.resident()
+    }
+
+    fn put(&mut self, key: Key, size: u64) {
+        self.inner.put(key, size);
+        self.drain();
+    }
+
+    fn remove(&mut self, key: Key) -> Option<u64> {
+        self.inner.remove(key)
+    }
+
+    fn update_size(&mut self, key: Key, new_size: u64) -> bool {
+        let resident = self.inner.update_size(key, new_size);
+        self.drain();
+        resident
+    }
+
+    fn advance_time(&mut self, timestamp: u64) {
+        self.inner.advance_time(timestamp);
+    }
+
+    fn set_capacity(&mut self, capacity: u64) {
+        self.capacity = capacity;
+        self.inner.set_capacity(capacity);
+        self.drain();
+    }
+
+    fn record_evictions(&mut self, enabled: bool) {
+        self.recording = enabled;
+        self.evicted.clear();
+    }
+
+    fn take_evictions(&mut self) -> Vec<Key> {
+        std::mem::take(&mut self.evicted)
+    }
+
+    fn take_expirations(&mut self) -> Vec<Key> {
+        self.inner.take_expirations()
+    }
+
+    fn debug_validate(&self) -> Result<(), String> {
+        self.inner.debug_validate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{evict_policy::LruPolicy, synthetic::ZipfTrace, AccessRecord};
+
+    fn trace() -> Vec<AccessRecord> {
+        ZipfTrace {
+            requests: 50_000,
+            keys: 5_000,
+            alpha: 0.9,
+            min_size: 100,
+            max_size: 100_000,
+            seed: 7,
+        }
+        .generate()
+    }
+
+    // Misses of `policy` over the trace, checking the resident bytes after
+    // every insertion when it records evictions
+    fn misses(mut policy: impl EvictPolicy, capacity: u64, watermark: f64) -> u64 {
+        policy.record_evictions(true);
+        let mut misses = 0;
+        for record in trace().iter() {
+            if policy.get(record.key).is_some() {
+                continue;
+            }
+            misses += 1;
+            policy.put(record.key, record.size as u64);
+            let resident = policy.resident().map(|(_, size)| size).sum::<u64>();
+            assert!(resident <= capacity);
+            if !policy.take_evictions().is_empty() {
+                assert!(resident as f64 <= capacity as f64 * watermark);
+            }
+        }
+        misses
+    }
+
+    #[test]
+    fn lower_watermark_misses_more_on_mixed_sizes() {
+        let capacity = 20_000_000;
+        let watermarked = |watermark| {
+            let policy = WatermarkWrapper::wrap(LruPolicy::new(capacity), capacity, watermark);
+            misses(policy, capacity, watermark)
+        };
+        let full = watermarked(1.0);
+        assert_eq!(full, misses(LruPolicy::new(capacity), capacity, 1.0));
+        let batched = watermarked(0.9);
+        assert!(batched > full, "{batched} vs {full}");
+    }
+}
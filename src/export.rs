@@ -0,0 +1,358 @@
+use std::{fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    analysis::MissRatioCurve,
+    config::{CapacityUnit, FigureOptions, ShardsWeighting, TimeSource},
+    SimError, SimulationResult,
+};
+
+// The curves of one run as raw counters, written with --export json and read
+// back by `combine`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunExport {
+    pub trace: String,
+    pub trace_checksum: Option<String>,
+    // Exports this one was combined from, empty for a simulated run
+    #[serde(default)]
+    pub sources: Vec<String>,
+    // How the standard errors of the points were estimated, when they were
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_estimate: Option<String>,
+    // How the SHARDS curves picked their keys, when some are sampled, their
+    // counters are scaled up from the sample
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shards_weighting: Option<ShardsWeighting>,
+    // The sampled hits each point of the figures was smoothed to, with
+    // --smooth-min-samples. The points here are the raw ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub smooth_min_samples: Option<u64>,
+    // The clock TTLs, --max-residency and the other time based options ran
+    // on, when any was set, see --time-source
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_source: Option<TimeSource>,
+    // The theme, preset and [plot] settings the figures were drawn with,
+    // `combine` draws with them unless it is given its own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub figure: Option<FigureOptions>,
+    pub curves: Vec<CurveExport>,
+    // SHARDS curves left out because their sample was too small to mean much
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub undersampled: Vec<UndersampledExport>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CurveExport {
+    pub label: String,
+    pub capacity_unit: CapacityUnit,
+    pub dashed: bool,
+    pub requests: u64,
+    // Distinct keys and requests the SHARDS sample admitted, for a sampled curve
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sampled_keys: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sampled_requests: Option<u64>,
+    pub points: Vec<PointExport>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UndersampledExport {
+    pub label: String,
+    pub rate: f64,
+    pub sampled_keys: u64,
+    pub sampled_requests: u64,
+}
+
+impl UndersampledExport {
+    // The entry of a curve skipped for its sample, None for other failures
+    pub fn from_error(error: &SimError) -> Option<Self> {
+        match error {
+            SimError::Undersampled {
+                label,
+                rate,
+                sampled,
+                ..
+            } => Some(UndersampledExport {
+                label: label.clone(),
+                rate: *rate,
+                sampled_keys: sampled.keys,
+                sampled_requests: sampled.requests,
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PointExport {
+    pub size: f64,
+    pub hits: u64,
+    pub misses: u64,
+    // Standard error of the miss ratio, with --segments
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stderr: Option<f64>,
+    // Hits the SHARDS sample counted at this size, before scaling up
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sampled_hits: Option<u64>,
+    // Bytes admitted, evicted and expired at this size, with --churn
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admitted_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub evicted_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expired_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admission_ratio: Option<f64>,
+}
+
+impl CurveExport {
+    pub fn from_result(result: &SimulationResult) -> Self {
+        let points = result
+            .points
+            .iter()
+            .enumerate()
+            .map(|(i, (size, miss_ratio))| {
+                let misses = (miss_ratio * result.requests as f64).round() as u64;
+                let churn = result.churn.get(i);
+                PointExport {
+                    size: *size,
+                    hits: result.requests.saturating_sub(misses),
+                    misses,
+                    stderr: result.stderr.get(i).copied(),
+                    sampled_hits: result.sampled_hits.get(i).copied(),
+                    admitted_bytes: churn.map(|churn| churn.admitted_bytes.round() as u64),
+                    evicted_bytes: churn.map(|churn| churn.evicted_bytes.round() as u64),
+                    expired_bytes: churn.map(|churn| churn.expired_bytes.round() as u64),
+                    admission_ratio: churn.map(|churn| churn.admission_ratio),
+                }
+            })
+            .collect();
+        CurveExport {
+            label: result.label.clone(),
+            capacity_unit: result.capacity_unit,
+            dashed: result.dashed,
+            requests: result.requests,
+            sampled_keys: result.sampled.map(|sampled| sampled.keys),
+            sampled_requests: result.sampled.map(|sampled| sampled.requests),
+            points,
+        }
+    }
+
+    fn miss_ratio_curve(&self) -> MissRatioCurve {
+        let requests = self.requests.max(1) as f64;
+        MissRatioCurve::new(
+            self.points
+                .iter()
+                .map(|point| (point.size, point.misses as f64 / requests))
+                .collect(),
+        )
+    }
+}
+
+// How a simulated curve compares with the golden curve of the same label
+#[derive(Debug, Clone)]
+pub struct GoldenCheck {
+    pub label: String,
+    // Largest difference between the two miss ratios at a size
+    pub max_deviation: f64,
+    // Why the curve doesn't match, None when it does
+    pub mismatch: Option<String>,
+}
+
+// Compare every curve of `golden` with the result of the same label. They
+// match when they are on the same sizes in the same unit and no miss ratio is
+// off by more than `tolerance`.
+pub fn check_golden(
+    results: &[SimulationResult],
+    golden: &RunExport,
+    tolerance: f64,
+) -> Vec<GoldenCheck> {
+    golden
+        .curves
+        .iter()
+        .map(|expected| {
+            let mut check = GoldenCheck {
+                label: expected.label.clone(),
+                max_deviation: 0.0,
+                mismatch: None,
+            };
+            let Some(result) = results.iter().find(|result| result.label == expected.label) else {
+                check.mismatch = Some("no such curve was simulated".to_string());
+                return check;
+            };
+            if result.capacity_unit != expected.capacity_unit {
+                check.mismatch = Some(format!(
+                    "simulated in {}, the golden curve is in {}",
+                    result.capacity_unit, expected.capacity_unit
+                ));
+                return check;
+            }
+            if result.points.len() != expected.points.len()
+                || result
+                    .points
+                    .iter()
+                    .zip(expected.points.iter())
+                    .any(|((size, _), point)| *size != point.size)
+            {
+                check.mismatch = Some(format!(
+                    "simulated on {} sizes, not on the {} of the golden curve",
+                    result.points.len(),
+                    expected.points.len()
+                ));
+                return check;
+            }
+            // Both as exported, so that an identical run matches exactly
+            let simulated = CurveExport::from_result(result);
+            let miss_ratio = |curve: &CurveExport, point: &PointExport| {
+                point.misses as f64 / curve.requests.max(1) as f64
+            };
+            for (point, golden_point) in simulated.points.iter().zip(expected.points.iter()) {
+                let (miss_ratio, golden) = (
+                    miss_ratio(&simulated, point),
+                    miss_ratio(expected, golden_point),
+                );
+                let deviation = (miss_ratio - golden).abs();
+                if deviation > tolerance && check.mismatch.is_none() {
+                    check.mismatch = Some(format!(
+                        "miss ratio {miss_ratio:.6} at size {}, the golden curve has {golden:.6}",
+                        point.size
+                    ));
+                }
+                check.max_deviation = check.max_deviation.max(deviation);
+            }
+            check
+        })
+        .collect()
+}
+
+pub fn write_json_export(export: &RunExport, path: &Path) -> std::io::Result<()> {
+    fs::write(path, serde_json::to_string_pretty(export)?)
+}
+
+pub fn read_json_export(path: &Path) -> Result<RunExport, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("failed to read {path:?}: {e}"))?;
+    serde_json::from_str(&content).map_err(|e| format!("{path:?} is not a cache_mrc export: {e}"))
+}
+
+// Sum the counters of the curves with the same label across the named
+// exports, weighting every run by its request count. Curves on another size
+// grid than the first export's are resampled onto it. Returns the combined
+// curves and what was resampled or extrapolated on the way.
+pub fn combine_exports(
+    exports: &[(String, RunExport)],
+) -> Result<(Vec<SimulationResult>, Vec<String>), String> {
+    let Some(((first_name, first), others)) = exports.split_first() else {
+        return Err("nothing to combine".to_string());
+    };
+    let mut notes = Vec::new();
+    if exports
+        .iter()
+        .any(|(_, export)| export.error_estimate.is_some())
+    {
+        notes.push("Dropped the standard errors, they don't combine across runs".to_string());
+    }
+    if exports.iter().any(|(_, export)| {
+        export.curves.iter().any(|curve| {
+            curve
+                .points
+                .iter()
+                .any(|point| point.sampled_hits.is_some())
+        })
+    }) {
+        notes.push("Dropped the sampled hit counts, the combined points have none".to_string());
+    }
+    if exports.iter().any(|(_, export)| {
+        export.curves.iter().any(|curve| {
+            curve
+                .points
+                .iter()
+                .any(|point| point.admitted_bytes.is_some())
+        })
+    }) {
+        notes.push("Dropped the admitted and evicted bytes, they aren't combined".to_string());
+    }
+    for (name, export) in others {
+        if let Some(curve) = export
+            .curves
+            .iter()
+            .find(|curve| !first.curves.iter().any(|c| c.label == curve.label))
+        {
+            return Err(format!(
+                "{name} has a {} curve that {first_name} doesn't have",
+                curve.label
+            ));
+        }
+    }
+    let mut results = Vec::new();
+    for base in first.curves.iter() {
+        let mut requests = base.requests;
+        let mut misses = base
+            .points
+            .iter()
+            .map(|point| point.misses)
+            .collect::<Vec<_>>();
+        for (name, export) in others {
+            let curve = export
+                .curves
+                .iter()
+                .find(|curve| curve.label == base.label)
+                .ok_or_else(|| format!("{name} has no {} curve", base.label))?;
+            if curve.capacity_unit != base.capacity_unit {
+                return Err(format!(
+                    "the {} curve is in {} in {first_name} but in {} in {name}",
+                    base.label, base.capacity_unit, curve.capacity_unit
+                ));
+            }
+            requests += curve.requests;
+            let same_grid = curve.points.len() == base.points.len()
+                && curve
+                    .points
+                    .iter()
+                    .zip(base.points.iter())
+                    .all(|(a, b)| a.size == b.size);
+            if same_grid {
+                for (total, point) in misses.iter_mut().zip(curve.points.iter()) {
+                    *total += point.misses;
+                }
+                continue;
+            }
+            let mrc = curve.miss_ratio_curve();
+            let mut clamped = 0;
+            for (total, point) in misses.iter_mut().zip(base.points.iter()) {
+                let (miss_ratio, outside) = mrc.lookup(point.size as u64);
+                clamped += outside as usize;
+                *total += (miss_ratio * curve.requests as f64).round() as u64;
+            }
+            notes.push(format!(
+                "Resampled the {} curve of {name} onto the sizes of {first_name}{}",
+                base.label,
+                if clamped > 0 {
+                    format!(", {clamped} sizes outside its range took its nearest point")
+                } else {
+                    String::new()
+                }
+            ));
+        }
+        let points = base
+            .points
+            .iter()
+            .zip(misses)
+            .map(|(point, misses)| (point.size, misses as f64 / requests.max(1) as f64))
+            .collect();
+        results.push(SimulationResult {
+            points,
+            label: base.label.clone(),
+            capacity_unit: base.capacity_unit,
+            dashed: base.dashed,
+            requests,
+            passes: Vec::new(),
+            stderr: Vec::new(),
+            sampled_hits: Vec::new(),
+            churn: Vec::new(),
+            sampled: None,
+            evictions: Vec::new(),
+        });
+    }
+    Ok((results, notes))
+}
@@ -0,0 +1,2373 @@
+use analysis::Footprint;
+use cancel::CancellationToken;
+use config::{AdaptiveConfig, CapacityUnit, EvictionPolicy, InnerConfig, ParallelStrategy};
+use draw::{draw_lines, draw_separate};
+use error::SimError;
+use evict_policy::{
+    registered_policy, EvictPolicy, FifoPolicy, FrozenHotPolicy, LargestFirstPolicy, LfuPolicy,
+    LruFrequencyPolicy, LruPolicy, MaxResidencyWrapper, NewPolicy, SlruPolicy, TwoQPolicy,
+    WatermarkWrapper,
+};
+use hashbrown::HashSet;
+use minisim::{MiniSim, ProbeSelector};
+use rayon::prelude::*;
+use shards::{ShardsFixedRate, ShardsSizeWeighted};
+use std::{
+    any::Any,
+    ops::Range,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    sync::Arc,
+    time::Instant,
+};
+use tracing::{debug, error, info, warn};
+
+pub mod affinity;
+pub mod analysis;
+pub mod anonymize;
+pub mod attribution;
+pub mod audit;
+pub mod cancel;
+pub mod cli;
+pub mod config;
+pub mod draw;
+pub mod error;
+pub mod evict_policy;
+pub mod export;
+pub mod memory;
+pub mod minisim;
+pub mod over_time;
+#[cfg(feature = "html")]
+pub mod report;
+pub mod shards;
+pub mod size_classes;
+pub mod synthetic;
+pub mod theme;
+pub mod timing;
+
+pub const NUM_CACHE_SIZE: u64 = 100;
+// Points in the coarse pass of an adaptive curve
+pub const COARSE_POINTS: usize = 16;
+// Records simulated between two checks of the cancellation token
+const CANCEL_CHECK_INTERVAL: usize = 1024;
+// Records below which --parallel-strategy auto keeps one job per curve
+const MIN_SPLIT_RECORDS: usize = 100_000;
+// Bytes of extra seen key copies --parallel-strategy auto accepts when splitting,
+// and of seen keys of the running curves before it nests the SHARDS rates
+const MAX_SPLIT_KEY_BYTES: u64 = 1 << 30;
+
+// Compile time checks of the thread-safety contracts, next to each trait:
+// policies and SHARDS samplers move onto pool workers and must be Send,
+// cancellation tokens and policy factories are shared by them and must be
+// Send + Sync. A type breaking its contract fails here, not where it is used.
+pub(crate) const fn assert_send<T: Send + ?Sized>() {}
+pub(crate) const fn assert_send_sync<T: Send + Sync + ?Sized>() {}
+pub type Key = u64;
+
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+pub struct AccessRecord {
+    pub timestamp: u64,
+    pub command: u8,
+    pub key: u64,
+    pub size: u32,
+    // -1 in the trace is stored as u32::MAX, both it and 0 mean never expire
+    #[serde(deserialize_with = "config::deserialize_ttl")]
+    pub ttl: u32,
+}
+
+// Bytes one cache size admitted, evicted and expired over the trace, with --churn
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Churn {
+    pub admitted_bytes: f64,
+    pub evicted_bytes: f64,
+    // Removed by their TTL rather than evicted to make room
+    #[serde(default)]
+    pub expired_bytes: f64,
+    // Bytes admitted per byte requested, the write amplification a flash
+    // cache pays for its hit ratio
+    pub admission_ratio: f64,
+}
+
+// Keys and requests a SHARDS simulation sampled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SampleCounts {
+    pub keys: u64,
+    pub requests: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationResult {
+    pub points: Vec<(f64, f64)>,
+    pub label: String,
+    pub capacity_unit: CapacityUnit,
+    // Drawn as a dashed line, for derived curves such as the virtual best
+    pub dashed: bool,
+    // Requests in the trace, to turn miss ratios back into counts
+    pub requests: u64,
+    // Adaptive pass each point came from (0 is the coarse pass), empty for a uniform grid
+    pub passes: Vec<usize>,
+    // Standard error of each point's miss ratio across --segments of the
+    // trace, empty without them
+    pub stderr: Vec<f64>,
+    // Sampled hits behind each point's miss ratio, empty for an exact curve
+    pub sampled_hits: Vec<u64>,
+    // What SHARDS sampled for the curve, None for an exact curve
+    pub sampled: Option<SampleCounts>,
+    // Bytes admitted and evicted at each point, empty without --churn
+    pub churn: Vec<Churn>,
+    // What left the audited cache and why, empty outside the replay of
+    // audit::policy_evictions
+    pub evictions: Vec<audit::Eviction>,
+}
+
+impl SimulationResult {
+    // The points as a curve that can be queried between grid sizes
+    pub fn miss_ratio_curve(&self) -> analysis::MissRatioCurve {
+        analysis::MissRatioCurve::new(self.points.clone())
+    }
+}
+
+// Replay the trace through `sim` into the curve labeled `label`. Returns
+// SimError::Cancelled with the curve so far once the simulator's
+// cancellation token is cancelled
+pub fn simulation<P: EvictPolicy>(
+    access_records: Arc<Vec<AccessRecord>>,
+    mut sim: MiniSim<P>,
+    label: String,
+    capacity_unit: CapacityUnit,
+) -> Result<SimulationResult, SimError> {
+    #[cfg(feature = "timing")]
+    sim.enable_timing();
+    let memory = memory::PeakTracker::start();
+    let start = std::time::Instant::now();
+    for (i, access) in access_records.iter().enumerate() {
+        if i % CANCEL_CHECK_INTERVAL == 0 && sim.is_cancelled() {
+            let partial = (i > 0 && sim.undersampled().is_none()).then(|| {
+                Box::new(SimulationResult {
+                    points: sim.curve(),
+                    label: label.clone(),
+                    capacity_unit,
+                    dashed: false,
+                    requests: i as u64,
+                    passes: Vec::new(),
+                    stderr: sim.standard_errors(),
+                    sampled_hits: sim.sampled_hits(),
+                    churn: sim.churn(),
+                    sampled: sim.sample_counts(),
+                    evictions: Vec::new(),
+                })
+            });
+            return Err(SimError::Cancelled { label, partial });
+        }
+        sim.handle(access);
+    }
+    if let Some(rate) = sim.undersampled() {
+        return Err(SimError::Undersampled {
+            label,
+            rate,
+            sampled: sim.sample_counts().unwrap(),
+            requests: access_records.len() as u64,
+        });
+    }
+    let points = sim.curve();
+    let elapsed = start.elapsed();
+    info!(
+        "{label} simulation took {elapsed:?}, working set {} bytes",
+        sim.expected_working_set_size()
+    );
+    #[cfg(feature = "timing")]
+    if let Some(report) = sim.timing_report() {
+        info!("{label} policy timing: {report:?}");
+    }
+    if let Some(peak) = memory.peak() {
+        info!("{label} allocated at most {peak} bytes while simulating");
+    }
+    if let Some(written) = sim.write_timeline() {
+        match written {
+            Ok((cache_size, path)) => {
+                info!("Wrote the {label} timeline at cache size {cache_size} to {path:?}")
+            }
+            Err(e) => warn!("Failed to write the {label} timeline: {e}"),
+        }
+    }
+    if let Some((cache_size, path)) = sim.contents_dump() {
+        match sim.dump_contents(*cache_size, path) {
+            Ok(dumped) => info!("Wrote the {label} contents at cache size {dumped} to {path:?}"),
+            Err(e) => warn!("Failed to write the {label} contents to {path:?}: {e}"),
+        }
+    }
+    if sim.probe_count() > 0 {
+        let probe_curve = sim.probe_curve();
+        let (first, last) = (probe_curve[0], probe_curve[probe_curve.len() - 1]);
+        info!(
+            "{label} probed {} requests without changing the cache, probe miss ratio {:.4} at {} to {:.4} at {}",
+            sim.probe_count(),
+            first.1,
+            first.0,
+            last.1,
+            last.0
+        );
+        debug!("{label} probe curve: {probe_curve:?}");
+    }
+    if sim.bypassed() > 0 {
+        info!(
+            "{label} bypassed {:.2}% of requests for keys outside the top keys",
+            sim.bypassed() as f64 * 100.0 / sim.access_count().max(1) as f64
+        );
+    }
+    Ok(SimulationResult {
+        points,
+        label,
+        capacity_unit,
+        dashed: false,
+        requests: access_records.len() as u64,
+        passes: Vec::new(),
+        stderr: sim.standard_errors(),
+        sampled_hits: sim.sampled_hits(),
+        churn: sim.churn(),
+        sampled: sim.sample_counts(),
+        evictions: sim.take_evictions(),
+    })
+}
+
+// Simulate a coarse log-spaced grid, then repeatedly add points where the
+// curve bends the most. `build` creates a simulator for the given sizes.
+pub fn simulation_adaptive<P: EvictPolicy>(
+    access_records: Arc<Vec<AccessRecord>>,
+    build: impl Fn(Vec<u64>) -> Result<MiniSim<P>, SimError>,
+    adaptive: AdaptiveConfig,
+    max_cache_size: u64,
+    label: String,
+    capacity_unit: CapacityUnit,
+) -> Result<SimulationResult, SimError> {
+    let coarse = adaptive.point_budget.min(COARSE_POINTS);
+    let sizes = analysis::log_spaced(max_cache_size / 1000, max_cache_size, coarse);
+    let mut result = simulation(
+        access_records.clone(),
+        build(sizes)?,
+        format!("{label} (coarse)"),
+        capacity_unit,
+    )?;
+    result.label = label;
+    result.passes = vec![0; result.points.len()];
+
+    let per_round = (adaptive.point_budget - result.points.len()) / adaptive.refine_rounds.max(1);
+    for pass in 1..=adaptive.refine_rounds {
+        let sizes = analysis::refine_sizes(&result.points, per_round);
+        if sizes.is_empty() {
+            break;
+        }
+        // A cancelled refinement keeps the passes that completed
+        let refined = match simulation(
+            access_records.clone(),
+            build(sizes)?,
+            format!("{} (pass {pass})", result.label),
+            capacity_unit,
+        ) {
+            Err(SimError::Cancelled { .. }) => {
+                return Err(SimError::Cancelled {
+                    label: result.label.clone(),
+                    partial: Some(Box::new(result)),
+                })
+            }
+            refined => refined?,
+        };
+        // Every pass samples and counts alike, so either all points have
+        // sampled hits (or churn) or none
+        let (len, refined_len) = (result.points.len(), refined.points.len());
+        let mut points = result
+            .points
+            .into_iter()
+            .zip(result.passes)
+            .zip(padded(result.sampled_hits, len).zip(padded(result.churn, len)))
+            .chain(refined.points.into_iter().map(|point| (point, pass)).zip(
+                padded(refined.sampled_hits, refined_len).zip(padded(refined.churn, refined_len)),
+            ))
+            .collect::<Vec<_>>();
+        points.sort_by(|a, b| a.0 .0 .0.total_cmp(&b.0 .0 .0));
+        let (points, counts): (Vec<_>, Vec<_>) = points.into_iter().unzip();
+        let (sampled_hits, churn): (Vec<_>, Vec<_>) = counts.into_iter().unzip();
+        (result.points, result.passes) = points.into_iter().unzip();
+        result.sampled_hits = sampled_hits
+            .into_iter()
+            .collect::<Option<_>>()
+            .unwrap_or_default();
+        result.churn = churn.into_iter().collect::<Option<_>>().unwrap_or_default();
+    }
+    info!(
+        "{} adaptive curve: {} points, knee near {:?}",
+        result.label,
+        result.points.len(),
+        analysis::max_curvature_interval(&result.points)
+    );
+    Ok(result)
+}
+
+// `values` then None, `len` items in all
+fn padded<T>(values: Vec<T>, len: usize) -> impl Iterator<Item = Option<T>> {
+    values
+        .into_iter()
+        .map(Some)
+        .chain(std::iter::repeat_with(|| None))
+        .take(len)
+}
+
+// Simulate one curve split over several simulators, each covering a run of
+// its sizes, and merge their points under `label`
+pub fn simulation_split<P: EvictPolicy + Send>(
+    access_records: Arc<Vec<AccessRecord>>,
+    sims: Vec<(MiniSim<P>, String)>,
+    label: String,
+    capacity_unit: CapacityUnit,
+) -> Result<SimulationResult, SimError> {
+    let outcomes = sims
+        .into_par_iter()
+        .map(|(sim, chunk_label)| {
+            simulation(access_records.clone(), sim, chunk_label, capacity_unit)
+        })
+        .collect::<Vec<_>>();
+    let mut points = Vec::new();
+    let mut stderr = Vec::new();
+    let mut sampled_hits = Vec::new();
+    let mut churn = Vec::new();
+    let mut evictions = Vec::new();
+    // Every simulator samples the same keys
+    let mut sampled = None;
+    let mut requests = access_records.len() as u64;
+    let mut cancelled = false;
+    for outcome in outcomes {
+        match outcome {
+            Ok(result) => {
+                points.extend(result.points);
+                stderr.extend(result.stderr);
+                sampled_hits.extend(result.sampled_hits);
+                churn.extend(result.churn);
+                sampled = sampled.or(result.sampled);
+                evictions.extend(result.evictions);
+            }
+            // The merged partial curve covers what every simulator reached
+            Err(SimError::Cancelled { partial, .. }) => {
+                cancelled = true;
+                match partial {
+                    Some(partial) => {
+                        requests = requests.min(partial.requests);
+                        points.extend(partial.points);
+                        stderr.extend(partial.stderr);
+                        sampled_hits.extend(partial.sampled_hits);
+                        churn.extend(partial.churn);
+                        sampled = sampled.or(partial.sampled);
+                    }
+                    None => requests = 0,
+                }
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    // Chunks cancelled before their second segment have no errors
+    if stderr.len() != points.len() {
+        stderr.clear();
+    }
+    if sampled_hits.len() != points.len() {
+        sampled_hits.clear();
+    }
+    if churn.len() != points.len() {
+        churn.clear();
+    }
+    let result = SimulationResult {
+        points,
+        label,
+        capacity_unit,
+        dashed: false,
+        requests,
+        passes: Vec::new(),
+        stderr,
+        sampled_hits,
+        sampled,
+        churn,
+        evictions,
+    };
+    if cancelled {
+        let partial = (requests > 0).then(|| Box::new(result.clone()));
+        return Err(SimError::Cancelled {
+            label: result.label,
+            partial,
+        });
+    }
+    Ok(result)
+}
+
+// Cache sizes one simulator covers
+enum SimSizes {
+    // Points of the uniform grid up to the maximum cache size
+    Grid(Range<u64>),
+    Listed(Vec<u64>),
+}
+
+// One curve to simulate: a policy at one capacity unit and sample rate
+struct SimulationRun {
+    label: String,
+    unit: CapacityUnit,
+    sample_rate: Option<f64>,
+    max_cache_size: u64,
+    // Sizes to simulate instead of the grid up to `max_cache_size`
+    cache_sizes: Option<Vec<u64>>,
+    // Simulators the sizes are split over, see ParallelStrategy
+    jobs_per_curve: usize,
+    cancel: CancellationToken,
+    // Configured size past the footprint the sweep was capped at, reported
+    // as one more point at the miss ratio of the largest simulated size
+    floor_at: Option<u64>,
+    // Of the trace, what size-weighted SHARDS samples relative to
+    mean_object_size: f64,
+    // Count the LRU hits from stack distances, see MiniSim::with_lru_stack
+    lru_stack: bool,
+}
+
+// Extend the curve flat to `size`, beyond the footprint nothing changes
+fn extend_floor(mut result: SimulationResult, size: u64) -> SimulationResult {
+    if let Some(&(_, miss_ratio)) = result.points.last() {
+        result.points.push((size as f64, miss_ratio));
+        if let Some(&pass) = result.passes.last() {
+            result.passes.push(pass);
+        }
+        if let Some(&stderr) = result.stderr.last() {
+            result.stderr.push(stderr);
+        }
+        if let Some(&sampled_hits) = result.sampled_hits.last() {
+            result.sampled_hits.push(sampled_hits);
+        }
+        // Past the footprint every object fits, the churn stays put too
+        if let Some(&churn) = result.churn.last() {
+            result.churn.push(churn);
+        }
+    }
+    result
+}
+
+// A simulation waiting for a worker of the pool
+type SimulationJob = Box<dyn FnOnce() -> Result<SimulationResult, SimError> + Send>;
+
+// Jobs own their simulator, boxed policies and samplers included
+const _: () = assert_send::<MiniSim<Box<dyn EvictPolicy>>>();
+
+fn simulation_job<P: EvictPolicy + 'static>(
+    access_records: Arc<Vec<AccessRecord>>,
+    args: &InnerConfig,
+    run: SimulationRun,
+    cacheable_keys: Option<Arc<HashSet<Key>>>,
+    build_policy: impl Fn(u64) -> P + Send + 'static,
+) -> Result<SimulationJob, SimError> {
+    let SimulationRun {
+        label,
+        unit,
+        sample_rate,
+        max_cache_size,
+        cache_sizes,
+        jobs_per_curve,
+        cancel,
+        floor_at,
+        mean_object_size,
+        lru_stack,
+    } = run;
+    let finish = move |result: SimulationResult| match floor_at {
+        Some(size) => extend_floor(result, size),
+        None => result,
+    };
+    let respect_ttl = args.respect_ttl;
+    let expiry_granularity = args.expiry_granularity;
+    let shards_weighting = args.shards_weighting;
+    let byte_miss_ratio = args.byte_miss_ratio;
+    let churn = args.churn;
+    let eviction_log = args.eviction_log;
+    let strict_invariants = args.strict_invariants;
+    let min_sampled = args.min_sampled;
+    let num_sizes = args.num_sizes;
+    let prometheus_every = args.prometheus.as_ref().map(|(_, every)| *every);
+    let probes = ProbeSelector::new(args.probe_commands.clone(), args.probe_rate);
+    let (write_commands, delete_commands) =
+        (args.write_commands.clone(), args.delete_commands.clone());
+    let warm_objects = args
+        .warm_start
+        .as_ref()
+        .map(|warm_start| warm_start.objects.clone());
+    // Adaptive passes each simulate the whole trace, their errors aren't merged
+    let segments = args.segments.filter(|_| args.adaptive.is_none());
+    let trace_len = access_records.len() as u64;
+    let probe_size = args.probe_size.unwrap_or(max_cache_size);
+    let contents_dump = args.dump_contents.as_ref().map(|path| {
+        (
+            probe_size,
+            draw::suffixed_path(path, &draw::curve_id(&label)),
+        )
+    });
+    // Every simulator of a split curve writes its own sizes to its own file
+    let prometheus = args.prometheus.clone();
+    let prometheus_file = move |label: &str| {
+        prometheus.as_ref().map(|(path, every)| {
+            (
+                draw::suffixed_path(path, &draw::curve_id(label)),
+                *every,
+                label.to_string(),
+            )
+        })
+    };
+    let timeline = args.timeline_every.map(|every| {
+        let id = format!("{}_timeline", draw::curve_id(&label));
+        (
+            probe_size,
+            every,
+            draw::suffixed_path(&args.output_path().with_extension("csv"), &id),
+        )
+    });
+    let build = move |sizes: SimSizes| {
+        let shards = match shards_weighting {
+            config::ShardsWeighting::Uniform => ShardsFixedRate::create_shards(sample_rate),
+            config::ShardsWeighting::Size => {
+                ShardsSizeWeighted::create_shards(sample_rate, mean_object_size)
+            }
+        };
+        let sim = match sizes {
+            SimSizes::Listed(cache_sizes) => {
+                MiniSim::from_sizes_with(cache_sizes, shards, &build_policy)?
+            }
+            SimSizes::Grid(points) => {
+                // A byte cache of 100 bytes or less holds hardly any object,
+                // an object cache only needs room for one
+                let first_size = max_cache_size / num_sizes;
+                if unit == CapacityUnit::Bytes && first_size <= 100 {
+                    return Err(SimError::InvalidCapacity {
+                        capacity: max_cache_size,
+                        reason: format!(
+                            "the grid of {num_sizes} sizes starts at {first_size} bytes, \
+                             over 100 is needed"
+                        ),
+                    });
+                }
+                MiniSim::new_with_points(max_cache_size, num_sizes, shards, &build_policy, points)?
+            }
+        };
+        Ok(sim
+            .with_capacity_unit(unit)
+            .with_byte_miss_ratio(byte_miss_ratio)
+            .with_ttl(respect_ttl)
+            .with_proactive_expiry(expiry_granularity)
+            .with_strict_invariants(strict_invariants)
+            .with_min_sampled(min_sampled)
+            .with_probes(probes.clone())
+            .with_write_commands(write_commands.clone(), delete_commands.clone())
+            .with_segments(segments, trace_len)
+            .with_cancellation(cancel.clone())
+            .with_cacheable_keys(cacheable_keys.clone())
+            .with_churn(churn)
+            .with_warm_start(warm_objects.as_deref().map(Vec::as_slice))
+            .with_lru_stack(lru_stack.then_some(trace_len as usize))
+            .with_byte_hit_window(prometheus_every)
+            .with_eviction_log(eviction_log.then_some(max_cache_size)))
+    };
+    match args.adaptive {
+        Some(adaptive) => {
+            // Fail before any worker starts if the largest size can't be simulated
+            build(SimSizes::Listed(vec![max_cache_size]))?;
+            Ok(Box::new(move || {
+                simulation_adaptive(
+                    access_records,
+                    |sizes| build(SimSizes::Listed(sizes)),
+                    adaptive,
+                    max_cache_size,
+                    label,
+                    unit,
+                )
+                .map(finish)
+            }))
+        }
+        // One stack distance pass covers every size, splitting it gains nothing
+        None if jobs_per_curve > 1 && !lru_stack => {
+            // Every simulator takes a contiguous run of the sizes, the one
+            // with the size closest to the probe size writes the contents
+            // and the timeline
+            let listed = cache_sizes.is_some();
+            let sizes = match cache_sizes {
+                Some(cache_sizes) => cache_sizes,
+                None => (1..=num_sizes)
+                    .map(|i| i * (max_cache_size / num_sizes))
+                    .collect(),
+            };
+            let closest = (contents_dump.is_some() || timeline.is_some()).then(|| {
+                (0..sizes.len())
+                    .min_by_key(|i| sizes[*i].abs_diff(probe_size))
+                    .unwrap()
+            });
+            let chunk_len = sizes.len().div_ceil(jobs_per_curve);
+            let mut sims = Vec::new();
+            for start in (0..sizes.len()).step_by(chunk_len) {
+                let points = start..(start + chunk_len).min(sizes.len());
+                let sim = if listed {
+                    build(SimSizes::Listed(sizes[points.clone()].to_vec()))?
+                } else {
+                    build(SimSizes::Grid(points.start as u64..points.end as u64))?
+                };
+                let probed = closest.filter(|i| points.contains(i));
+                let dump = probed.and(contents_dump.clone());
+                let chunk_timeline = probed.and(timeline.clone());
+                let chunk_label = format!(
+                    "{label} (sizes {} to {})",
+                    sizes[points.start],
+                    sizes[points.end - 1]
+                );
+                sims.push((
+                    sim.with_contents_dump(dump)
+                        .with_timeline(chunk_timeline)
+                        .with_prometheus_file(prometheus_file(&chunk_label)),
+                    chunk_label,
+                ));
+            }
+            Ok(Box::new(move || {
+                simulation_split(access_records, sims, label, unit).map(finish)
+            }))
+        }
+        None => {
+            let sizes = match cache_sizes {
+                Some(cache_sizes) => SimSizes::Listed(cache_sizes),
+                None => SimSizes::Grid(0..num_sizes),
+            };
+            let sim = build(sizes)?
+                .with_contents_dump(contents_dump)
+                .with_timeline(timeline)
+                .with_prometheus_file(prometheus_file(&label));
+            Ok(Box::new(move || {
+                simulation(access_records, sim, label, unit).map(finish)
+            }))
+        }
+    }
+}
+
+// Build the job with the policy wrapped in the WatermarkWrapper its spec asks
+// for, then in the MaxResidencyWrapper the config asks for
+fn wrapped_job<P: EvictPolicy + 'static>(
+    access_records: Arc<Vec<AccessRecord>>,
+    args: &InnerConfig,
+    run: SimulationRun,
+    cacheable_keys: Option<Arc<HashSet<Key>>>,
+    watermark: Option<f64>,
+    build_policy: impl Fn(u64) -> P + Send + 'static,
+) -> Result<SimulationJob, SimError> {
+    match watermark {
+        Some(watermark) => {
+            residency_job(access_records, args, run, cacheable_keys, move |capacity| {
+                WatermarkWrapper::wrap(build_policy(capacity), capacity, watermark)
+            })
+        }
+        None => residency_job(access_records, args, run, cacheable_keys, build_policy),
+    }
+}
+
+fn residency_job<P: EvictPolicy + 'static>(
+    access_records: Arc<Vec<AccessRecord>>,
+    args: &InnerConfig,
+    run: SimulationRun,
+    cacheable_keys: Option<Arc<HashSet<Key>>>,
+    build_policy: impl Fn(u64) -> P + Send + 'static,
+) -> Result<SimulationJob, SimError> {
+    match args.max_residency {
+        Some(max_residency) => {
+            simulation_job(access_records, args, run, cacheable_keys, move |capacity| {
+                MaxResidencyWrapper::wrap(build_policy(capacity), max_residency)
+            })
+        }
+        None => simulation_job(access_records, args, run, cacheable_keys, build_policy),
+    }
+}
+
+fn policy_job(
+    policy: &EvictionPolicy,
+    access_records: Arc<Vec<AccessRecord>>,
+    args: &InnerConfig,
+    run: SimulationRun,
+    cacheable_keys: Option<Arc<HashSet<Key>>>,
+) -> Result<SimulationJob, SimError> {
+    let invalid = |reason: &str| {
+        Err(SimError::InvalidPolicy {
+            policy: policy.to_string(),
+            reason: reason.to_string(),
+        })
+    };
+    let (base, watermark) = policy.split_watermark();
+    if watermark.is_some_and(|watermark| !(watermark > 0.0 && watermark <= 1.0)) {
+        return invalid("the watermark must be in (0, 1]");
+    }
+    let (records, keys) = (access_records, cacheable_keys);
+    match *base {
+        EvictionPolicy::LRU => wrapped_job(records, args, run, keys, watermark, LruPolicy::new),
+        EvictionPolicy::FIFO => wrapped_job(records, args, run, keys, watermark, FifoPolicy::new),
+        EvictionPolicy::LFU => wrapped_job(records, args, run, keys, watermark, LfuPolicy::new),
+        EvictionPolicy::TWOQ => wrapped_job(records, args, run, keys, watermark, TwoQPolicy::new),
+        EvictionPolicy::LARGEST => {
+            wrapped_job(records, args, run, keys, watermark, LargestFirstPolicy::new)
+        }
+        EvictionPolicy::SLRU { protected } if !(0.0..=1.0).contains(&protected) => {
+            invalid("the protected fraction must be in 0..=1")
+        }
+        EvictionPolicy::SLRU { protected } => {
+            wrapped_job(records, args, run, keys, watermark, move |capacity| {
+                SlruPolicy::with_protected(capacity, protected)
+            })
+        }
+        EvictionPolicy::LRUFQ { threshold } => {
+            wrapped_job(records, args, run, keys, watermark, move |capacity| {
+                LruFrequencyPolicy::with_threshold(capacity, threshold)
+            })
+        }
+        EvictionPolicy::FROZENHOT { hot, .. } if !(0.0..=1.0).contains(&hot) => {
+            invalid("the hot fraction must be in 0..=1")
+        }
+        EvictionPolicy::FROZENHOT { hot, rebuild } => {
+            wrapped_job(records, args, run, keys, watermark, move |capacity| {
+                FrozenHotPolicy::with_params(capacity, hot, rebuild)
+            })
+        }
+        EvictionPolicy::Registered {
+            ref name,
+            ref params,
+        } => {
+            let Some(factory) = registered_policy(name) else {
+                return invalid("no policy is registered under this name");
+            };
+            let params = params.clone();
+            wrapped_job(records, args, run, keys, watermark, move |capacity| {
+                factory(capacity, &params)
+            })
+        }
+        EvictionPolicy::Watermark { .. } => invalid("a policy can only have one watermark"),
+    }
+}
+
+// How the standard errors of --segments are estimated, for the metadata of
+// the exports and the logs
+pub fn describe_segments(segments: usize) -> String {
+    format!(
+        "across {segments} contiguous segments of the trace in time, workload drift between \
+         them widens the error beyond sampling noise"
+    )
+}
+
+// The JSON export of `results`, see `write_exports`
+pub fn run_export(
+    results: &[SimulationResult],
+    failures: &[SimError],
+    args: &InnerConfig,
+    sources: &[String],
+) -> export::RunExport {
+    export::RunExport {
+        trace: args.trace.display().to_string(),
+        trace_checksum: args.trace_checksum.map(config::format_checksum),
+        sources: sources.to_vec(),
+        error_estimate: args.segments.map(describe_segments),
+        shards_weighting: (args.sample_rate.is_some() || args.shards_rates.is_some())
+            .then_some(args.shards_weighting),
+        smooth_min_samples: args.smooth_min_samples,
+        time_source: args.time_source,
+        figure: Some(args.figure.clone()),
+        curves: results
+            .iter()
+            .map(export::CurveExport::from_result)
+            .collect(),
+        undersampled: failures
+            .iter()
+            .filter_map(export::UndersampledExport::from_error)
+            .collect(),
+    }
+}
+
+// Write the --export outputs of `results` next to args.output. `failures` are
+// the curves that could not be simulated, `sources` the exports the curves
+// were combined from, both empty when combining or simulating respectively.
+// Returns the files written.
+pub fn write_exports(
+    results: &[SimulationResult],
+    failures: &[SimError],
+    args: &InnerConfig,
+    sources: &[String],
+) -> Vec<PathBuf> {
+    let mut written = Vec::new();
+    if args.export.contains(&config::ExportFormat::Json) {
+        let run = run_export(results, failures, args, sources);
+        let path = args.output_path().with_extension("json");
+        match export::write_json_export(&run, &path) {
+            Ok(()) => {
+                info!("Wrote the curve counters to {path:?}");
+                written.push(path);
+            }
+            Err(e) => warn!("Failed to write the curve counters to {path:?}: {e}"),
+        }
+    }
+    if args.export.contains(&config::ExportFormat::Html) {
+        written.extend(write_html_report(results, args, sources));
+    }
+    written
+}
+
+#[cfg(feature = "html")]
+fn write_html_report(
+    results: &[SimulationResult],
+    args: &InnerConfig,
+    sources: &[String],
+) -> Option<PathBuf> {
+    let requests = results.first().map_or(0, |r| r.requests).to_string();
+    // A combined run has no trace or simulation settings of its own
+    let metadata = if !sources.is_empty() {
+        let labels = results
+            .iter()
+            .map(|result| result.label.as_str())
+            .collect::<Vec<_>>();
+        vec![
+            ("Combined from", sources.join(", ")),
+            ("Requests", requests),
+            ("Curves", labels.join(", ")),
+        ]
+    } else {
+        vec![
+            ("Trace", args.trace.display().to_string()),
+            (
+                "Trace checksum",
+                args.trace_checksum
+                    .map_or("unknown".to_string(), config::format_checksum),
+            ),
+            ("Requests", requests),
+            ("Policies", format!("{:?}", args.policies)),
+            ("Cache size", args.describe_cache_size()),
+            ("Capacity units", format!("{:?}", args.capacity_units)),
+            ("Sample rate", format!("{:?}", args.sample_rate)),
+            ("SHARDS rates", format!("{:?}", args.shards_rates)),
+            ("SHARDS weighting", format!("{:?}", args.shards_weighting)),
+            (
+                "Miss ratio",
+                if args.byte_miss_ratio {
+                    "bytes"
+                } else {
+                    "requests"
+                }
+                .to_string(),
+            ),
+            ("Adaptive", format!("{:?}", args.adaptive)),
+            (
+                "Slab classes",
+                args.slab_classes
+                    .as_ref()
+                    .map_or("none".to_string(), ToString::to_string),
+            ),
+            ("Respect TTL", args.respect_ttl.to_string()),
+            (
+                "Standard errors",
+                args.segments.map_or("none".to_string(), describe_segments),
+            ),
+            (
+                "Shuffle",
+                match args.shuffle {
+                    Some((seed, Some(block))) => format!("seed {seed}, blocks of {block} records"),
+                    Some((seed, None)) => format!("seed {seed}, whole trace"),
+                    None => "none".to_string(),
+                },
+            ),
+        ]
+    };
+    let path = args
+        .report
+        .clone()
+        .unwrap_or_else(|| args.output_path().with_extension("html"));
+    match report::write_html_report(results, &metadata, &path) {
+        Ok(()) => {
+            info!("Wrote HTML report to {path:?}");
+            Some(path)
+        }
+        Err(e) => {
+            warn!("Failed to write the HTML report to {path:?}: {e}");
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "html"))]
+fn write_html_report(
+    _results: &[SimulationResult],
+    _args: &InnerConfig,
+    _sources: &[String],
+) -> Option<PathBuf> {
+    warn!("The HTML report needs cache_mrc built with --features html, skipping");
+    None
+}
+
+// Text of a panic payload caught from a worker
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload.downcast_ref::<&str>().map_or_else(
+            || "unknown panic".to_string(),
+            |message| message.to_string(),
+        ),
+    }
+}
+
+// The strategy `strategy` resolves to for `curves` curves, `rates` of them
+// per policy and capacity unit, on `threads` threads, and the simulators each
+// curve is split over
+pub fn choose_parallel_strategy(
+    strategy: ParallelStrategy,
+    curves: usize,
+    rates: usize,
+    threads: usize,
+    records: usize,
+    unique_keys: u64,
+) -> (ParallelStrategy, usize) {
+    let jobs_per_curve = threads
+        .div_ceil(curves.max(1))
+        .clamp(1, NUM_CACHE_SIZE as usize);
+    match strategy {
+        ParallelStrategy::PerPolicy => (ParallelStrategy::PerPolicy, 1),
+        ParallelStrategy::PerSize => (ParallelStrategy::PerSize, jobs_per_curve),
+        ParallelStrategy::NestedShards => (ParallelStrategy::NestedShards, 1),
+        ParallelStrategy::Auto => {
+            // Each extra simulator keeps a hash set entry per seen key
+            let extra_key_bytes = unique_keys * 16 * (jobs_per_curve as u64 - 1) * curves as u64;
+            let running_key_bytes = unique_keys * 16 * curves.min(threads) as u64;
+            if curves < threads
+                && records >= MIN_SPLIT_RECORDS
+                && extra_key_bytes <= MAX_SPLIT_KEY_BYTES
+            {
+                (ParallelStrategy::PerSize, jobs_per_curve)
+            } else if rates > 1 && running_key_bytes > MAX_SPLIT_KEY_BYTES {
+                (ParallelStrategy::NestedShards, 1)
+            } else {
+                (ParallelStrategy::PerPolicy, 1)
+            }
+        }
+    }
+}
+
+// A built job and the label it reports failures under
+type LabeledJob = (String, Result<SimulationJob, SimError>);
+
+// Run one job, turning a panic into SimError::WorkerPanicked
+fn run_job((label, job): LabeledJob) -> Result<SimulationResult, SimError> {
+    let job = job?;
+    panic::catch_unwind(AssertUnwindSafe(job)).unwrap_or_else(|payload| {
+        Err(SimError::WorkerPanicked {
+            label,
+            message: panic_message(payload),
+        })
+    })
+}
+
+// Run the jobs on a worker pool, the outcomes in the order of the jobs
+// Run every group of jobs on the pool, the jobs of a group one after another
+// on one worker. Returns the outcomes in the order of the jobs.
+fn run_on_pool(
+    args: &InnerConfig,
+    groups: Vec<Vec<LabeledJob>>,
+) -> Vec<Result<SimulationResult, SimError>> {
+    // Every simulation runs on this pool, so --threads bounds the whole run
+    let mut builder = rayon::ThreadPoolBuilder::new().num_threads(args.threads.unwrap_or(0));
+    if args.pin_cores {
+        let cores = affinity::physical_cores();
+        if cores.is_empty() {
+            warn!("No core topology available, --pin-cores is ignored");
+        } else {
+            info!("Pinning workers round-robin to cores {cores:?}");
+            builder = builder.start_handler(move |worker| {
+                let cpu = cores[worker % cores.len()];
+                if !affinity::pin_current_thread(cpu) {
+                    warn!("Could not pin worker {worker} to core {cpu}");
+                }
+            });
+        }
+    }
+    let pool = builder.build().unwrap();
+    info!(
+        "Running {} simulations on {} threads",
+        groups.iter().map(Vec::len).sum::<usize>(),
+        pool.current_num_threads()
+    );
+    pool.install(|| {
+        groups
+            .into_par_iter()
+            .map(|group| group.into_iter().map(run_job).collect::<Vec<_>>())
+            .collect::<Vec<_>>()
+    })
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+// Simulate every configured curve without drawing. Returns all the curves,
+// the unsampled ones among them when SHARDS rates are compared, and the
+// curves that failed to build or panicked.
+pub fn run_simulations(
+    access_records: Arc<Vec<AccessRecord>>,
+    args: &InnerConfig,
+    footprint: &Footprint,
+    cacheable_keys: Option<Arc<HashSet<Key>>>,
+) -> (Vec<SimulationResult>, Vec<SimulationResult>, Vec<SimError>) {
+    info!("Simulation policies: {:?}", args.policies);
+    // One run without SHARDS plus one per rate, or just the single sample rate
+    let sample_rates = match args.shards_rates.as_ref() {
+        Some(rates) => std::iter::once(None)
+            .chain(rates.iter().map(|rate| Some(*rate)))
+            .collect::<Vec<_>>(),
+        None => vec![args.sample_rate],
+    };
+    info!("Simple rates: {:?}", sample_rates);
+    info!("Capacity units: {:?}", args.capacity_units);
+    // The --max-time budget starts with the simulations, not with the prepasses
+    let cancel = match args.max_time {
+        Some(budget) => args.cancel.clone().with_deadline(Instant::now() + budget),
+        None => args.cancel.clone(),
+    };
+    // Object count sweeps cover the same number of mean-sized objects as the byte sweep
+    let in_unit = |size: u64, unit| match unit {
+        CapacityUnit::Bytes => size,
+        CapacityUnit::Objects => (size as f64 / footprint.mean_object_size.max(1.0)) as u64,
+    };
+    let configured_size = |unit| in_unit(args.cache_size, unit);
+    let listed_sizes = |unit| {
+        args.cache_sizes.as_ref().map(|sizes| {
+            let mut sizes = sizes
+                .iter()
+                .map(|size| in_unit(*size, unit).max(1))
+                .collect::<Vec<_>>();
+            sizes.dedup();
+            sizes
+        })
+    };
+    // Past the footprint everything fits, so the curve is flat at the
+    // compulsory misses. Sweep up to the footprint and report the
+    // configured size as one flat point. Listed sizes are simulated as given.
+    let capped_size = |unit| {
+        if args.cache_sizes.is_some() {
+            return None;
+        }
+        let footprint_size = match unit {
+            CapacityUnit::Bytes => footprint.unique_bytes,
+            CapacityUnit::Objects => footprint.unique_keys,
+        };
+        (!args.sweep_beyond_footprint && configured_size(unit) > footprint_size)
+            .then_some(footprint_size)
+    };
+    for unit in args.capacity_units.iter() {
+        if let Some(capped) = capped_size(*unit) {
+            info!(
+                "Cache size {} {unit} is past the trace footprint, simulating up to {capped} \
+                 (--sweep-beyond-footprint to simulate it all)",
+                configured_size(*unit)
+            );
+        }
+    }
+    let threads = args
+        .threads
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |threads| threads.get()));
+    let curves = args.policies.len() * args.capacity_units.len() * sample_rates.len();
+    let (strategy, jobs_per_curve) = match args.adaptive {
+        // Adaptive passes pick their sizes as they go, they are never split
+        Some(_) => (ParallelStrategy::PerPolicy, 1),
+        None if args.sequential => (ParallelStrategy::PerPolicy, 1),
+        None => choose_parallel_strategy(
+            args.parallel_strategy,
+            curves,
+            sample_rates.len(),
+            threads,
+            access_records.len(),
+            footprint.unique_keys,
+        ),
+    };
+    info!("Parallel strategy: {strategy}, {jobs_per_curve} jobs per curve");
+    // With --stack-distance, LRU curves are counted from one pass over the
+    // trace when nothing the pass can't reproduce is set, the rest are simulated
+    let stacked = args.stack_distance && args.policies.contains(&EvictionPolicy::LRU);
+    let stack_blocker = [
+        ("--respect-ttl", args.respect_ttl),
+        (
+            "probes",
+            !args.probe_commands.is_empty() || args.probe_rate.is_some(),
+        ),
+        (
+            "write commands",
+            !args.write_commands.is_empty() || !args.delete_commands.is_empty(),
+        ),
+        ("--max-residency", args.max_residency.is_some()),
+        ("--dump-contents", args.dump_contents.is_some()),
+        ("--timeline-every", args.timeline_every.is_some()),
+        ("--warm-start", args.warm_start.is_some()),
+        ("--churn", args.churn),
+    ]
+    .into_iter()
+    .find(|(_, set)| *set)
+    .map(|(setting, _)| setting);
+    let sizes_vary = stacked && args.capacity_units.contains(&CapacityUnit::Bytes) && {
+        let mut sizes = hashbrown::HashMap::new();
+        access_records
+            .iter()
+            .any(|record| *sizes.entry(record.key).or_insert(record.size) != record.size)
+    };
+    if let Some(setting) = stack_blocker.filter(|_| stacked) {
+        warn!("LRU is simulated, the stack distance pass can't reproduce {setting}");
+    } else if sizes_vary {
+        warn!(
+            "LRU by bytes is simulated, the stack distance pass needs every key to keep one size"
+        );
+    }
+    let one_pass = |policy: &EvictionPolicy, unit: CapacityUnit| {
+        stacked
+            && *policy == EvictionPolicy::LRU
+            && stack_blocker.is_none()
+            && !(unit == CapacityUnit::Bytes && sizes_vary)
+    };
+    if stacked {
+        let passes = args
+            .capacity_units
+            .iter()
+            .filter(|unit| one_pass(&EvictionPolicy::LRU, **unit))
+            .count()
+            * sample_rates.len();
+        info!("{passes} of the {curves} curves come from one stack distance pass each");
+    }
+    let (jobs, exact): (Vec<_>, Vec<_>) = args
+        .policies
+        .iter()
+        .flat_map(|policy| args.capacity_units.iter().map(move |unit| (policy, *unit)))
+        .flat_map(|(policy, unit)| sample_rates.iter().map(move |rate| (policy, unit, *rate)))
+        .map(|(policy, unit, sample_rate)| {
+            let access_records = Arc::clone(&access_records);
+            let mut label = if args.capacity_units.len() > 1 {
+                format!("{policy} ({unit})")
+            } else {
+                policy.to_string()
+            };
+            if sample_rates.len() > 1 {
+                match sample_rate {
+                    Some(rate) => {
+                        label.push_str(&format!(" SHARDS {}%", rate * 100.0));
+                        if args.shards_weighting == config::ShardsWeighting::Size {
+                            label.push_str(" size-weighted");
+                        }
+                    }
+                    None => label.push_str(" exact"),
+                }
+            }
+            let capped = capped_size(unit);
+            let run = SimulationRun {
+                label: label.clone(),
+                unit,
+                sample_rate,
+                max_cache_size: capped.unwrap_or_else(|| configured_size(unit)),
+                cache_sizes: listed_sizes(unit),
+                jobs_per_curve,
+                cancel: cancel.clone(),
+                floor_at: capped.map(|_| configured_size(unit)),
+                mean_object_size: footprint.mean_object_size,
+                lru_stack: one_pass(policy, unit),
+            };
+            let job = policy_job(policy, access_records, args, run, cacheable_keys.clone());
+            ((label, job), sample_rate.is_none())
+        })
+        .unzip();
+
+    let outcomes = if args.sequential {
+        info!("Running {} simulations one after another", jobs.len());
+        jobs.into_iter().map(run_job).collect::<Vec<_>>()
+    } else {
+        // The jobs come policy by policy and unit by unit, each at every rate
+        let group_len = match strategy {
+            ParallelStrategy::NestedShards => sample_rates.len(),
+            _ => 1,
+        };
+        let mut jobs = jobs.into_iter().peekable();
+        let mut groups = Vec::new();
+        while jobs.peek().is_some() {
+            groups.push(jobs.by_ref().take(group_len).collect());
+        }
+        run_on_pool(args, groups)
+    };
+    let mut failures = Vec::new();
+    let mut results = Vec::new();
+    let mut exact_results = Vec::new();
+    for (outcome, exact) in outcomes.into_iter().zip(exact) {
+        match outcome {
+            Ok(result) => {
+                if exact {
+                    exact_results.push(result.clone());
+                }
+                results.push(result);
+            }
+            // Out of --max-time, the curve over the records simulated so far
+            // is still worth drawing
+            Err(SimError::Cancelled {
+                label,
+                partial: Some(partial),
+            }) if cancel.timed_out() => {
+                let covered = partial.requests as f64 / access_records.len().max(1) as f64;
+                warn!(
+                    "{label} ran out of time, drawing its curve over the first {:.1}% of the trace",
+                    covered * 100.0
+                );
+                let result = SimulationResult {
+                    label: format!("{label} (partial)"),
+                    ..*partial
+                };
+                if exact {
+                    exact_results.push(result.clone());
+                }
+                results.push(result);
+            }
+            Err(e @ SimError::Cancelled { .. }) => {
+                warn!("{e}");
+                failures.push(e);
+            }
+            // A rate too low for the trace, the other curves are still meaningful
+            Err(e @ SimError::Undersampled { .. }) => {
+                let keys = footprint.unique_keys.max(1);
+                let wanted = args.min_sampled.keys.max(100);
+                warn!(
+                    "{e}, raise the rate: {:.1e} samples about {wanted} of the trace's {keys} keys",
+                    (wanted as f64 / keys as f64).min(1.0)
+                );
+                failures.push(e);
+            }
+            Err(e) => {
+                error!("{e}");
+                failures.push(e);
+            }
+        }
+    }
+    (results, exact_results, failures)
+}
+
+// Simulate and draw every configured curve. Curves that fail to build or
+// panic are logged and returned, the others are still drawn and returned.
+pub fn simulate_all(
+    access_records: Arc<Vec<AccessRecord>>,
+    args: &InnerConfig,
+    footprint: &Footprint,
+    cacheable_keys: Option<Arc<HashSet<Key>>>,
+) -> (Vec<SimulationResult>, Vec<SimError>) {
+    let (mut results, exact_results, failures) =
+        run_simulations(access_records.clone(), args, footprint, cacheable_keys);
+    let working_set = args
+        .x_normalize
+        .map(|config::XNormalize::WorkingSet| footprint);
+    // Listed sizes are deduplicated, the footprint cap adds a flat point and
+    // --adaptive spends its own budget, so the count can differ from the one asked
+    let requested = match (args.adaptive, args.cache_sizes.as_ref()) {
+        (Some(adaptive), _) => adaptive.point_budget as u64,
+        (None, Some(sizes)) => sizes.len() as u64,
+        (None, None) => args.num_sizes,
+    };
+    for result in results.iter() {
+        info!(
+            "{}: {} distinct cache sizes simulated ({requested} requested)",
+            result.label,
+            result.points.len()
+        );
+    }
+    if let Some(segments) = args.segments {
+        info!("Standard errors are {}", describe_segments(segments));
+        for result in results.iter().filter(|result| !result.stderr.is_empty()) {
+            let widest = result.stderr.iter().copied().fold(0.0, f64::max);
+            info!("{}: standard error at most {widest:.4}", result.label);
+        }
+    }
+    let compulsory = args
+        .annotate_asymptotes
+        .then(|| footprint.compulsory_miss_ratio());
+    if let Some(compulsory) = compulsory {
+        for result in results.iter() {
+            if let (Some((first_size, first)), Some((last_size, last))) =
+                (result.points.first(), result.points.last())
+            {
+                info!(
+                    "{}: miss ratio {first:.4} at {first_size}, {last:.4} at {last_size} \
+                     (1 at size 0, {compulsory:.4} compulsory)",
+                    result.label
+                );
+            }
+        }
+    }
+    // Ground truth on its own figure when it is compared against SHARDS
+    let mut written = Vec::new();
+    if args.shards_rates.is_some() {
+        match args.output.as_ref() {
+            Some(output) => {
+                let exact_output = draw::suffixed_path(output, "exact");
+                info!("Writing the exact curves to {exact_output:?}");
+                written.extend(draw_lines(
+                    &exact_results,
+                    exact_output,
+                    args.output_format,
+                    working_set,
+                    compulsory,
+                    args.error_bands,
+                    &args.theme,
+                ));
+            }
+            None => print!("Exact curves:\n{}", draw::format_table(&exact_results)),
+        }
+    }
+    if args.ensemble {
+        let target = args.target_cache_size.unwrap_or(args.cache_size);
+        if let Some((label, miss_ratio)) = analysis::best_policy_at(&results, target) {
+            info!("Best policy at cache size {target}: {label} (miss ratio {miss_ratio:.4})");
+        }
+        results.extend(analysis::virtual_best(&results));
+    }
+    written.extend(write_exports(&results, &failures, args, &[]));
+    if let Some(model) = args.cost_model.as_ref() {
+        let requests = access_records.len() as u64;
+        let curves = results
+            .iter()
+            .filter_map(|result| analysis::cost_curve(result, model, requests))
+            .collect::<Vec<_>>();
+        for curve in curves.iter() {
+            let (cache_size, cost) = curve.optimum;
+            info!(
+                "{}: cheapest cache size {cache_size} (total cost {cost:.2})",
+                curve.label
+            );
+        }
+        let cost_output = args.output_path().with_extension("cost.csv");
+        match analysis::write_cost_curves(&curves, &cost_output) {
+            Ok(()) => {
+                info!("Wrote cost curves to {cost_output:?}");
+                written.push(cost_output);
+            }
+            Err(e) => warn!("Failed to write the cost curves to {cost_output:?}: {e}"),
+        }
+    }
+    // The figures and the table show the smoothed curves, the exports above
+    // keep the raw points
+    let smoothed = args.smooth_min_samples.map(|min_samples| {
+        results
+            .iter()
+            .map(|result| {
+                let smoothed = analysis::smooth_sampled(result, min_samples);
+                if smoothed.points.len() < result.points.len() {
+                    info!(
+                        "{}: smoothed {} points into {} of at least {min_samples} sampled hits",
+                        result.label,
+                        result.points.len(),
+                        smoothed.points.len()
+                    );
+                }
+                smoothed
+            })
+            .collect::<Vec<_>>()
+    });
+    let plotted = smoothed.as_deref().unwrap_or(&results);
+    match args.output.clone() {
+        Some(output) => written.extend(draw_lines(
+            plotted,
+            output,
+            args.output_format,
+            working_set,
+            compulsory,
+            args.error_bands,
+            &args.theme,
+        )),
+        None => print!("{}", draw::format_table(plotted)),
+    }
+    if let Some(path) = args.emit_gnuplot_script.as_ref() {
+        match draw::write_gnuplot_script(
+            plotted,
+            path,
+            working_set,
+            compulsory,
+            args.error_bands,
+            &args.theme,
+        ) {
+            Ok(()) => {
+                info!("Wrote the gnuplot script to {path:?}");
+                written.push(path.clone());
+            }
+            Err(e) => warn!("Failed to write the gnuplot script to {path:?}: {e}"),
+        }
+    }
+    if args.separate_figures {
+        written.extend(draw_separate(
+            plotted,
+            args.output_path(),
+            args.output_format,
+            working_set,
+            compulsory,
+            args.error_bands,
+            &args.theme,
+        ));
+    }
+    if args.churn {
+        for result in results.iter() {
+            if let (Some(&(size, _)), Some(churn)) = (result.points.last(), result.churn.last()) {
+                info!(
+                    "{}: at {size} admitted {:.0} bytes, evicted {:.0} and expired {:.0}, \
+                     admission ratio {:.4}",
+                    result.label,
+                    churn.admitted_bytes,
+                    churn.evicted_bytes,
+                    churn.expired_bytes,
+                    churn.admission_ratio
+                );
+            }
+        }
+        written.extend(draw::draw_admission(
+            &results,
+            draw::suffixed_path(args.output_path(), "admission"),
+            args.output_format,
+            working_set,
+            &args.theme,
+        ));
+    }
+    if args.plot_reuse_cdf {
+        let reuse = analysis::reuse_distances(&access_records);
+        info!(
+            "Reuse distances: median {:.0}, p90 {:.0}, cold requests {:.1}%",
+            reuse.histogram.percentile(50.0),
+            reuse.histogram.percentile(90.0),
+            reuse.cold_fraction() * 100.0
+        );
+        written.extend(draw::draw_reuse_cdf(
+            &reuse,
+            draw::suffixed_path(args.output_path(), "reuse_cdf"),
+            args.output_format,
+        ));
+    }
+    info!("Wrote {} files:", written.len());
+    for path in written.iter() {
+        info!("  {path:?}");
+    }
+    (results, failures)
+}
+
+// Simulate `alternate` on the same trace and write the miss ratio delta of its
+// first curve against the first primary curve, next to the primary output
+pub fn compare_configs(
+    access_records: Arc<Vec<AccessRecord>>,
+    primary: &[SimulationResult],
+    alternate: &InnerConfig,
+    footprint: &Footprint,
+    cacheable_keys: Option<Arc<HashSet<Key>>>,
+    output: &std::path::Path,
+) -> Vec<SimError> {
+    let (results, _, failures) =
+        run_simulations(access_records, alternate, footprint, cacheable_keys);
+    let (Some(primary), Some(other)) = (primary.first(), results.first()) else {
+        warn!("Nothing to compare, a configuration produced no curve");
+        return failures;
+    };
+    if primary.capacity_unit != other.capacity_unit {
+        warn!(
+            "Comparing {} curve {} with {} curve {}",
+            primary.capacity_unit, primary.label, other.capacity_unit, other.label
+        );
+    }
+    let delta = analysis::delta_curve(primary, other);
+    for (start, end, effect) in analysis::delta_regions(&delta) {
+        info!(
+            "{} vs {}: alternate {effect} from cache size {start} to {end}",
+            other.label, primary.label
+        );
+    }
+    let path = output.with_extension("delta.csv");
+    match analysis::write_delta_curve(&delta, &path) {
+        Ok(()) => info!("Wrote the miss ratio delta to {path:?}"),
+        Err(e) => warn!("Failed to write the miss ratio delta to {path:?}: {e}"),
+    }
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::Config;
+    use evict_policy::LruPolicy;
+    use std::{
+        path::PathBuf,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Mutex,
+        },
+        time::Duration,
+    };
+
+    // The curves of the golden sample trace with its config changed by `configure`
+    pub(crate) fn simulate_golden(configure: impl FnOnce(&mut Config)) -> Vec<SimulationResult> {
+        // Not `Config::from_file`, which also parses the test binary's arguments
+        let path = PathBuf::from("data/golden/twitter_sample.toml");
+        let mut config: Config = toml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        config.config_file = Some(path);
+        configure(&mut config);
+        let (access_records, _) = config::load_access_records(&mut config).unwrap();
+        let mut args = InnerConfig::try_from(config).unwrap();
+        let footprint = analysis::footprint(&access_records);
+        if args.needs_working_set() {
+            args.resolve_cache_sizes(Some(footprint.unique_bytes))
+                .unwrap();
+        }
+        let (results, _, failures) =
+            run_simulations(Arc::new(access_records), &args, &footprint, None);
+        assert!(failures.is_empty(), "{failures:?}");
+        results
+    }
+
+    #[test]
+    fn one_policy_gives_one_curve_per_capacity_unit() {
+        let results = simulate_golden(|config| {
+            config.policies = Some(vec![EvictionPolicy::LRU]);
+            config.capacity_units = Some(vec![CapacityUnit::Objects, CapacityUnit::Bytes]);
+        });
+        let labels = results
+            .iter()
+            .map(|result| (result.label.as_str(), result.capacity_unit))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            labels,
+            vec![
+                ("LRU (objects)", CapacityUnit::Objects),
+                ("LRU (bytes)", CapacityUnit::Bytes)
+            ]
+        );
+        // The object sweep starts below the 100 bytes a byte grid needs
+        assert!(results[0].points[0].0 < 100.0);
+        assert_ne!(results[0].points, results[1].points);
+    }
+
+    #[test]
+    fn one_policy_at_two_parameters_gives_two_labeled_curves() {
+        let results = simulate_golden(|config| {
+            config.policies = Some(vec![
+                "SLRU(protected=0.5)".parse().unwrap(),
+                "SLRU(protected=0.8)".parse().unwrap(),
+            ]);
+        });
+        let labels = results
+            .iter()
+            .map(|result| result.label.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(labels, vec!["SLRU(protected=0.5)", "SLRU(protected=0.8)"]);
+        assert_ne!(results[0].points, results[1].points);
+    }
+
+    #[test]
+    fn lru_and_fifo_each_run_once_under_their_own_label() {
+        let results = simulate_golden(|config| {
+            config.policies = Some(vec![EvictionPolicy::LRU, EvictionPolicy::FIFO]);
+        });
+        let labels = results
+            .iter()
+            .map(|result| result.label.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(labels, vec!["LRU", "FIFO"]);
+        // Each curve is the one its policy gives when run alone
+        for (result, policy) in results
+            .iter()
+            .zip([EvictionPolicy::LRU, EvictionPolicy::FIFO])
+        {
+            let alone = simulate_golden(|config| config.policies = Some(vec![policy]));
+            assert_eq!(alone.len(), 1);
+            assert_eq!(result.points, alone[0].points, "{}", result.label);
+        }
+        assert_ne!(results[0].points, results[1].points);
+    }
+
+    #[test]
+    fn num_sizes_bounds_the_points_of_every_curve() {
+        let results = simulate_golden(|config| {
+            config.policies = Some(vec![EvictionPolicy::LRU, EvictionPolicy::FIFO]);
+            config.capacity_units = Some(vec![CapacityUnit::Objects, CapacityUnit::Bytes]);
+            config.num_sizes = Some(50);
+        });
+        assert_eq!(results.len(), 4);
+        for result in results.iter() {
+            assert!(
+                (2..=50).contains(&result.points.len()),
+                "{}: {} points",
+                result.label,
+                result.points.len()
+            );
+        }
+        assert_eq!(results[1].points.len(), 50);
+
+        let config = Config {
+            num_sizes: Some(1),
+            ..Default::default()
+        };
+        let error = config.validate().unwrap_err();
+        assert!(error.contains("--num-sizes must be at least 2"), "{error}");
+    }
+
+    #[test]
+    fn collapse_consecutive_simulates_the_collapsed_sequence() {
+        let path =
+            std::env::temp_dir().join(format!("cache_mrc_repeats_{}.csv", std::process::id()));
+        std::fs::write(
+            &path,
+            "timestamp,command,key,size,ttl\n1,0,7,100,0\n2,0,7,100,0\n3,0,7,100,0\n4,0,8,100,0\n5,0,7,100,0\n",
+        )
+        .unwrap();
+        let mut config = Config {
+            trace: Some(path.clone()),
+            collapse_consecutive: true,
+            ..Default::default()
+        };
+        let (access_records, _) = config::load_access_records(&mut config).unwrap();
+        let keys = access_records.iter().map(|r| r.key).collect::<Vec<_>>();
+        assert_eq!(keys, vec![7, 8, 7]);
+
+        // (requests, misses) of the largest cache
+        let misses = |collapse_consecutive| {
+            let results = simulate_golden(|config| {
+                config.trace = Some(path.clone());
+                config.trace_checksum = None;
+                config.policies = Some(vec![EvictionPolicy::LRU]);
+                config.cache_size = Some(vec![
+                    config::CacheSizeSpec::Bytes(100),
+                    config::CacheSizeSpec::Bytes(200),
+                ]);
+                config.collapse_consecutive = collapse_consecutive;
+            });
+            let (size, miss_ratio) = *results[0].points.last().unwrap();
+            assert_eq!(size, 200.0);
+            let requests = results[0].requests;
+            (requests, (miss_ratio * requests as f64).round() as u64)
+        };
+        // Both keys fit, only their first requests miss
+        let collapsed = misses(true);
+        let repeated = misses(false);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(collapsed, (3, 2));
+        assert_eq!(repeated, (5, 2));
+    }
+
+    #[test]
+    fn zero_timestamps_expire_ttls_on_the_request_index() {
+        let path =
+            std::env::temp_dir().join(format!("cache_mrc_zero_time_{}.csv", std::process::id()));
+        std::fs::write(
+            &path,
+            "timestamp,command,key,size,ttl\n0,0,7,100,2\n0,0,8,100,0\n0,0,9,100,0\n0,0,7,100,2\n",
+        )
+        .unwrap();
+        // (clock, misses of the largest cache, clock in the export)
+        let run = |time_source| {
+            let mut config: Config = toml::from_str(
+                "policies = [\"LRU\"]\ncache_size = [\"400\", \"800\"]\nrespect_ttl = true",
+            )
+            .unwrap();
+            config.trace = Some(path.clone());
+            config.time_source = time_source;
+            let (access_records, _) = config::load_access_records(&mut config).unwrap();
+            let mut args = InnerConfig::try_from(config).unwrap();
+            let footprint = analysis::footprint(&access_records);
+            args.resolve_cache_sizes(Some(footprint.unique_bytes))
+                .unwrap();
+            let (results, _, failures) =
+                run_simulations(Arc::new(access_records), &args, &footprint, None);
+            assert!(failures.is_empty(), "{failures:?}");
+            let export = run_export(&results, &failures, &args, &[]);
+            let (_, miss_ratio) = *results[0].points.last().unwrap();
+            (
+                args.time_source,
+                (miss_ratio * 4.0).round() as u64,
+                export.time_source,
+            )
+        };
+        let detected = run(None);
+        let column = run(Some(config::TimeSource::Column));
+        std::fs::remove_file(&path).unwrap();
+
+        // Key 7 expires 2 requests after it was put, before its second request
+        let index = Some(config::TimeSource::Index);
+        assert_eq!(detected, (index, 4, index));
+        // With every request at time 0 nothing ever expires
+        let column_source = Some(config::TimeSource::Column);
+        assert_eq!(column, (column_source, 3, column_source));
+    }
+
+    #[test]
+    fn sort_by_time_orders_the_trace_before_ttls_expire() {
+        let path =
+            std::env::temp_dir().join(format!("cache_mrc_out_of_order_{}.csv", std::process::id()));
+        std::fs::write(
+            &path,
+            "timestamp,command,key,size,ttl\n20,0,7,100,5\n8,0,7,100,5\n10,0,8,100,0\n10,0,9,100,0\n",
+        )
+        .unwrap();
+        let run = |sort_by_time| {
+            let mut config: Config = toml::from_str(
+                "policies = [\"LRU\"]\ncache_size = [\"400\", \"800\"]\nrespect_ttl = true",
+            )
+            .unwrap();
+            config.trace = Some(path.clone());
+            config.sort_by_time = sort_by_time;
+            let (access_records, _) = config::load_access_records(&mut config).unwrap();
+            let order = access_records
+                .iter()
+                .map(|record| (record.timestamp, record.key))
+                .collect::<Vec<_>>();
+            let mut args = InnerConfig::try_from(config).unwrap();
+            let footprint = analysis::footprint(&access_records);
+            args.resolve_cache_sizes(Some(footprint.unique_bytes))
+                .unwrap();
+            let (results, _, failures) =
+                run_simulations(Arc::new(access_records), &args, &footprint, None);
+            assert!(failures.is_empty(), "{failures:?}");
+            let (_, miss_ratio) = *results[0].points.last().unwrap();
+            (order, (miss_ratio * 4.0).round() as u64)
+        };
+        let sorted = run(true);
+        let unsorted = run(false);
+        std::fs::remove_file(&path).unwrap();
+
+        // Stable: the two requests at time 10 keep their trace order
+        assert_eq!(sorted.0, vec![(8, 7), (10, 8), (10, 9), (20, 7)]);
+        // Key 7 put at 8 expires at 13, its request at 20 misses
+        assert_eq!(sorted.1, 4);
+        // Read as given the clock never passes 25, where the put at 20 expires
+        assert_eq!(unsorted.0[0], (20, 7));
+        assert_eq!(unsorted.1, 3);
+    }
+
+    #[test]
+    fn kb_sizes_scaled_on_input_give_the_byte_curves() {
+        let mut config = Config {
+            trace: Some(PathBuf::from("data/golden/twitter_sample.csv")),
+            ..Default::default()
+        };
+        let (mut access_records, _) = config::load_access_records(&mut config).unwrap();
+        for record in access_records.iter_mut() {
+            record.size = record.size.div_ceil(1024).max(1);
+        }
+        let dir = std::env::temp_dir();
+        let kb_path = dir.join(format!("cache_mrc_kb_sizes_{}.csv", std::process::id()));
+        anonymize::write_trace(&access_records, &kb_path).unwrap();
+        for record in access_records.iter_mut() {
+            record.size *= 1024;
+        }
+        let byte_path = dir.join(format!("cache_mrc_byte_sizes_{}.csv", std::process::id()));
+        anonymize::write_trace(&access_records, &byte_path).unwrap();
+
+        let points = |path: &PathBuf, size_unit_in| {
+            simulate_golden(|config| {
+                config.policies = Some(vec![EvictionPolicy::LRU, EvictionPolicy::LARGEST]);
+                config.trace = Some(path.clone());
+                config.trace_checksum = None;
+                config.size_unit_in = size_unit_in;
+            })
+            .into_iter()
+            .map(|result| (result.label, result.points))
+            .collect::<Vec<_>>()
+        };
+        let kb = points(&kb_path, Some(config::SizeUnit::Kb));
+        let bytes = points(&byte_path, None);
+        std::fs::remove_file(&kb_path).unwrap();
+        std::fs::remove_file(&byte_path).unwrap();
+        assert_eq!(kb.len(), 2);
+        assert_eq!(kb, bytes);
+    }
+
+    #[test]
+    fn slab_classes_charge_small_objects_the_smallest_chunk() {
+        let mut access_records = synthetic::ZipfTrace {
+            requests: 20_000,
+            keys: 2_000,
+            alpha: 0.9,
+            min_size: 1,
+            max_size: 50,
+            seed: 13,
+        }
+        .generate();
+        let dir = std::env::temp_dir();
+        let small = dir.join(format!(
+            "cache_mrc_small_objects_{}.csv",
+            std::process::id()
+        ));
+        anonymize::write_trace(&access_records, &small).unwrap();
+        // memcached's smallest chunk holds every one of them
+        for record in access_records.iter_mut() {
+            record.size = 96;
+        }
+        let chunked = dir.join(format!(
+            "cache_mrc_chunked_objects_{}.csv",
+            std::process::id()
+        ));
+        anonymize::write_trace(&access_records, &chunked).unwrap();
+
+        let points = |path: &PathBuf, slab_classes| {
+            simulate_golden(|config| {
+                config.trace = Some(path.clone());
+                config.trace_checksum = None;
+                config.policies = Some(vec![EvictionPolicy::LRU]);
+                config.cache_size = Some(
+                    [10_000, 20_000, 40_000, 80_000]
+                        .map(config::CacheSizeSpec::Bytes)
+                        .to_vec(),
+                );
+                config.slab_classes = slab_classes;
+            })
+            .remove(0)
+            .points
+        };
+        let requested = points(&small, None);
+        let quantized = points(&small, Some(config::SlabClasses::Memcached));
+        let charged = points(&chunked, None);
+        std::fs::remove_file(&small).unwrap();
+        std::fs::remove_file(&chunked).unwrap();
+
+        assert_eq!(quantized, charged);
+        assert_eq!(quantized.len(), 4);
+        for (quantized, requested) in quantized.iter().zip(requested.iter()) {
+            assert_eq!(quantized.0, requested.0);
+            assert!(quantized.1 > requested.1, "{quantized:?} vs {requested:?}");
+        }
+    }
+
+    #[test]
+    fn capacity_past_the_footprint_ends_at_the_compulsory_misses() {
+        let mut config = Config {
+            trace: Some(PathBuf::from("data/golden/twitter_sample.csv")),
+            ..Default::default()
+        };
+        let (access_records, _) = config::load_access_records(&mut config).unwrap();
+        let footprint = analysis::footprint(&access_records);
+
+        let configured = 100u64 << 30;
+        let results = simulate_golden(|config| {
+            config.policies = Some(vec![EvictionPolicy::LRU, EvictionPolicy::FIFO]);
+            config.cache_size = Some(vec!["100GB".parse().unwrap()]);
+        });
+        assert_eq!(results.len(), 2);
+        for result in results.iter() {
+            let (last, simulated) = result.points.split_last().unwrap();
+            assert_eq!(
+                *last,
+                (configured as f64, footprint.compulsory_miss_ratio())
+            );
+            assert!(simulated
+                .iter()
+                .all(|(size, _)| *size <= footprint.unique_bytes as f64));
+            // The largest simulated size, a grid step short of the footprint,
+            // already holds about every object
+            let (size, miss_ratio) = simulated.last().unwrap();
+            assert!(*size > 0.99 * footprint.unique_bytes as f64, "{size}");
+            assert_eq!(*miss_ratio, footprint.compulsory_miss_ratio());
+        }
+    }
+
+    #[test]
+    fn curves_run_from_all_misses_to_the_compulsory_misses() {
+        let mut records = synthetic::ZipfTrace {
+            requests: 50_000,
+            keys: 5_000,
+            alpha: 0.9,
+            min_size: 100,
+            max_size: 10_000,
+            seed: 5,
+        }
+        .generate();
+        // Policies admit an object larger than the cache, which then serves a
+        // repeat of it, so a trace without repeats misses every request of
+        // the smallest cache
+        records.dedup_by_key(|record| record.key);
+        let requests = records.len();
+        let footprint = analysis::footprint(&records);
+        let config: Config = toml::from_str(
+            "policies = [\"LRU\", \"FIFO\", \"LFU\"]\ncache_size = [\"0\", \"1024GB\"]\n\
+             annotate_asymptotes = true",
+        )
+        .unwrap();
+        let mut args = InnerConfig::try_from(config).unwrap();
+        args.resolve_cache_sizes(Some(footprint.unique_bytes))
+            .unwrap();
+        let (results, _, failures) = run_simulations(Arc::new(records), &args, &footprint, None);
+        assert!(failures.is_empty(), "{failures:?}");
+        assert_eq!(results.len(), 3);
+        for result in results.iter() {
+            // Size 0 is simulated as 1 byte
+            assert_eq!(result.points[0], (1.0, 1.0));
+            let (_, last) = *result.points.last().unwrap();
+            assert!(
+                (last - footprint.compulsory_miss_ratio()).abs() < 1e-12,
+                "{last}"
+            );
+        }
+        assert_eq!(
+            footprint.compulsory_miss_ratio(),
+            footprint.unique_keys as f64 / requests as f64
+        );
+    }
+
+    // Log lines written by `f` on this thread
+    fn logs_of<T>(f: impl FnOnce() -> T) -> (T, String) {
+        #[derive(Clone, Default)]
+        struct Buffer(Arc<Mutex<Vec<u8>>>);
+        impl std::io::Write for Buffer {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .finish();
+        let value = tracing::subscriber::with_default(subscriber, f);
+        let logs = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        (value, logs)
+    }
+
+    #[test]
+    fn rate_that_samples_nothing_warns_and_draws_no_nan() {
+        let records = synthetic::ZipfTrace {
+            requests: 1_000,
+            keys: 100,
+            alpha: 0.8,
+            min_size: 100,
+            max_size: 1_000,
+            seed: 17,
+        }
+        .generate();
+        let config: Config = toml::from_str(
+            "policies = [\"LRU\"]\ncache_size = \"0.5ws\"\nshards_rates = [1e-9]\nsequential = true",
+        )
+        .unwrap();
+        let mut args = InnerConfig::try_from(config).unwrap();
+        let footprint = analysis::footprint(&records);
+        args.resolve_cache_sizes(Some(footprint.unique_bytes))
+            .unwrap();
+        let ((results, exact, failures), logs) =
+            logs_of(|| run_simulations(Arc::new(records), &args, &footprint, None));
+
+        // Only the exact curve is left, and it is all numbers
+        assert_eq!(results.len(), 1);
+        assert_eq!(exact.len(), 1);
+        assert!(results[0]
+            .points
+            .iter()
+            .all(|(size, miss_ratio)| size.is_finite() && miss_ratio.is_finite()));
+        match &failures[..] {
+            [SimError::Undersampled { sampled, .. }] => assert_eq!(sampled.keys, 0),
+            other => panic!("{other:?}"),
+        }
+        assert!(logs.contains("sampled 0 keys"), "{logs}");
+        assert!(logs.contains("raise the rate"), "{logs}");
+    }
+
+    #[test]
+    fn max_time_draws_the_partial_curve_and_logs_its_coverage() {
+        let records = synthetic::ZipfTrace {
+            requests: 2_000_000,
+            keys: 200_000,
+            alpha: 0.8,
+            min_size: 100,
+            max_size: 10_000,
+            seed: 3,
+        }
+        .generate();
+        let mut config: Config =
+            toml::from_str("policies = [\"LRU\"]\ncache_size = \"0.5ws\"\nsequential = true")
+                .unwrap();
+        config.num_sizes = Some(100);
+        let mut args = InnerConfig::try_from(config).unwrap();
+        args.max_time = Some(Duration::from_millis(50));
+        let footprint = analysis::footprint(&records);
+        args.resolve_cache_sizes(Some(footprint.unique_bytes))
+            .unwrap();
+        let ((results, _, failures), logs) =
+            logs_of(|| run_simulations(Arc::new(records), &args, &footprint, None));
+
+        assert!(failures.is_empty(), "{failures:?}");
+        assert_eq!(results.len(), 1);
+        let partial = &results[0];
+        assert_eq!(partial.label, "LRU (partial)");
+        assert!(partial.requests > 0 && partial.requests < 2_000_000);
+        assert!(!partial.points.is_empty());
+        assert!(partial
+            .points
+            .iter()
+            .all(|(_, miss_ratio)| (0.0..=1.0).contains(miss_ratio)));
+        let covered = partial.requests as f64 / 2_000_000.0 * 100.0;
+        assert!(
+            logs.contains(&format!(
+                "LRU ran out of time, drawing its curve over the first {covered:.1}% of the trace"
+            )),
+            "{logs}"
+        );
+    }
+
+    #[test]
+    fn stack_distance_curves_match_simulated_ones() {
+        for sample_rate in [None, Some(0.1)] {
+            let run = |policies: Vec<EvictionPolicy>, stack_distance| {
+                simulate_golden(|config| {
+                    config.policies = Some(policies);
+                    config.capacity_units = Some(vec![CapacityUnit::Objects, CapacityUnit::Bytes]);
+                    config.sample_rate = sample_rate;
+                    config.stack_distance = stack_distance;
+                })
+            };
+            let mixed = run(vec![EvictionPolicy::LRU, EvictionPolicy::FIFO], true);
+            let mut separate = run(vec![EvictionPolicy::LRU], false);
+            separate.extend(run(vec![EvictionPolicy::FIFO], true));
+            assert_eq!(mixed.len(), 4);
+            for (mixed, separate) in mixed.iter().zip(separate.iter()) {
+                assert_eq!(mixed.label, separate.label, "SHARDS {sample_rate:?}");
+                assert_eq!(
+                    mixed.points, separate.points,
+                    "{} with SHARDS {sample_rate:?}",
+                    mixed.label
+                );
+            }
+        }
+    }
+
+    // LRU that counts the simulations running a cache operation at the same
+    // time, and the threads they run on, for lack of observer hooks
+    struct ProbePolicy(LruPolicy);
+
+    static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+    static MAX_IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+    static THREADS: Mutex<Vec<std::thread::ThreadId>> = Mutex::new(Vec::new());
+
+    impl ProbePolicy {
+        fn observe<T>(&mut self, operation: impl FnOnce(&mut LruPolicy) -> T) -> T {
+            let in_flight = IN_FLIGHT.fetch_add(1, Ordering::SeqCst) + 1;
+            MAX_IN_FLIGHT.fetch_max(in_flight, Ordering::SeqCst);
+            let mut threads = THREADS.lock().unwrap();
+            let id = std::thread::current().id();
+            if !threads.contains(&id) {
+                threads.push(id);
+            }
+            drop(threads);
+            let result = operation(&mut self.0);
+            IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+            result
+        }
+    }
+
+    impl NewPolicy for ProbePolicy {
+        fn new(capacity: u64) -> Self {
+            ProbePolicy(LruPolicy::new(capacity))
+        }
+    }
+
+    impl EvictPolicy for ProbePolicy {
+        fn get(&mut self, key: Key) -> Option<()> {
+            self.observe(|lru| lru.get(key))
+        }
+
+        fn contains(&self, key: Key) -> bool {
+            self.0.contains(key)
+        }
+
+        fn resident(&self) -> Box<dyn Iterator<Item = (Key, u64)> + '_> {
+            self.0.resident()
+        }
+
+        fn put(&mut self, key: Key, size: u64) {
+            self.observe(|lru| lru.put(key, size))
+        }
+
+        fn remove(&mut self, key: Key) -> Option<u64> {
+            self.0.remove(key)
+        }
+
+        fn update_size(&mut self, key: Key, new_size: u64) -> bool {
+            self.0.update_size(key, new_size)
+        }
+    }
+
+    #[test]
+    fn simulations_stay_within_the_thread_budget() {
+        evict_policy::register_policy(
+            "PROBE",
+            Box::new(|capacity, _| Box::new(ProbePolicy::new(capacity))),
+        );
+        let probe = |n: f64| EvictionPolicy::Registered {
+            name: "PROBE".to_string(),
+            params: [("n".to_string(), n)].into_iter().collect(),
+        };
+        // 32 curves of one job each, then 2 curves each split over 2 jobs
+        // by a nested parallel iterator
+        let runs = [
+            (
+                vec![probe(1.0), probe(2.0), probe(3.0), probe(4.0)],
+                Some(vec![0.5, 0.2, 0.1]),
+                32,
+            ),
+            (vec![probe(1.0)], None, 2),
+        ];
+        for (policies, shards_rates, curves) in runs {
+            MAX_IN_FLIGHT.store(0, Ordering::SeqCst);
+            THREADS.lock().unwrap().clear();
+            let results = simulate_golden(|config| {
+                config.policies = Some(policies);
+                config.capacity_units = Some(vec![CapacityUnit::Objects, CapacityUnit::Bytes]);
+                config.shards_rates = shards_rates;
+                config.threads = Some(4);
+                config.parallel_strategy = Some(ParallelStrategy::PerSize);
+            });
+            assert_eq!(results.len(), curves);
+            let max_in_flight = MAX_IN_FLIGHT.load(Ordering::SeqCst);
+            let threads = THREADS.lock().unwrap().len();
+            assert!(max_in_flight <= 4, "{max_in_flight} simulations at once");
+            assert!(threads <= 4, "simulations ran on {threads} threads");
+        }
+    }
+
+    #[test]
+    fn sequential_runs_export_what_the_pool_exports() {
+        let exports = |sequential: bool| {
+            simulate_golden(|config| {
+                config.sequential = sequential;
+                config.threads = (!sequential).then_some(4);
+                config.policies = Some(vec![
+                    EvictionPolicy::LRU,
+                    EvictionPolicy::FIFO,
+                    EvictionPolicy::LFU,
+                    EvictionPolicy::SLRU { protected: 0.8 },
+                ]);
+                config.capacity_units = Some(vec![CapacityUnit::Objects, CapacityUnit::Bytes]);
+                config.shards_rates = Some(vec![0.5]);
+                config.churn = true;
+                config.segments = Some(4);
+            })
+            .iter()
+            .map(|result| serde_json::to_string(&export::CurveExport::from_result(result)).unwrap())
+            .collect::<Vec<_>>()
+        };
+        let pool = exports(false);
+        // 4 policies by 2 units, exact and sampled
+        assert_eq!(pool.len(), 16);
+        assert_eq!(exports(true), pool);
+    }
+
+    #[test]
+    fn every_parallel_strategy_gives_the_same_curves() {
+        let curves = |strategy| {
+            simulate_golden(|config| {
+                config.policies = Some(vec![
+                    EvictionPolicy::LRU,
+                    EvictionPolicy::FIFO,
+                    EvictionPolicy::LFU,
+                ]);
+                config.capacity_units = Some(vec![CapacityUnit::Objects, CapacityUnit::Bytes]);
+                config.shards_rates = Some(vec![0.5, 0.2]);
+                config.threads = Some(4);
+                config.parallel_strategy = Some(strategy);
+            })
+            .into_iter()
+            .map(|result| (result.label, result.points))
+            .collect::<Vec<_>>()
+        };
+        let per_policy = curves(ParallelStrategy::PerPolicy);
+        assert_eq!(per_policy.len(), 18);
+        for strategy in [
+            ParallelStrategy::PerSize,
+            ParallelStrategy::NestedShards,
+            ParallelStrategy::Auto,
+        ] {
+            assert_eq!(curves(strategy), per_policy, "{strategy}");
+        }
+    }
+
+    #[test]
+    fn auto_parallel_strategy_resolves_to_a_valid_one() {
+        use ParallelStrategy::*;
+        // Curves, SHARDS rates per policy and unit, threads, records, unique
+        // keys, and the strategy auto should pick
+        let cases = [
+            (2, 1, 8, 1_000_000, 10_000, PerSize),
+            // Too few records to be worth splitting
+            (2, 1, 8, 1_000, 10_000, PerPolicy),
+            // Every thread already has a curve
+            (16, 1, 8, 1_000_000, 10_000, PerPolicy),
+            // 8 running curves would hold 12.8GB of seen keys
+            (16, 4, 8, 1_000_000, 100_000_000, NestedShards),
+            // No rates to nest
+            (16, 1, 8, 1_000_000, 100_000_000, PerPolicy),
+            // Splitting would copy the keys, nesting bounds them
+            (2, 2, 8, 1_000_000, 100_000_000, NestedShards),
+        ];
+        for (curves, rates, threads, records, unique_keys, expected) in cases {
+            let (strategy, jobs_per_curve) =
+                choose_parallel_strategy(Auto, curves, rates, threads, records, unique_keys);
+            assert_eq!(strategy, expected, "{curves} curves on {threads} threads");
+            match strategy {
+                PerSize => assert!(
+                    jobs_per_curve > 1
+                        && jobs_per_curve * curves >= threads
+                        && jobs_per_curve as u64 <= NUM_CACHE_SIZE
+                ),
+                _ => assert_eq!(jobs_per_curve, 1),
+            }
+        }
+    }
+
+    #[test]
+    fn every_policy_variant_simulates() {
+        evict_policy::register_policy(
+            "PLUGIN_FIFO",
+            Box::new(|capacity, _| Box::new(evict_policy::FifoPolicy::new(capacity))),
+        );
+        let policies = [
+            "LRU",
+            "FIFO",
+            "LFU",
+            "TWOQ",
+            "LARGEST",
+            "SLRU(protected=0.5)",
+            "LRUFQ(threshold=2)",
+            "FROZENHOT(hot=0.5;rebuild=1000)",
+            "PLUGIN_FIFO",
+            "LRU(watermark=0.9)",
+        ]
+        .map(|spec| EvictionPolicy::try_from(spec.to_string()).unwrap());
+        // A new variant fails to compile here until it is listed above
+        let covered = policies
+            .iter()
+            .map(|policy| match policy {
+                EvictionPolicy::LRU
+                | EvictionPolicy::FIFO
+                | EvictionPolicy::LFU
+                | EvictionPolicy::TWOQ
+                | EvictionPolicy::LARGEST
+                | EvictionPolicy::SLRU { .. }
+                | EvictionPolicy::LRUFQ { .. }
+                | EvictionPolicy::FROZENHOT { .. }
+                | EvictionPolicy::Registered { .. }
+                | EvictionPolicy::Watermark { .. } => std::mem::discriminant(policy),
+            })
+            .collect::<HashSet<_>>();
+        assert_eq!(covered.len(), policies.len());
+
+        let results = simulate_golden(|config| {
+            config.policies = Some(policies.to_vec());
+            config.num_sizes = Some(20);
+        });
+        assert_eq!(results.len(), policies.len());
+        for (result, policy) in results.iter().zip(policies.iter()) {
+            assert_eq!(result.label, policy.to_string());
+            assert_eq!(result.points.len(), 20, "{}", result.label);
+            assert!(
+                result
+                    .points
+                    .iter()
+                    .all(|(_, miss_ratio)| (0.0..=1.0).contains(miss_ratio)),
+                "{}: {:?}",
+                result.label,
+                result.points
+            );
+            // Every policy hits more in the largest cache than in the smallest
+            let (first, last) = (result.points[0].1, result.points[19].1);
+            assert!(last < first, "{}: {first} to {last}", result.label);
+        }
+    }
+
+    #[test]
+    fn bounded_log_sweep_starts_and_ends_at_its_bounds() {
+        let results = simulate_golden(|config| {
+            config.policies = Some(vec![EvictionPolicy::LRU]);
+            config.cache_size = None;
+            config.min_cache_size = Some(1 << 20);
+            config.max_cache_size = Some(16 << 20);
+            config.num_sizes = Some(10);
+            config.size_spacing = Some(config::SizeSpacing::Log);
+        });
+        let sizes = results[0]
+            .points
+            .iter()
+            .map(|(size, _)| *size)
+            .collect::<Vec<_>>();
+        assert_eq!(sizes.len(), 10);
+        assert_eq!(sizes[0], (1 << 20) as f64);
+        assert_eq!(sizes[9], (16 << 20) as f64);
+        // Every step multiplies the size by the same 16^(1/9), up to rounding
+        for pair in sizes.windows(2) {
+            let ratio = pair[1] / pair[0];
+            assert!((ratio - 16f64.powf(1.0 / 9.0)).abs() < 1e-5, "{sizes:?}");
+        }
+    }
+    #[test]
+    fn size_weighted_shards_estimates_byte_curves_better_than_uniform() {
+        // Mean absolute error of the 10% SHARDS byte curve of LRU against
+        // the exact one on `records`, sampling keys with `weighting`
+        let error = |records: &[AccessRecord], weighting: &str| {
+            let mut config: Config = toml::from_str(&format!(
+                "policies = [\"LRU\"]\ncache_size = \"0.3ws\"\nshards_rates = [0.1]\n\
+                 shards_weighting = \"{weighting}\"\nbyte_miss_ratio = true"
+            ))
+            .unwrap();
+            config.num_sizes = Some(20);
+            let mut args = InnerConfig::try_from(config).unwrap();
+            let footprint = analysis::footprint(records);
+            args.resolve_cache_sizes(Some(footprint.unique_bytes))
+                .unwrap();
+            let (results, _, failures) =
+                run_simulations(Arc::new(records.to_vec()), &args, &footprint, None);
+            assert!(failures.is_empty(), "{failures:?}");
+            let (exact, sampled) = (&results[0], &results[1]);
+            assert_eq!(exact.label, "LRU exact");
+            exact
+                .points
+                .iter()
+                .zip(sampled.points.iter())
+                .map(|((_, exact), (_, sampled))| (exact - sampled).abs())
+                .sum::<f64>()
+                / exact.points.len() as f64
+        };
+        let (mut uniform, mut size_weighted) = (0.0, 0.0);
+        for seed in 0..4 {
+            // Sizes log-uniform from 100B to 4MB, most bytes are in few keys
+            let records = synthetic::ZipfTrace {
+                requests: 200_000,
+                keys: 20_000,
+                alpha: 0.9,
+                min_size: 100,
+                max_size: 4_000_000,
+                seed: 10 + seed,
+            }
+            .generate();
+            uniform += error(&records, "uniform") / 4.0;
+            size_weighted += error(&records, "size") / 4.0;
+        }
+        assert!(
+            size_weighted < uniform,
+            "size-weighted mean error {size_weighted:.3}, uniform {uniform:.3}"
+        );
+    }
+    #[test]
+    fn prometheus_file_is_written_per_curve_during_the_run() {
+        let dir = std::env::temp_dir().join(format!("cache_mrc_prometheus_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        simulate_golden(|config| {
+            config.policies = Some(vec![EvictionPolicy::LRU, EvictionPolicy::FIFO]);
+            config.prometheus_file = Some(dir.join("metrics.prom"));
+            config.prometheus_every = Some(1000);
+        });
+        for id in ["lru", "fifo"] {
+            let text = std::fs::read_to_string(dir.join(format!("metrics_{id}.prom"))).unwrap();
+            let label = id.to_uppercase();
+            // The last write is at the last of the 10000 requests
+            assert!(
+                text.contains(&format!(
+                    "cache_mrc_processed_requests{{policy=\"{label}\"}} 10000\n"
+                )),
+                "{text}"
+            );
+            let windows = text
+                .lines()
+                .filter(|line| line.starts_with("cache_mrc_window_byte_hit_ratio{"))
+                .count();
+            assert_eq!(windows, NUM_CACHE_SIZE as usize);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
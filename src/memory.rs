@@ -0,0 +1,174 @@
+// Peak bytes allocated by each simulation, to compare what the policies cost
+// to simulate. The allocations are only tracked with the `memory` cargo
+// feature, which installs `TrackingAllocator` as the global allocator; without
+// it `PeakTracker` measures nothing.
+//
+// The counts are per thread, a simulation runs on one worker so the curves on
+// the other workers don't show up in its peak.
+
+#[cfg(feature = "memory")]
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+#[cfg(feature = "memory")]
+thread_local! {
+    // Bytes allocated minus bytes freed on this thread
+    static CURRENT: Cell<isize> = const { Cell::new(0) };
+    // Highest CURRENT since the innermost PeakTracker started
+    static PEAK: Cell<isize> = const { Cell::new(0) };
+    // Allocations and growing reallocations made on this thread
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+#[cfg(feature = "memory")]
+fn record(delta: isize) {
+    // Allocations while the thread is torn down are not tracked
+    if delta > 0 {
+        let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+    }
+    let _ = CURRENT.try_with(|current| {
+        let now = current.get() + delta;
+        current.set(now);
+        let _ = PEAK.try_with(|peak| peak.set(peak.get().max(now)));
+    });
+}
+
+// The system allocator, counting the bytes of every thread
+#[cfg(feature = "memory")]
+pub struct TrackingAllocator;
+
+#[cfg(feature = "memory")]
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            record(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            record(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        record(-(layout.size() as isize));
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            record(new_size as isize - layout.size() as isize);
+        }
+        new_ptr
+    }
+}
+
+#[cfg(feature = "memory")]
+#[global_allocator]
+static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+
+// Allocations made on this thread so far, None without the `memory` feature
+#[cfg(feature = "memory")]
+pub fn allocations() -> Option<u64> {
+    Some(ALLOCATIONS.with(Cell::get))
+}
+
+#[cfg(not(feature = "memory"))]
+pub fn allocations() -> Option<u64> {
+    None
+}
+
+// Peak of the bytes allocated on this thread since `start`, freed bytes
+// included until they are freed
+#[cfg(feature = "memory")]
+pub struct PeakTracker {
+    baseline: isize,
+    // Peak of the enclosing tracker, restored on drop
+    outer_peak: isize,
+}
+
+#[cfg(feature = "memory")]
+impl PeakTracker {
+    pub fn start() -> Self {
+        let baseline = CURRENT.with(Cell::get);
+        let outer_peak = PEAK.with(|peak| peak.replace(baseline));
+        PeakTracker {
+            baseline,
+            outer_peak,
+        }
+    }
+
+    pub fn peak(&self) -> Option<u64> {
+        Some(PEAK.with(Cell::get).saturating_sub(self.baseline).max(0) as u64)
+    }
+}
+
+#[cfg(feature = "memory")]
+impl Drop for PeakTracker {
+    fn drop(&mut self) {
+        let outer_peak = self.outer_peak;
+        PEAK.with(|peak| peak.set(peak.get().max(outer_peak)));
+    }
+}
+
+#[cfg(not(feature = "memory"))]
+pub struct PeakTracker;
+
+#[cfg(not(feature = "memory"))]
+impl PeakTracker {
+    #[inline(always)]
+    pub fn start() -> Self {
+        PeakTracker
+    }
+
+    #[inline(always)]
+    pub fn peak(&self) -> Option<u64> {
+        None
+    }
+}
+
+#[cfg(all(test, feature = "memory"))]
+mod tests {
+    use super::*;
+    use crate::{
+        evict_policy::{FifoPolicy, LfuPolicy, NewPolicy},
+        minisim::MiniSim,
+        AccessRecord,
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    // Peak bytes of building the caches and replaying a skewed trace
+    fn peak<P: NewPolicy>() -> u64 {
+        let mut rng = StdRng::seed_from_u64(0);
+        let tracker = PeakTracker::start();
+        let mut sim = MiniSim::<P>::new(4 * 1024 * 1024, None).unwrap();
+        for timestamp in 0..20_000 {
+            let key = rng.gen_range(0..5_000u64).min(rng.gen_range(0..5_000));
+            sim.handle(&AccessRecord {
+                timestamp,
+                command: 0,
+                key,
+                size: 100,
+                ttl: 0,
+            });
+        }
+        drop(sim);
+        tracker.peak().unwrap()
+    }
+
+    #[test]
+    fn lfu_peaks_higher_than_fifo() {
+        // LFU keeps a list of keys per frequency next to its key map
+        let (fifo, lfu) = (peak::<FifoPolicy>(), peak::<LfuPolicy>());
+        assert!(fifo > 0);
+        assert!(lfu > fifo, "LFU peaked at {lfu} bytes, FIFO at {fifo}");
+    }
+}
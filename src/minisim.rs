@@ -1,94 +1,1971 @@
-use crate::{evict_policy::EvictPolicy, shards::Shards, AccessRecord, Key, NUM_CACHE_SIZE};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use hashbrown::{HashMap, HashSet};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use tracing::warn;
+
+use crate::{
+    analysis::{LruStack, SizeClass, SizeClassCounter, TimeBucket, TimeBucketCounter},
+    audit::{Eviction, EvictionReason},
+    cancel::CancellationToken,
+    config::CapacityUnit,
+    error::SimError,
+    evict_policy::{EvictPolicy, NewPolicy},
+    shards::Shards,
+    timing::Timer,
+    AccessRecord, Churn, Key, SampleCounts, NUM_CACHE_SIZE,
+};
+
+// Picks the requests that only probe the caches: they are counted as hits or
+// misses on their own but never admit, promote or evict anything
+#[derive(Debug, Clone)]
+pub struct ProbeSelector {
+    commands: Vec<u8>,
+    rate: f64,
+    // Fixed seed so every policy probes the same requests
+    rng: StdRng,
+}
+
+impl ProbeSelector {
+    // Requests whose command is in `commands`, plus a `rate` fraction of the
+    // others picked at random. None when nothing would be probed.
+    pub fn new(commands: Vec<u8>, rate: Option<f64>) -> Option<Self> {
+        let rate = rate.unwrap_or(0.0).clamp(0.0, 1.0);
+        (!commands.is_empty() || rate > 0.0).then(|| ProbeSelector {
+            commands,
+            rate,
+            rng: StdRng::seed_from_u64(0),
+        })
+    }
+
+    fn is_probe(&mut self, request: Request) -> bool {
+        self.commands.contains(&request.command)
+            || (self.rate > 0.0 && self.rng.gen_bool(self.rate))
+    }
+}
+
+// What a request does to the caches, by its command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Read,
+    Write,
+    Delete,
+}
+
+// The fields of a record the simulation reads, worked out once by `handle`
+// and passed by value from there down to the loop over the cache sizes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Request {
+    key: Key,
+    size: u32,
+    command: u8,
+    timestamp: u64,
+    // See `MiniSim::expires_at`
+    expires_at: u64,
+    op: Op,
+}
+
+// Keys of one cache by the bucket of trace time they expire in, see
+// `with_proactive_expiry`. Entries of keys rewritten or removed since they
+// were inserted are stale and skipped when their bucket is swept.
+#[derive(Debug, Clone)]
+struct ExpiryWheel {
+    granularity: u64,
+    buckets: BTreeMap<u64, Vec<(Key, u64)>>,
+}
+
+impl ExpiryWheel {
+    fn insert(&mut self, key: Key, expires_at: u64) {
+        if expires_at != u64::MAX {
+            let bucket = expires_at / self.granularity;
+            self.buckets
+                .entry(bucket)
+                .or_default()
+                .push((key, expires_at));
+        }
+    }
+
+    // The entries of every bucket whose last timestamp is past at `timestamp`,
+    // each swept at most granularity - 1 after it expired
+    fn expired(&mut self, timestamp: u64) -> Vec<(Key, u64)> {
+        let mut expired = Vec::new();
+        while let Some(bucket) = self.buckets.first_entry() {
+            if (bucket.key() + 1).saturating_mul(self.granularity) > timestamp.saturating_add(1) {
+                break;
+            }
+            expired.extend(bucket.remove());
+        }
+        expired
+    }
+}
+
+// Bytes put into, deleted from and expired out of every cache, see
+// `with_churn`. Evictions are what the puts left behind minus those and what
+// is still resident.
+#[derive(Debug, Clone, Default)]
+struct ChurnCounter {
+    // Per cache, bytes of the --warm-start snapshot it was loaded with
+    warmed: Vec<f64>,
+    admitted: Vec<f64>,
+    deleted: Vec<f64>,
+    // Removed by --respect-ttl on a read or by the proactive expiry sweep
+    expired: Vec<f64>,
+    // Bytes of the last value of every key, over its sampling probability
+    bytes: HashMap<Key, f64>,
+}
+
+impl ChurnCounter {
+    fn resident_bytes(&self, cache: &impl EvictPolicy) -> f64 {
+        cache
+            .resident()
+            .filter_map(|(key, _)| self.bytes.get(&key))
+            .sum()
+    }
+}
+
+// Every object that left one cache and why, in trace order, see `with_eviction_log`
+#[derive(Debug, Clone)]
+struct EvictionLog {
+    cache: usize,
+    // Index in the trace of the request that last admitted every resident key
+    admitted: HashMap<Key, usize>,
+    evictions: Vec<Eviction>,
+}
+
+impl EvictionLog {
+    fn admit(&mut self, cache: usize, key: Key, access_index: usize) {
+        if cache == self.cache {
+            self.admitted.insert(key, access_index);
+        }
+    }
+
+    fn remove(&mut self, cache: usize, key: Key, access_index: usize, reason: EvictionReason) {
+        if cache != self.cache {
+            return;
+        }
+        let admitted = self.admitted.remove(&key).unwrap_or(access_index);
+        self.evictions.push(Eviction {
+            access_index,
+            key,
+            reason,
+            age: access_index - admitted,
+        });
+    }
+}
+
+// Hits and requests of every cache in contiguous segments of the trace, for
+// the spread of the miss ratio across segments
+#[derive(Debug, Clone)]
+struct SegmentCounter {
+    // Requests in the trace, sampled or not, to place each one in its segment
+    trace_len: u64,
+    requests: Vec<u64>,
+    // Per segment, the hits of every cache
+    hits: Vec<Vec<u64>>,
+}
+
+impl SegmentCounter {
+    fn new(segments: usize, trace_len: u64, caches: usize) -> Self {
+        SegmentCounter {
+            trace_len: trace_len.max(1),
+            requests: vec![0; segments],
+            hits: vec![vec![0; caches]; segments],
+        }
+    }
+
+    // Segment of the `handled`th request of the trace, counting from 1
+    fn segment(&self, handled: u64) -> usize {
+        let segments = self.requests.len() as u64;
+        (((handled - 1) * segments / self.trace_len) as usize).min(self.requests.len() - 1)
+    }
+
+    // Standard error of the mean miss ratio over the segments with requests,
+    // for every cache. Empty with fewer than two such segments.
+    fn standard_errors(&self) -> Vec<f64> {
+        let counted = self
+            .requests
+            .iter()
+            .zip(self.hits.iter())
+            .filter(|(requests, _)| **requests > 0)
+            .collect::<Vec<_>>();
+        if counted.len() < 2 {
+            return Vec::new();
+        }
+        let n = counted.len() as f64;
+        (0..self.hits[0].len())
+            .map(|i| {
+                let ratios = counted
+                    .iter()
+                    .map(|(requests, hits)| 1.0 - hits[i] as f64 / **requests as f64)
+                    .collect::<Vec<_>>();
+                let mean = ratios.iter().sum::<f64>() / n;
+                let variance = ratios
+                    .iter()
+                    .map(|ratio| (ratio - mean).powi(2))
+                    .sum::<f64>()
+                    / (n - 1.0);
+                (variance / n).sqrt()
+            })
+            .collect()
+    }
+}
+
+// Snapshots of one cache's resident objects every `every` requests, the
+// frames of an animation of its contents
+#[derive(Debug, Clone)]
+struct Timeline {
+    cache: usize,
+    every: u64,
+    path: PathBuf,
+    // Request each key was last put at
+    put_at: HashMap<Key, u64>,
+    frames: Vec<TimelineFrame>,
+}
+
+// Byte hit ratio at every cache size over the last complete window of
+// requests, see `with_byte_hit_window`
+struct ByteHitWindow {
+    requests: u64,
+    // Weighted bytes requested and hit at every cache size when the open
+    // window started
+    start_bytes: f64,
+    start_hit_bytes: Vec<f64>,
+    last: Option<Vec<f64>>,
+}
+
+impl ByteHitWindow {
+    // Close the open window at the weighted totals so far and open the next
+    fn close(&mut self, bytes: f64, hit_bytes: &[f64]) {
+        let requested = bytes - self.start_bytes;
+        // A window SHARDS sampled nothing from says nothing, keep the last one
+        if requested > 0.0 {
+            self.last = Some(
+                hit_bytes
+                    .iter()
+                    .zip(self.start_hit_bytes.iter())
+                    .map(|(hit, start)| ((hit - start) / requested).clamp(0.0, 1.0))
+                    .collect(),
+            );
+        }
+        self.start_bytes = bytes;
+        self.start_hit_bytes.copy_from_slice(hit_bytes);
+    }
+}
+
+// Where `handle` rewrites the Prometheus text of the simulation, and how often
+struct PrometheusFile {
+    path: PathBuf,
+    every: u64,
+    policy: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TimelineFrame {
+    request: u64,
+    objects: u64,
+    bytes: u64,
+    // Requests since the resident objects were put
+    mean_age: f64,
+    max_age: u64,
+    miss_ratio: f64,
+}
 
 pub struct MiniSim<P: EvictPolicy> {
-    max_cache_size: u64,
+    // Cache size each curve point is reported at
+    cache_sizes: Vec<u64>,
+    // Capacity each cache was built with, scaled for SHARDS
+    capacities: Vec<u64>,
+    // Counts the hits of an LRU at every capacity instead of running the caches
+    lru_stack: Option<LruStack>,
     caches: Vec<P>,
     hits: Vec<u64>,
+    misses: Vec<u64>,
+    // Hits over the probability each was sampled with, for size-weighted SHARDS
+    estimated_hits: Vec<f64>,
+    // Report the fraction of requested bytes that miss instead of requests
+    byte_miss_ratio: bool,
+    // Bytes of every request handed to `handle`, sampled or not
+    trace_bytes: u64,
+    // Bytes hit over the probability each was sampled with
+    estimated_hit_bytes: Vec<f64>,
+    // Requests and bytes simulated, each over the probability it was sampled
+    // with, the estimates the weighted hits are a fraction of
+    estimated_requests: f64,
+    estimated_bytes: f64,
     access_count: u64,
+    // Requests handed to `handle`, sampled or not
+    handled: u64,
+    // Validate every cache after each request, see EvictPolicy::debug_validate
+    strict_invariants: bool,
     shards: Option<Box<dyn Shards>>,
-    shards_global_t: u64,
+    // Fewest keys and requests SHARDS must sample for a curve, see `undersampled`
+    min_sampled: SampleCounts,
+    capacity_unit: CapacityUnit,
+    respect_ttl: bool,
+    // Per cache, the timestamp each key expires at, see `expires_at`
+    expirations: Vec<HashMap<Key, u64>>,
+    // Per cache, with proactive expiry, the keys by when they expire
+    expiry_wheels: Vec<ExpiryWheel>,
+    // Only these keys may be cached, the rest always miss and are never admitted
+    cacheable_keys: Option<Arc<HashSet<Key>>>,
+    bypassed: u64,
+    // Keys put so far and the sum of their first seen sizes
+    seen_keys: HashSet<Key>,
+    working_set_bytes: u64,
+    // Index of the cache whose requests are counted per object size class
+    size_classes: Option<(usize, SizeClassCounter)>,
+    // Index of the cache whose miss ratio is counted per span of trace time
+    time_buckets: Option<(usize, TimeBucketCounter)>,
+    probes: Option<ProbeSelector>,
+    // Commands whose requests write or delete the object instead of reading it
+    write_commands: Vec<u8>,
+    delete_commands: Vec<u8>,
+    probe_count: u64,
+    probe_hits: Vec<u64>,
+    // Hits per segment of the trace, see `standard_errors`
+    segments: Option<SegmentCounter>,
+    // Cache size and file the resident objects are written to after a run
+    contents_dump: Option<(u64, PathBuf)>,
+    timeline: Option<Timeline>,
+    byte_hit_window: Option<ByteHitWindow>,
+    prometheus_file: Option<PrometheusFile>,
+    eviction_log: Option<EvictionLog>,
+    churn: Option<ChurnCounter>,
+    timer: Timer,
+    cancel: CancellationToken,
 }
 
 fn get_caches<P: EvictPolicy>(
     max_cache_size: u64,
     num_caches: u64,
+    points: Range<u64>,
     shards: &Option<Box<dyn Shards>>,
-) -> Vec<P> {
-    (1..=num_caches)
-        .map(|i| {
-            let mut cache_size = (i + 1) * (max_cache_size / num_caches as u64);
-            // check cache_size > 100
-            assert!(cache_size > 100);
+    build: impl Fn(u64) -> P,
+) -> Result<Vec<(u64, P)>, SimError> {
+    points
+        .map(|point| {
+            // The size the point is reported at, see `new_with_points`
+            let mut cache_size = (point + 1) * (max_cache_size / num_caches);
+            if cache_size == 0 {
+                return Err(SimError::InvalidCapacity {
+                    capacity: max_cache_size,
+                    reason: format!(
+                        "the grid of {num_caches} sizes starts at 0, at least 1 is needed"
+                    ),
+                });
+            }
             if let Some(shards) = shards.as_ref() {
                 cache_size = shards.scale(cache_size);
             }
-            P::new(cache_size)
+            Ok((cache_size, build(cache_size)))
         })
         .collect()
 }
 
+// Policies with a default configuration, see `new_with` for the others
+impl<P: NewPolicy> MiniSim<P> {
+    pub fn new(max_cache_size: u64, shards: Option<Box<dyn Shards>>) -> Result<Self, SimError> {
+        Self::new_with(max_cache_size, shards, P::new)
+    }
+
+    // Simulate exactly the given cache sizes instead of a uniform grid
+    pub fn from_sizes(
+        cache_sizes: Vec<u64>,
+        shards: Option<Box<dyn Shards>>,
+    ) -> Result<Self, SimError> {
+        Self::from_sizes_with(cache_sizes, shards, P::new)
+    }
+}
+
 impl<P: EvictPolicy> MiniSim<P> {
-    pub fn new(max_cache_size: u64, shards: Option<Box<dyn Shards>>) -> Self {
-        let caches = get_caches(max_cache_size, NUM_CACHE_SIZE, &shards);
-        let shards_global_t = shards
-            .as_ref()
-            .map(|shards| shards.get_global_t())
-            .unwrap_or(0);
+    // Like `new`, creating every cache with `build(capacity)`, for parameterized policies
+    pub fn new_with(
+        max_cache_size: u64,
+        shards: Option<Box<dyn Shards>>,
+        build: impl Fn(u64) -> P,
+    ) -> Result<Self, SimError> {
+        Self::new_with_points(
+            max_cache_size,
+            NUM_CACHE_SIZE,
+            shards,
+            build,
+            0..NUM_CACHE_SIZE,
+        )
+    }
+
+    // Like `new_with` on a grid of `num_caches` sizes, keeping only the grid
+    // points in `points`, so that one curve can be split over several
+    // simulators with the same cache sizes
+    pub fn new_with_points(
+        max_cache_size: u64,
+        num_caches: u64,
+        shards: Option<Box<dyn Shards>>,
+        build: impl Fn(u64) -> P,
+        points: Range<u64>,
+    ) -> Result<Self, SimError> {
+        let caches = get_caches(max_cache_size, num_caches, points.clone(), &shards, build)?;
+        let cache_sizes = points
+            .map(|point| (point + 1) * (max_cache_size / num_caches))
+            .collect();
+        Ok(MiniSim::with_caches(cache_sizes, caches, shards))
+    }
 
+    pub fn from_sizes_with(
+        cache_sizes: Vec<u64>,
+        shards: Option<Box<dyn Shards>>,
+        build: impl Fn(u64) -> P,
+    ) -> Result<Self, SimError> {
+        if let Some(capacity) = cache_sizes.iter().find(|size| **size == 0) {
+            return Err(SimError::InvalidCapacity {
+                capacity: *capacity,
+                reason: "cache sizes must be positive".to_string(),
+            });
+        }
+        let caches = cache_sizes
+            .iter()
+            .map(|size| {
+                let capacity = match shards.as_ref() {
+                    Some(shards) => shards.scale(*size),
+                    None => *size,
+                };
+                (capacity, build(capacity))
+            })
+            .collect();
+        Ok(MiniSim::with_caches(cache_sizes, caches, shards))
+    }
+
+    fn with_caches(
+        cache_sizes: Vec<u64>,
+        caches: Vec<(u64, P)>,
+        shards: Option<Box<dyn Shards>>,
+    ) -> Self {
+        let (capacities, caches): (Vec<_>, Vec<_>) = caches.into_iter().unzip();
         MiniSim {
-            max_cache_size,
+            hits: vec![0; caches.len()],
+            misses: vec![0; caches.len()],
+            estimated_hits: vec![0.0; caches.len()],
+            byte_miss_ratio: false,
+            trace_bytes: 0,
+            estimated_hit_bytes: vec![0.0; caches.len()],
+            estimated_requests: 0.0,
+            estimated_bytes: 0.0,
+            probe_hits: vec![0; caches.len()],
+            cache_sizes,
+            capacities,
+            lru_stack: None,
             caches,
-            hits: vec![0; NUM_CACHE_SIZE as usize],
             access_count: 0,
+            handled: 0,
+            strict_invariants: false,
             shards,
-            shards_global_t,
+            min_sampled: SampleCounts {
+                keys: 1,
+                requests: 1,
+            },
+            capacity_unit: CapacityUnit::Bytes,
+            respect_ttl: false,
+            expirations: Vec::new(),
+            expiry_wheels: Vec::new(),
+            cacheable_keys: None,
+            bypassed: 0,
+            seen_keys: HashSet::new(),
+            working_set_bytes: 0,
+            size_classes: None,
+            time_buckets: None,
+            probes: None,
+            write_commands: Vec::new(),
+            delete_commands: Vec::new(),
+            probe_count: 0,
+            segments: None,
+            contents_dump: None,
+            timeline: None,
+            byte_hit_window: None,
+            prometheus_file: None,
+            eviction_log: None,
+            churn: None,
+            timer: Timer::new(),
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    // Measure the time spent in every policy get and put
+    #[cfg(feature = "timing")]
+    pub fn enable_timing(&mut self) {
+        self.timer.enable();
+    }
+
+    #[cfg(feature = "timing")]
+    pub fn timing_report(&self) -> Option<crate::timing::PolicyTimingReport> {
+        self.timer.report()
+    }
+
+    pub fn with_cacheable_keys(mut self, cacheable_keys: Option<Arc<HashSet<Key>>>) -> Self {
+        self.cacheable_keys = cacheable_keys;
+        self
+    }
+
+    // Count the bytes every cache admits and evicts, see `churn`
+    pub fn with_churn(mut self, enabled: bool) -> Self {
+        self.churn = enabled.then(|| ChurnCounter {
+            warmed: vec![0.0; self.caches.len()],
+            admitted: vec![0.0; self.caches.len()],
+            deleted: vec![0.0; self.caches.len()],
+            expired: vec![0.0; self.caches.len()],
+            bytes: HashMap::new(),
+        });
+        self
+    }
+
+    // Bytes admitted, evicted and expired at every cache size, scaled up from
+    // the sample with SHARDS, empty without `with_churn`. A rewritten value
+    // counts as evicted once replaced, a deleted one doesn't.
+    pub fn churn(&self) -> Vec<Churn> {
+        let Some(counter) = self.churn.as_ref() else {
+            return Vec::new();
+        };
+        self.caches
+            .iter()
+            .enumerate()
+            .map(|(i, cache)| {
+                let admitted_bytes = counter.admitted[i];
+                let expired_bytes = counter.expired[i];
+                let evicted_bytes = (counter.warmed[i] + admitted_bytes
+                    - counter.deleted[i]
+                    - expired_bytes
+                    - counter.resident_bytes(cache))
+                .max(0.0);
+                Churn {
+                    admitted_bytes,
+                    evicted_bytes,
+                    expired_bytes,
+                    admission_ratio: admitted_bytes / self.trace_bytes.max(1) as f64,
+                }
+            })
+            .collect()
+    }
+
+    // Load every cache with the `objects` of a --warm-start snapshot, in
+    // eviction order with the next victim first. They count as neither hits
+    // nor misses. Call after `with_capacity_unit`, `with_cacheable_keys` and
+    // `with_churn`, keys outside the cacheable ones are left out.
+    pub fn with_warm_start(mut self, objects: Option<&[(Key, u64)]>) -> Self {
+        let Some(objects) = objects else {
+            return self;
+        };
+        let objects = objects
+            .iter()
+            .filter(|(key, _)| {
+                self.cacheable_keys
+                    .as_ref()
+                    .is_none_or(|cacheable_keys| cacheable_keys.contains(key))
+            })
+            .map(|(key, size)| match self.capacity_unit {
+                CapacityUnit::Objects => (*key, 1),
+                CapacityUnit::Bytes => (*key, *size),
+            })
+            .collect::<Vec<_>>();
+        for cache in self.caches.iter_mut() {
+            cache.warm(&objects);
+        }
+        if let Some(counter) = self.churn.as_mut() {
+            for (key, size) in objects.iter() {
+                counter.bytes.insert(*key, *size as f64);
+            }
+            for (warmed, cache) in counter.warmed.iter_mut().zip(self.caches.iter()) {
+                *warmed = counter
+                    .bytes
+                    .iter()
+                    .filter(|(key, _)| cache.contains(**key))
+                    .map(|(_, bytes)| bytes)
+                    .sum();
+            }
+        }
+        self
+    }
+
+    // Requests for uncacheable keys, counted as misses at every size
+    pub fn bypassed(&self) -> u64 {
+        self.bypassed
+    }
+
+    pub fn access_count(&self) -> u64 {
+        self.access_count
+    }
+
+    // Treat a SHARDS curve with fewer sampled keys or requests than `min` as
+    // undersampled. The default only rejects a sample with nothing in it.
+    pub fn with_min_sampled(mut self, min: SampleCounts) -> Self {
+        self.min_sampled = min;
+        self
+    }
+
+    // Keys and requests SHARDS sampled so far, None without SHARDS
+    pub fn sample_counts(&self) -> Option<SampleCounts> {
+        self.shards.as_ref().map(|shards| SampleCounts {
+            keys: self.seen_keys.len() as u64,
+            requests: shards.get_sampled_count(),
+        })
+    }
+
+    // The SHARDS rate when it sampled fewer keys or requests than
+    // `with_min_sampled` asks for, too few to estimate the curve from
+    pub fn undersampled(&self) -> Option<f64> {
+        let shards = self.shards.as_ref()?;
+        let sampled = self.sample_counts()?;
+        (shards.get_total_count() > 0
+            && (sampled.keys < self.min_sampled.keys
+                || sampled.requests < self.min_sampled.requests))
+            .then(|| shards.get_rate())
+    }
+
+    // Bytes of distinct objects admitted so far, scaled up from the sample with SHARDS
+    pub fn expected_working_set_size(&self) -> u64 {
+        match self.shards.as_ref() {
+            Some(shards) if shards.get_rate() > 0.0 => {
+                (self.working_set_bytes as f64 / shards.get_rate()) as u64
+            }
+            _ => self.working_set_bytes,
+        }
+    }
+
+    // Index of the cache whose size is closest to `cache_size`
+    fn closest_cache(&self, cache_size: u64) -> usize {
+        (0..self.cache_sizes.len())
+            .min_by_key(|i| self.cache_sizes[*i].abs_diff(cache_size))
+            .unwrap()
+    }
+
+    // Count requests and hits per object size class on the cache closest to
+    // `cache_size`, see `size_classes`
+    pub fn with_size_classes(mut self, cache_size: u64, edges: Option<&[u64]>) -> Self {
+        self.size_classes = Some((self.closest_cache(cache_size), SizeClassCounter::new(edges)));
+        self
+    }
+
+    // The cache size counted by `with_size_classes` and its size classes,
+    // scaled up from the sample with SHARDS
+    pub fn size_classes(&self) -> Option<(u64, Vec<SizeClass>)> {
+        let (i, counter) = self.size_classes.as_ref()?;
+        let scale = match self.shards.as_ref() {
+            Some(shards) if shards.get_rate() > 0.0 => 1.0 / shards.get_rate(),
+            _ => 1.0,
+        };
+        Some((self.cache_sizes[*i], counter.classes(scale)))
+    }
+
+    // Count requests and hits per `width` of trace time from `origin` on the
+    // cache closest to `cache_size`, see `time_buckets`
+    pub fn with_time_buckets(mut self, cache_size: u64, origin: u64, width: u64) -> Self {
+        self.time_buckets = Some((
+            self.closest_cache(cache_size),
+            TimeBucketCounter::new(origin, width),
+        ));
+        self
+    }
+
+    // The cache size counted by `with_time_buckets` and its miss ratio per
+    // span of trace time
+    pub fn time_buckets(&self) -> Option<(u64, Vec<TimeBucket>)> {
+        let (i, counter) = self.time_buckets.as_ref()?;
+        Some((self.cache_sizes[*i], counter.buckets()))
+    }
+
+    // Handle the requests `probes` selects as read-only probes, see `probe_curve`
+    pub fn with_probes(mut self, probes: Option<ProbeSelector>) -> Self {
+        self.probes = probes;
+        self
+    }
+
+    // Requests of `writes` commands write the object and those of `deletes`
+    // delete it, see `handle`
+    pub fn with_write_commands(mut self, writes: Vec<u8>, deletes: Vec<u8>) -> Self {
+        self.write_commands = writes;
+        self.delete_commands = deletes;
+        self
+    }
+
+    fn request(&self, access: &AccessRecord) -> Request {
+        let op = if self.write_commands.contains(&access.command) {
+            Op::Write
+        } else if self.delete_commands.contains(&access.command) {
+            Op::Delete
+        } else {
+            Op::Read
+        };
+        Request {
+            key: access.key,
+            size: access.size,
+            command: access.command,
+            timestamp: access.timestamp,
+            expires_at: Self::expires_at(access),
+            op,
+        }
+    }
+
+    // Count the hits of an LRU cache at every capacity from the stack
+    // distances of up to `requests` requests, instead of running the caches.
+    // Only for LRU caches without TTLs, probes, write commands, a contents
+    // dump or a timeline, on a trace whose keys keep one size, see LruStack.
+    pub fn with_lru_stack(mut self, requests: Option<usize>) -> Self {
+        self.lru_stack = requests.map(LruStack::new);
+        self
+    }
+
+    // Probe requests seen so far, after SHARDS sampling
+    pub fn probe_count(&self) -> u64 {
+        self.probe_count
+    }
+
+    // Miss ratio of the probe requests at every cache size
+    pub fn probe_curve(&self) -> Vec<(f64, f64)> {
+        self.cache_sizes
+            .iter()
+            .zip(self.probe_hits.iter())
+            .map(|(cache_size, hits)| {
+                let miss_ratio = 1.0 - *hits as f64 / self.probe_count.max(1) as f64;
+                (*cache_size as f64, miss_ratio)
+            })
+            .collect()
+    }
+
+    // Also count hits in `segments` contiguous runs of the `trace_len`
+    // requests, see `standard_errors`
+    pub fn with_segments(mut self, segments: Option<usize>, trace_len: u64) -> Self {
+        self.segments = segments
+            .filter(|segments| *segments > 0)
+            .map(|segments| SegmentCounter::new(segments, trace_len, self.caches.len()));
+        self
+    }
+
+    // Hits the sample counted at every cache size, empty without SHARDS. The
+    // miss ratios of the points with few of them are noisy.
+    pub fn sampled_hits(&self) -> Vec<u64> {
+        match self.shards {
+            Some(_) => self.hits.clone(),
+            None => Vec::new(),
+        }
+    }
+
+    // Standard error of every point's miss ratio across the segments of the
+    // trace, empty without `with_segments`. The segments are consecutive in
+    // time, so a workload that drifts widens the error too.
+    pub fn standard_errors(&self) -> Vec<f64> {
+        self.segments
+            .as_ref()
+            .map_or_else(Vec::new, SegmentCounter::standard_errors)
+    }
+
+    // Ask `simulation` to write the contents of the cache closest to
+    // `cache_size` to `path` once the trace is done, see `dump_contents`
+    pub fn with_contents_dump(mut self, contents_dump: Option<(u64, PathBuf)>) -> Self {
+        self.contents_dump = contents_dump;
+        self
+    }
+
+    pub fn contents_dump(&self) -> Option<&(u64, PathBuf)> {
+        self.contents_dump.as_ref()
+    }
+
+    // Snapshot the cache closest to `cache_size` every `every` requests,
+    // sampled or not, for `write_timeline` to write to `path`
+    pub fn with_timeline(mut self, timeline: Option<(u64, u64, PathBuf)>) -> Self {
+        self.timeline = timeline.map(|(cache_size, every, path)| Timeline {
+            cache: self.closest_cache(cache_size),
+            every: every.max(1),
+            path,
+            put_at: HashMap::new(),
+            frames: Vec::new(),
+        });
+        self
+    }
+
+    // Keep the byte hit ratio at every cache size over the last complete
+    // window of `requests` requests, sampled or not, for
+    // `window_byte_hit_ratios`
+    pub fn with_byte_hit_window(mut self, requests: Option<u64>) -> Self {
+        self.byte_hit_window = requests.map(|requests| ByteHitWindow {
+            requests: requests.max(1),
+            start_bytes: 0.0,
+            start_hit_bytes: vec![0.0; self.caches.len()],
+            last: None,
+        });
+        self
+    }
+
+    // The byte hit ratio at every cache size over the last complete window,
+    // None before the first one closes or without `with_byte_hit_window`
+    pub fn window_byte_hit_ratios(&self) -> Option<Vec<(f64, f64)>> {
+        let last = self.byte_hit_window.as_ref()?.last.as_ref()?;
+        Some(
+            self.cache_sizes
+                .iter()
+                .zip(last.iter())
+                .map(|(cache_size, hit_ratio)| (*cache_size as f64, *hit_ratio))
+                .collect(),
+        )
+    }
+
+    // Rewrite `path` with `write_prometheus` every `every` requests, sampled
+    // or not, labelled with `policy`
+    pub fn with_prometheus_file(mut self, file: Option<(PathBuf, u64, String)>) -> Self {
+        self.prometheus_file = file.map(|(path, every, policy)| PrometheusFile {
+            path,
+            every: every.max(1),
+            policy,
+        });
+        self
+    }
+
+    // Log every object that leaves the cache closest to `cache_size`, with why
+    // it left, for `take_evictions`. Call after `with_warm_start`.
+    pub fn with_eviction_log(mut self, cache_size: Option<u64>) -> Self {
+        self.eviction_log = cache_size.map(|cache_size| {
+            let cache = self.closest_cache(cache_size);
+            self.caches[cache].record_evictions(true);
+            EvictionLog {
+                cache,
+                admitted: HashMap::new(),
+                evictions: Vec::new(),
+            }
+        });
+        self
+    }
+
+    // The removals logged so far, see `with_eviction_log`
+    pub fn take_evictions(&mut self) -> Vec<Eviction> {
+        self.eviction_log
+            .as_mut()
+            .map(|log| std::mem::take(&mut log.evictions))
+            .unwrap_or_default()
+    }
+
+    fn snapshot(&mut self) {
+        let Some(timeline) = self.timeline.as_mut() else {
+            return;
+        };
+        let i = timeline.cache;
+        let (mut objects, mut bytes, mut total_age, mut max_age) = (0, 0, 0, 0);
+        for (key, size) in self.caches[i].resident() {
+            let age = timeline
+                .put_at
+                .get(&key)
+                .map_or(0, |put_at| self.handled - put_at);
+            objects += 1;
+            bytes += size;
+            total_age += age;
+            max_age = max_age.max(age);
+        }
+        timeline.frames.push(TimelineFrame {
+            request: self.handled,
+            objects,
+            bytes,
+            mean_age: total_age as f64 / objects.max(1) as f64,
+            max_age,
+            miss_ratio: 1.0 - self.hits[i] as f64 / self.access_count.max(1) as f64,
+        });
+    }
+
+    // Write the snapshots of `with_timeline` as CSV, one row per frame.
+    // Returns the cache size and file written, None without a timeline.
+    pub fn write_timeline(&self) -> Option<std::io::Result<(u64, &Path)>> {
+        let timeline = self.timeline.as_ref()?;
+        let write = || {
+            let mut writer = BufWriter::new(File::create(&timeline.path)?);
+            writeln!(writer, "request,objects,bytes,mean_age,max_age,miss_ratio")?;
+            for frame in timeline.frames.iter() {
+                writeln!(
+                    writer,
+                    "{},{},{},{:.1},{},{:.6}",
+                    frame.request,
+                    frame.objects,
+                    frame.bytes,
+                    frame.mean_age,
+                    frame.max_age,
+                    frame.miss_ratio
+                )?;
+            }
+            writer.flush()?;
+            Ok((self.cache_sizes[timeline.cache], timeline.path.as_path()))
+        };
+        Some(write())
+    }
+
+    // Write the resident objects of the cache closest to `cache_size` as
+    // `key,size` CSV in eviction order, the next victim first. With SHARDS
+    // only sampled keys are resident. Returns the cache size written.
+    pub fn dump_contents(&self, cache_size: u64, path: &Path) -> std::io::Result<u64> {
+        let closest = self.closest_cache(cache_size);
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "key,size")?;
+        for (key, size) in self.caches[closest].resident() {
+            writeln!(writer, "{key},{size}")?;
+        }
+        writer.flush()?;
+        Ok(self.cache_sizes[closest])
+    }
+
+    pub fn with_ttl(mut self, respect_ttl: bool) -> Self {
+        self.respect_ttl = respect_ttl;
+        self.expirations = if respect_ttl {
+            (0..self.caches.len()).map(|_| HashMap::new()).collect()
+        } else {
+            Vec::new()
+        };
+        self
+    }
+
+    // Remove expired objects from every cache as the trace clock passes them,
+    // in buckets of `granularity` trace time, instead of only when they are
+    // requested again, so the space they held is free for other objects.
+    // None keeps the lazy check. Call after `with_ttl`.
+    pub fn with_proactive_expiry(mut self, granularity: Option<u64>) -> Self {
+        self.expiry_wheels = match granularity.filter(|_| self.respect_ttl) {
+            Some(granularity) => (0..self.caches.len())
+                .map(|_| ExpiryWheel {
+                    granularity: granularity.max(1),
+                    buckets: BTreeMap::new(),
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        self
+    }
+
+    // Whether the object inserted by `record` has expired at `current_timestamp`.
+    // A TTL of 0 or u32::MAX (-1 in the trace) marks a permanent object.
+    pub fn is_expired(record: &AccessRecord, current_timestamp: u64) -> bool {
+        current_timestamp >= Self::expires_at(record)
+    }
+
+    // Timestamp the object inserted by `record` expires at, u64::MAX for a
+    // permanent object
+    pub fn expires_at(record: &AccessRecord) -> u64 {
+        if record.ttl == 0 || record.ttl == u32::MAX {
+            return u64::MAX;
+        }
+        record.timestamp.saturating_add(record.ttl as u64)
+    }
+
+    pub fn with_strict_invariants(mut self, strict_invariants: bool) -> Self {
+        self.strict_invariants = strict_invariants;
+        self
+    }
+
+    // Stop `simulation` early once `cancel` is cancelled
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    // Panic on the first cache whose accounting is off, naming the request
+    fn validate(&self) {
+        for (i, cache) in self.caches.iter().enumerate() {
+            let request = self.handled - 1;
+            let cache_size = self.cache_sizes[i];
+            if let Err(e) = cache.debug_validate() {
+                panic!("invariant violated at request {request}, cache size {cache_size}: {e}");
+            }
+            let counted = self.hits[i] + self.misses[i] + self.bypassed;
+            if counted != self.access_count {
+                panic!(
+                    "invariant violated at request {request}, cache size {cache_size}: {counted} hits and misses for {} requests",
+                    self.access_count
+                );
+            }
         }
     }
 
-    fn verify_shards(&mut self, key: Key) -> bool {
+    // Report the byte miss ratio in `curve`, see `byte_curve`
+    pub fn with_byte_miss_ratio(mut self, byte_miss_ratio: bool) -> Self {
+        self.byte_miss_ratio = byte_miss_ratio;
+        self
+    }
+
+    pub fn with_capacity_unit(mut self, capacity_unit: CapacityUnit) -> Self {
+        self.capacity_unit = capacity_unit;
+        self
+    }
+
+    fn verify_shards(&mut self, request: Request) -> bool {
         if let Some(ref mut shards) = self.shards.as_mut() {
-            if !shards.sample(&key) {
+            if !shards.sample_sized(&request.key, request.size) {
                 return false;
             }
         }
         true
     }
 
-    fn process(&mut self, access: &AccessRecord) {
+    fn process(&mut self, request: Request) {
         self.access_count += 1;
+        let weight = self.shards.as_ref().map_or(1.0, |shards| {
+            1.0 / shards.inclusion_probability(request.size)
+        });
+        self.estimated_requests += weight;
+        self.estimated_bytes += request.size as f64 * weight;
+        let segment = self.segments.as_mut().map(|segments| {
+            let segment = segments.segment(self.handled);
+            segments.requests[segment] += 1;
+            segment
+        });
+        if let Some(cacheable_keys) = self.cacheable_keys.as_ref() {
+            if !cacheable_keys.contains(&request.key) {
+                self.bypassed += 1;
+                return;
+            }
+        }
+
+        if self.seen_keys.insert(request.key) {
+            self.working_set_bytes += match self.shards.as_ref() {
+                Some(shards) => shards.charge(request.size as u64, request.size),
+                None => request.size as u64,
+            };
+        }
 
+        let mut size = match self.capacity_unit {
+            CapacityUnit::Objects => 1,
+            CapacityUnit::Bytes => request.size.max(1) as u64,
+        };
+        // Hits are estimated over the probability they were sampled with
+        let mut hit_weight = 1.0;
+        if let Some(shards) = self.shards.as_ref() {
+            size = shards.charge(size, request.size);
+            hit_weight = 1.0 / shards.inclusion_probability(request.size);
+        }
+        let weighted_hits = self
+            .shards
+            .as_ref()
+            .is_some_and(|shards| shards.size_weighted());
+        let count_hit_bytes = self.byte_miss_ratio || self.byte_hit_window.is_some();
+        // Everything below that only depends on the request is worked out
+        // once here, not once per cache size
+        let Request {
+            key,
+            timestamp,
+            expires_at,
+            op,
+            ..
+        } = request;
+        let size_class_cache = self.size_classes.as_ref().map(|(cache, _)| *cache);
+        let timeline_cache = self.timeline.as_ref().map(|timeline| timeline.cache);
+        let churn_bytes = request.size as f64 * hit_weight;
+        let access_index = (self.handled - 1) as usize;
+        if let Some(stack) = self.lru_stack.as_mut() {
+            let need = stack.access(key, size);
+            for (i, capacity) in self.capacities.iter().enumerate() {
+                let hit = need <= *capacity;
+                if hit {
+                    self.hits[i] += 1;
+                    if weighted_hits {
+                        self.estimated_hits[i] += hit_weight;
+                    }
+                    if count_hit_bytes {
+                        self.estimated_hit_bytes[i] += request.size as f64 * hit_weight;
+                    }
+                    if let (Some(segments), Some(segment)) = (self.segments.as_mut(), segment) {
+                        segments.hits[segment][i] += 1;
+                    }
+                } else {
+                    self.misses[i] += 1;
+                }
+                if size_class_cache == Some(i) {
+                    if let Some((_, counter)) = self.size_classes.as_mut() {
+                        counter.record(request.size, hit);
+                    }
+                }
+            }
+            return;
+        }
         for (i, cache) in self.caches.iter_mut().enumerate() {
-            if let Some(_) = cache.get(access.key) {
-                self.hits[i] += 1;
-            } else {
-                let size = if access.size == 0 { 1 } else { access.size };
-                cache.put(access.key, size as u64);
+            cache.advance_time(timestamp);
+            if let Some(log) = self.eviction_log.as_mut() {
+                for expired in cache.take_expirations() {
+                    log.remove(i, expired, access_index, EvictionReason::Ttl);
+                }
+            }
+            if let Some(wheel) = self.expiry_wheels.get_mut(i) {
+                for (key, expires_at) in wheel.expired(timestamp) {
+                    if self.expirations[i].get(&key) == Some(&expires_at) {
+                        self.expirations[i].remove(&key);
+                        let removed = cache.remove(key).is_some();
+                        if let Some(counter) = self.churn.as_mut().filter(|_| removed) {
+                            counter.expired[i] += counter.bytes.get(&key).copied().unwrap_or(0.0);
+                        }
+                        if let Some(log) = self.eviction_log.as_mut() {
+                            log.remove(i, key, access_index, EvictionReason::Ttl);
+                        }
+                    }
+                }
+            }
+            if op != Op::Read {
+                // Writes and deletes are never served from the cache
+                self.misses[i] += 1;
+                if size_class_cache == Some(i) {
+                    if let Some((_, counter)) = self.size_classes.as_mut() {
+                        counter.record(request.size, false);
+                    }
+                }
+                if op == Op::Delete {
+                    let removed = cache.remove(key).is_some();
+                    if let Some(counter) = self.churn.as_mut().filter(|_| removed) {
+                        counter.deleted[i] += counter.bytes.get(&key).copied().unwrap_or(0.0);
+                    }
+                    if let Some(log) = self.eviction_log.as_mut().filter(|_| removed) {
+                        log.remove(i, key, access_index, EvictionReason::Delete);
+                    }
+                    if self.respect_ttl {
+                        self.expirations[i].remove(&key);
+                    }
+                    continue;
+                }
+                // A write replaces the value: a cached copy, expired or not,
+                // takes the new size in place, an uncached one is admitted.
+                // Either way the expiry restarts from the request's ttl.
+                if let Some(counter) = self.churn.as_mut() {
+                    counter.admitted[i] += churn_bytes;
+                }
+                if cache.contains(key) {
+                    // The cached value is gone, the new one takes its place
+                    if let Some(log) = self.eviction_log.as_mut() {
+                        log.remove(i, key, access_index, EvictionReason::Invalidate);
+                    }
+                    cache.update_size(key, size);
+                } else {
+                    self.timer.time_put(|| cache.put(key, size));
+                    if timeline_cache == Some(i) {
+                        if let Some(timeline) = self.timeline.as_mut() {
+                            timeline.put_at.insert(key, self.handled);
+                        }
+                    }
+                }
+                if let Some(log) = self.eviction_log.as_mut() {
+                    log.admit(i, key, access_index);
+                }
+                if self.respect_ttl {
+                    self.expirations[i].insert(key, expires_at);
+                    if let Some(wheel) = self.expiry_wheels.get_mut(i) {
+                        wheel.insert(key, expires_at);
+                    }
+                }
+                continue;
+            }
+            if self.timer.time_get(|| cache.get(key)).is_some() {
+                let expired = self.respect_ttl
+                    && self.expirations[i]
+                        .get(&key)
+                        .is_some_and(|expires_at| timestamp >= *expires_at);
+                if !expired {
+                    self.hits[i] += 1;
+                    if weighted_hits {
+                        self.estimated_hits[i] += hit_weight;
+                    }
+                    if count_hit_bytes {
+                        self.estimated_hit_bytes[i] += request.size as f64 * hit_weight;
+                    }
+                    if let (Some(segments), Some(segment)) = (self.segments.as_mut(), segment) {
+                        segments.hits[segment][i] += 1;
+                    }
+                    if size_class_cache == Some(i) {
+                        if let Some((_, counter)) = self.size_classes.as_mut() {
+                            counter.record(request.size, true);
+                        }
+                    }
+                    continue;
+                }
+                cache.remove(key);
+                if let Some(counter) = self.churn.as_mut() {
+                    counter.expired[i] += counter.bytes.get(&key).copied().unwrap_or(0.0);
+                }
+                if let Some(log) = self.eviction_log.as_mut() {
+                    log.remove(i, key, access_index, EvictionReason::Ttl);
+                }
+            }
+            self.misses[i] += 1;
+            if size_class_cache == Some(i) {
+                if let Some((_, counter)) = self.size_classes.as_mut() {
+                    counter.record(request.size, false);
+                }
+            }
+            if let Some(counter) = self.churn.as_mut() {
+                counter.admitted[i] += churn_bytes;
+            }
+            self.timer.time_put(|| cache.put(key, size));
+            if timeline_cache == Some(i) {
+                if let Some(timeline) = self.timeline.as_mut() {
+                    timeline.put_at.insert(key, self.handled);
+                }
+            }
+            if let Some(log) = self.eviction_log.as_mut() {
+                log.admit(i, key, access_index);
+            }
+            if self.respect_ttl {
+                self.expirations[i].insert(key, expires_at);
+                if let Some(wheel) = self.expiry_wheels.get_mut(i) {
+                    wheel.insert(key, expires_at);
+                }
+            }
+        }
+        // What the puts and resizes of this request evicted to make room
+        if let Some(log) = self.eviction_log.as_mut() {
+            let cache = log.cache;
+            for evicted in self.caches[cache].take_evictions() {
+                log.remove(cache, evicted, access_index, EvictionReason::Capacity);
+            }
+        }
+        // Every cache holding the key now holds this value
+        if let Some(counter) = self.churn.as_mut().filter(|_| op != Op::Delete) {
+            counter.bytes.insert(key, churn_bytes);
+        }
+    }
+
+    // Whether each cache would hit `request`, leaving the caches untouched
+    fn probe(&mut self, request: Request) {
+        self.probe_count += 1;
+        for (i, cache) in self.caches.iter().enumerate() {
+            let expired = self.respect_ttl
+                && self.expirations[i]
+                    .get(&request.key)
+                    .is_some_and(|expires_at| request.timestamp >= *expires_at);
+            if cache.contains(request.key) && !expired {
+                self.probe_hits[i] += 1;
             }
         }
     }
 
+    // Simulate `access` at every cache size. A read hits an unexpired cached
+    // copy and admits the object on a miss. A write or a delete, see
+    // `with_write_commands`, always counts as a miss: a write resizes or
+    // admits the object and restarts its expiry, a delete removes it.
     pub fn handle(&mut self, access: &AccessRecord) {
-        if !self.verify_shards(access.key) {
+        self.handled += 1;
+        self.trace_bytes += access.size as u64;
+        self.handle_sampled(self.request(access));
+        if self
+            .timeline
+            .as_ref()
+            .is_some_and(|timeline| self.handled.is_multiple_of(timeline.every))
+        {
+            self.snapshot();
+        }
+        if let Some(window) = self
+            .byte_hit_window
+            .as_mut()
+            .filter(|window| self.handled.is_multiple_of(window.requests))
+        {
+            window.close(self.estimated_bytes, &self.estimated_hit_bytes);
+        }
+        if let Some(file) = self
+            .prometheus_file
+            .as_ref()
+            .filter(|file| self.handled.is_multiple_of(file.every))
+        {
+            if let Err(e) = self.write_prometheus(&file.policy, &file.path) {
+                warn!(
+                    "Failed to write the {} metrics to {:?}: {e}",
+                    file.policy, file.path
+                );
+            }
+        }
+    }
+
+    // The request, if SHARDS samples it
+    fn handle_sampled(&mut self, request: Request) {
+        if !self.verify_shards(request) {
             return;
         }
+        if let Some(probes) = self.probes.as_mut() {
+            if probes.is_probe(request) {
+                self.probe(request);
+                return;
+            }
+        }
 
-        self.process(access);
+        let time_bucket_hits = self.time_buckets.as_ref().map(|(i, _)| self.hits[*i]);
+        self.process(request);
+        if let (Some(hits), Some((i, counter))) = (time_bucket_hits, self.time_buckets.as_mut()) {
+            // Every sampled request stands for the requests it was sampled over
+            let weight = self.shards.as_ref().map_or(1.0, |shards| {
+                1.0 / shards.inclusion_probability(request.size)
+            });
+            counter.record(request.timestamp, weight, self.hits[*i] > hits);
+        }
+        if self.strict_invariants {
+            self.validate();
+        }
     }
 
     pub fn curve(&self) -> Vec<(f64, f64)> {
+        if self.byte_miss_ratio {
+            return self.byte_curve();
+        }
         let mut points = Vec::new();
-        for (i, hit) in self.hits.iter().enumerate() {
-            let cache_size = (i + 1) * (self.max_cache_size as usize / NUM_CACHE_SIZE as usize);
+        for (i, (cache_size, hit)) in self.cache_sizes.iter().zip(self.hits.iter()).enumerate() {
             let mut miss_ratio = 1.0 - (*hit as f64 / self.access_count as f64);
 
-            if let Some(shards) = self.shards.as_ref() {
-                miss_ratio = ((miss_ratio * shards.get_sampled_count() as f64)
-                    / shards.get_expected_count() as f64)
-                    .clamp(0.0, 1.0)
+            match self.shards.as_ref() {
+                // Estimated hits over the estimated requests of the sample,
+                // not the requests of the trace: both count the same sampled
+                // keys, so the luck of which large keys were drawn cancels
+                Some(shards) if shards.size_weighted() => {
+                    miss_ratio = (1.0 - self.estimated_hits[i] / self.estimated_requests.max(1.0))
+                        .clamp(0.0, 1.0)
+                }
+                Some(shards) => {
+                    miss_ratio = ((miss_ratio * shards.get_sampled_count() as f64)
+                        / shards.get_expected_count() as f64)
+                        .clamp(0.0, 1.0)
+                }
+                None => {}
+            }
+
+            points.push((*cache_size as f64, miss_ratio));
+        }
+        points
+    }
+
+    // Fraction of the requested bytes that miss at every cache size. With
+    // SHARDS every sampled hit counts its bytes over the probability it was
+    // sampled with, a fraction of the sampled bytes weighted the same way.
+    pub fn byte_curve(&self) -> Vec<(f64, f64)> {
+        self.cache_sizes
+            .iter()
+            .zip(self.estimated_hit_bytes.iter())
+            .map(|(cache_size, hit_bytes)| {
+                let miss_ratio = 1.0 - hit_bytes / self.estimated_bytes.max(1.0);
+                (*cache_size as f64, miss_ratio.clamp(0.0, 1.0))
+            })
+            .collect()
+    }
+
+    // The state of the simulation in the Prometheus text exposition format,
+    // for a service that feeds `handle` and scrapes it. Every metric is a
+    // gauge labelled with `policy`, and the names are stable:
+    //
+    //   cache_mrc_hit_ratio{policy, cache_size}  hit ratio so far at every
+    //                                            cache size, as in `curve`
+    //   cache_mrc_processed_requests{policy}     requests passed to `handle`
+    //   cache_mrc_sampled_keys{policy}           distinct keys SHARDS sampled,
+    //                                            only with SHARDS
+    //   cache_mrc_window_byte_hit_ratio{policy, cache_size}
+    //                                            byte hit ratio over the last
+    //                                            complete window of requests,
+    //                                            see `with_byte_hit_window`,
+    //                                            once one closed
+    pub fn prometheus_text(&self, policy: &str) -> String {
+        let policy = policy
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n");
+        let mut text = String::new();
+        text.push_str("# HELP cache_mrc_hit_ratio Hit ratio so far at each simulated cache size\n");
+        text.push_str("# TYPE cache_mrc_hit_ratio gauge\n");
+        for (cache_size, miss_ratio) in self.curve() {
+            text.push_str(&format!(
+                "cache_mrc_hit_ratio{{policy=\"{policy}\",cache_size=\"{cache_size}\"}} {}\n",
+                1.0 - miss_ratio
+            ));
+        }
+        text.push_str("# HELP cache_mrc_processed_requests Requests simulated so far\n");
+        text.push_str("# TYPE cache_mrc_processed_requests gauge\n");
+        text.push_str(&format!(
+            "cache_mrc_processed_requests{{policy=\"{policy}\"}} {}\n",
+            self.handled
+        ));
+        if let Some(sampled) = self.sample_counts() {
+            text.push_str("# HELP cache_mrc_sampled_keys Distinct keys sampled by SHARDS so far\n");
+            text.push_str("# TYPE cache_mrc_sampled_keys gauge\n");
+            text.push_str(&format!(
+                "cache_mrc_sampled_keys{{policy=\"{policy}\"}} {}\n",
+                sampled.keys
+            ));
+        }
+        if let Some(ratios) = self.window_byte_hit_ratios() {
+            text.push_str(
+                "# HELP cache_mrc_window_byte_hit_ratio Byte hit ratio over the last complete \
+                 window of requests at each simulated cache size\n",
+            );
+            text.push_str("# TYPE cache_mrc_window_byte_hit_ratio gauge\n");
+            for (cache_size, hit_ratio) in ratios {
+                text.push_str(&format!(
+                    "cache_mrc_window_byte_hit_ratio{{policy=\"{policy}\",cache_size=\"{cache_size}\"}} \
+                     {hit_ratio}\n"
+                ));
+            }
+        }
+        text
+    }
+
+    // Write `prometheus_text` to `path` for the node exporter's textfile
+    // collector, through a temporary file renamed over it so a scrape never
+    // reads half a file
+    pub fn write_prometheus(&self, policy: &str, path: &Path) -> std::io::Result<()> {
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        std::fs::write(&temporary, self.prometheus_text(policy))?;
+        std::fs::rename(&temporary, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evict_policy::{LruPolicy, MaxResidencyWrapper};
+    use crate::shards::{ShardsFixedRate, ShardsSizeWeighted};
+    use crate::synthetic::ZipfTrace;
+    #[cfg(feature = "memory")]
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn record(timestamp: u64, command: u8, key: Key, ttl: u32) -> AccessRecord {
+        AccessRecord {
+            timestamp,
+            command,
+            key,
+            size: 100,
+            ttl,
+        }
+    }
+
+    fn logged(evictions: &[Eviction]) -> Vec<(usize, Key, EvictionReason, usize)> {
+        evictions
+            .iter()
+            .map(|e| (e.access_index, e.key, e.reason, e.age))
+            .collect()
+    }
+
+    #[test]
+    fn eviction_log_lists_lru_victims_in_order() {
+        let mut sim = MiniSim::<LruPolicy>::from_sizes(vec![3, 10], None)
+            .unwrap()
+            .with_capacity_unit(CapacityUnit::Objects)
+            .with_eviction_log(Some(3));
+        for key in [1, 2, 3, 1, 4, 5, 1] {
+            sim.handle(&record(0, 0, key, 0));
+        }
+        // 1 was requested again before 4 came in, so 2 and 3 go first
+        assert_eq!(
+            logged(&sim.take_evictions()),
+            vec![
+                (4, 2, EvictionReason::Capacity, 3),
+                (5, 3, EvictionReason::Capacity, 3),
+            ]
+        );
+        assert!(sim.take_evictions().is_empty());
+    }
+
+    #[test]
+    fn eviction_log_gives_every_removal_a_reason() {
+        let mut sim = MiniSim::<LruPolicy>::from_sizes(vec![10], None)
+            .unwrap()
+            .with_capacity_unit(CapacityUnit::Objects)
+            .with_ttl(true)
+            .with_write_commands(vec![1], vec![2])
+            .with_eviction_log(Some(10));
+        sim.handle(&record(0, 0, 1, 5));
+        // A write replaces the cached value, a delete removes it
+        sim.handle(&record(1, 1, 1, 5));
+        sim.handle(&record(2, 0, 2, 0));
+        sim.handle(&record(3, 2, 2, 0));
+        // Written at 1 with a TTL of 5, expired by 10
+        sim.handle(&record(10, 0, 1, 5));
+        assert_eq!(
+            logged(&sim.take_evictions()),
+            vec![
+                (1, 1, EvictionReason::Invalidate, 1),
+                (3, 2, EvictionReason::Delete, 1),
+                (4, 1, EvictionReason::Ttl, 3),
+            ]
+        );
+
+        // Proactive expiry removes the key as the clock passes it
+        let mut sim = MiniSim::<LruPolicy>::from_sizes(vec![10], None)
+            .unwrap()
+            .with_ttl(true)
+            .with_proactive_expiry(Some(1))
+            .with_eviction_log(Some(10));
+        sim.handle(&record(20, 0, 3, 2));
+        sim.handle(&record(30, 0, 4, 0));
+        assert_eq!(
+            logged(&sim.take_evictions()),
+            vec![(1, 3, EvictionReason::Ttl, 1)]
+        );
+
+        // So does --max-residency, whatever the TTL
+        let mut sim = MiniSim::from_sizes_with(vec![1000], None, |capacity| {
+            MaxResidencyWrapper::wrap(LruPolicy::new(capacity), 5)
+        })
+        .unwrap()
+        .with_eviction_log(Some(1000));
+        sim.handle(&record(0, 0, 9, 0));
+        sim.handle(&record(10, 0, 8, 0));
+        assert_eq!(
+            logged(&sim.take_evictions()),
+            vec![(1, 9, EvictionReason::Ttl, 1)]
+        );
+    }
+
+    // A hot set of 20 keys that never expire, each request followed by a key
+    // requested once with a TTL of 2
+    fn short_ttl_sim(granularity: Option<u64>) -> MiniSim<LruPolicy> {
+        let mut sim = MiniSim::<LruPolicy>::from_sizes(vec![30, 200], None)
+            .unwrap()
+            .with_capacity_unit(CapacityUnit::Objects)
+            .with_churn(true)
+            .with_ttl(true)
+            .with_proactive_expiry(granularity);
+        for timestamp in 0..2000 {
+            sim.handle(&record(timestamp, 0, timestamp % 20, 0));
+            sim.handle(&record(timestamp, 0, 1000 + timestamp, 2));
+        }
+        sim
+    }
+
+    #[test]
+    fn proactive_expiry_beats_lazy_at_small_sizes() {
+        let (lazy, proactive) = (short_ttl_sim(None), short_ttl_sim(Some(1)));
+        let (lazy_curve, proactive_curve) = (lazy.curve(), proactive.curve());
+        // At 30 objects the expired keys crowd the hot set out unless swept,
+        // at 200 everything fits either way
+        assert!(
+            proactive_curve[0].1 < lazy_curve[0].1 - 0.4,
+            "proactive {proactive_curve:?}, lazy {lazy_curve:?}"
+        );
+        assert_eq!(proactive_curve[1], lazy_curve[1]);
+
+        // Swept bytes count as expired, not as evicted to make room
+        let (lazy_churn, proactive_churn) = (lazy.churn(), proactive.churn());
+        assert_eq!(lazy_churn[0].expired_bytes, 0.0);
+        assert!(proactive_churn[0].expired_bytes > 0.0);
+        assert!(
+            proactive_churn[0].evicted_bytes < lazy_churn[0].evicted_bytes,
+            "proactive {proactive_churn:?}, lazy {lazy_churn:?}"
+        );
+        let churn = &proactive_churn[1];
+        assert_eq!(
+            churn.admitted_bytes,
+            churn.expired_bytes + churn.evicted_bytes + 100.0 * 20.0 + 100.0 * 2.0,
+            "{churn:?}"
+        );
+    }
+
+    #[test]
+    fn prometheus_text_parses_with_stable_names() {
+        let mut sim = MiniSim::<LruPolicy>::from_sizes(vec![2, 10], None)
+            .unwrap()
+            .with_capacity_unit(CapacityUnit::Objects)
+            .with_byte_hit_window(Some(3));
+        for key in [1, 2, 3, 1, 2, 3, 1] {
+            sim.handle(&record(0, 0, key, 0));
+        }
+        let text = sim.prometheus_text("LRU \"bytes\"");
+
+        // name{label="value",...} value, after a TYPE line for the name
+        let mut typed = Vec::new();
+        let mut samples = Vec::new();
+        for line in text.lines() {
+            if let Some(rest) = line.strip_prefix("# TYPE ") {
+                let (name, kind) = rest.split_once(' ').unwrap();
+                assert_eq!(kind, "gauge");
+                typed.push(name.to_string());
+                continue;
             }
+            if line.starts_with("# HELP ") {
+                continue;
+            }
+            let (series, value) = line.rsplit_once(' ').unwrap();
+            let (name, labels) = series.split_once('{').unwrap();
+            assert!(typed.iter().any(|typed| typed == name), "{line}");
+            let mut parsed = Vec::new();
+            let mut rest = labels.strip_suffix('}').unwrap();
+            while !rest.is_empty() {
+                let (label, quoted) = rest.split_once("=\"").unwrap();
+                let (mut value, mut end) = (String::new(), 0);
+                let mut chars = quoted.char_indices();
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '\\' => value.push(chars.next().unwrap().1),
+                        '"' => {
+                            end = i + 1;
+                            break;
+                        }
+                        c => value.push(c),
+                    }
+                }
+                parsed.push((label.to_string(), value));
+                rest = quoted[end..].trim_start_matches(',');
+            }
+            samples.push((name.to_string(), parsed, value.parse::<f64>().unwrap()));
+        }
+
+        let policy = ("policy".to_string(), "LRU \"bytes\"".to_string());
+        let gauge = |metric: &str, cache_size: &str| {
+            let labels = vec![
+                policy.clone(),
+                ("cache_size".to_string(), cache_size.to_string()),
+            ];
+            samples
+                .iter()
+                .find(|(name, parsed, _)| name == metric && *parsed == labels)
+                .map(|(_, _, value)| *value)
+        };
+        // Cycling through 3 keys never hits 2 objects of LRU, and hits 10
+        // objects after the first 3
+        assert_eq!(gauge("cache_mrc_hit_ratio", "2"), Some(0.0));
+        assert_eq!(gauge("cache_mrc_hit_ratio", "10"), Some(4.0 / 7.0));
+        // The last complete window is the second pass over the 3 keys, the
+        // seventh request is in the open one
+        let window = "cache_mrc_window_byte_hit_ratio";
+        assert_eq!(gauge(window, "2"), Some(0.0));
+        assert_eq!(gauge(window, "10"), Some(1.0));
+        assert!(samples.contains(&(
+            "cache_mrc_processed_requests".to_string(),
+            vec![policy.clone()],
+            7.0
+        )));
+        // Sampled keys are only reported with SHARDS
+        assert!(!text.contains("cache_mrc_sampled_keys"));
+        assert_eq!(samples.len(), 5);
+    }
+
+    #[test]
+    fn prometheus_file_is_rewritten_every_n_requests() {
+        let path = std::env::temp_dir().join(format!("cache_mrc_{}.prom", std::process::id()));
+        let mut sim = MiniSim::<LruPolicy>::from_sizes(vec![10], None)
+            .unwrap()
+            .with_capacity_unit(CapacityUnit::Objects)
+            .with_byte_hit_window(Some(4))
+            .with_prometheus_file(Some((path.clone(), 4, "LRU".to_string())));
+        let written = || std::fs::read_to_string(&path).ok();
+        for key in [1, 2, 3] {
+            sim.handle(&record(0, 0, key, 0));
+        }
+        assert_eq!(written(), None);
+        sim.handle(&record(0, 0, 4, 0));
+        let first = written().unwrap();
+        assert!(first.contains("cache_mrc_processed_requests{policy=\"LRU\"} 4\n"));
+        assert!(
+            first.contains("cache_mrc_window_byte_hit_ratio{policy=\"LRU\",cache_size=\"10\"} 0\n")
+        );
+        // Only the next multiple rewrites it, with the window of hits since
+        for key in [1, 2, 3, 4] {
+            sim.handle(&record(0, 0, key, 0));
+            if key < 4 {
+                assert_eq!(written().unwrap(), first);
+            }
+        }
+        let second = written().unwrap();
+        assert!(second.contains("cache_mrc_processed_requests{policy=\"LRU\"} 8\n"));
+        assert!(second
+            .contains("cache_mrc_window_byte_hit_ratio{policy=\"LRU\",cache_size=\"10\"} 1\n"));
+        std::fs::remove_file(&path).unwrap();
+    }
 
-            points.push((cache_size as f64, miss_ratio));
+    #[test]
+    fn timeline_has_a_frame_every_n_requests() {
+        let path =
+            std::env::temp_dir().join(format!("cache_mrc_timeline_{}.csv", std::process::id()));
+        for (every, frames) in [(1, 100), (7, 14), (100, 1), (101, 0)] {
+            let mut sim = MiniSim::<LruPolicy>::from_sizes(vec![5, 10], None)
+                .unwrap()
+                .with_capacity_unit(CapacityUnit::Objects)
+                .with_timeline(Some((10, every, path.clone())));
+            for i in 0..100 {
+                sim.handle(&record(i, 0, i % 15, 0));
+            }
+            let (cache_size, _) = sim.write_timeline().unwrap().unwrap();
+            assert_eq!(cache_size, 10);
+            let written = std::fs::read_to_string(&path).unwrap();
+            let requests = written
+                .lines()
+                .skip(1)
+                .map(|line| line.split(',').next().unwrap().parse::<u64>().unwrap())
+                .collect::<Vec<_>>();
+            let expected = (1..=frames).map(|frame| frame * every).collect::<Vec<_>>();
+            assert_eq!(requests, expected, "every {every}");
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn warm_start_from_a_converged_snapshot_skips_the_warmup() {
+        let day = |seed| {
+            ZipfTrace {
+                requests: 100_000,
+                keys: 10_000,
+                alpha: 0.9,
+                min_size: 1,
+                max_size: 1,
+                seed,
+            }
+            .generate()
+        };
+        let sim = || {
+            MiniSim::<LruPolicy>::from_sizes(vec![2000], None)
+                .unwrap()
+                .with_capacity_unit(CapacityUnit::Objects)
+        };
+        let miss_ratio = |mut sim: MiniSim<LruPolicy>, records: &[AccessRecord]| {
+            for record in records {
+                sim.handle(record);
+            }
+            sim.curve()[0].1
+        };
+
+        // Yesterday's contents once the cache has converged
+        let mut yesterday = sim();
+        for record in day(1).iter() {
+            yesterday.handle(record);
+        }
+        let snapshot = yesterday.caches[0].resident().collect::<Vec<_>>();
+        assert_eq!(snapshot.len(), 2000);
+
+        let today = day(2);
+        let warm = || sim().with_warm_start(Some(&snapshot));
+        let steady = miss_ratio(warm(), &today);
+        let warm_start = miss_ratio(warm(), &today[..2000]);
+        let cold_start = miss_ratio(sim(), &today[..2000]);
+        assert!(
+            (warm_start - steady).abs() < 0.03,
+            "{warm_start} vs {steady}"
+        );
+        assert!(cold_start > steady + 0.1, "{cold_start} vs {steady}");
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum State {
+        Expired,
+        Fresh,
+        Absent,
+    }
+
+    #[test]
+    fn reads_writes_and_deletes_of_expired_fresh_and_absent_keys() {
+        // (command, state of key 1, hit, cached size and expiry after)
+        let fresh_write = Some((60, 35));
+        let cases = [
+            (0, State::Fresh, true, Some((100, 10))),
+            (0, State::Expired, false, Some((60, 50))),
+            (0, State::Absent, false, fresh_write),
+            (1, State::Fresh, false, fresh_write),
+            (1, State::Expired, false, Some((60, 50))),
+            (1, State::Absent, false, fresh_write),
+            (2, State::Fresh, false, None),
+            (2, State::Expired, false, None),
+            (2, State::Absent, false, None),
+        ];
+        for (command, state, hit, after) in cases {
+            let mut sim = MiniSim::<LruPolicy>::from_sizes(vec![1000], None)
+                .unwrap()
+                .with_ttl(true)
+                .with_write_commands(vec![1], vec![2]);
+            // Admitted at 0 with 100 bytes, expiring at 10
+            if state != State::Absent {
+                sim.handle(&AccessRecord {
+                    timestamp: 0,
+                    command: 0,
+                    key: 1,
+                    size: 100,
+                    ttl: 10,
+                });
+            }
+            let (hits, misses) = (sim.hits[0], sim.misses[0]);
+            let timestamp = if state == State::Expired { 20 } else { 5 };
+            sim.handle(&AccessRecord {
+                timestamp,
+                command,
+                key: 1,
+                size: 60,
+                ttl: 30,
+            });
+            let case = format!("command {command} on a {state:?} key");
+            assert_eq!(sim.hits[0] - hits, hit as u64, "{case}");
+            assert_eq!(sim.misses[0] - misses, !hit as u64, "{case}");
+            let cached = sim.caches[0].resident().collect::<Vec<_>>();
+            let expiry = sim.expirations[0].get(&1).copied();
+            match after {
+                Some((size, expires_at)) => {
+                    assert_eq!(cached, vec![(1, size)], "{case}");
+                    assert_eq!(expiry, Some(expires_at), "{case}");
+                }
+                None => {
+                    assert!(cached.is_empty(), "{case}: {cached:?}");
+                    assert_eq!(expiry, None, "{case}");
+                }
+            }
+        }
+    }
+
+    // Replays `requests` requests of 2000 keys after as many to warm up, and
+    // returns the allocations made by the replay. Every object has the same
+    // size, so the caches hold as many objects once full and their maps and
+    // queues stop growing.
+    #[cfg(feature = "memory")]
+    fn steady_state_allocations<P: EvictPolicy>(sim: MiniSim<P>, requests: u64) -> u64 {
+        let mut sim = sim;
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut access = |sim: &mut MiniSim<P>, timestamp| {
+            let key = rng.gen_range(0..2000u64);
+            sim.handle(&AccessRecord {
+                timestamp,
+                command: (key % 7 == 0) as u8,
+                key,
+                size: 100,
+                ttl: 1000,
+            });
+        };
+        for timestamp in 0..requests {
+            access(&mut sim, timestamp);
+        }
+        let before = crate::memory::allocations().unwrap();
+        for timestamp in requests..2 * requests {
+            access(&mut sim, timestamp);
+        }
+        crate::memory::allocations().unwrap() - before
+    }
+
+    #[cfg(feature = "memory")]
+    #[test]
+    fn requests_allocate_nothing_once_warm() {
+        use crate::evict_policy::FifoPolicy;
+
+        // Evicting and expiring at every size, with writes
+        let sim = MiniSim::<FifoPolicy>::new(100_000, None)
+            .unwrap()
+            .with_ttl(true)
+            .with_write_commands(vec![1], Vec::new());
+        assert_eq!(steady_state_allocations(sim, 200_000), 0);
+
+        // Every key fits, so the LRU list never grows
+        let sim = MiniSim::<LruPolicy>::from_sizes(vec![1 << 20, 1 << 21], None).unwrap();
+        assert_eq!(steady_state_allocations(sim, 200_000), 0);
+    }
+    #[test]
+    fn probes_count_hits_and_misses_but_leave_the_caches_alone() {
+        let mut sim = MiniSim::<LruPolicy>::from_sizes(vec![2], None)
+            .unwrap()
+            .with_capacity_unit(CapacityUnit::Objects)
+            .with_probes(ProbeSelector::new(vec![9], None));
+        for key in [1, 2] {
+            sim.handle(&record(0, 0, key, 0));
+        }
+        let resident = |sim: &MiniSim<LruPolicy>| sim.caches[0].resident().collect::<Vec<_>>();
+        let before = resident(&sim);
+        // A probe hit of 1 would make 2 the LRU victim, a probe miss of 3
+        // would admit it
+        for key in [1, 3, 1] {
+            sim.handle(&record(0, 9, key, 0));
+        }
+        assert_eq!(resident(&sim), before);
+        assert_eq!(sim.probe_count(), 3);
+        assert_eq!(sim.probe_curve(), vec![(2.0, 1.0 - 2.0 / 3.0)]);
+        assert_eq!((sim.hits[0], sim.misses[0]), (0, 2));
+        // 3 evicts 1, not 2, as if the probes never happened
+        sim.handle(&record(0, 0, 3, 0));
+        assert!(sim.caches[0].contains(2) && !sim.caches[0].contains(1));
+    }
+
+    #[test]
+    fn grid_caches_have_the_size_they_are_reported_at() {
+        let sim =
+            MiniSim::<LruPolicy>::new_with_points(1000, 10, None, LruPolicy::new, 2..5).unwrap();
+        assert_eq!(sim.cache_sizes, vec![300, 400, 500]);
+        assert_eq!(sim.capacities, sim.cache_sizes);
+    }
+
+    #[test]
+    fn single_huge_object_steps_where_it_first_fits() {
+        // A 59500 byte object between distinct 100 byte keys: caches too
+        // small for it miss everything, the others hit it on every repeat
+        let mut sim = MiniSim::<LruPolicy>::new(100_000, None).unwrap();
+        for timestamp in 0..2000 {
+            for (key, size) in [(0, 59_500), (1 + timestamp, 100)] {
+                sim.handle(&AccessRecord {
+                    timestamp,
+                    command: 0,
+                    key,
+                    size,
+                    ttl: 0,
+                });
+            }
+        }
+        let curve = sim.curve();
+        let step = crate::analysis::step_at(&curve, 59_500).unwrap();
+        assert_eq!(step.below, Some((59_000.0, 1.0)));
+        assert_eq!(step.fits, (60_000.0, 2001.0 / 4000.0));
+        assert!(curve
+            .iter()
+            .all(|(size, miss_ratio)| *miss_ratio == if *size < 60_000.0 { 1.0 } else { 0.50025 }));
+    }
+
+    // Mean absolute error of the LRU byte miss ratio curve SHARDS estimates
+    // against the exact one, at ten sizes up to a third of the footprint
+    fn byte_curve_error(trace: &[AccessRecord], shards: Box<dyn Shards>) -> f64 {
+        let footprint = trace
+            .iter()
+            .map(|access| (access.key, access.size as u64))
+            .collect::<HashMap<_, _>>()
+            .values()
+            .sum::<u64>();
+        let cache_sizes = (1..=10).map(|i| footprint * i / 30).collect::<Vec<_>>();
+        let curve = |shards| {
+            let mut sim = MiniSim::<LruPolicy>::from_sizes(cache_sizes.clone(), shards)
+                .unwrap()
+                .with_byte_miss_ratio(true);
+            for access in trace {
+                sim.handle(access);
+            }
+            sim.curve()
+        };
+        let exact = curve(None);
+        let estimate = curve(Some(shards));
+        estimate
+            .iter()
+            .zip(exact.iter())
+            .map(|((_, estimated), (_, exact))| (estimated - exact).abs())
+            .sum::<f64>()
+            / exact.len() as f64
+    }
+
+    #[test]
+    fn size_weighted_byte_curves_beat_key_sampling_on_heavy_tails() {
+        let (mut uniform, mut size_weighted) = (0.0, 0.0);
+        for seed in 0..4 {
+            // Log-uniform sizes over 4.5 orders of magnitude: the largest
+            // hundredth of the keys holds about a tenth of the bytes
+            let trace = ZipfTrace {
+                requests: 200_000,
+                keys: 20_000,
+                alpha: 0.9,
+                min_size: 100,
+                max_size: 4_000_000,
+                seed,
+            }
+            .generate();
+            let mean_size = trace
+                .iter()
+                .map(|access| (access.key, access.size as f64))
+                .collect::<HashMap<_, _>>()
+                .values()
+                .sum::<f64>()
+                / 20_000.0;
+            let uniform_error = byte_curve_error(&trace, Box::new(ShardsFixedRate::new(0.1)));
+            let size_error =
+                byte_curve_error(&trace, Box::new(ShardsSizeWeighted::new(0.1, mean_size)));
+            uniform += uniform_error / 4.0;
+            size_weighted += size_error / 4.0;
         }
-        return points;
+        assert!(
+            size_weighted < uniform && size_weighted < 0.05,
+            "size-weighted mean error {size_weighted:.3}, uniform {uniform:.3}"
+        );
     }
 }
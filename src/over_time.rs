@@ -0,0 +1,135 @@
+use crate::{
+    analysis::TimeBucket,
+    config::EvictionPolicy,
+    error::SimError,
+    evict_policy::{
+        registered_policy, EvictPolicy, FifoPolicy, FrozenHotPolicy, LargestFirstPolicy, LfuPolicy,
+        LruFrequencyPolicy, LruPolicy, NewPolicy, SlruPolicy, TwoQPolicy, WatermarkWrapper,
+    },
+    minisim::MiniSim,
+    shards::ShardsFixedRate,
+    AccessRecord,
+};
+
+// How one policy's miss ratio moves over trace time at a single cache size
+#[derive(Debug, Clone)]
+pub struct OverTimeRun<'a> {
+    pub access_records: &'a [AccessRecord],
+    pub capacity: u64,
+    pub sample_rate: Option<f64>,
+    pub respect_ttl: bool,
+    // See MiniSim::with_write_commands
+    pub write_commands: &'a [u8],
+    pub delete_commands: &'a [u8],
+    // First timestamp of the trace and the span of trace time per bucket
+    pub origin: u64,
+    pub width: u64,
+}
+
+fn replay<P: EvictPolicy>(
+    run: &OverTimeRun,
+    build_policy: impl Fn(u64) -> P,
+) -> Result<Vec<TimeBucket>, SimError> {
+    let shards = ShardsFixedRate::create_shards(run.sample_rate);
+    let mut sim = MiniSim::from_sizes_with(vec![run.capacity], shards, build_policy)?
+        .with_ttl(run.respect_ttl)
+        .with_write_commands(run.write_commands.to_vec(), run.delete_commands.to_vec())
+        .with_time_buckets(run.capacity, run.origin, run.width);
+    for access in run.access_records {
+        sim.handle(access);
+    }
+    Ok(sim.time_buckets().unwrap().1)
+}
+
+// `replay` with the policy's watermark applied, if any
+fn replay_with_watermark<P: EvictPolicy>(
+    run: &OverTimeRun,
+    watermark: Option<f64>,
+    build_policy: impl Fn(u64) -> P,
+) -> Result<Vec<TimeBucket>, SimError> {
+    match watermark {
+        Some(watermark) => replay(run, |capacity| {
+            WatermarkWrapper::wrap(build_policy(capacity), capacity, watermark)
+        }),
+        None => replay(run, build_policy),
+    }
+}
+
+// Replay the trace with `policy` at the run's cache size and work out its
+// miss ratio per span of trace time
+pub fn miss_ratio_over_time(
+    policy: &EvictionPolicy,
+    run: &OverTimeRun,
+) -> Result<Vec<TimeBucket>, SimError> {
+    let (policy, watermark) = policy.split_watermark();
+    match policy {
+        EvictionPolicy::LRU => replay_with_watermark(run, watermark, LruPolicy::new),
+        EvictionPolicy::FIFO => replay_with_watermark(run, watermark, FifoPolicy::new),
+        EvictionPolicy::LFU => replay_with_watermark(run, watermark, LfuPolicy::new),
+        EvictionPolicy::TWOQ => replay_with_watermark(run, watermark, TwoQPolicy::new),
+        EvictionPolicy::LARGEST => replay_with_watermark(run, watermark, LargestFirstPolicy::new),
+        EvictionPolicy::SLRU { protected } => replay_with_watermark(run, watermark, |capacity| {
+            SlruPolicy::with_protected(capacity, *protected)
+        }),
+        EvictionPolicy::LRUFQ { threshold } => replay_with_watermark(run, watermark, |capacity| {
+            LruFrequencyPolicy::with_threshold(capacity, *threshold)
+        }),
+        EvictionPolicy::FROZENHOT { hot, rebuild } => {
+            replay_with_watermark(run, watermark, |capacity| {
+                FrozenHotPolicy::with_params(capacity, *hot, *rebuild)
+            })
+        }
+        EvictionPolicy::Registered { name, params } => {
+            let factory = registered_policy(name).expect("policies stay registered");
+            replay_with_watermark(run, watermark, |capacity| factory(capacity, params))
+        }
+        EvictionPolicy::Watermark { .. } => unreachable!("watermarks don't nest"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn miss_ratio_falls_across_buckets_as_the_cache_warms() {
+        // 10000 one-off keys, then 10000 requests cycling 100 keys that fit
+        let access_records = (0..20_000u64)
+            .map(|timestamp| AccessRecord {
+                timestamp,
+                command: 0,
+                key: if timestamp < 10_000 {
+                    1_000 + timestamp
+                } else {
+                    timestamp % 100
+                },
+                size: 100,
+                ttl: 0,
+            })
+            .collect::<Vec<_>>();
+        let run = OverTimeRun {
+            access_records: &access_records,
+            capacity: 1 << 20,
+            sample_rate: None,
+            respect_ttl: false,
+            write_commands: &[],
+            delete_commands: &[],
+            origin: 0,
+            width: 2_000,
+        };
+        for policy in [EvictionPolicy::LRU, EvictionPolicy::FIFO] {
+            let buckets = miss_ratio_over_time(&policy, &run).unwrap();
+            let misses = buckets
+                .iter()
+                .map(|bucket| (bucket.miss_ratio * bucket.requests as f64).round() as u64)
+                .collect::<Vec<_>>();
+            assert_eq!(
+                misses,
+                vec![2_000, 2_000, 2_000, 2_000, 2_000, 100, 0, 0, 0, 0],
+                "{policy:?}"
+            );
+            assert!(buckets.iter().all(|bucket| bucket.requests == 2_000));
+            assert_eq!(buckets[5].start, 10_000);
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::{fs, path::Path};
+
+use serde_json::json;
+
+use crate::SimulationResult;
+
+const PLOT_SCRIPT: &str = include_str!("../assets/mrc_report.js");
+
+// Write a self-contained HTML page with the curves, a metadata table on top
+// and the vendored plot script, so it opens without network access.
+pub fn write_html_report(
+    results: &[SimulationResult],
+    metadata: &[(&str, String)],
+    path: &Path,
+) -> std::io::Result<()> {
+    let curves = results
+        .iter()
+        .map(|result| {
+            let points = result
+                .points
+                .iter()
+                .map(|(size, miss_ratio)| {
+                    let misses = (miss_ratio * result.requests as f64).round() as u64;
+                    json!({
+                        "size": size,
+                        "miss_ratio": miss_ratio,
+                        "hits": result.requests.saturating_sub(misses),
+                        "misses": misses,
+                    })
+                })
+                .collect::<Vec<_>>();
+            json!({ "label": result.label, "dashed": result.dashed, "points": points })
+        })
+        .collect::<Vec<_>>();
+    // Keep the data from closing the script element early
+    let data = json!({ "curves": curves })
+        .to_string()
+        .replace("</", "<\\/");
+
+    let mut rows = String::new();
+    for (name, value) in metadata {
+        rows.push_str(&format!(
+            "<tr><th>{}</th><td>{}</td></tr>\n",
+            escape_html(name),
+            escape_html(value)
+        ));
+    }
+
+    // One row per curve with the miss ratio at its largest simulated size
+    let mut summary = String::new();
+    for result in results {
+        let (size, miss_ratio) = result.points.last().copied().unwrap_or_default();
+        let lowest = result
+            .points
+            .iter()
+            .map(|(_, miss_ratio)| *miss_ratio)
+            .fold(f64::NAN, f64::min);
+        summary.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{size}</td><td>{miss_ratio:.4}</td><td>{lowest:.4}</td></tr>\n",
+            escape_html(&result.label),
+            result.points.len(),
+        ));
+    }
+
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Miss ratio curve</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; }}
+table {{ border-collapse: collapse; margin-bottom: 1em; }}
+th, td {{ border: 1px solid #ccc; padding: 4px 8px; text-align: left; }}
+#legend label {{ margin-right: 1em; cursor: pointer; }}
+#tooltip {{ position: absolute; display: none; background: #fff; border: 1px solid #888; padding: 4px; font-size: 12px; }}
+</style>
+</head>
+<body>
+<h1>Miss ratio curve</h1>
+<table>
+{rows}</table>
+<table>
+<tr><th>Curve</th><th>Points</th><th>Largest size</th><th>Miss ratio there</th><th>Lowest miss ratio</th></tr>
+{summary}</table>
+<p>Drag over the plot to zoom into a range of cache sizes, double click to reset.</p>
+<label><input type="checkbox" id="log-x"> Log scale cache size</label>
+<div id="legend"></div>
+<div id="plot"></div>
+<div id="tooltip"></div>
+<script>
+var MRC_DATA = {data};
+</script>
+<script>
+{PLOT_SCRIPT}
+</script>
+</body>
+</html>
+"#
+    );
+    fs::write(path, html)
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CapacityUnit;
+
+    fn result(label: &str, points: Vec<(f64, f64)>) -> SimulationResult {
+        SimulationResult {
+            points,
+            label: label.to_string(),
+            capacity_unit: CapacityUnit::Bytes,
+            dashed: false,
+            requests: 1000,
+            passes: Vec::new(),
+            stderr: Vec::new(),
+            sampled_hits: Vec::new(),
+            sampled: None,
+            churn: Vec::new(),
+            evictions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn report_embeds_the_curves_as_json() {
+        let results = [
+            result("LRU", vec![(100.0, 0.9), (200.0, 0.5)]),
+            // A label that would end the script element if it were not escaped
+            result("FIFO </script>", vec![(100.0, 0.95), (200.0, 0.625)]),
+        ];
+        let path =
+            std::env::temp_dir().join(format!("cache_mrc_report_{}.html", std::process::id()));
+        write_html_report(&results, &[("Trace", "t.csv".to_string())], &path).unwrap();
+        let html = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(html.starts_with("<!DOCTYPE html>\n<html>\n"));
+        assert!(html.ends_with("</html>\n"));
+        assert_eq!(html.matches("<script>").count(), 2);
+        assert_eq!(html.matches("</script>").count(), 2);
+        assert!(html.contains("<tr><th>Trace</th><td>t.csv</td></tr>"));
+
+        let start = html.find("var MRC_DATA = ").unwrap() + "var MRC_DATA = ".len();
+        let end = start + html[start..].find(";\n</script>").unwrap();
+        let data: serde_json::Value = serde_json::from_str(&html[start..end]).unwrap();
+        let curves = data["curves"].as_array().unwrap();
+        assert_eq!(curves.len(), results.len());
+        for (curve, result) in curves.iter().zip(results.iter()) {
+            assert_eq!(curve["label"], result.label.as_str());
+            assert_eq!(curve["dashed"], false);
+            let points = curve["points"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|point| {
+                    let misses = point["misses"].as_u64().unwrap();
+                    assert_eq!(point["hits"].as_u64().unwrap() + misses, 1000);
+                    (
+                        point["size"].as_f64().unwrap(),
+                        point["miss_ratio"].as_f64().unwrap(),
+                        misses,
+                    )
+                })
+                .collect::<Vec<_>>();
+            let expected = result
+                .points
+                .iter()
+                .map(|(size, miss_ratio)| (*size, *miss_ratio, (miss_ratio * 1000.0) as u64))
+                .collect::<Vec<_>>();
+            assert_eq!(points, expected);
+        }
+    }
+}
@@ -2,11 +2,13 @@ use fasthash::murmur3;
 
 use crate::Key;
 
-const MODULUS: u64 = 1000;
+// Hash space keys are sampled from, fine enough for rates well below 0.1%
+pub const MODULUS: u64 = 1 << 24;
 fn hash(key: Key) -> u128 {
     murmur3::hash128(key.to_le_bytes())
 }
 
+// Samplers are owned by the simulator on its pool worker, so must be Send
 pub trait Shards: Send {
     fn get_global_t(&self) -> u64;
     fn get_sampled_count(&self) -> u64;
@@ -23,6 +25,31 @@ pub trait Shards: Send {
 
     fn sample(&mut self, access: &Key) -> bool;
 
+    // Like `sample`, for a request of an object of `size` bytes
+    fn sample_sized(&mut self, access: &Key, _size: u32) -> bool {
+        self.sample(access)
+    }
+
+    // Whether the inclusion probability depends on the object size, so that
+    // every sampled request is weighted by its inverse
+    fn size_weighted(&self) -> bool {
+        false
+    }
+
+    // Probability that a request for an object of `size` bytes is sampled
+    fn inclusion_probability(&self, _size: u32) -> f64 {
+        self.get_rate()
+    }
+
+    // Capacity a sampled object of `size` bytes takes in the scaled cache,
+    // for a `charge` in the full cache: its charge scaled by the rate, over
+    // the probability it was sampled with, so the scaled cache holds the
+    // same share of the estimated working set
+    fn charge(&self, charge: u64, size: u32) -> u64 {
+        let weight = self.get_rate() / self.inclusion_probability(size);
+        ((charge as f64 * weight).round() as u64).max(1)
+    }
+
     fn sample_key(&self, key: Key) -> Option<u64> {
         let t = (hash(key) % MODULUS as u128) as u64;
 
@@ -44,25 +71,43 @@ pub struct ShardsFixedRate {
 }
 
 impl ShardsFixedRate {
-    pub fn new(global_t: u64) -> Self {
+    // Sample keys whose hash modulo MODULUS is below the threshold T = rate * MODULUS
+    pub fn new(rate: f64) -> Self {
+        assert!(
+            rate > 0.0 && rate <= 1.0,
+            "SHARDS rate {rate} is not in (0, 1]"
+        );
         ShardsFixedRate {
-            global_t,
+            global_t: ((rate * MODULUS as f64).round() as u64).max(1),
             sampled_count: 0,
             total_count: 0,
         }
     }
 
+    #[deprecated(note = "use ShardsFixedRate::new with a rate in (0, 1]")]
+    pub fn with_percent(percent: u64) -> Self {
+        Self::new(percent as f64 / 100.0)
+    }
+
+    // The threshold T keys are sampled below
+    pub fn threshold(&self) -> u64 {
+        self.global_t
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.get_rate()
+    }
+
     pub fn create_shards(simple_rate: Option<f64>) -> Option<Box<dyn Shards>> {
-        match simple_rate {
-            Some(rate) => Some(Box::new(ShardsFixedRate::new(
-                (rate * MODULUS as f64) as u64,
-            ))),
-            None => None,
-        }
+        simple_rate.map(|rate| Box::new(ShardsFixedRate::new(rate)) as Box<dyn Shards>)
     }
 }
 
 impl Shards for ShardsFixedRate {
+    fn charge(&self, charge: u64, _size: u32) -> u64 {
+        charge
+    }
+
     fn get_global_t(&self) -> u64 {
         self.global_t
     }
@@ -91,3 +136,130 @@ impl Shards for ShardsFixedRate {
         true
     }
 }
+
+// SHARDS with an inclusion probability proportional to the object size, capped
+// at 1: a key of `size` bytes is sampled with min(1, rate * size / mean_size).
+// Large objects, which make up most of the bytes of a heavy-tailed trace, are
+// sampled more often, so the bytes of the scaled cache vary less with which
+// few large keys happen to be sampled. Every sampled request is weighted by
+// the inverse of its inclusion probability.
+pub struct ShardsSizeWeighted {
+    global_t: u64,
+    // Size sampled at the base rate, the mean object size of the trace
+    mean_size: f64,
+    sampled_count: u64,
+    total_count: u64,
+}
+
+impl ShardsSizeWeighted {
+    // Objects of `mean_size` bytes are sampled at `rate`
+    pub fn new(rate: f64, mean_size: f64) -> Self {
+        assert!(
+            rate > 0.0 && rate <= 1.0,
+            "SHARDS rate {rate} is not in (0, 1]"
+        );
+        ShardsSizeWeighted {
+            global_t: ((rate * MODULUS as f64).round() as u64).max(1),
+            mean_size: mean_size.max(1.0),
+            sampled_count: 0,
+            total_count: 0,
+        }
+    }
+
+    pub fn create_shards(simple_rate: Option<f64>, mean_size: f64) -> Option<Box<dyn Shards>> {
+        simple_rate
+            .map(|rate| Box::new(ShardsSizeWeighted::new(rate, mean_size)) as Box<dyn Shards>)
+    }
+}
+
+impl Shards for ShardsSizeWeighted {
+    fn get_global_t(&self) -> u64 {
+        self.global_t
+    }
+
+    fn get_sampled_count(&self) -> u64 {
+        self.sampled_count
+    }
+
+    fn get_total_count(&self) -> u64 {
+        self.total_count
+    }
+
+    fn get_expected_count(&self) -> u64 {
+        (self.get_rate() * self.total_count as f64) as u64
+    }
+
+    // Without a size the key is sampled at the base rate
+    fn sample(&mut self, access: &Key) -> bool {
+        self.sample_sized(access, self.mean_size as u32)
+    }
+
+    fn sample_sized(&mut self, access: &Key, size: u32) -> bool {
+        self.total_count += 1;
+        let threshold = (self.inclusion_probability(size) * MODULUS as f64).round() as u64;
+        if (hash(*access) % MODULUS as u128) as u64 >= threshold.max(1) {
+            return false;
+        }
+        self.sampled_count += 1;
+        true
+    }
+
+    fn size_weighted(&self) -> bool {
+        true
+    }
+
+    fn inclusion_probability(&self, size: u32) -> f64 {
+        (self.get_rate() * size.max(1) as f64 / self.mean_size).min(1.0)
+    }
+}
+
+const _: () = {
+    crate::assert_send::<Box<dyn Shards>>();
+    crate::assert_send::<ShardsFixedRate>();
+    crate::assert_send::<ShardsSizeWeighted>();
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    #[test]
+    fn sampled_fraction_converges_to_the_rate() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let keys = (0..2_000_000).map(|_| rng.gen::<Key>()).collect::<Vec<_>>();
+        for rate in [1.0, 0.5, 0.1, 0.0025, 0.001] {
+            let mut shards = ShardsFixedRate::new(rate);
+            for key in keys.iter() {
+                shards.sample(key);
+            }
+            // Every key is sampled independently, allow 5 standard deviations
+            let n = keys.len() as f64;
+            let expected = rate * n;
+            let tolerance = 5.0 * (n * rate * (1.0 - rate)).sqrt();
+            let sampled = shards.get_sampled_count() as f64;
+            assert!(
+                (sampled - expected).abs() <= tolerance,
+                "rate {rate}: sampled {sampled} of {n}, expected {expected} ± {tolerance}"
+            );
+            assert_eq!(shards.get_total_count(), keys.len() as u64);
+        }
+    }
+
+    #[test]
+    fn threshold_is_the_rate_of_the_modulus() {
+        let shards = ShardsFixedRate::new(0.0025);
+        assert_eq!(shards.threshold(), (0.0025 * MODULUS as f64).round() as u64);
+        assert!((shards.rate() - 0.0025).abs() < 1.0 / MODULUS as f64);
+        assert_eq!(ShardsFixedRate::new(1.0).threshold(), MODULUS);
+        #[allow(deprecated)]
+        let percent = ShardsFixedRate::with_percent(10);
+        assert_eq!(percent.threshold(), ShardsFixedRate::new(0.1).threshold());
+    }
+
+    #[test]
+    #[should_panic(expected = "is not in (0, 1]")]
+    fn zero_rate_is_rejected() {
+        ShardsFixedRate::new(0.0);
+    }
+}
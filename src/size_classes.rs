@@ -0,0 +1,87 @@
+use crate::{
+    analysis::SizeClass,
+    config::EvictionPolicy,
+    error::SimError,
+    evict_policy::{
+        registered_policy, EvictPolicy, FifoPolicy, FrozenHotPolicy, LargestFirstPolicy, LfuPolicy,
+        LruFrequencyPolicy, LruPolicy, NewPolicy, SlruPolicy, TwoQPolicy, WatermarkWrapper,
+    },
+    minisim::MiniSim,
+    shards::ShardsFixedRate,
+    AccessRecord,
+};
+
+// How one policy hits each object size class at a single cache size
+#[derive(Debug, Clone)]
+pub struct SizeClassRun<'a> {
+    pub access_records: &'a [AccessRecord],
+    pub capacity: u64,
+    pub sample_rate: Option<f64>,
+    pub respect_ttl: bool,
+    // See MiniSim::with_write_commands
+    pub write_commands: &'a [u8],
+    pub delete_commands: &'a [u8],
+    // Class edges in bytes, log2 classes without any
+    pub edges: Option<&'a [u64]>,
+}
+
+fn replay<P: EvictPolicy>(
+    run: &SizeClassRun,
+    build_policy: impl Fn(u64) -> P,
+) -> Result<Vec<SizeClass>, SimError> {
+    let shards = ShardsFixedRate::create_shards(run.sample_rate);
+    let mut sim = MiniSim::from_sizes_with(vec![run.capacity], shards, build_policy)?
+        .with_ttl(run.respect_ttl)
+        .with_write_commands(run.write_commands.to_vec(), run.delete_commands.to_vec())
+        .with_size_classes(run.capacity, run.edges);
+    for access in run.access_records {
+        sim.handle(access);
+    }
+    Ok(sim.size_classes().unwrap().1)
+}
+
+// `replay` with the policy's watermark applied, if any
+fn replay_with_watermark<P: EvictPolicy>(
+    run: &SizeClassRun,
+    watermark: Option<f64>,
+    build_policy: impl Fn(u64) -> P,
+) -> Result<Vec<SizeClass>, SimError> {
+    match watermark {
+        Some(watermark) => replay(run, |capacity| {
+            WatermarkWrapper::wrap(build_policy(capacity), capacity, watermark)
+        }),
+        None => replay(run, build_policy),
+    }
+}
+
+// Replay the trace with `policy` at the run's cache size and count the hits
+// per object size class, scaled up from the sample with SHARDS
+pub fn size_classes(
+    policy: &EvictionPolicy,
+    run: &SizeClassRun,
+) -> Result<Vec<SizeClass>, SimError> {
+    let (policy, watermark) = policy.split_watermark();
+    match policy {
+        EvictionPolicy::LRU => replay_with_watermark(run, watermark, LruPolicy::new),
+        EvictionPolicy::FIFO => replay_with_watermark(run, watermark, FifoPolicy::new),
+        EvictionPolicy::LFU => replay_with_watermark(run, watermark, LfuPolicy::new),
+        EvictionPolicy::TWOQ => replay_with_watermark(run, watermark, TwoQPolicy::new),
+        EvictionPolicy::LARGEST => replay_with_watermark(run, watermark, LargestFirstPolicy::new),
+        EvictionPolicy::SLRU { protected } => replay_with_watermark(run, watermark, |capacity| {
+            SlruPolicy::with_protected(capacity, *protected)
+        }),
+        EvictionPolicy::LRUFQ { threshold } => replay_with_watermark(run, watermark, |capacity| {
+            LruFrequencyPolicy::with_threshold(capacity, *threshold)
+        }),
+        EvictionPolicy::FROZENHOT { hot, rebuild } => {
+            replay_with_watermark(run, watermark, |capacity| {
+                FrozenHotPolicy::with_params(capacity, *hot, *rebuild)
+            })
+        }
+        EvictionPolicy::Registered { name, params } => {
+            let factory = registered_policy(name).expect("policies stay registered");
+            replay_with_watermark(run, watermark, |capacity| factory(capacity, params))
+        }
+        EvictionPolicy::Watermark { .. } => unreachable!("watermarks don't nest"),
+    }
+}
@@ -0,0 +1,68 @@
+// Generated traces, to try the simulator without a trace file, see --demo
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::AccessRecord;
+
+// A trace whose keys are drawn from a Zipf distribution: the key of rank r is
+// requested with a probability proportional to 1 / r^alpha
+#[derive(Debug, Clone, Copy)]
+pub struct ZipfTrace {
+    pub requests: usize,
+    pub keys: u64,
+    pub alpha: f64,
+    // Smallest and largest object size, sizes are log-uniform in between
+    pub min_size: u32,
+    pub max_size: u32,
+    pub seed: u64,
+}
+
+impl ZipfTrace {
+    // The --demo trace, small enough to simulate exactly in seconds
+    pub fn demo() -> Self {
+        ZipfTrace {
+            requests: 200_000,
+            keys: 20_000,
+            alpha: 0.9,
+            min_size: 64,
+            max_size: 16 * 1024,
+            seed: 0,
+        }
+    }
+
+    // One request per second, without commands or TTLs. The same parameters
+    // always give the same trace.
+    pub fn generate(&self) -> Vec<AccessRecord> {
+        if self.keys == 0 {
+            return Vec::new();
+        }
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let (low, high) = (
+            (self.min_size.max(1) as f64).ln(),
+            (self.max_size.max(self.min_size).max(1) as f64).ln(),
+        );
+        let sizes = (0..self.keys)
+            .map(|_| rng.gen_range(low..=high).exp().round() as u32)
+            .collect::<Vec<_>>();
+        let mut cdf = Vec::with_capacity(self.keys as usize);
+        let mut total = 0.0;
+        for rank in 1..=self.keys {
+            total += (rank as f64).powf(-self.alpha);
+            cdf.push(total);
+        }
+        (0..self.requests)
+            .map(|i| {
+                let draw = rng.gen::<f64>() * total;
+                let rank = cdf
+                    .partition_point(|weight| *weight < draw)
+                    .min(cdf.len() - 1);
+                AccessRecord {
+                    timestamp: i as u64,
+                    command: 0,
+                    key: rank as u64,
+                    size: sizes[rank],
+                    ttl: 0,
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,194 @@
+// Colors, fonts and line widths of the miss ratio figures, see --theme and
+// --plot-preset
+use std::collections::BTreeMap;
+
+use crate::config::{FigureOptions, PlotPreset, ThemeName};
+
+// Okabe-Ito colors, with the gray often paired with them in place of the
+// yellow that is hard to see on white
+const OKABE_ITO: [&str; 8] = [
+    "#0072B2", "#E69F00", "#009E73", "#D55E00", "#CC79A7", "#56B4E9", "#000000", "#999999",
+];
+
+// Settings left unset keep gnuplot's (or the LaTeX document's) defaults
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Theme {
+    pub background: Option<String>,
+    // Text and border color
+    pub foreground: Option<String>,
+    pub grid: Option<String>,
+    // gnuplot font, "Name,size"
+    pub font: Option<String>,
+    // Replaces the size of `font`, in points
+    pub font_size: Option<f64>,
+    pub tick_font_size: Option<f64>,
+    pub line_width: Option<f64>,
+    // PNG resolution as a multiple of 1920x1080, fonts and lines scale along
+    pub scale: Option<f64>,
+    // Colors the curves without one of their own take in turn
+    pub palette: Vec<String>,
+    // Uppercase policy name to the color of its curve
+    pub colors: BTreeMap<String, String>,
+}
+
+impl Theme {
+    // The built-in theme of `figure`, then its preset, then its [plot] settings
+    pub fn new(figure: &FigureOptions) -> Self {
+        let mut theme = Theme::builtin(figure.theme);
+        // Sizes for the 1920x1080 canvas, scaled down about 5 times in a
+        // paper column and 2 times on a slide
+        let (font_size, tick_font_size, line_width) = match figure.preset {
+            PlotPreset::Default => (None, None, None),
+            PlotPreset::Paper => (Some(40.0), Some(34.0), Some(6.0)),
+            PlotPreset::Slides => (Some(28.0), Some(24.0), Some(4.0)),
+        };
+        theme.font_size = font_size;
+        theme.tick_font_size = tick_font_size;
+        theme.line_width = line_width.or(theme.line_width);
+        let Some(style) = figure.plot.as_ref() else {
+            return theme;
+        };
+        let set = |setting: &mut Option<String>, value: &Option<String>| {
+            if value.is_some() {
+                setting.clone_from(value);
+            }
+        };
+        set(&mut theme.background, &style.background);
+        set(&mut theme.foreground, &style.foreground);
+        set(&mut theme.grid, &style.grid);
+        set(&mut theme.font, &style.font);
+        theme.line_width = style.line_width.or(theme.line_width);
+        theme.font_size = style.font_size.or(theme.font_size);
+        theme.tick_font_size = style.tick_font_size.or(theme.tick_font_size);
+        theme.scale = style.scale;
+        if let Some(palette) = &style.palette {
+            theme.palette.clone_from(palette);
+        }
+        for (policy, color) in style.colors.iter().flatten() {
+            theme.colors.insert(policy.to_uppercase(), color.clone());
+        }
+        theme
+    }
+
+    fn builtin(name: ThemeName) -> Self {
+        let owned = |colors: &[&str]| colors.iter().map(|color| color.to_string()).collect();
+        match name {
+            ThemeName::Light => Theme::default(),
+            ThemeName::Dark => Theme {
+                background: Some("#1E1E1E".to_string()),
+                foreground: Some("#D4D4D4".to_string()),
+                grid: Some("#4A4A4A".to_string()),
+                line_width: Some(2.0),
+                palette: owned(&[
+                    "#4FC1FF", "#FFB454", "#7EE787", "#FF7B72", "#D2A8FF", "#F2CC60", "#56D4DD",
+                ]),
+                ..Theme::default()
+            },
+            ThemeName::Colorblind => Theme {
+                line_width: Some(2.0),
+                palette: owned(&OKABE_ITO),
+                // One color each for the shipped policies
+                colors: [
+                    "LRU",
+                    "FIFO",
+                    "LFU",
+                    "TWOQ",
+                    "SLRU",
+                    "LARGEST",
+                    "FROZENHOT",
+                    "LRUFQ",
+                ]
+                .into_iter()
+                .zip(OKABE_ITO)
+                .map(|(policy, color)| (policy.to_string(), color.to_string()))
+                .collect(),
+                ..Theme::default()
+            },
+        }
+    }
+
+    // The gnuplot font, "Name,size", with `font_size` in place of its size
+    pub fn gnuplot_font(&self) -> Option<String> {
+        match (self.font.as_deref(), self.font_size) {
+            (font, Some(size)) => {
+                let name = font.and_then(|font| font.split(',').next());
+                Some(format!("{},{size}", name.unwrap_or_default()))
+            }
+            (font, None) => font.map(str::to_string),
+        }
+    }
+
+    // The color of every curve of a figure, by label. A curve takes the color
+    // of its policy unless an earlier curve of the figure already has it, as
+    // the SHARDS rates of one policy do, then the next palette color no other
+    // curve has. None keeps gnuplot's own color.
+    pub fn curve_colors(&self, labels: &[&str]) -> Vec<Option<String>> {
+        let mut colors = vec![None; labels.len()];
+        let mut taken = Vec::new();
+        for (color, label) in colors.iter_mut().zip(labels) {
+            let policy = label
+                .split(|c: char| !c.is_ascii_alphanumeric())
+                .next()
+                .unwrap_or_default()
+                .to_uppercase();
+            if let Some(own) = self.colors.get(&policy).filter(|own| !taken.contains(own)) {
+                taken.push(own);
+                *color = Some(own.clone());
+            }
+        }
+        let mut free = self
+            .palette
+            .iter()
+            .filter(|color| !taken.contains(color))
+            .chain(self.palette.iter().cycle());
+        for color in colors.iter_mut().filter(|color| color.is_none()) {
+            *color = free.next().cloned();
+        }
+        colors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Curve labels of every shipped policy, as the golden sample draws them
+    const SHIPPED: [&str; 8] = [
+        "LRU",
+        "FIFO",
+        "LFU",
+        "TWOQ",
+        "LARGEST",
+        "SLRU(protected=0.8)",
+        "LRUFQ(threshold=1)",
+        "FROZENHOT(hot=0.5,rebuild=100000)",
+    ];
+
+    #[test]
+    fn colorblind_theme_gives_every_shipped_policy_its_own_color() {
+        let theme = Theme::builtin(ThemeName::Colorblind);
+        let colors = theme
+            .curve_colors(&SHIPPED)
+            .into_iter()
+            .map(Option::unwrap)
+            .collect::<Vec<_>>();
+        let mut distinct = colors.clone();
+        distinct.sort();
+        distinct.dedup();
+        assert_eq!(distinct.len(), SHIPPED.len(), "{colors:?}");
+        assert!(colors
+            .iter()
+            .all(|color| OKABE_ITO.contains(&color.as_str())));
+
+        // A policy keeps its color whatever else is in the figure
+        let mut reversed = SHIPPED;
+        reversed.reverse();
+        let mut reversed_colors = theme.curve_colors(&reversed);
+        reversed_colors.reverse();
+        assert_eq!(reversed_colors, theme.curve_colors(&SHIPPED));
+        assert_eq!(
+            theme.curve_colors(&["FIFO"]),
+            vec![Some("#E69F00".to_string())]
+        );
+    }
+}
@@ -0,0 +1,77 @@
+// Time spent in the policies' get and put, for performance tuning. The
+// measurements are only compiled in with the `timing` cargo feature; without
+// it `Timer` is a no-op.
+
+#[cfg(feature = "timing")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PolicyTimingReport {
+    pub get_count: u64,
+    pub put_count: u64,
+    pub get_time_ns: u64,
+    pub put_time_ns: u64,
+}
+
+#[cfg(feature = "timing")]
+#[derive(Default)]
+pub(crate) struct Timer {
+    report: Option<PolicyTimingReport>,
+}
+
+#[cfg(feature = "timing")]
+impl Timer {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn enable(&mut self) {
+        self.report.get_or_insert_with(Default::default);
+    }
+
+    pub(crate) fn report(&self) -> Option<PolicyTimingReport> {
+        self.report
+    }
+
+    #[inline]
+    pub(crate) fn time_get<R>(&mut self, f: impl FnOnce() -> R) -> R {
+        let Some(report) = self.report.as_mut() else {
+            return f();
+        };
+        let start = std::time::Instant::now();
+        let result = f();
+        report.get_time_ns += start.elapsed().as_nanos() as u64;
+        report.get_count += 1;
+        result
+    }
+
+    #[inline]
+    pub(crate) fn time_put<R>(&mut self, f: impl FnOnce() -> R) -> R {
+        let Some(report) = self.report.as_mut() else {
+            return f();
+        };
+        let start = std::time::Instant::now();
+        let result = f();
+        report.put_time_ns += start.elapsed().as_nanos() as u64;
+        report.put_count += 1;
+        result
+    }
+}
+
+#[cfg(not(feature = "timing"))]
+pub(crate) struct Timer;
+
+#[cfg(not(feature = "timing"))]
+impl Timer {
+    pub(crate) fn new() -> Self {
+        Timer
+    }
+
+    #[inline(always)]
+    pub(crate) fn time_get<R>(&mut self, f: impl FnOnce() -> R) -> R {
+        f()
+    }
+
+    #[inline(always)]
+    pub(crate) fn time_put<R>(&mut self, f: impl FnOnce() -> R) -> R {
+        f()
+    }
+}
@@ -0,0 +1,39 @@
+// The cache_mrc binary as a user runs it
+use std::{path::PathBuf, process::Command};
+
+#[test]
+fn without_output_prints_a_table_and_draws_nothing() {
+    let trace = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("data/golden/twitter_sample.csv");
+    // An empty working directory, to see that nothing is written next to the run
+    let dir = std::env::temp_dir().join(format!("cache_mrc_no_output_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_cache_mrc"))
+        .current_dir(&dir)
+        .arg("--trace")
+        .arg(&trace)
+        .args(["--policies", "LRU,FIFO", "--cache-size", "0.5ws"])
+        .output()
+        .unwrap();
+    let written = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(written.is_empty(), "{written:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let rows = stdout
+        .lines()
+        .skip_while(|line| !line.starts_with("curve "))
+        .take(3)
+        .collect::<Vec<_>>();
+    assert_eq!(rows.len(), 3, "{stdout}");
+    assert!(rows[0].contains("miss ratio"), "{stdout}");
+    assert!(rows[1].starts_with("LRU "), "{stdout}");
+    assert!(rows[2].starts_with("FIFO "), "{stdout}");
+}
@@ -0,0 +1,51 @@
+// The golden sample simulated from its config must still give the curves of
+// its trusted export, as `cache_mrc check` does by hand.
+use std::{path::PathBuf, sync::Arc};
+
+use cache_mrc::{
+    analysis,
+    config::{self, format_checksum, Config, InnerConfig},
+    export, run_simulations,
+};
+
+// Largest difference allowed between two miss ratios, as in `check`
+const TOLERANCE: f64 = 0.001;
+
+#[test]
+fn golden_sample_matches_its_export() {
+    let path = PathBuf::from("data/golden/twitter_sample.toml");
+    // Not `Config::from_file`, which also parses the test binary's arguments
+    let mut config: Config = toml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    config.config_file = Some(path);
+    let (access_records, checksum) = config::load_access_records(&mut config).unwrap();
+    let mut args = InnerConfig::try_from(config).unwrap();
+    assert_eq!(
+        args.trace_checksum,
+        Some(checksum),
+        "the sample trace changed"
+    );
+
+    let golden =
+        export::read_json_export(&PathBuf::from("data/golden/twitter_sample.json")).unwrap();
+    assert_eq!(
+        golden.trace_checksum.as_deref(),
+        Some(format_checksum(checksum).as_str())
+    );
+
+    let footprint = analysis::footprint(&access_records);
+    args.resolve_cache_sizes(Some(footprint.unique_bytes))
+        .unwrap();
+    let (results, _, failures) = run_simulations(Arc::new(access_records), &args, &footprint, None);
+    assert!(failures.is_empty(), "{failures:?}");
+
+    let checks = export::check_golden(&results, &golden, TOLERANCE);
+    assert_eq!(checks.len(), args.policies.len());
+    for check in checks {
+        assert!(
+            check.mismatch.is_none(),
+            "{}: {}",
+            check.label,
+            check.mismatch.unwrap()
+        );
+    }
+}